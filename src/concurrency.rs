@@ -30,10 +30,13 @@
 //! # }
 //! ```
 
-use crate::data_handler::{DataHandler, ErrorDetails};
-use anyhow::{Context, Result};
+use crate::data_handler::{DatabaseOptions, DataHandler, ErrorDetails};
+use anyhow::{anyhow, Context, Result};
+use rayon::prelude::*;
 use rusqlite::Connection;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Thread-safe aggregator for collecting DES file failures from concurrent operations.
 ///
@@ -302,6 +305,170 @@ impl Default for ErrorAggregator {
     }
 }
 
+/// Converts record or byte counts into throttled 0.0–1.0 progress updates.
+///
+/// GUI progress widgets want a single fraction rather than raw counts, and
+/// redrawing on every batch floods them. The callback fires only when the
+/// whole percentage increases, so it runs at most 101 times (0% to 100%)
+/// however often `report` is called. Safe to share between worker threads;
+/// under concurrency, updates may arrive slightly out of order.
+pub struct PercentReporter {
+    total: u64,
+    /// Last reported whole percentage plus one (0 = nothing reported yet)
+    last: AtomicU64,
+    callback: Box<dyn Fn(f64) + Send + Sync>,
+}
+
+impl PercentReporter {
+    /// Creates a reporter for `total` units of work.
+    ///
+    /// # Arguments
+    ///
+    /// * `total` - Total records or bytes; 0 reports completion immediately
+    /// * `callback` - Receives the completed fraction, from 0.0 to 1.0
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ncdac_opi_parser::concurrency::PercentReporter;
+    ///
+    /// let reporter = PercentReporter::new(1_000, |fraction| println!("{:.0}%", fraction * 100.0));
+    /// reporter.report(250);
+    /// ```
+    pub fn new(total: u64, callback: impl Fn(f64) + Send + Sync + 'static) -> Self {
+        Self {
+            total,
+            last: AtomicU64::new(0),
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Reports the units completed so far.
+    ///
+    /// Calls the callback if the whole percentage is higher than the last
+    /// one reported. Values above `total` are treated as complete.
+    ///
+    /// # Arguments
+    ///
+    /// * `current` - Units completed so far (not an increment)
+    pub fn report(&self, current: u64) {
+        let current = current.min(self.total);
+        let percent = (current * 100).checked_div(self.total).unwrap_or(100);
+
+        if self.last.fetch_max(percent + 1, Ordering::Relaxed) < percent + 1 {
+            let fraction = if self.total == 0 { 1.0 } else { current as f64 / self.total as f64 };
+            (self.callback)(fraction);
+        }
+    }
+}
+
+impl std::fmt::Debug for PercentReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PercentReporter")
+            .field("total", &self.total)
+            .field("last", &self.last)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Processes items in parallel, then retries each failure once serially.
+///
+/// Failures during the parallel pass are often transient (e.g. `SQLITE_BUSY`
+/// from lock contention between worker connections). Retrying them after the
+/// parallel pass, when no other writers are active, recovers those files
+/// without a manual rerun.
+///
+/// `process` receives the item and the attempt number (1 for the parallel
+/// pass, 2 for the serial retry) so callers can clean up partial work from
+/// the failed attempt before retrying.
+///
+/// # Returns
+///
+/// Items that failed on both attempts, each paired with an error reporting
+/// both causes: "first attempt: …; retry: …".
+///
+/// # Example
+///
+/// ```
+/// use ncdac_opi_parser::concurrency::process_with_serial_retry;
+///
+/// let failed = process_with_serial_retry(&[1, 2, 3], |item, _attempt| {
+///     if *item > 0 { Ok(()) } else { anyhow::bail!("bad item") }
+/// });
+/// assert!(failed.is_empty());
+/// ```
+pub fn process_with_serial_retry<T, F>(items: &[T], process: F) -> Vec<(T, anyhow::Error)>
+where
+    T: Clone + Send + Sync,
+    F: Fn(&T, usize) -> Result<()> + Sync,
+{
+    let retry_queue = Mutex::new(Vec::new());
+
+    items.par_iter().for_each(|item| {
+        if let Err(e) = process(item, 1) {
+            retry_queue
+                .lock()
+                .expect("Retry queue mutex poisoned")
+                .push((item.clone(), e));
+        }
+    });
+
+    retry_queue
+        .into_inner()
+        .expect("Retry queue mutex poisoned")
+        .into_iter()
+        .filter_map(|(item, first)| {
+            process(&item, 2)
+                .err()
+                .map(|retry| (item, anyhow!("first attempt: {:#}; retry: {:#}", first, retry)))
+        })
+        .collect()
+}
+
+/// Connection tuning applied to every worker handler.
+///
+/// Building all worker connections from one `WorkerConfig` keeps their
+/// pragmas identical, instead of each call site re-applying its own list.
+/// The default matches the historical worker setup: the standard page and
+/// cache size, `synchronous=NORMAL`, and SQLite's default journal mode and
+/// busy handling.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use ncdac_opi_parser::concurrency::WorkerConfig;
+///
+/// let config = WorkerConfig {
+///     journal_mode: Some("WAL"),
+///     busy_timeout: Some(Duration::from_secs(5)),
+///     ..Default::default()
+/// };
+/// assert_eq!(config.synchronous, "NORMAL");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerConfig {
+    /// Page size and cache size for the connection
+    pub database: DatabaseOptions,
+    /// Value for `PRAGMA synchronous` (`OFF`, `NORMAL`, `FULL` or `EXTRA`)
+    pub synchronous: &'static str,
+    /// Value for `PRAGMA journal_mode`; `None` keeps the database's mode
+    pub journal_mode: Option<&'static str>,
+    /// How long to wait on a locked database; `None` keeps SQLite's default
+    pub busy_timeout: Option<Duration>,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            database: DatabaseOptions::default(),
+            synchronous: "NORMAL",
+            journal_mode: None,
+            busy_timeout: None,
+        }
+    }
+}
+
 /// Creates a new DataHandler instance with a separate SQLite connection for parallel processing.
 ///
 /// This function implements the connection-per-thread strategy required for SQLite concurrent writes.
@@ -316,36 +483,53 @@ impl Default for ErrorAggregator {
 ///
 /// **PRAGMA Configuration:**
 /// - `PRAGMA foreign_keys=ON` - Enforced on all connections to maintain referential integrity
-/// - `PRAGMA synchronous=NORMAL` - Applied to non-reference table connections for performance
+/// - `PRAGMA page_size` / `PRAGMA cache_size` - From `config.database`
+/// - `PRAGMA synchronous` - From `config.synchronous` (NORMAL by default) for performance
+/// - `PRAGMA journal_mode` / `busy_timeout` - Only when set in `config`
 ///
 /// # Arguments
 ///
 /// * `database_path` - Path to the SQLite database file
+/// * `config` - Connection tuning shared by all workers
 ///
 /// # Errors
 ///
 /// Returns an error if:
 /// - The database cannot be opened
 /// - Foreign key enforcement cannot be enabled
-/// - PRAGMA synchronous cannot be set
+/// - Any configured PRAGMA cannot be set
 ///
 /// # Example
 ///
 /// ```no_run
-/// use ncdac_opi_parser::concurrency::create_worker_handler;
+/// use ncdac_opi_parser::concurrency::{create_worker_handler, WorkerConfig};
 ///
 /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// // Each parallel worker thread creates its own handler
-/// let handler = create_worker_handler("database.db")?;
+/// let handler = create_worker_handler("database.db", &WorkerConfig::default())?;
 /// # Ok(())
 /// # }
 /// ```
-pub fn create_worker_handler(database_path: &str) -> Result<DataHandler> {
-    let handler = DataHandler::new(database_path)
+pub fn create_worker_handler(database_path: &str, config: &WorkerConfig) -> Result<DataHandler> {
+    let handler = DataHandler::with_options(database_path, &config.database)
         .with_context(|| format!("Failed to create worker DataHandler for {}", database_path))?;
 
-    set_pragma_synchronous_normal(handler.connection())
-        .context("Failed to set PRAGMA synchronous=NORMAL on worker connection")?;
+    let conn = handler.connection();
+
+    conn.pragma_update(None, "synchronous", config.synchronous)
+        .with_context(|| {
+            format!("Failed to set PRAGMA synchronous={} on worker connection", config.synchronous)
+        })?;
+
+    if let Some(mode) = config.journal_mode {
+        conn.pragma_update_and_check(None, "journal_mode", mode, |_| Ok(()))
+            .with_context(|| format!("Failed to set PRAGMA journal_mode={} on worker connection", mode))?;
+    }
+
+    if let Some(timeout) = config.busy_timeout {
+        conn.busy_timeout(timeout)
+            .context("Failed to set busy timeout on worker connection")?;
+    }
 
     Ok(handler)
 }
@@ -579,8 +763,8 @@ mod tests {
         let temp_file = NamedTempFile::new()?;
         let path = temp_file.path().to_str().unwrap();
 
-        let handler1 = create_worker_handler(path)?;
-        let handler2 = create_worker_handler(path)?;
+        let handler1 = create_worker_handler(path, &WorkerConfig::default())?;
+        let handler2 = create_worker_handler(path, &WorkerConfig::default())?;
 
         let fk1: i32 = handler1.connection().pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
         let fk2: i32 = handler2.connection().pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
@@ -671,7 +855,7 @@ mod tests {
         let temp_file = NamedTempFile::new()?;
         let path = temp_file.path().to_str().unwrap();
 
-        let handler = create_worker_handler(path)?;
+        let handler = create_worker_handler(path, &WorkerConfig::default())?;
 
         let fk_enabled: i32 = handler.connection()
             .pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
@@ -688,7 +872,7 @@ mod tests {
         let temp_file = NamedTempFile::new()?;
         let path = temp_file.path().to_str().unwrap();
 
-        let handler = create_worker_handler(path)?;
+        let handler = create_worker_handler(path, &WorkerConfig::default())?;
 
         let sync_mode: i32 = handler.connection()
             .pragma_query_value(None, "synchronous", |row| row.get(0))?;
@@ -698,6 +882,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_worker_handler_custom_config() -> Result<()> {
+        use tempfile::NamedTempFile;
+
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let config = WorkerConfig {
+            database: DatabaseOptions { page_size: 8_192, cache_size: -2_000 },
+            synchronous: "FULL",
+            journal_mode: Some("WAL"),
+            busy_timeout: Some(Duration::from_millis(2_500)),
+        };
+        let handler = create_worker_handler(path, &config)?;
+        let conn = handler.connection();
+
+        let page_size: i64 = conn.pragma_query_value(None, "page_size", |row| row.get(0))?;
+        let cache_size: i64 = conn.pragma_query_value(None, "cache_size", |row| row.get(0))?;
+        let sync_mode: i32 = conn.pragma_query_value(None, "synchronous", |row| row.get(0))?;
+        let journal_mode: String = conn.pragma_query_value(None, "journal_mode", |row| row.get(0))?;
+        let busy_timeout: i64 = conn.pragma_query_value(None, "busy_timeout", |row| row.get(0))?;
+        let fk_enabled: i32 = conn.pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
+
+        assert_eq!(page_size, 8_192);
+        assert_eq!(cache_size, -2_000);
+        assert_eq!(sync_mode, 2);
+        assert_eq!(journal_mode, "wal");
+        assert_eq!(busy_timeout, 2_500);
+        assert_eq!(fk_enabled, 1);
+
+        Ok(())
+    }
+
     #[test]
     fn test_parallel_workers_isolated_connections() -> Result<()> {
         use tempfile::NamedTempFile;
@@ -706,7 +923,7 @@ mod tests {
         let path = temp_file.path().to_str().unwrap();
 
         let handlers: Vec<_> = (0..4)
-            .map(|_| create_worker_handler(path))
+            .map(|_| create_worker_handler(path, &WorkerConfig::default()))
             .collect::<Result<Vec<_>>>()?;
 
         for handler in &handlers {
@@ -722,6 +939,66 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_serial_retry_recovers_transient_failure() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let flaky_attempts = AtomicUsize::new(0);
+        let retried_attempt = AtomicUsize::new(0);
+
+        let failed = process_with_serial_retry(&["A", "FLAKY", "B"], |item, attempt| {
+            if *item == "FLAKY" {
+                if flaky_attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    anyhow::bail!("database is locked");
+                }
+                retried_attempt.store(attempt, Ordering::SeqCst);
+            }
+            Ok(())
+        });
+
+        assert!(failed.is_empty(), "Flaky file should succeed on serial retry");
+        assert_eq!(flaky_attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(retried_attempt.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_serial_retry_reports_permanent_failure() {
+        let failed = process_with_serial_retry(&["OK", "BROKEN"], |item, attempt| {
+            if *item == "BROKEN" {
+                anyhow::bail!("permanent failure on attempt {}", attempt);
+            }
+            Ok(())
+        });
+
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, "BROKEN");
+        assert_eq!(
+            failed[0].1.to_string(),
+            "first attempt: permanent failure on attempt 1; retry: permanent failure on attempt 2"
+        );
+    }
+
+    #[test]
+    fn test_percent_reporter_throttles_updates() {
+        let fractions = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&fractions);
+        let reporter = PercentReporter::new(1_000, move |fraction| recorded.lock().unwrap().push(fraction));
+
+        // 1,000 single-record increments reach each whole percent from 0% to
+        // 100% once; repeated and stale reports are ignored.
+        for current in 1..=1_000 {
+            reporter.report(current);
+        }
+        reporter.report(1_000);
+        reporter.report(5);
+
+        let fractions = fractions.lock().unwrap();
+        assert_eq!(fractions.len(), 101);
+        assert_eq!(fractions.first(), Some(&0.001));
+        assert_eq!(fractions[1], 0.01);
+        assert_eq!(fractions.last(), Some(&1.0));
+    }
+
     #[test]
     fn test_error_aggregator_batch_collection_from_parallel_workers() {
         let aggregator = ErrorAggregator::new();
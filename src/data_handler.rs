@@ -31,29 +31,90 @@
 //! # }
 //! ```
 
-use crate::concurrency::{set_pragma_synchronous_full};
+use crate::concurrency::{create_worker_handler, set_pragma_synchronous_full, WorkerConfig};
 use crate::file_description::FileDescription;
 use crate::files::FileMetadata;
-use crate::parser::DataParser;
-use crate::utilities::{get_primary_key_field, to_snake_case};
+use crate::parser::{ControlCharPolicy, DataParser, RecordIterator, RAW_SUFFIX};
+use crate::utilities::{
+    avoid_sqlite_keyword, count_lines, data_directory, extracted_file_path, get_primary_key_field, is_sqlite_keyword, quote_identifier,
+    to_snake_case,
+};
 use anyhow::{anyhow, Context, Result};
 use indicatif::ProgressBar;
-use rusqlite::Connection;
-use std::collections::HashSet;
+use rayon::prelude::*;
+use rusqlite::{Connection, DatabaseName, OptionalExtension};
+use sha2::{Digest, Sha256};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 /// The batch size for transaction commits.
 ///
 /// Records are inserted in batches of this size to balance performance
 /// and memory usage. Through benchmarking, 250 was found to be optimal,
 /// providing 14% faster performance than 1000 (see BATCH_SIZE_OPTIMIZATION.md).
+/// `benchmark_batch_sizes` repeats the measurement.
 const BATCH_SIZE: usize = 250;
 
+/// Number of records sampled when inferring a key for a file without a
+/// named key field.
+const KEY_INFERENCE_SAMPLE: usize = 10_000;
+
+/// Default number of rows written between explicit flushes during export.
+pub const DEFAULT_EXPORT_FLUSH_ROWS: usize = 10_000;
+
 /// SQLite extended result code for foreign key constraint violations.
 ///
 /// Used to detect when inserted rows reference missing parent records so we
 /// can surface a detailed error message to the caller.
 const FOREIGN_KEY_ERROR_CODE: i32 = 787;
 
+/// SQLite extended result code for primary key constraint violations.
+///
+/// Raised when the reference file repeats a key already in its table.
+const PRIMARY_KEY_ERROR_CODE: i32 = 1555;
+
+/// Name of the optional column recording each row's source file ID.
+///
+/// The leading underscore keeps it clear of DES field codes.
+pub const SOURCE_COLUMN: &str = "_source";
+
+/// Suffix of the staging table a file is loaded into by `refresh_file_atomic`.
+const STAGING_SUFFIX: &str = "_new";
+
+/// A file that could not be loaded, with the reason.
+///
+/// Unlike `ErrorDetails`, which describes a single rejected row, this
+/// describes a whole file whose table was not (fully) loaded, such as a
+/// table creation error or a missing key field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileFailure {
+    /// The file ID that failed
+    pub file_id: String,
+    /// Why the file failed to load
+    pub reason: String,
+}
+
+impl FileFailure {
+    /// Creates a new FileFailure.
+    pub fn new(file_id: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            file_id: file_id.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for FileFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.file_id, self.reason)
+    }
+}
+
 /// Details about a processing error.
 ///
 /// This struct captures information about errors that occur during processing,
@@ -68,6 +129,44 @@ pub struct ErrorDetails {
     pub message: String,
     /// The underlying error message from SQLite
     pub error_message: String,
+    /// Line number of the offending record in the DAT file, if known
+    pub line_number: Option<usize>,
+    /// Column holding the foreign key value, if known
+    pub key_column: Option<String>,
+    /// Foreign key value of the offending record, if known
+    pub key_value: Option<String>,
+    /// Whether the row was rejected or inserted with a warning
+    pub severity: Severity,
+    /// What kind of problem this is
+    pub kind: ErrorKind,
+}
+
+/// How serious a collected processing problem is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Severity {
+    /// The row was rejected (e.g., a foreign key violation)
+    #[default]
+    Error,
+    /// The row was inserted but failed a validation rule
+    Warning,
+}
+
+/// What kind of problem an `ErrorDetails` records.
+///
+/// Separates rows whose key is well-formed but has no reference record
+/// (orphans) from rows whose key is garbage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorKind {
+    /// Any other problem
+    #[default]
+    Other,
+    /// The row's key has no matching record in the reference table
+    ForeignKeyViolation,
+    /// The row's key is NULL or does not match the key pattern, so the row
+    /// was rejected before the insert was attempted
+    MalformedKey,
+    /// The reference file repeats a key already loaded into its table
+    DuplicateKey,
 }
 
 impl ErrorDetails {
@@ -83,6 +182,154 @@ impl ErrorDetails {
             table_name,
             message,
             error_message,
+            line_number: None,
+            key_column: None,
+            key_value: None,
+            severity: Severity::Error,
+            kind: ErrorKind::Other,
+        }
+    }
+
+    /// Sets the severity of this error.
+    ///
+    /// # Arguments
+    ///
+    /// * `severity` - The severity to record
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Sets the kind of this error.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The kind to record
+    pub fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Attaches the record location and foreign key to this error.
+    ///
+    /// Lets tools aggregate violations by missing key without parsing
+    /// `message`.
+    ///
+    /// # Arguments
+    ///
+    /// * `line_number` - Line number of the offending record
+    /// * `key_column` - Column holding the foreign key value
+    /// * `key_value` - Foreign key value of the offending record
+    pub fn with_record(
+        mut self,
+        line_number: usize,
+        key_column: Option<String>,
+        key_value: Option<String>,
+    ) -> Self {
+        self.line_number = Some(line_number);
+        self.key_column = key_column;
+        self.key_value = key_value;
+        self
+    }
+}
+
+/// A declarative check applied to a column's coerced value while loading.
+///
+/// Registered with `DataHandler::add_validation`. Failures are recorded as
+/// `Severity::Warning` errors; the row is still inserted. Apart from
+/// `NonNull`, rules pass NULL values.
+#[derive(Debug, Clone)]
+pub enum ValidationRule {
+    /// The value must match the regular expression
+    MatchesRegex(regex::Regex),
+    /// The value must be one of the listed values
+    InSet(Vec<String>),
+    /// The value's length in characters must be within `min..=max`
+    LengthRange {
+        /// Minimum length
+        min: usize,
+        /// Maximum length
+        max: usize,
+    },
+    /// The value must not be NULL
+    NonNull,
+}
+
+impl ValidationRule {
+    /// Checks a coerced value, returning why it failed.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The coerced value (None for NULL)
+    ///
+    /// # Returns
+    ///
+    /// `None` if the value passes, otherwise a description of the failure.
+    pub fn check(&self, value: Option<&str>) -> Option<String> {
+        match (self, value) {
+            (Self::NonNull, None) => Some("value is NULL".to_string()),
+            (_, None) | (Self::NonNull, Some(_)) => None,
+            (Self::MatchesRegex(regex), Some(value)) => (!regex.is_match(value))
+                .then(|| format!("{:?} does not match /{}/", value, regex.as_str())),
+            (Self::InSet(allowed), Some(value)) => (!allowed.iter().any(|a| a == value))
+                .then(|| format!("{:?} is not one of {:?}", value, allowed)),
+            (Self::LengthRange { min, max }, Some(value)) => {
+                let length = value.chars().count();
+                (length < *min || length > *max).then(|| {
+                    format!("{:?} has length {} (expected {}..={})", value, length, min, max)
+                })
+            }
+        }
+    }
+}
+
+/// The key column of a processed table and the table it references.
+///
+/// Returned by `DataHandler::relationships` for drawing the database schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relationship {
+    /// The file ID the table was loaded from
+    pub file_id: String,
+    /// The table name in snake_case
+    pub table: String,
+    /// The table's key column (the primary key of the reference table)
+    pub key_column: String,
+    /// The table referenced by the key column (None for the reference table)
+    pub target_table: Option<String>,
+    /// The column referenced by the key column (None for the reference table)
+    pub target_column: Option<String>,
+}
+
+/// A recommended index, with the statement that would create it.
+///
+/// Returned by `DataHandler::suggest_indexes`; nothing is executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexSuggestion {
+    /// The table to index
+    pub table: String,
+    /// The column to index
+    pub column: String,
+    /// Why the index is recommended
+    pub reason: String,
+    /// The `CREATE INDEX IF NOT EXISTS` statement for the index
+    pub statement: String,
+}
+
+impl IndexSuggestion {
+    fn new(table: &str, column: &str, reason: String) -> Self {
+        let index_name = format!("idx_{}_{}", table, to_snake_case(column));
+        let statement = format!(
+            "CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+            quote_identifier(&index_name),
+            quote_identifier(table),
+            quote_identifier(column)
+        );
+
+        Self {
+            table: table.to_string(),
+            column: column.to_string(),
+            reason,
+            statement,
         }
     }
 }
@@ -96,12 +343,469 @@ pub struct ProcessingResults {
     pub processed: usize,
     /// Errors encountered during processing (typically foreign key violations)
     pub errors: Vec<ErrorDetails>,
+    /// Number of values coerced to NULL, per column
+    pub null_counts: HashMap<String, usize>,
+    /// Summary statistics of each DECIMAL column
+    pub numeric_stats: HashMap<String, NumericStats>,
+    /// Number of exact duplicate records skipped (see `RowDeduplication`)
+    pub deduplicated: usize,
+}
+
+/// Running summary statistics of a numeric column.
+///
+/// Accumulated for DECIMAL columns during the insert pass. NULLs and values
+/// that do not parse as numbers are not counted.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NumericStats {
+    /// Number of numeric values seen
+    pub count: usize,
+    /// Smallest value seen
+    pub min: f64,
+    /// Largest value seen
+    pub max: f64,
+    /// Sum of all values seen
+    pub sum: f64,
+}
+
+impl NumericStats {
+    /// Adds a value to the statistics.
+    pub fn add(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    /// Returns the mean of the values seen, or None if there were none.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+
+    /// Combines statistics accumulated separately over the same column.
+    pub fn merge(&mut self, other: &NumericStats) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.count += other.count;
+        self.sum += other.sum;
+    }
 }
 
 impl ProcessingResults {
     /// Creates a new ProcessingResults instance.
     pub fn new(processed: usize, errors: Vec<ErrorDetails>) -> Self {
-        Self { processed, errors }
+        Self {
+            processed,
+            errors,
+            null_counts: HashMap::new(),
+            numeric_stats: HashMap::new(),
+            deduplicated: 0,
+        }
+    }
+
+    /// Adds the results of another part of the same file, such as a range
+    /// loaded in parallel.
+    pub fn merge(&mut self, other: ProcessingResults) {
+        self.processed += other.processed;
+        self.deduplicated += other.deduplicated;
+        self.errors.extend(other.errors);
+        for (column, count) in other.null_counts {
+            *self.null_counts.entry(column).or_default() += count;
+        }
+        for (column, stats) in other.numeric_stats {
+            self.numeric_stats.entry(column).or_default().merge(&stats);
+        }
+    }
+
+    /// Returns the columns whose every processed value was NULL, sorted by name.
+    ///
+    /// A column that is entirely NULL usually signals a misaligned field in
+    /// the DES file. Returns nothing if no records were processed.
+    pub fn fully_null_columns(&self) -> Vec<&str> {
+        if self.processed == 0 {
+            return Vec::new();
+        }
+
+        let mut columns: Vec<&str> = self
+            .null_counts
+            .iter()
+            .filter(|(_, count)| **count == self.processed)
+            .map(|(column, _)| column.as_str())
+            .collect();
+        columns.sort_unstable();
+        columns
+    }
+}
+
+/// A row that references a missing parent row.
+///
+/// Reported by `PRAGMA foreign_key_check`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKeyViolation {
+    /// The table containing the orphaned row
+    pub table_name: String,
+    /// The rowid of the orphaned row, if the table has one
+    pub rowid: Option<i64>,
+    /// The referenced (parent) table
+    pub parent_table: String,
+}
+
+/// Results of a database-wide integrity check.
+///
+/// Returned by `DataHandler::verify_integrity`. Unlike the per-row
+/// `errors` collected during load, this reflects the final state of the
+/// database, including rows inserted while foreign key enforcement was off.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Rows that violate a foreign key constraint
+    pub foreign_key_violations: Vec<ForeignKeyViolation>,
+    /// Problems reported by `PRAGMA integrity_check` (empty when "ok")
+    pub integrity_errors: Vec<String>,
+}
+
+impl IntegrityReport {
+    /// Returns whether no violations or integrity problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.foreign_key_violations.is_empty() && self.integrity_errors.is_empty()
+    }
+}
+
+/// Source and loaded record counts of one file.
+///
+/// Returned by `DataHandler::verify_counts`. Every record of the DAT file
+/// should either be in the table, or have been rejected with an error or
+/// skipped as a duplicate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountCheck {
+    /// The file ID
+    pub file_id: String,
+    /// The table the file was loaded into
+    pub table_name: String,
+    /// Lines in the DAT file, as counted by `count_lines`
+    pub source_records: u64,
+    /// Rows in the table
+    pub table_rows: u64,
+    /// Records rejected with a `Severity::Error` error
+    pub rejected: u64,
+    /// Records skipped as exact duplicates
+    pub skipped: u64,
+}
+
+impl CountCheck {
+    /// Returns the number of source records not accounted for.
+    ///
+    /// Positive when records were silently dropped, negative when the
+    /// table holds more rows than the file (for example, left over from an
+    /// earlier load).
+    pub fn discrepancy(&self) -> i64 {
+        self.source_records as i64 - (self.table_rows + self.rejected + self.skipped) as i64
+    }
+
+    /// Returns whether every source record is accounted for.
+    pub fn is_ok(&self) -> bool {
+        self.discrepancy() == 0
+    }
+}
+
+impl std::fmt::Display for CountCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}): {} source records, {} rows, {} rejected, {} skipped",
+            self.file_id, self.table_name, self.source_records, self.table_rows, self.rejected, self.skipped
+        )?;
+        if !self.is_ok() {
+            write!(f, " ({} unaccounted for)", self.discrepancy())?;
+        }
+        Ok(())
+    }
+}
+
+/// SQLite storage settings applied when a database connection is opened.
+///
+/// The defaults favour bulk loading: a 16 KiB page size reduces the number of
+/// page writes for wide rows, and a 64 MiB page cache keeps hot B-tree pages
+/// in memory across batches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DatabaseOptions {
+    /// Database page size in bytes (a power of two between 512 and 65536).
+    ///
+    /// Only takes effect on a database that has not been written to yet.
+    pub page_size: u32,
+    /// Page cache size, as passed to `PRAGMA cache_size`.
+    ///
+    /// Positive values are a number of pages; negative values are a size in KiB.
+    pub cache_size: i64,
+}
+
+impl Default for DatabaseOptions {
+    fn default() -> Self {
+        Self {
+            page_size: 16_384,
+            cache_size: -65_536,
+        }
+    }
+}
+
+/// How DECIMAL fields are stored in SQLite.
+///
+/// REAL columns round values to a 64-bit float, so monetary fields such as
+/// `CPCOPBAL` can drift. `Exact` keeps the trimmed digit string from the DAT
+/// file. It uses TEXT rather than NUMERIC affinity because SQLite converts
+/// any well-formed decimal literal in a NUMERIC column to REAL as well;
+/// arithmetic in SQL still works, as SQLite casts text operands to numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecimalStorage {
+    /// Store DECIMAL fields as REAL (the default)
+    #[default]
+    Real,
+    /// Store DECIMAL fields as their exact digit string
+    Exact,
+}
+
+/// Journal files found next to a database when it was opened.
+///
+/// A leftover rollback journal or WAL usually means a previous run crashed
+/// mid-transaction. SQLite rolls back a hot journal automatically on the
+/// first read, so this records what was found for reporting; a WAL can be
+/// folded back into the database with `DataHandler::checkpoint_wal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JournalState {
+    /// No journal files were present
+    #[default]
+    Clean,
+    /// A non-empty `-journal` rollback journal was present
+    RollbackJournal,
+    /// A non-empty `-wal` write-ahead log was present
+    Wal,
+}
+
+impl JournalState {
+    /// Inspects the journal files next to `database_path`.
+    ///
+    /// Empty journal files are ignored, since SQLite leaves them behind in
+    /// some journal modes without any pending changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `database_path` - Path to the SQLite database file
+    pub fn detect(database_path: &Path) -> Self {
+        let non_empty = |suffix: &str| {
+            let mut path = database_path.as_os_str().to_owned();
+            path.push(suffix);
+            std::fs::metadata(PathBuf::from(path))
+                .map(|metadata| metadata.len() > 0)
+                .unwrap_or(false)
+        };
+
+        if non_empty("-wal") {
+            Self::Wal
+        } else if non_empty("-journal") {
+            Self::RollbackJournal
+        } else {
+            Self::Clean
+        }
+    }
+}
+
+/// Where the reference key set used for foreign key pre-checks is kept.
+///
+/// See `DataHandler::precheck_foreign_keys`. With millions of offenders the
+/// in-memory set is large; `OnDisk` trades some speed for memory bounded
+/// by SQLite's page cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReferenceKeyStore {
+    /// A hash set in memory (the default)
+    #[default]
+    InMemory,
+    /// A table with a unique index in a temporary on-disk SQLite database,
+    /// deleted when the set is dropped
+    OnDisk,
+}
+
+/// The key values of the reference table.
+///
+/// Returned by `DataHandler::reference_keys`, backed as chosen with
+/// `DataHandler::set_reference_key_store`. Keys are compared as text.
+#[derive(Debug)]
+pub struct ReferenceKeySet {
+    keys: ReferenceKeys,
+}
+
+#[derive(Debug)]
+enum ReferenceKeys {
+    InMemory(HashSet<String>),
+    OnDisk(Connection),
+}
+
+impl ReferenceKeySet {
+    /// Reads the non-null values of `column` in `table` into a new set.
+    ///
+    /// `table` must already be quoted (and schema-qualified if needed).
+    fn load(database: &Connection, table: &str, column: &str, store: ReferenceKeyStore) -> Result<Self> {
+        let sql = format!(
+            "SELECT CAST({column} AS TEXT) FROM {table} WHERE {column} IS NOT NULL",
+            column = quote_identifier(column),
+            table = table
+        );
+        let mut stmt = database
+            .prepare(&sql)
+            .with_context(|| format!("Failed to read reference keys from {}", table))?;
+        let mut rows = stmt.query([])?;
+
+        let keys = match store {
+            ReferenceKeyStore::InMemory => {
+                let mut keys = HashSet::new();
+                while let Some(row) = rows.next()? {
+                    keys.insert(row.get(0)?);
+                }
+                ReferenceKeys::InMemory(keys)
+            }
+            ReferenceKeyStore::OnDisk => {
+                // An empty path opens a private temporary database on disk
+                let mut keys = Connection::open("").context("Failed to create on-disk key store")?;
+                let tx = keys.transaction()?;
+                tx.execute("CREATE TABLE reference_keys (key TEXT PRIMARY KEY) WITHOUT ROWID", [])?;
+                {
+                    let mut insert = tx.prepare("INSERT OR IGNORE INTO reference_keys (key) VALUES (?1)")?;
+                    while let Some(row) = rows.next()? {
+                        insert.execute([row.get::<_, String>(0)?])?;
+                    }
+                }
+                tx.commit().context("Failed to fill on-disk key store")?;
+                ReferenceKeys::OnDisk(keys)
+            }
+        };
+
+        Ok(Self { keys })
+    }
+
+    /// Checks whether `key` is a reference key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the on-disk store cannot be queried.
+    pub fn contains(&self, key: &str) -> Result<bool> {
+        match &self.keys {
+            ReferenceKeys::InMemory(keys) => Ok(keys.contains(key)),
+            ReferenceKeys::OnDisk(keys) => Ok(keys
+                .prepare_cached("SELECT 1 FROM reference_keys WHERE key = ?1")?
+                .exists([key])?),
+        }
+    }
+
+    /// Returns the number of distinct keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the on-disk store cannot be queried.
+    pub fn len(&self) -> Result<usize> {
+        match &self.keys {
+            ReferenceKeys::InMemory(keys) => Ok(keys.len()),
+            ReferenceKeys::OnDisk(keys) => Ok(keys.query_row(
+                "SELECT COUNT(*) FROM reference_keys",
+                [],
+                |row| row.get::<_, i64>(0),
+            )? as usize),
+        }
+    }
+
+    /// Returns whether the set has no keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the on-disk store cannot be queried.
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+/// Whether and how exact duplicate records are skipped during a load.
+///
+/// Duplicates are detected by a SHA-256 hash of each record's field values.
+/// The set of seen hashes grows with the file, so `OnDisk` and `PerBatch`
+/// are offered to bound memory use on large files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RowDeduplication {
+    /// Every record is inserted (the default)
+    #[default]
+    Off,
+    /// Hashes of all records of a file are kept in memory
+    InMemory,
+    /// Hashes are kept in a temporary on-disk SQLite database, deleted
+    /// when the file has been loaded
+    OnDisk,
+    /// Only duplicates within the same insert batch are skipped, which
+    /// catches adjacent repeats with memory bounded by `BATCH_SIZE`
+    PerBatch,
+}
+
+/// Hashes of the records seen so far in a load.
+enum RowHashes {
+    Off,
+    InMemory(HashSet<[u8; 32]>),
+    OnDisk(Connection),
+}
+
+impl RowHashes {
+    fn new(mode: RowDeduplication) -> Result<Self> {
+        Ok(match mode {
+            RowDeduplication::Off => Self::Off,
+            RowDeduplication::InMemory | RowDeduplication::PerBatch => Self::InMemory(HashSet::new()),
+            RowDeduplication::OnDisk => {
+                // An empty path opens a private temporary database on disk
+                let hashes = Connection::open("").context("Failed to create on-disk row hash store")?;
+                hashes.execute("CREATE TABLE row_hashes (hash BLOB PRIMARY KEY) WITHOUT ROWID", [])?;
+                Self::OnDisk(hashes)
+            }
+        })
+    }
+
+    /// Records the values of a record, returning `false` if they were seen before.
+    fn insert<'a>(&mut self, values: impl Iterator<Item = Option<&'a str>>) -> Result<bool> {
+        if let Self::Off = self {
+            return Ok(true);
+        }
+
+        let mut hasher = Sha256::new();
+        for value in values {
+            match value {
+                None => hasher.update([0]),
+                Some(value) => {
+                    hasher.update([1]);
+                    hasher.update((value.len() as u64).to_le_bytes());
+                    hasher.update(value.as_bytes());
+                }
+            }
+        }
+        let hash: [u8; 32] = hasher.finalize().into();
+
+        match self {
+            Self::Off => Ok(true),
+            Self::InMemory(hashes) => Ok(hashes.insert(hash)),
+            Self::OnDisk(hashes) => Ok(hashes
+                .prepare_cached("INSERT OR IGNORE INTO row_hashes (hash) VALUES (?1)")?
+                .execute([&hash[..]])?
+                == 1),
+        }
+    }
+
+    /// Forgets the records seen so far.
+    fn clear(&mut self) {
+        if let Self::InMemory(hashes) = self {
+            hashes.clear();
+        }
     }
 }
 
@@ -146,6 +850,90 @@ pub struct DataHandler {
     pub errors: Vec<ErrorDetails>,
     /// Collection of file IDs that failed due to missing or invalid DES files
     pub des_file_failures: Vec<String>,
+    /// Files that failed to load, with the reason for each
+    pub file_failures: Vec<FileFailure>,
+    /// Columns to build lookup tables for, keyed by file ID
+    lookup_columns: HashMap<String, Vec<String>>,
+    /// Indexes `finalize` creates, as (columns, unique), keyed by file ID
+    indexes: HashMap<String, Vec<(Vec<String>, bool)>>,
+    /// Whether `check_strict` fails when violations exceed `max_violations`
+    strict: bool,
+    /// Number of collected errors tolerated in strict mode
+    max_violations: usize,
+    /// Journal files found when the database was opened
+    journal_state: JournalState,
+    /// Attached schema that tables are created in, if not `main`
+    schema: Option<String>,
+    /// Rows written between explicit writer flushes during export
+    export_flush_interval: usize,
+    /// Text written for NULL values in CSV exports
+    csv_null_repr: String,
+    /// Whether `init` reuses a populated reference table instead of reloading it
+    reuse_existing_reference: bool,
+    /// Number of connections that load each file in parallel
+    intra_file_workers: usize,
+    /// Number of connections that load the reference file, if not `intra_file_workers`
+    reference_workers: Option<usize>,
+    /// Line number of the record before a range worker's first record
+    line_offset: usize,
+    /// Reference keys a range worker leaves to an earlier range
+    shadowed_keys: HashSet<String>,
+    /// Where `reference_keys` keeps the reference key set
+    reference_key_store: ReferenceKeyStore,
+    /// Number of duplicate records skipped, keyed by file ID
+    skipped_rows: HashMap<String, usize>,
+    /// Parsed DES files, keyed by file ID, so each is read once per run
+    descriptions: Mutex<HashMap<String, FileDescription>>,
+    /// Connection tuning for the handlers that load ranges in parallel
+    worker_config: WorkerConfig,
+    /// Settings and per-table state that loading records reads; range
+    /// workers receive a copy
+    load: LoadSettings,
+}
+
+/// The parts of a `DataHandler` that loading a file depends on.
+///
+/// Kept together so a range worker (see `DataHandler::set_intra_file_workers`)
+/// loads with exactly the settings of the handler that spawned it.
+#[derive(Debug, Clone)]
+struct LoadSettings {
+    /// Key column of each created table, keyed by file ID
+    key_columns: HashMap<String, String>,
+    /// The data directory containing the extracted DES and DAT files
+    data_dir: PathBuf,
+    /// Whether each row records its source file ID in a `_source` column
+    store_source_id: bool,
+    /// How control characters in parsed field values are handled
+    control_chars: ControlCharPolicy,
+    /// Whether blank field values are stored as NULL rather than ""
+    empty_to_null: bool,
+    /// Column type used for DECIMAL fields
+    decimal_storage: DecimalStorage,
+    /// Preferred leading column order, keyed by file ID
+    column_orders: HashMap<String, Vec<String>>,
+    /// Time after which loading stops with an error
+    deadline: Option<Instant>,
+    /// Columns that also keep their untrimmed value, keyed by file ID
+    raw_shadow_columns: HashMap<String, Vec<String>>,
+    /// Validation rules as `(column, rule)` pairs, keyed by file ID
+    validations: HashMap<String, Vec<(String, ValidationRule)>>,
+    /// Y/N flag columns stored as INTEGER 1/0, keyed by file ID
+    boolean_columns: HashMap<String, Vec<String>>,
+    /// File IDs loaded without a foreign key because their key does not
+    /// match the reference key
+    standalone_files: HashSet<String>,
+    /// Whether each record's key is checked before it is inserted
+    validate_keys: bool,
+    /// Pattern a well-formed key must match when keys are validated
+    key_pattern: Option<regex::Regex>,
+    /// Key field chosen explicitly, keyed by file ID
+    key_overrides: HashMap<String, String>,
+    /// Whether fields named after SQLite keywords get a suffixed column name
+    rename_reserved_columns: bool,
+    /// Whether exact duplicate records are skipped during a load
+    row_deduplication: RowDeduplication,
+    /// Whether records longer than the schema are reported after a load
+    report_trailing_bytes: bool,
 }
 
 impl DataHandler {
@@ -175,541 +963,785 @@ impl DataHandler {
     /// # }
     /// ```
     pub fn new(database_path: &str) -> Result<Self> {
-        let database = Connection::open(database_path)
-            .with_context(|| format!("Failed to open database: {}", database_path))?;
-
-        database
-            .pragma_update(None, "foreign_keys", "ON")
-            .context("Failed to enable foreign key constraints")?;
-
-        database
-            .execute(
-                "CREATE TABLE IF NOT EXISTS column_descriptions (
-                    table_name TEXT NOT NULL,
-                    column_name TEXT NOT NULL,
-                    description TEXT NOT NULL
-                )",
-                [],
-            )
-            .context("Failed to create column_descriptions table")?;
-
-        Ok(Self {
-            database,
-            reference_file: None,
-            reference_table_name: None,
-            reference_field: None,
-            is_initialized: false,
-            processed_files: HashSet::new(),
-            errors: Vec::new(),
-            des_file_failures: Vec::new(),
-        })
+        Self::with_options(database_path, &DatabaseOptions::default())
     }
 
-    /// Initializes the handler with a reference file.
-    ///
-    /// The reference file serves as the primary key source for the database.
-    /// All other tables will have foreign key constraints referencing this table.
+    /// Creates a new `DataHandler` with explicit SQLite storage settings.
     ///
-    /// This method must be called before processing any other files.
-    ///
-    /// **Reference File Processing Guarantees:**
-    /// - Uses `PRAGMA synchronous=FULL` for maximum data durability
-    /// - Reference file must complete successfully before any parallel processing begins
-    /// - Returns early with error if reference file processing fails
-    /// - Maintains existing initialization logic from lines 232-252
+    /// The page size and cache size are applied before any table is created,
+    /// so the page size takes effect on a fresh database. On an existing
+    /// database the page size is left unchanged.
     ///
     /// # Arguments
     ///
-    /// * `reference_file` - The file to use as the reference table
-    /// * `pb` - Optional progress bar for tracking reference file processing
+    /// * `database_path` - Path to the SQLite database file
+    /// * `options` - The page size and cache size to apply
     ///
     /// # Errors
     ///
     /// Returns an error if:
-    /// - The reference file's schema doesn't contain a recognized primary key field
-    /// - PRAGMA synchronous=FULL cannot be set
-    /// - The reference file cannot be processed
+    /// - The database cannot be opened or created
+    /// - The database is locked by another process, or a leftover journal
+    ///   from a crashed run cannot be recovered
+    /// - A pragma cannot be applied
+    /// - Foreign key enforcement cannot be enabled
     ///
     /// # Example
     ///
     /// ```no_run
-    /// use ncdac_opi_parser::data_handler::DataHandler;
-    /// use ncdac_opi_parser::files::get_file_by_id;
+    /// use ncdac_opi_parser::data_handler::{DatabaseOptions, DataHandler};
     ///
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let mut handler = DataHandler::new("database.db")?;
-    /// let reference = get_file_by_id("OFNT3AA1").unwrap();
-    /// let results = handler.init(reference, None)?;
+    /// let options = DatabaseOptions { page_size: 65_536, ..Default::default() };
+    /// let handler = DataHandler::with_options("my_database.db", &options)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn init(&mut self, reference_file: &FileMetadata, pb: Option<&ProgressBar>) -> Result<ProcessingResults> {
-        set_pragma_synchronous_full(&self.database)
-            .context("Failed to set PRAGMA synchronous=FULL for reference table processing")?;
+    pub fn with_options(database_path: &str, options: &DatabaseOptions) -> Result<Self> {
+        let journal_state = JournalState::detect(Path::new(database_path));
 
-        let reference_table_name = to_snake_case(reference_file.name);
-        let reference_description = FileDescription::new(reference_file.id)?;
+        let database = Connection::open(database_path)
+            .with_context(|| format!("Failed to open database: {}", database_path))?;
 
-        let reference_field = get_primary_key_field(&reference_description.schema)
-            .ok_or_else(|| {
-                anyhow!(
-                    "Reference table {} does not contain an expected key field",
-                    reference_table_name
-                )
-            })?;
+        database
+            .pragma_update(None, "page_size", options.page_size)
+            .context("Failed to set page size")?;
 
-        self.reference_file = Some(*reference_file);
-        self.reference_table_name = Some(reference_table_name);
-        self.reference_field = Some(reference_field.to_string());
-        self.is_initialized = true;
+        database
+            .pragma_update(None, "cache_size", options.cache_size)
+            .context("Failed to set cache size")?;
 
-        let results = self.process_file(reference_file, pb)?;
+        database
+            .pragma_update(None, "foreign_keys", "ON")
+            .context("Failed to enable foreign key constraints")?;
 
-        results.ok_or_else(|| anyhow!("Failed to process reference file: reference file processing must complete successfully before continuing"))
+        create_column_descriptions_table(&database, &quote_identifier("column_descriptions"))
+            .map_err(|e| locked_database_error(e, database_path, journal_state))
+            .context("Failed to create column_descriptions table")?;
+
+        Ok(Self::with_connection(database, journal_state))
     }
 
-    /// Creates a table for the specified file.
-    ///
-    /// Generates a CREATE TABLE statement from the file's DES schema and executes it.
-    /// Maps field types to SQLite types (DECIMAL → REAL, everything else → TEXT).
+    /// Creates a `DataHandler` on a connection owned by the caller.
     ///
-    /// If the table is the reference table, adds a PRIMARY KEY constraint.
-    /// Otherwise, adds a FOREIGN KEY constraint referencing the reference table.
+    /// Lets an application that manages its own connection (with its own
+    /// pragmas, attached databases and tables) load NC DAC tables alongside
+    /// its schema. Foreign key enforcement is switched on and the
+    /// column_descriptions table is created; page and cache size are left
+    /// as the caller configured them.
     ///
     /// # Arguments
     ///
-    /// * `file` - The file metadata for which to create a table
+    /// * `database` - The connection to load into
     ///
-    /// # Returns
+    /// # Errors
     ///
-    /// The table name (in snake_case) that was created.
+    /// Returns an error if foreign key enforcement cannot be enabled or the
+    /// column_descriptions table cannot be created.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use rusqlite::Connection;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let conn = Connection::open("app.db")?;
+    /// let handler = DataHandler::from_connection(conn)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_connection(database: Connection) -> Result<Self> {
+        database
+            .pragma_update(None, "foreign_keys", "ON")
+            .context("Failed to enable foreign key constraints")?;
+
+        create_column_descriptions_table(&database, &quote_identifier("column_descriptions"))
+            .context("Failed to create column_descriptions table")?;
+
+        Ok(Self::with_connection(database, JournalState::Clean))
+    }
+
+    /// Wraps a prepared connection in a handler with default settings.
+    fn with_connection(database: Connection, journal_state: JournalState) -> Self {
+        Self {
+            database,
+            reference_file: None,
+            reference_table_name: None,
+            reference_field: None,
+            is_initialized: false,
+            processed_files: HashSet::new(),
+            errors: Vec::new(),
+            des_file_failures: Vec::new(),
+            file_failures: Vec::new(),
+            lookup_columns: HashMap::new(),
+            indexes: HashMap::new(),
+            strict: false,
+            max_violations: 0,
+            journal_state,
+            schema: None,
+            export_flush_interval: DEFAULT_EXPORT_FLUSH_ROWS,
+            csv_null_repr: String::new(),
+            reuse_existing_reference: false,
+            intra_file_workers: 1,
+            reference_workers: None,
+            line_offset: 0,
+            shadowed_keys: HashSet::new(),
+            reference_key_store: ReferenceKeyStore::default(),
+            skipped_rows: HashMap::new(),
+            load: LoadSettings {
+                data_dir: data_directory(),
+                store_source_id: false,
+                control_chars: ControlCharPolicy::default(),
+                empty_to_null: true,
+                decimal_storage: DecimalStorage::default(),
+                column_orders: HashMap::new(),
+                deadline: None,
+                raw_shadow_columns: HashMap::new(),
+                validations: HashMap::new(),
+                boolean_columns: HashMap::new(),
+                key_columns: HashMap::new(),
+                key_overrides: HashMap::new(),
+                standalone_files: HashSet::new(),
+                validate_keys: false,
+                key_pattern: None,
+                rename_reserved_columns: false,
+                row_deduplication: RowDeduplication::default(),
+                report_trailing_bytes: false,
+            },
+            descriptions: Mutex::new(HashMap::new()),
+            worker_config: WorkerConfig::default(),
+        }
+    }
+
+    /// Creates a `DataHandler` that writes its tables into an attached database.
+    ///
+    /// Opens `main_path` as usual, then `ATTACH`es `attach_path` under
+    /// `schema_name`. Every table, index, lookup table and column description
+    /// the handler creates goes into the attached schema, which lets a run
+    /// write a fresh snapshot alongside an existing database.
+    ///
+    /// Foreign keys are declared against the unqualified reference table,
+    /// since SQLite resolves a foreign key's parent in the child's own schema.
+    ///
+    /// # Arguments
+    ///
+    /// * `main_path` - Path to the main SQLite database file
+    /// * `attach_path` - Path to the database file to attach
+    /// * `schema_name` - Name the attached database is known by
     ///
     /// # Errors
     ///
-    /// Returns an error if:
-    /// - The file's schema doesn't contain a recognized primary key field
-    /// - The table creation SQL fails to execute
+    /// Returns an error if either database cannot be opened, the attach
+    /// fails (for example because `schema_name` is already in use), or the
+    /// column_descriptions table cannot be created in the attached schema.
     ///
     /// # Example
     ///
     /// ```no_run
     /// use ncdac_opi_parser::data_handler::DataHandler;
-    /// use ncdac_opi_parser::files::get_file_by_id;
     ///
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let handler = DataHandler::new("database.db")?;
-    /// let file = get_file_by_id("OFNT1BA1").unwrap();
-    /// // Note: handler must be initialized first in real usage
-    /// // let table_name = handler.create_table_for_file(file)?;
+    /// let handler = DataHandler::new_with_schema("current.db", "snapshot.db", "snapshot")?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn create_table_for_file(&self, file: &FileMetadata) -> Result<(String, FileDescription)> {
-        let table_name = to_snake_case(file.name);
-        let description = FileDescription::new(file.id)?;
+    pub fn new_with_schema(main_path: &str, attach_path: &str, schema_name: &str) -> Result<Self> {
+        let mut handler = Self::new(main_path)?;
 
-        let primary_key = get_primary_key_field(&description.schema).ok_or_else(|| {
-            anyhow!(
-                "Table {} does not contain an expected key field",
-                table_name
+        handler
+            .database
+            .execute(
+                &format!("ATTACH DATABASE ?1 AS {}", quote_identifier(schema_name)),
+                [attach_path],
             )
-        })?;
+            .with_context(|| format!("Failed to attach {} as {}", attach_path, schema_name))?;
 
-        let columns: Vec<String> = description
-            .schema
-            .iter()
-            .map(|(field, definition)| {
-                let column_type = map_type_to_sqlite(&definition.field_type);
-                format!("{} {}", field, column_type)
-            })
-            .collect();
+        handler.schema = Some(schema_name.to_string());
 
-        let mut constraints = Vec::new();
+        create_column_descriptions_table(&handler.database, &handler.qualified("column_descriptions"))
+            .with_context(|| format!("Failed to create column_descriptions table in {}", schema_name))?;
 
-        if Some(&table_name) == self.reference_table_name.as_ref() {
-            constraints.push(format!("PRIMARY KEY ({})", primary_key));
-        } else {
-            let reference_table = self.reference_table_name.as_ref().ok_or_else(|| {
-                anyhow!("Cannot create table: handler not initialized with reference table")
-            })?;
-            let reference_field = self.reference_field.as_ref().ok_or_else(|| {
-                anyhow!("Cannot create table: reference field not set")
-            })?;
+        Ok(handler)
+    }
 
-            constraints.push(format!(
-                "FOREIGN KEY ({}) REFERENCES {}({})",
-                primary_key, reference_table, reference_field
+    /// Returns the attached schema tables are created in, or `None` for `main`.
+    pub fn schema(&self) -> Option<&str> {
+        self.schema.as_deref()
+    }
+
+    /// Quotes a table name, prefixed with the attached schema if there is one.
+    fn qualified(&self, table: &str) -> String {
+        qualified_name(self.schema.as_deref(), table)
+    }
+
+    /// Returns the journal files found when the database was opened.
+    ///
+    /// Anything other than `JournalState::Clean` means a previous process
+    /// was interrupted (or is still running). A rollback journal has already
+    /// been recovered by SQLite once the handler opens successfully.
+    pub fn journal_state(&self) -> JournalState {
+        self.journal_state
+    }
+
+    /// Checkpoints a leftover write-ahead log into the database.
+    ///
+    /// Copies every frame in the `-wal` file into the main database and
+    /// truncates the log, so the database file is complete on its own.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the checkpoint fails or cannot complete because
+    /// another connection is still reading or writing the database.
+    pub fn checkpoint_wal(&self) -> Result<()> {
+        let busy: i64 = self
+            .database
+            .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| row.get(0))
+            .context("Failed to checkpoint write-ahead log")?;
+
+        if busy != 0 {
+            return Err(anyhow!(
+                "Could not checkpoint write-ahead log: the database is in use by another process"
             ));
         }
 
-        let mut sql_parts = columns;
-        sql_parts.extend(constraints);
-        let sql = format!(
-            "CREATE TABLE IF NOT EXISTS {} ({})",
-            table_name,
-            sql_parts.join(", ")
-        );
+        Ok(())
+    }
 
-        self.database
-            .execute(&sql, [])
-            .with_context(|| format!("Failed to create table {}", table_name))?;
+    /// Sets the data directory that DES and DAT files are read from.
+    ///
+    /// Defaults to `utilities::data_directory()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data_dir` - The data directory containing the extracted files
+    pub fn set_data_dir(&mut self, data_dir: impl Into<PathBuf>) {
+        self.load.data_dir = data_dir.into();
+        self.clear_description_cache();
+    }
 
-        Ok((table_name, description))
+    /// Discards the cached DES schemas.
+    ///
+    /// Each file's DES is parsed once and reused by table creation, record
+    /// insertion and key checks. Clear the cache when extracted files are
+    /// replaced mid-run so the next lookup reads them again.
+    pub fn clear_description_cache(&mut self) {
+        self.descriptions.get_mut().unwrap_or_else(|e| e.into_inner()).clear();
     }
 
-    /// Inserts column descriptions from a FileDescription schema into the column_descriptions table.
+    /// Returns the schema for a file, parsing its DES on first use.
     ///
-    /// Takes the pre-parsed schema and inserts column names and descriptions
-    /// into the column_descriptions table.
+    /// Failures are not cached, so a DES that is missing now is read again
+    /// on the next call.
+    fn file_description(&self, file_id: &str) -> Result<FileDescription> {
+        let mut descriptions = self.descriptions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(description) = descriptions.get(file_id) {
+            return Ok(description.clone());
+        }
+
+        let description = FileDescription::from_data_dir(file_id, &self.load.data_dir)?;
+        descriptions.insert(file_id.to_string(), description.clone());
+        Ok(description)
+    }
+
+    /// Returns the data directory that DES and DAT files are read from.
+    pub fn data_dir(&self) -> &Path {
+        &self.load.data_dir
+    }
+
+    /// Enables or disables the `_source` provenance column.
+    ///
+    /// When enabled, tables created afterwards get a `_source` column holding
+    /// the ID of the file each row was loaded from, so rows keep their origin
+    /// when several tables are combined with `UNION ALL`. Disabled by default.
     ///
     /// # Arguments
     ///
-    /// * `table_name` - The name of the table
-    /// * `description` - The FileDescription containing the schema with descriptions
+    /// * `store_source_id` - Whether to add and fill the `_source` column
+    pub fn set_store_source_id(&mut self, store_source_id: bool) {
+        self.load.store_source_id = store_source_id;
+    }
+
+    /// Returns whether rows record their source file ID.
+    pub fn store_source_id(&self) -> bool {
+        self.load.store_source_id
+    }
+
+    /// Enables or disables strict mode.
     ///
-    /// # Errors
+    /// In strict mode, `check_strict` fails when more than `max_violations`
+    /// errors (typically foreign key violations) were collected. Validation
+    /// warnings are not counted. Loading is unaffected; violations are still
+    /// collected rather than aborting.
     ///
-    /// Returns an error if the database insertion fails.
-    pub fn insert_column_descriptions(&mut self, table_name: &str, description: &FileDescription) -> Result<()> {
-        let tx = self.database.transaction()
-            .context("Failed to begin transaction for column descriptions")?;
+    /// # Arguments
+    ///
+    /// * `strict` - Whether to enforce the violation threshold
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Sets a time after which loading stops.
+    ///
+    /// The deadline is checked before each batch is committed. Once it has
+    /// passed, the file being loaded fails with an error, leaving the rows
+    /// of earlier batches in place. `None` (the default) disables the check.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - When loading must stop, if ever
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.load.deadline = deadline;
+    }
 
+    /// Fails if the deadline set with `set_deadline` has passed.
+    fn check_deadline(&self, file: &FileMetadata) -> Result<()> {
+        if let Some(deadline) = self.load.deadline
+            && Instant::now() >= deadline
         {
-            let mut stmt = tx.prepare(
-                "INSERT INTO column_descriptions (table_name, column_name, description) VALUES (?, ?, ?)"
-            ).context("Failed to prepare INSERT statement for column descriptions")?;
-
-            for (column_name, field_def) in &description.schema {
-                stmt.execute([table_name, column_name.as_str(), field_def.description.as_str()])
-                    .with_context(|| {
-                        format!("Failed to insert description for {}.{}", table_name, column_name)
-                    })?;
-            }
+            return Err(anyhow!("Deadline reached while loading {}", file.id));
         }
-
-        tx.commit().context("Failed to commit column descriptions transaction")?;
-
         Ok(())
     }
 
-    /// Inserts records from a file into its table.
-    ///
-    /// Parses the file's DAT records and inserts them in batches within transactions.
-    /// Foreign key constraint violations are collected but don't stop processing.
+    /// Sets the number of errors tolerated in strict mode (default 0).
     ///
     /// # Arguments
     ///
-    /// * `file` - The file metadata for which to insert records
-    ///
-    /// # Returns
+    /// * `max_violations` - The largest error count that still passes
+    pub fn set_max_violations(&mut self, max_violations: usize) {
+        self.max_violations = max_violations;
+    }
+
+    /// Fails if strict mode is on and too many errors were collected.
     ///
-    /// A `ProcessingResults` with the count of processed records and any errors.
+    /// Call after all files are processed so pipelines can gate on data
+    /// quality.
     ///
     /// # Errors
     ///
-    /// Returns an error if:
-    /// - The table doesn't exist (call `create_table_for_file` first)
-    /// - A non-foreign-key database error occurs
-    /// - The data parser encounters an error
+    /// Returns an error if strict mode is enabled and `errors` holds more
+    /// than `max_violations` entries with `Severity::Error`.
     ///
     /// # Example
     ///
     /// ```no_run
     /// use ncdac_opi_parser::data_handler::DataHandler;
-    /// use ncdac_opi_parser::files::get_file_by_id;
     ///
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut handler = DataHandler::new("database.db")?;
-    /// let file = get_file_by_id("OFNT1BA1").unwrap();
-    /// // Note: table must exist first in real usage
-    /// // let results = handler.insert_records_for_file(file)?;
+    /// handler.set_strict(true);
+    /// // ... process files ...
+    /// handler.check_strict()?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn insert_records_for_file(&mut self, file: &FileMetadata, pb: Option<&ProgressBar>) -> Result<ProcessingResults> {
-        let table_name = to_snake_case(file.name);
-        let description = FileDescription::new(file.id)?;
-        let parser = DataParser::new(file.id)?;
-
-        let columns: Vec<String> = description.schema.keys().cloned().collect();
-
-        let placeholders = columns.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
-        let insert_sql = format!(
-            "INSERT INTO {} ({}) VALUES ({})",
-            table_name,
-            columns.join(", "),
-            placeholders
-        );
-
-        let mut processed = 0;
-        let mut local_errors = Vec::new();
-        let mut batch: Vec<(Vec<Option<String>>, usize)> = Vec::new();
-        let mut line_number = 0;
-
-        for record_result in parser.parse()? {
-            let record = record_result?;
-            line_number += 1;
-
-            let values: Vec<Option<String>> = columns
-                .iter()
-                .map(|column| record.get(column).cloned().unwrap_or(None))
-                .collect();
-
-            batch.push((values, line_number));
-
-            if batch.len() >= BATCH_SIZE {
-                let batch_errors = self.commit_batch(&insert_sql, &batch, file, &table_name)?;
-                local_errors.extend(batch_errors);
-                processed += batch.len();
-
-                if let Some(progress) = pb {
-                    progress.inc(batch.len() as u64);
-                }
-
-                batch.clear();
-            }
+    pub fn check_strict(&self) -> Result<()> {
+        let violations = self
+            .errors
+            .iter()
+            .filter(|error| error.severity == Severity::Error)
+            .count();
+
+        if self.strict && violations > self.max_violations {
+            return Err(anyhow!(
+                "Strict mode: {} errors encountered (at most {} allowed)",
+                violations,
+                self.max_violations
+            ));
         }
 
-        if !batch.is_empty() {
-            let batch_errors = self.commit_batch(&insert_sql, &batch, file, &table_name)?;
-            local_errors.extend(batch_errors);
-            processed += batch.len();
-
-            if let Some(progress) = pb {
-                progress.inc(batch.len() as u64);
-            }
-        }
+        Ok(())
+    }
 
-        self.errors.extend(local_errors.clone());
+    /// Sets how control characters in field values are handled when loading.
+    ///
+    /// Defaults to `ControlCharPolicy::Preserve`, which stores values exactly
+    /// as they appear in the DAT file.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The control character policy
+    pub fn set_control_char_policy(&mut self, policy: ControlCharPolicy) {
+        self.load.control_chars = policy;
+    }
 
-        Ok(ProcessingResults::new(processed, local_errors))
+    /// Sets whether blank field values are stored as NULL when loading.
+    ///
+    /// Defaults to `true`. When `false`, blank fixed-width fields are stored
+    /// as empty strings (see `DataParser::set_empty_to_null`).
+    ///
+    /// # Arguments
+    ///
+    /// * `empty_to_null` - Whether blank values become NULL
+    pub fn set_empty_to_null(&mut self, empty_to_null: bool) {
+        self.load.empty_to_null = empty_to_null;
     }
 
-    /// Commits a batch of records within a transaction.
+    /// Sets whether each record's key is checked before it is inserted.
     ///
-    /// This is an internal helper that executes a batch of INSERT statements
-    /// within a single transaction. Foreign key violations are caught and
-    /// collected without stopping the transaction.
+    /// When enabled, a record whose key column is NULL, or does not match
+    /// the pattern set with `set_key_pattern`, is not inserted. It is
+    /// recorded as an error of kind `ErrorKind::MalformedKey` instead, so
+    /// garbage keys are reported apart from foreign key violations.
+    /// Defaults to `false`.
     ///
     /// # Arguments
     ///
-    /// * `insert_sql` - The prepared INSERT statement
-    /// * `batch` - The batch of records to insert (values and line numbers)
-    /// * `file` - The file metadata for error reporting
-    /// * `table_name` - The table name for error reporting
+    /// * `validate_keys` - Whether to check keys before inserting
+    pub fn set_key_validation(&mut self, validate_keys: bool) {
+        self.load.validate_keys = validate_keys;
+    }
+
+    /// Sets the pattern a well-formed key must match.
     ///
-    /// # Returns
+    /// Only applies while key validation is enabled (see
+    /// `set_key_validation`). With no pattern, only NULL keys are malformed.
     ///
-    /// A vector of errors encountered during the batch commit.
+    /// # Arguments
     ///
-    /// # Errors
+    /// * `pattern` - The key pattern, such as `^\d{7}$` for CMDORNUM
     ///
-    /// Returns an error if a non-foreign-key database error occurs.
-    fn commit_batch(
-        &mut self,
-        insert_sql: &str,
-        batch: &[(Vec<Option<String>>, usize)],
-        file: &FileMetadata,
-        table_name: &str,
-    ) -> Result<Vec<ErrorDetails>> {
-        let mut errors = Vec::new();
-
-        let tx = self
-            .database
-            .transaction()
-            .context("Failed to begin transaction")?;
-
-        {
-            let mut stmt = tx
-                .prepare(insert_sql)
-                .context("Failed to prepare INSERT statement")?;
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use regex::Regex;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.set_key_validation(true);
+    /// handler.set_key_pattern(Some(Regex::new(r"^\d{7}$")?));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_key_pattern(&mut self, pattern: Option<regex::Regex>) {
+        self.load.key_pattern = pattern;
+    }
 
-            for (values, line_number) in batch {
-                let params: Vec<rusqlite::types::Value> = values
-                    .iter()
-                    .map(|v| match v {
-                        Some(s) => rusqlite::types::Value::Text(s.clone()),
-                        None => rusqlite::types::Value::Null,
-                    })
-                    .collect();
+    /// Renames columns whose field code is an SQLite keyword.
+    ///
+    /// A field such as `ORDER` works as a column only when quoted, which
+    /// trips up hand-written queries. When enabled, such fields are stored
+    /// as the code plus `RESERVED_WORD_SUFFIX` (`ORDER` becomes `ORDER_col`),
+    /// and inserts, column descriptions, lookup tables and index suggestions
+    /// all use the new name. Either way, each such field is reported as a
+    /// `Severity::Warning` when its file is loaded. Disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to rename keyword columns
+    pub fn set_rename_reserved_columns(&mut self, enabled: bool) {
+        self.load.rename_reserved_columns = enabled;
+    }
 
-                match stmt.execute(rusqlite::params_from_iter(params.iter())) {
-                    Ok(_) => {}
-                    Err(rusqlite::Error::SqliteFailure(err, _))
-                        if err.code == rusqlite::ErrorCode::ConstraintViolation =>
-                    {
-                        if err.extended_code == FOREIGN_KEY_ERROR_CODE {
-                            let message = format!(
-                                "Foreign key violation inserting into {}\n  File: {} ({})\n  Line: {}\n  Values: {:?}",
-                                table_name, file.id, file.name, line_number, values
-                            );
+    /// Reports records that are longer than the file's schema.
+    ///
+    /// Bytes past the schema's record length are never loaded. When
+    /// enabled, each file is read once more after it is loaded, and if any
+    /// record has such a tail a `Severity::Warning` with the count and the
+    /// most common trailing length is recorded; see
+    /// `DataParser::trailing_bytes`. Disabled by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to check for trailing bytes
+    pub fn set_report_trailing_bytes(&mut self, enabled: bool) {
+        self.load.report_trailing_bytes = enabled;
+    }
 
-                            let error_details = ErrorDetails::new(
-                                file.id.to_string(),
-                                table_name.to_string(),
-                                message,
-                                err.to_string(),
-                            );
+    /// Chooses where the reference key set for foreign key pre-checks is kept.
+    ///
+    /// `ReferenceKeyStore::OnDisk` keeps memory use bounded on constrained
+    /// machines, at the cost of a query per lookup. Defaults to `InMemory`.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The backend for `reference_keys`
+    pub fn set_reference_key_store(&mut self, store: ReferenceKeyStore) {
+        self.reference_key_store = store;
+    }
 
-                            errors.push(error_details);
-                            continue;
-                        } else {
-                            return Err(rusqlite::Error::SqliteFailure(err, None).into());
-                        }
-                    }
-                    Err(e) => {
-                        return Err(e)
-                            .with_context(|| format!("Failed to insert record at line {}", line_number))?;
-                    }
-                }
-            }
-        }
+    /// Sets whether exact duplicate records are skipped during a load.
+    ///
+    /// A record is a duplicate if every field value, after coercion, equals
+    /// that of an earlier record of the same file. Skipped records are
+    /// counted in `ProcessingResults::deduplicated` and not inserted. Files
+    /// loaded in parallel ranges (see `set_intra_file_workers`) are
+    /// deduplicated within each range. Defaults to `RowDeduplication::Off`.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - How duplicates are detected
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::{DataHandler, RowDeduplication};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.set_row_deduplication(RowDeduplication::OnDisk);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_row_deduplication(&mut self, mode: RowDeduplication) {
+        self.load.row_deduplication = mode;
+    }
 
-        tx.commit().context("Failed to commit transaction")?;
+    /// Reads the key values of the reference table.
+    ///
+    /// The set is held as chosen with `set_reference_key_store`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the handler has not been initialized with a
+    /// reference file or the reference table cannot be read.
+    pub fn reference_keys(&self) -> Result<ReferenceKeySet> {
+        let (Some(table), Some(field)) = (&self.reference_table_name, &self.reference_field) else {
+            return Err(anyhow!("Cannot read reference keys: handler not initialized with reference table"));
+        };
 
-        Ok(errors)
+        ReferenceKeySet::load(&self.database, &self.qualified(table), &self.column_name(field), self.reference_key_store)
     }
 
-    /// Processes a complete file (creates table and inserts records).
+    /// Finds the records of a file whose key has no reference record, without loading it.
     ///
-    /// This is the main entry point for processing a file. It:
-    /// 1. Checks if the handler is initialized
-    /// 2. Checks if the file has already been processed
-    /// 3. Creates the table for the file
-    /// 4. Inserts column descriptions from the DES file
-    /// 5. Inserts all records from the file
-    /// 6. Marks the file as processed
+    /// Parses the file's extracted DAT file and checks each record's key
+    /// against `reference_keys`, so orphans can be reviewed before a load
+    /// rejects them. Records with an empty key are not orphans, as a NULL
+    /// foreign key is allowed. Files loaded without a foreign key (see
+    /// `standalone_files`) have no orphans.
     ///
     /// # Arguments
     ///
-    /// * `file` - The file metadata to process
+    /// * `file` - The dependent file to check
     ///
     /// # Returns
     ///
-    /// - `Ok(Some(results))` if the file was processed
-    /// - `Ok(None)` if the file was already processed (skipped)
+    /// One `ErrorKind::ForeignKeyViolation` error per orphaned record, with
+    /// its line number and key, in file order.
     ///
     /// # Errors
     ///
-    /// Returns an error if:
-    /// - The handler hasn't been initialized
-    /// - Table creation fails
-    /// - Record insertion fails (excluding foreign key violations)
+    /// Returns an error if the handler is not initialized, the file has no
+    /// key field, or its DES or DAT file cannot be read.
     ///
     /// # Example
     ///
     /// ```no_run
-    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use ncdac_opi_parser::data_handler::{DataHandler, ReferenceKeyStore};
     /// use ncdac_opi_parser::files::get_file_by_id;
     ///
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.set_reference_key_store(ReferenceKeyStore::OnDisk);
+    /// handler.init(get_file_by_id("OFNT3AA1").unwrap(), None)?;
     ///
-    /// // Initialize with reference file
-    /// let reference = get_file_by_id("OFNT3AA1").unwrap();
-    /// handler.init(reference, None)?;
-    ///
-    /// // Process another file
-    /// let file = get_file_by_id("OFNT1BA1").unwrap();
-    /// if let Some(results) = handler.process_file(file, None)? {
-    ///     println!("Processed {} records", results.processed);
-    /// }
+    /// let orphans = handler.precheck_foreign_keys(get_file_by_id("OFNT1BA1").unwrap())?;
+    /// println!("{} orphaned records", orphans.len());
     /// # Ok(())
     /// # }
     /// ```
-    pub fn process_file(
-        &mut self,
-        file: &FileMetadata,
-        pb: Option<&ProgressBar>,
-    ) -> Result<Option<ProcessingResults>> {
-        if !self.is_initialized {
-            return Err(anyhow!("DataHandler is not initialized"));
-        }
+    pub fn precheck_foreign_keys(&self, file: &FileMetadata) -> Result<Vec<ErrorDetails>> {
+        let keys = self.reference_keys()?;
+        let table_name = to_snake_case(file.name);
+        let description = self.file_description(file.id)?;
+        let key = self
+            .key_field(&description)
+            .ok_or_else(|| anyhow!("Table {} does not contain an expected key field", table_name))?;
 
-        if self.processed_files.contains(file.id) {
-            return Ok(None);
+        if !self.matches_reference_key(&description) {
+            return Ok(Vec::new());
         }
 
-        // Try to create the table and get the description
-        let (table_name, description) = match self.create_table_for_file(file) {
-            Ok(result) => result,
-            Err(e) => {
-                // Check if this is a DES file error (file not found or parse error)
-                let error_msg = format!("{:#}", e);
-                if error_msg.contains("Failed to read DES file") || error_msg.contains("DES file") {
-                    // Collect DES file failure and return Ok(None) to continue processing other files
-                    self.des_file_failures.push(file.id.to_string());
-                    return Ok(None);
-                }
-                // For other errors, propagate them
-                return Err(e);
+        let mut parser = DataParser::from_description(description.clone(), &self.load.data_dir);
+        parser.set_control_char_policy(self.load.control_chars);
+        parser.set_empty_to_null(self.load.empty_to_null);
+
+        let mut orphans = Vec::new();
+        for (index, record) in parser.parse()?.enumerate() {
+            let record = record?;
+            let Some(Some(value)) = record.get(&key) else {
+                continue;
+            };
+
+            if !keys.contains(value)? {
+                orphans.push(
+                    ErrorDetails::new(
+                        file.id.to_string(),
+                        table_name.clone(),
+                        format!(
+                            "Orphaned key in {}.{}: {} has no {} record",
+                            table_name,
+                            key,
+                            value,
+                            self.reference_table_name.as_deref().unwrap_or_default()
+                        ),
+                        "No matching reference record".to_string(),
+                    )
+                    .with_record(index + 1, Some(key.clone()), Some(value.clone()))
+                    .with_kind(ErrorKind::ForeignKeyViolation),
+                );
             }
-        };
-
-        self.insert_column_descriptions(&table_name, &description)?;
-        let results = self.insert_records_for_file(file, pb)?;
-
-        self.processed_files.insert(file.id.to_string());
-
-        Ok(Some(results))
-    }
+        }
 
-    /// Returns whether the handler has been initialized.
-    pub fn is_initialized(&self) -> bool {
-        self.is_initialized
+        Ok(orphans)
     }
 
-    /// Returns a reference to the reference file metadata.
-    pub fn reference_file(&self) -> Option<&FileMetadata> {
-        self.reference_file.as_ref()
+    /// Chooses the key field of a file instead of detecting it.
+    ///
+    /// Detection takes the first of CMDORNUM, CIDORNUM and CDDORNUM that the
+    /// schema contains, which can pick the wrong one in a schema with
+    /// several. The chosen field becomes the foreign key column of a
+    /// dependent table. It must exist in the file's DES schema; table
+    /// creation fails otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The file the key applies to (e.g., "OFNT1BA1")
+    /// * `field` - The field code to use as the key
+    pub fn set_key_field(&mut self, file_id: &str, field: &str) {
+        self.load.key_overrides.insert(file_id.to_string(), field.to_string());
     }
 
-    /// Returns a reference to the reference table name.
-    pub fn reference_table_name(&self) -> Option<&str> {
-        self.reference_table_name.as_deref()
+    /// Chooses the primary key of the reference file instead of detecting it.
+    ///
+    /// The same as `set_key_field`, for the file later passed to `init`.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The reference file (e.g., "OFNT3AA1")
+    /// * `field` - The field code to use as the primary key
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use ncdac_opi_parser::files::get_file_by_id;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.set_reference_key("OFNT3CE1", "CIDORNUM");
+    /// handler.init(get_file_by_id("OFNT3CE1").unwrap(), None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_reference_key(&mut self, file_id: &str, field: &str) {
+        self.set_key_field(file_id, field);
     }
 
-    /// Returns a reference to the reference field name.
-    pub fn reference_field(&self) -> Option<&str> {
-        self.reference_field.as_deref()
+    /// Sets how many connections load each file in parallel.
+    ///
+    /// With more than one, the DAT file is split into record-aligned byte
+    /// ranges (see `DataParser::split_ranges`) that are parsed and inserted
+    /// concurrently, each by a worker with its own connection and batch
+    /// transactions. This helps when one large file dominates a run. It
+    /// requires a file-backed database without an attached schema. Each
+    /// worker's connection is tuned by `set_worker_config`. Defaults to 1.
+    ///
+    /// # Arguments
+    ///
+    /// * `workers` - The number of ranges loaded at once
+    pub fn set_intra_file_workers(&mut self, workers: usize) {
+        self.intra_file_workers = workers.max(1);
     }
 
-    /// Returns the set of processed file IDs.
-    pub fn processed_files(&self) -> &HashSet<String> {
-        &self.processed_files
+    /// Sets the connection tuning of the workers that load ranges in parallel.
+    ///
+    /// Range workers are opened with `concurrency::create_worker_handler`,
+    /// so they get the same page size, cache size, journal mode and busy
+    /// timeout as the pipeline's per-file workers. Defaults to
+    /// `WorkerConfig::default()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The worker connection settings
+    pub fn set_worker_config(&mut self, config: WorkerConfig) {
+        self.worker_config = config;
     }
 
-    /// Returns a reference to the underlying SQLite connection.
+    /// Sets how many connections load the reference file in `init`.
     ///
-    /// This is primarily used for PRAGMA configuration in concurrent processing scenarios.
+    /// Dependent files cannot start until the reference table is complete,
+    /// so a large reference file otherwise leaves every other core idle.
+    /// With more than one worker the reference is loaded in ranges as
+    /// described for `set_intra_file_workers`, after a first parallel pass
+    /// that counts each range's records and collects its keys. A key that
+    /// also appears in an earlier range is left to that range, so the first
+    /// occurrence in the file is kept and every later one is reported as an
+    /// `ErrorKind::DuplicateKey` error with its line number in the whole
+    /// file, exactly as a serial load reports it. `init` returns only once
+    /// every range has committed. Defaults to the `set_intra_file_workers`
+    /// count.
+    ///
+    /// # Arguments
+    ///
+    /// * `workers` - The number of ranges of the reference file loaded at once
     ///
     /// # Example
     ///
     /// ```no_run
     /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use ncdac_opi_parser::files::get_file_by_id;
     ///
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let handler = DataHandler::new("database.db")?;
-    /// let conn = handler.connection();
-    /// // Configure PRAGMA settings on the connection
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.set_reference_workers(8);
+    /// handler.init(get_file_by_id("OFNT3AA1").unwrap(), None)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn connection(&self) -> &Connection {
-        &self.database
+    pub fn set_reference_workers(&mut self, workers: usize) {
+        self.reference_workers = Some(workers.max(1));
     }
 
-    /// Initializes this handler with reference metadata from another handler.
+    /// Sets how DECIMAL fields are stored in tables created afterwards.
     ///
-    /// This is used to set up worker handlers in parallel processing scenarios.
-    /// Each worker needs to know about the reference table to properly create
-    /// foreign key constraints.
+    /// Defaults to `DecimalStorage::Real`. Use `DecimalStorage::Exact` to
+    /// keep monetary values digit-for-digit.
     ///
     /// # Arguments
     ///
-    /// * `reference_file` - The reference file metadata
-    /// * `reference_table_name` - The name of the reference table
-    /// * `reference_field` - The primary key field name of the reference table
+    /// * `storage` - The DECIMAL storage mode
+    pub fn set_decimal_storage(&mut self, storage: DecimalStorage) {
+        self.load.decimal_storage = storage;
+    }
+
+    /// Returns how DECIMAL fields are stored.
+    pub fn decimal_storage(&self) -> DecimalStorage {
+        self.load.decimal_storage
+    }
+
+    /// Initializes the handler with a reference file.
+    ///
+    /// The reference file serves as the primary key source for the database.
+    /// All other tables will have foreign key constraints referencing this table.
+    ///
+    /// This method must be called before processing any other files.
+    ///
+    /// **Reference File Processing Guarantees:**
+    /// - Uses `PRAGMA synchronous=FULL` for maximum data durability
+    /// - Reference file must complete successfully before any parallel processing begins
+    /// - Returns early with error if reference file processing fails
+    /// - Maintains existing initialization logic from lines 232-252
+    ///
+    /// With `set_reuse_existing_reference(true)`, a reference table that
+    /// already exists, holds rows, and has exactly the expected columns is
+    /// kept as-is: nothing is reinserted and the returned results report
+    /// zero processed records.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference_file` - The file to use as the reference table
+    /// * `pb` - Optional progress bar for tracking reference file processing
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The reference file's schema doesn't contain a recognized primary key field
+    /// - PRAGMA synchronous=FULL cannot be set
+    /// - The reference file cannot be processed
     ///
     /// # Example
     ///
@@ -718,448 +1750,5064 @@ impl DataHandler {
     /// use ncdac_opi_parser::files::get_file_by_id;
     ///
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let main_handler = DataHandler::new("database.db")?;
-    /// let mut worker_handler = DataHandler::new("database.db")?;
-    ///
-    /// // Copy reference metadata from main handler to worker
-    /// if let (Some(ref_file), Some(ref_table), Some(ref_field)) = (
-    ///     main_handler.reference_file(),
-    ///     main_handler.reference_table_name(),
-    ///     main_handler.reference_field()
-    /// ) {
-    ///     worker_handler.init_from_reference(ref_file, ref_table, ref_field);
-    /// }
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// let reference = get_file_by_id("OFNT3AA1").unwrap();
+    /// let results = handler.init(reference, None)?;
     /// # Ok(())
     /// # }
     /// ```
-    pub fn init_from_reference(
-        &mut self,
-        reference_file: &FileMetadata,
-        reference_table_name: &str,
-        reference_field: &str,
-    ) {
+    pub fn init(&mut self, reference_file: &FileMetadata, pb: Option<&ProgressBar>) -> Result<ProcessingResults> {
+        set_pragma_synchronous_full(&self.database)
+            .context("Failed to set PRAGMA synchronous=FULL for reference table processing")?;
+        if let Some(schema) = &self.schema {
+            self.database
+                .pragma_update(Some(DatabaseName::Attached(schema)), "synchronous", "FULL")
+                .with_context(|| format!("Failed to set PRAGMA synchronous=FULL for {}", schema))?;
+        }
+
+        let reference_table_name = to_snake_case(reference_file.name);
+        let reference_description = self.file_description(reference_file.id)?;
+
+        let reference_field = self
+            .key_field(&reference_description)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Reference table {} does not contain an expected key field",
+                    reference_table_name
+                )
+            })?;
+
         self.reference_file = Some(*reference_file);
-        self.reference_table_name = Some(reference_table_name.to_string());
-        self.reference_field = Some(reference_field.to_string());
+        self.reference_table_name = Some(reference_table_name.clone());
+        self.reference_field = Some(reference_field.clone());
         self.is_initialized = true;
+
+        if self.reuse_existing_reference
+            && self.is_reusable_reference_table(&reference_table_name, &reference_description)?
+        {
+            self.load.key_columns.insert(reference_file.id.to_string(), reference_field);
+            self.processed_files.insert(reference_file.id.to_string());
+            return Ok(ProcessingResults::new(0, Vec::new()));
+        }
+
+        let results = self.process_file(reference_file, pb)?;
+
+        results.ok_or_else(|| anyhow!("Failed to process reference file: reference file processing must complete successfully before continuing"))
+    }
+
+    /// Creates a table for the specified file.
+    ///
+    /// Generates a CREATE TABLE statement from the file's DES schema and executes it.
+    /// Maps field types to SQLite types (DECIMAL → REAL, everything else → TEXT).
+    ///
+    /// If the table is the reference table, adds a PRIMARY KEY constraint.
+    /// Otherwise, adds a FOREIGN KEY constraint referencing the reference table,
+    /// unless the file's key type or length differs from the reference key, in
+    /// which case the table is created standalone.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The file metadata for which to create a table
+    ///
+    /// # Returns
+    ///
+    /// The table name (in snake_case) that was created.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The file's schema doesn't contain a recognized primary key field
+    /// - The table creation SQL fails to execute
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use ncdac_opi_parser::files::get_file_by_id;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = DataHandler::new("database.db")?;
+    /// let file = get_file_by_id("OFNT1BA1").unwrap();
+    /// // Note: handler must be initialized first in real usage
+    /// // let table_name = handler.create_table_for_file(file)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_table_for_file(&self, file: &FileMetadata) -> Result<(String, FileDescription)> {
+        let table_name = to_snake_case(file.name);
+        let description = self.file_description(file.id)?;
+
+        let sql = self.create_table_sql(&table_name, &description)?;
+
+        self.database
+            .execute(&sql, [])
+            .with_context(|| format!("Failed to create table {}", table_name))?;
+
+        Ok((table_name, description))
+    }
+
+    /// Sets whether `init` reuses an already populated reference table.
+    ///
+    /// Enables incremental loads of dependent files into a database built by
+    /// an earlier run, without reloading (or re-downloading) the large
+    /// reference file. The reference DES file is still read to check that the
+    /// existing table has the expected columns; a table that is empty or has
+    /// different columns is loaded as usual. Defaults to `false`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reuse` - Whether to keep a matching, non-empty reference table
+    pub fn set_reuse_existing_reference(&mut self, reuse: bool) {
+        self.reuse_existing_reference = reuse;
+    }
+
+    /// Checks whether an existing reference table can be used as-is.
+    ///
+    /// The table must exist, contain at least one row, and have exactly the
+    /// columns `create_table_sql` would create, in the same order.
+    fn is_reusable_reference_table(&self, table_name: &str, description: &FileDescription) -> Result<bool> {
+        let Ok(existing) = table_info_in(&self.database, self.schema.as_deref(), table_name) else {
+            return Ok(false);
+        };
+
+        let mut expected: Vec<String> = self
+            .column_order(description)?
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        for column in self.raw_shadow_columns(description)? {
+            expected.push(format!("{}{}", column, RAW_SUFFIX));
+        }
+        if self.load.store_source_id {
+            expected.push(SOURCE_COLUMN.to_string());
+        }
+
+        if !existing.into_iter().map(|(name, _)| name).eq(expected) {
+            return Ok(false);
+        }
+
+        let has_rows: bool = self
+            .database
+            .query_row(
+                &format!("SELECT EXISTS (SELECT 1 FROM {})", self.qualified(table_name)),
+                [],
+                |row| row.get(0),
+            )
+            .with_context(|| format!("Failed to read {}", table_name))?;
+
+        Ok(has_rows)
+    }
+
+    /// Builds the CREATE TABLE statement for a table from its DES schema.
+    ///
+    /// All table and column names are quoted with `quote_identifier`, and the
+    /// table is qualified with the attached schema if one is set.
+    fn create_table_sql(&self, table_name: &str, description: &FileDescription) -> Result<String> {
+        if let Some(field) = self.load.key_overrides.get(&description.filename)
+            && !description.schema.contains_key(field)
+        {
+            return Err(anyhow!(
+                "Key field {} chosen for {} is not in its DES schema",
+                field,
+                description.filename
+            ));
+        }
+
+        let primary_key = self.key_field(description).ok_or_else(|| {
+            anyhow!(
+                "Table {} does not contain an expected key field",
+                table_name
+            )
+        })?;
+
+        let boolean_columns = self.boolean_columns(description)?;
+        let columns: Vec<String> = self
+            .column_order(description)?
+            .into_iter()
+            .map(|field| {
+                let column_type = if boolean_columns.contains(&field) {
+                    "INTEGER"
+                } else {
+                    map_type_to_sqlite_with(&description.schema[field].field_type, self.load.decimal_storage)
+                };
+                format!("{} {}", quote_identifier(&self.column_name(field)), column_type)
+            })
+            .collect();
+
+        let mut constraints = Vec::new();
+
+        if Some(table_name) == self.reference_table_name.as_deref() {
+            constraints.push(format!("PRIMARY KEY ({})", quote_identifier(&self.column_name(&primary_key))));
+        } else if self.matches_reference_key(description) {
+            let reference_table = self.reference_table_name.as_ref().ok_or_else(|| {
+                anyhow!("Cannot create table: handler not initialized with reference table")
+            })?;
+            let reference_field = self.reference_field.as_ref().ok_or_else(|| {
+                anyhow!("Cannot create table: reference field not set")
+            })?;
+
+            constraints.push(format!(
+                "FOREIGN KEY ({}) REFERENCES {}({})",
+                quote_identifier(&self.column_name(&primary_key)),
+                quote_identifier(reference_table),
+                quote_identifier(&self.column_name(reference_field))
+            ));
+        }
+
+        let mut sql_parts = columns;
+        for column in self.raw_shadow_columns(description)? {
+            sql_parts.push(format!("{} TEXT", quote_identifier(&format!("{}{}", column, RAW_SUFFIX))));
+        }
+        if self.load.store_source_id {
+            sql_parts.push(format!("{} TEXT", quote_identifier(SOURCE_COLUMN)));
+        }
+        sql_parts.extend(constraints);
+
+        Ok(format!(
+            "CREATE TABLE IF NOT EXISTS {} ({})",
+            self.qualified(table_name),
+            sql_parts.join(", ")
+        ))
+    }
+
+    /// Inserts column descriptions from a FileDescription schema into the column_descriptions table.
+    ///
+    /// Takes the pre-parsed schema and inserts column names and descriptions
+    /// into the column_descriptions table.
+    ///
+    /// # Arguments
+    ///
+    /// * `table_name` - The name of the table
+    /// * `description` - The FileDescription containing the schema with descriptions
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database insertion fails.
+    pub fn insert_column_descriptions(&mut self, table_name: &str, description: &FileDescription) -> Result<()> {
+        let rows = self.column_description_rows(description)?;
+        let descriptions_table = self.qualified("column_descriptions");
+
+        let tx = self.database.transaction()
+            .context("Failed to begin transaction for column descriptions")?;
+        write_column_descriptions(&tx, &descriptions_table, table_name, &rows)?;
+        tx.commit().context("Failed to commit column descriptions transaction")?;
+
+        Ok(())
+    }
+
+    /// Returns the `(column, description)` rows describing a table's columns.
+    fn column_description_rows(&self, description: &FileDescription) -> Result<Vec<(String, String)>> {
+        let mut rows: Vec<(String, String)> = description
+            .schema
+            .iter()
+            .map(|(field, field_def)| (self.column_name(field).into_owned(), field_def.description.clone()))
+            .collect();
+
+        for column_name in self.raw_shadow_columns(description)? {
+            rows.push((
+                format!("{}{}", column_name, RAW_SUFFIX),
+                format!("{} (untrimmed)", description.schema[column_name].description),
+            ));
+        }
+
+        Ok(rows)
+    }
+
+    /// Inserts records from a file into its table.
+    ///
+    /// Parses the file's DAT records and inserts them in batches within transactions.
+    /// Foreign key constraint violations are collected but don't stop processing.
+    /// A final record shorter than the schema width is reported as a
+    /// `Severity::Warning`, since it usually means the file was truncated.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The file metadata for which to insert records
+    ///
+    /// # Returns
+    ///
+    /// A `ProcessingResults` with the count of processed records and any errors.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The table doesn't exist (call `create_table_for_file` first)
+    /// - A non-foreign-key database error occurs
+    /// - The data parser encounters an error
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use ncdac_opi_parser::files::get_file_by_id;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// let file = get_file_by_id("OFNT1BA1").unwrap();
+    /// // Note: table must exist first in real usage
+    /// // let results = handler.insert_records_for_file(file)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn insert_records_for_file(&mut self, file: &FileMetadata, pb: Option<&ProgressBar>) -> Result<ProcessingResults> {
+        self.insert_records_into(file, &to_snake_case(file.name), pb)
+    }
+
+    /// Inserts records from a file's extracted DAT file into `table_name`.
+    fn insert_records_into(
+        &mut self,
+        file: &FileMetadata,
+        table_name: &str,
+        pb: Option<&ProgressBar>,
+    ) -> Result<ProcessingResults> {
+        let table_name = table_name.to_string();
+        let description = self.file_description(file.id)?;
+        let mut parser = DataParser::from_description(description.clone(), &self.load.data_dir);
+        parser.set_control_char_policy(self.load.control_chars);
+        parser.set_empty_to_null(self.load.empty_to_null);
+        parser.set_raw_fields(&self.raw_shadow_columns(&description)?);
+
+        let is_reference = self.reference_file.is_some_and(|reference| reference.id == file.id);
+        let workers = match self.reference_workers {
+            Some(workers) if is_reference => workers,
+            _ => self.intra_file_workers,
+        };
+
+        // A gzipped DAT file cannot be split into byte ranges
+        let mut results = if workers > 1 && !parser.is_gzipped() {
+            self.insert_records_in_ranges(file, &table_name, &description, &parser, workers, pb)?
+        } else {
+            self.insert_records(file, &table_name, &description, parser.parse()?, pb)?
+        };
+        self.report_reserved_columns(file, &table_name, &description, &mut results);
+        *self.skipped_rows.entry(file.id.to_string()).or_default() += results.deduplicated;
+
+        if let Some(truncated) = parser.truncated_final_record()? {
+            let warning = ErrorDetails::new(
+                file.id.to_string(),
+                table_name.clone(),
+                format!("Possible truncation in {} ({}): {}", file.id, file.name, truncated),
+                truncated.to_string(),
+            )
+            .with_severity(Severity::Warning);
+            self.errors.push(warning.clone());
+            results.errors.push(warning);
+        }
+
+        if self.load.report_trailing_bytes
+            && let Some(trailing) = parser.trailing_bytes()?
+        {
+            let warning = ErrorDetails::new(
+                file.id.to_string(),
+                table_name.clone(),
+                format!("Unparsed trailing bytes in {} ({}): {}", file.id, file.name, trailing),
+                trailing.to_string(),
+            )
+            .with_severity(Severity::Warning);
+            self.errors.push(warning.clone());
+            results.errors.push(warning);
+        }
+
+        Ok(results)
+    }
+
+    /// Inserts a file's records from `workers` ranges in parallel.
+    ///
+    /// Each record-aligned range of the DAT file is parsed and inserted by
+    /// a worker handler on its own connection. SQLite still serializes the
+    /// writes themselves, so the gain comes from parsing concurrently. A
+    /// counting pass first gives each worker the line number its range
+    /// starts after, so errors carry the same line numbers as a serial
+    /// load. For the reference file, `shadowed_reference_keys` does this
+    /// while also deciding which range keeps each repeated key (see
+    /// `set_reference_workers`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database is in memory or uses an attached
+    /// schema, a worker connection cannot be opened, or any range fails.
+    fn insert_records_in_ranges(
+        &mut self,
+        file: &FileMetadata,
+        table_name: &str,
+        description: &FileDescription,
+        parser: &DataParser,
+        workers: usize,
+        pb: Option<&ProgressBar>,
+    ) -> Result<ProcessingResults> {
+        if self.schema.is_some() {
+            return Err(anyhow!("Loading {} in parallel ranges is not supported with an attached schema", file.id));
+        }
+        let database_path = self
+            .database
+            .path()
+            .filter(|path| !path.is_empty())
+            .ok_or_else(|| anyhow!("Loading {} in parallel ranges requires a database file", file.id))?
+            .to_string();
+
+        let ranges = parser.split_ranges(workers)?;
+        let is_reference = self.reference_file.is_some_and(|reference| reference.id == file.id);
+        let mut workers = ranges
+            .iter()
+            .map(|_| self.range_worker(&database_path))
+            .collect::<Result<Vec<_>>>()?;
+
+        if is_reference {
+            let shadowed = self.shadowed_reference_keys(parser, &ranges)?;
+            for (worker, (line_offset, keys)) in workers.iter_mut().zip(shadowed) {
+                worker.line_offset = line_offset;
+                worker.shadowed_keys = keys;
+            }
+        } else {
+            let counts = ranges
+                .par_iter()
+                .map(|range| parser.count_range_records(range.clone()))
+                .collect::<Result<Vec<_>>>()?;
+            let mut line_offset = 0;
+            for (worker, count) in workers.iter_mut().zip(counts) {
+                worker.line_offset = line_offset;
+                line_offset += count;
+            }
+        }
+
+        let outcomes: Vec<Result<ProcessingResults>> = workers
+            .into_par_iter()
+            .zip(ranges)
+            .map(|(mut worker, range)| {
+                worker.insert_records(file, table_name, description, parser.parse_range(range)?, pb)
+            })
+            .collect();
+
+        let mut results = ProcessingResults::new(0, self.standalone_warning(file, table_name).into_iter().collect());
+        for outcome in outcomes {
+            results.merge(outcome?);
+        }
+
+        self.errors.extend(results.errors.clone());
+
+        Ok(results)
     }
 
-    /// Returns a formatted warning message for files that failed due to missing or invalid DES files.
-    ///
-    /// Returns None if no DES file failures occurred.
-    pub fn report_des_file_failures(&self) -> Option<String> {
-        if self.des_file_failures.is_empty() {
-            return None;
-        }
+    /// Scans the reference file's ranges for where each range starts and
+    /// which of its keys an earlier range already holds.
+    ///
+    /// The ranges are read in parallel. For each range, in order, this
+    /// returns the number of records before it and its keys that also occur
+    /// in an earlier range, which that range inserts first. Keys that key
+    /// validation would reject are not collected.
+    fn shadowed_reference_keys(
+        &self,
+        parser: &DataParser,
+        ranges: &[std::ops::Range<u64>],
+    ) -> Result<Vec<(usize, HashSet<String>)>> {
+        let key_field = self
+            .reference_field
+            .clone()
+            .ok_or_else(|| anyhow!("Reference key field is not set"))?;
+        let key_pattern = self.load.key_pattern.as_ref().filter(|_| self.load.validate_keys);
+
+        let scans = ranges
+            .par_iter()
+            .map(|range| -> Result<(usize, HashSet<String>)> {
+                let mut records = 0;
+                let mut keys = HashSet::new();
+                for record in parser.parse_range(range.clone())? {
+                    records += 1;
+                    if let Some(key) = record?.remove(&key_field).flatten()
+                        && key_pattern.is_none_or(|pattern| pattern.is_match(&key))
+                    {
+                        keys.insert(key);
+                    }
+                }
+                Ok((records, keys))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut seen = HashSet::new();
+        let mut line_offset = 0;
+        let mut shadowed = Vec::with_capacity(scans.len());
+
+        for (records, keys) in scans {
+            shadowed.push((line_offset, keys.iter().filter(|key| seen.contains(*key)).cloned().collect()));
+            line_offset += records;
+            seen.extend(keys);
+        }
+
+        Ok(shadowed)
+    }
+
+    /// Opens a handler on a separate connection with this handler's load settings.
+    ///
+    /// The connection is tuned by the handler's `WorkerConfig`, except that
+    /// `synchronous` follows this connection, so a reference load stays at
+    /// FULL (see `init`).
+    fn range_worker(&self, database_path: &str) -> Result<DataHandler> {
+        let mut worker = create_worker_handler(database_path, &self.worker_config)
+            .with_context(|| format!("Failed to open worker connection for {}", database_path))?;
+
+        let synchronous: i64 = self
+            .database
+            .pragma_query_value(None, "synchronous", |row| row.get(0))?;
+        worker
+            .database
+            .pragma_update(None, "synchronous", synchronous)
+            .context("Failed to set PRAGMA synchronous on worker connection")?;
+
+        worker.reference_file = self.reference_file;
+        worker.reference_table_name = self.reference_table_name.clone();
+        worker.reference_field = self.reference_field.clone();
+        worker.is_initialized = self.is_initialized;
+        worker.load = self.load.clone();
+        worker.descriptions = Mutex::new(self.descriptions.lock().unwrap_or_else(|e| e.into_inner()).clone());
+
+        Ok(worker)
+    }
+
+    /// Warns about each field of a file named after an SQLite keyword.
+    fn report_reserved_columns(
+        &mut self,
+        file: &FileMetadata,
+        table_name: &str,
+        description: &FileDescription,
+        results: &mut ProcessingResults,
+    ) {
+        let mut fields: Vec<&String> = description.schema.keys().filter(|field| is_sqlite_keyword(field)).collect();
+        fields.sort();
+
+        for field in fields {
+            let message = if self.load.rename_reserved_columns {
+                format!(
+                    "Field {} of {} is an SQLite keyword; stored as column {}",
+                    field,
+                    table_name,
+                    self.column_name(field)
+                )
+            } else {
+                format!(
+                    "Field {} of {} is an SQLite keyword; queries must quote the column name",
+                    field, table_name
+                )
+            };
+            let warning = ErrorDetails::new(
+                file.id.to_string(),
+                table_name.to_string(),
+                message,
+                "Column name is an SQLite reserved word".to_string(),
+            )
+            .with_severity(Severity::Warning);
+            self.errors.push(warning.clone());
+            results.errors.push(warning);
+        }
+    }
+
+    /// Returns the column a field is stored in.
+    ///
+    /// The field code itself, unless `set_rename_reserved_columns` is on and
+    /// the code is an SQLite keyword.
+    fn column_name<'a>(&self, field: &'a str) -> Cow<'a, str> {
+        if self.load.rename_reserved_columns {
+            avoid_sqlite_keyword(field)
+        } else {
+            Cow::Borrowed(field)
+        }
+    }
+
+    /// Returns the warning recorded when a file is loaded without a foreign key.
+    fn standalone_warning(&self, file: &FileMetadata, table_name: &str) -> Option<ErrorDetails> {
+        self.load.standalone_files.contains(file.id).then(|| {
+            ErrorDetails::new(
+                file.id.to_string(),
+                table_name.to_string(),
+                format!(
+                    "Loaded {} without a foreign key: its key does not match the reference key {}",
+                    table_name,
+                    self.reference_field.as_deref().unwrap_or_default()
+                ),
+                "Key type or length differs from the reference table".to_string(),
+            )
+            .with_severity(Severity::Warning)
+        })
+    }
+
+    /// Inserts parsed records into a file's table in batches.
+    ///
+    /// Shared by the on-disk and ZIP-streaming load paths. Foreign key
+    /// violations are collected, and NULL counts are tallied per column.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The file metadata for error reporting and the `_source` column
+    /// * `table_name` - The table to insert into
+    /// * `description` - The schema whose fields become the inserted columns
+    /// * `records` - The parsed records, in file order
+    /// * `pb` - Optional progress bar, advanced per committed batch
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a record cannot be read or a non-foreign-key
+    /// database error occurs.
+    fn insert_records<I>(
+        &mut self,
+        file: &FileMetadata,
+        table_name: &str,
+        description: &FileDescription,
+        records: I,
+        pb: Option<&ProgressBar>,
+    ) -> Result<ProcessingResults>
+    where
+        I: Iterator<Item = Result<HashMap<String, Option<String>>>>,
+    {
+        let mut columns: Vec<String> = self
+            .column_order(description)?
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        let null_counted = columns.len();
+        for column in self.raw_shadow_columns(description)? {
+            columns.push(format!("{}{}", column, RAW_SUFFIX));
+        }
+        let record_columns = columns.len();
+        if self.load.store_source_id {
+            columns.push(SOURCE_COLUMN.to_string());
+        }
+        let table_columns: Vec<String> = columns
+            .iter()
+            .map(|column| self.column_name(column).into_owned())
+            .collect();
+        let insert_sql = build_insert_sql(&self.qualified(table_name), &table_columns);
+
+        let mut processed = 0;
+        let mut local_errors: Vec<ErrorDetails> = self.standalone_warning(file, table_name).into_iter().collect();
+        let mut batch: Vec<(Vec<Option<String>>, usize)> = Vec::new();
+        let mut line_number = self.line_offset;
+        let mut null_counts = vec![0usize; null_counted];
+        let mut numeric_stats: Vec<(usize, NumericStats)> = columns[..null_counted]
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| description.schema[column.as_str()].field_type == "DECIMAL")
+            .map(|(index, _)| (index, NumericStats::default()))
+            .collect();
+
+        let boolean_columns = self.boolean_columns(description)?;
+        let boolean_indices: Vec<usize> = columns[..null_counted]
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| boolean_columns.contains(&column.as_str()))
+            .map(|(index, _)| index)
+            .collect();
+
+        let validations = self.load.validations.get(file.id).cloned().unwrap_or_default();
+        if let Some((column, _)) = validations
+            .iter()
+            .find(|(column, _)| !description.schema.contains_key(column))
+        {
+            return Err(anyhow!("Validation rule for {} names unknown column {}", file.id, column));
+        }
+        let key_column = self.reference_field.clone();
+        let checked_key = if self.load.validate_keys {
+            self.load.key_columns
+                .get(file.id)
+                .cloned()
+                .or_else(|| self.key_field(description))
+        } else {
+            None
+        };
+        let mut key_rules = vec![ValidationRule::NonNull];
+        key_rules.extend(self.load.key_pattern.clone().map(ValidationRule::MatchesRegex));
+        let mut row_hashes = RowHashes::new(self.load.row_deduplication)?;
+        let mut deduplicated = 0;
+
+        for record_result in records {
+            let record = record_result?;
+            line_number += 1;
+
+            if let Some(column) = &checked_key {
+                let value = record.get(column).cloned().flatten();
+                if let Some(failure) = key_rules.iter().find_map(|rule| rule.check(value.as_deref())) {
+                    let message = format!(
+                        "Malformed key in {}.{}\n  File: {} ({})\n  Line: {}\n  {}",
+                        table_name, column, file.id, file.name, line_number, failure
+                    );
+
+                    local_errors.push(
+                        ErrorDetails::new(file.id.to_string(), table_name.to_string(), message, failure)
+                            .with_record(line_number, Some(column.clone()), value)
+                            .with_kind(ErrorKind::MalformedKey),
+                    );
+                    continue;
+                }
+            }
+
+            let distinct = row_hashes.insert(
+                columns[..record_columns]
+                    .iter()
+                    .map(|column| record.get(column).and_then(Option::as_deref)),
+            )?;
+            if !distinct {
+                deduplicated += 1;
+                if let Some(progress) = pb {
+                    progress.inc(1);
+                }
+                continue;
+            }
+
+            for (column, rule) in &validations {
+                let value = record.get(column).and_then(|value| value.as_deref());
+                if let Some(failure) = rule.check(value) {
+                    let message = format!(
+                        "Validation failed for {}.{}\n  File: {} ({})\n  Line: {}\n  {}",
+                        table_name, column, file.id, file.name, line_number, failure
+                    );
+                    let key_value = key_column
+                        .as_ref()
+                        .and_then(|key| record.get(key).cloned().flatten());
+
+                    local_errors.push(
+                        ErrorDetails::new(file.id.to_string(), table_name.to_string(), message, failure)
+                            .with_record(line_number, key_column.clone(), key_value)
+                            .with_severity(Severity::Warning),
+                    );
+                }
+            }
+
+            let mut values: Vec<Option<String>> = columns[..record_columns]
+                .iter()
+                .map(|column| record.get(column).cloned().unwrap_or(None))
+                .collect();
+
+            if self.load.store_source_id {
+                values.push(Some(file.id.to_string()));
+            }
+
+            for &index in &boolean_indices {
+                let value = values[index].take();
+                values[index] = match value.as_deref() {
+                    None | Some("") => None,
+                    Some("Y") => Some("1".to_string()),
+                    Some("N") => Some("0".to_string()),
+                    Some(other) => {
+                        let column = &columns[index];
+                        let failure = format!("Expected Y, N, or blank, got '{}'", other);
+                        let message = format!(
+                            "Unrecognized boolean value in {}.{}\n  File: {} ({})\n  Line: {}\n  {}",
+                            table_name, column, file.id, file.name, line_number, failure
+                        );
+                        let key_value = key_column
+                            .as_ref()
+                            .and_then(|key| record.get(key).cloned().flatten());
+
+                        local_errors.push(
+                            ErrorDetails::new(file.id.to_string(), table_name.to_string(), message, failure)
+                                .with_record(line_number, key_column.clone(), key_value)
+                                .with_severity(Severity::Warning),
+                        );
+                        None
+                    }
+                };
+            }
+
+            for (count, value) in null_counts.iter_mut().zip(&values) {
+                if value.is_none() {
+                    *count += 1;
+                }
+            }
+
+            for (index, stats) in &mut numeric_stats {
+                if let Some(number) = values[*index].as_deref().and_then(|value| value.parse::<f64>().ok()) {
+                    stats.add(number);
+                }
+            }
+
+            // A key an earlier range inserts would fail the primary key here
+            if !self.shadowed_keys.is_empty()
+                && let Some(key) = key_column.as_ref().and_then(|key| record.get(key).cloned().flatten())
+                && self.shadowed_keys.contains(&key)
+            {
+                let column = key_column.clone().unwrap_or_default();
+                local_errors.push(duplicate_key_error(file, table_name, line_number, column, key));
+                processed += 1;
+                if let Some(progress) = pb {
+                    progress.inc(1);
+                }
+                continue;
+            }
+
+            batch.push((values, line_number));
+
+            if batch.len() >= BATCH_SIZE {
+                self.check_deadline(file)?;
+                let batch_errors = self.commit_batch(&insert_sql, &columns, &batch, file, table_name)?;
+                local_errors.extend(batch_errors);
+                processed += batch.len();
+
+                if let Some(progress) = pb {
+                    progress.inc(batch.len() as u64);
+                }
+
+                batch.clear();
+                if self.load.row_deduplication == RowDeduplication::PerBatch {
+                    row_hashes.clear();
+                }
+            }
+        }
+
+        if !batch.is_empty() {
+            self.check_deadline(file)?;
+            let batch_errors = self.commit_batch(&insert_sql, &columns, &batch, file, table_name)?;
+            local_errors.extend(batch_errors);
+            processed += batch.len();
+
+            if let Some(progress) = pb {
+                progress.inc(batch.len() as u64);
+            }
+        }
+
+        self.errors.extend(local_errors.clone());
+
+        let mut results = ProcessingResults::new(processed, local_errors);
+        results.deduplicated = deduplicated;
+        results.numeric_stats = numeric_stats
+            .into_iter()
+            .map(|(index, stats)| (columns[index].clone(), stats))
+            .collect();
+        results.null_counts = columns.into_iter().zip(null_counts).collect();
+
+        Ok(results)
+    }
+
+    /// Commits a batch of records within a transaction.
+    ///
+    /// This is an internal helper that executes a batch of INSERT statements
+    /// within a single transaction. Foreign key violations are caught and
+    /// collected without stopping the transaction.
+    ///
+    /// # Arguments
+    ///
+    /// * `insert_sql` - The prepared INSERT statement
+    /// * `columns` - The column order of the values in each record
+    /// * `batch` - The batch of records to insert (values and line numbers)
+    /// * `file` - The file metadata for error reporting
+    /// * `table_name` - The table name for error reporting
+    ///
+    /// # Returns
+    ///
+    /// A vector of errors encountered during the batch commit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a non-foreign-key database error occurs.
+    fn commit_batch(
+        &mut self,
+        insert_sql: &str,
+        columns: &[String],
+        batch: &[(Vec<Option<String>>, usize)],
+        file: &FileMetadata,
+        table_name: &str,
+    ) -> Result<Vec<ErrorDetails>> {
+        let mut errors = Vec::new();
+
+        let key_index = self
+            .reference_field
+            .as_deref()
+            .and_then(|field| columns.iter().position(|column| column == field));
+
+        let tx = self
+            .database
+            .transaction()
+            .context("Failed to begin transaction")?;
+
+        {
+            let mut stmt = tx
+                .prepare(insert_sql)
+                .context("Failed to prepare INSERT statement")?;
+
+            for (values, line_number) in batch {
+                let params: Vec<rusqlite::types::Value> = values
+                    .iter()
+                    .map(|v| match v {
+                        Some(s) => rusqlite::types::Value::Text(s.clone()),
+                        None => rusqlite::types::Value::Null,
+                    })
+                    .collect();
+
+                match stmt.execute(rusqlite::params_from_iter(params.iter())) {
+                    Ok(_) => {}
+                    Err(rusqlite::Error::SqliteFailure(err, _))
+                        if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+                    {
+                        if err.extended_code == FOREIGN_KEY_ERROR_CODE {
+                            let message = format!(
+                                "Foreign key violation inserting into {}\n  File: {} ({})\n  Line: {}\n  Values: {:?}",
+                                table_name, file.id, file.name, line_number, values
+                            );
+
+                            let error_details = ErrorDetails::new(
+                                file.id.to_string(),
+                                table_name.to_string(),
+                                message,
+                                err.to_string(),
+                            )
+                            .with_record(
+                                *line_number,
+                                key_index.map(|index| columns[index].clone()),
+                                key_index.and_then(|index| values[index].clone()),
+                            )
+                            .with_kind(ErrorKind::ForeignKeyViolation);
+
+                            errors.push(error_details);
+                            continue;
+                        } else if err.extended_code == PRIMARY_KEY_ERROR_CODE
+                            && let Some(index) = key_index
+                            && let Some(key) = values[index].clone()
+                        {
+                            errors.push(duplicate_key_error(file, table_name, *line_number, columns[index].clone(), key));
+                            continue;
+                        } else {
+                            return Err(rusqlite::Error::SqliteFailure(err, None).into());
+                        }
+                    }
+                    Err(e) => {
+                        return Err(e)
+                            .with_context(|| format!("Failed to insert record at line {}", line_number))?;
+                    }
+                }
+            }
+        }
+
+        tx.commit().context("Failed to commit transaction")?;
+
+        Ok(errors)
+    }
+
+    /// Processes a complete file (creates table and inserts records).
+    ///
+    /// This is the main entry point for processing a file. It:
+    /// 1. Checks if the handler is initialized
+    /// 2. Checks if the file has already been processed
+    /// 3. Creates the table for the file
+    /// 4. Inserts column descriptions from the DES file
+    /// 5. Inserts all records from the file
+    /// 6. Marks the file as processed
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The file metadata to process
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(Some(results))` if the file was processed
+    /// - `Ok(None)` if the file was already processed (skipped)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The handler hasn't been initialized
+    /// - Table creation fails
+    /// - Record insertion fails (excluding foreign key violations)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use ncdac_opi_parser::files::get_file_by_id;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    ///
+    /// // Initialize with reference file
+    /// let reference = get_file_by_id("OFNT3AA1").unwrap();
+    /// handler.init(reference, None)?;
+    ///
+    /// // Process another file
+    /// let file = get_file_by_id("OFNT1BA1").unwrap();
+    /// if let Some(results) = handler.process_file(file, None)? {
+    ///     println!("Processed {} records", results.processed);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn process_file(
+        &mut self,
+        file: &FileMetadata,
+        pb: Option<&ProgressBar>,
+    ) -> Result<Option<ProcessingResults>> {
+        if !self.is_initialized {
+            return Err(anyhow!("DataHandler is not initialized"));
+        }
+
+        if self.processed_files.contains(file.id) {
+            return Ok(None);
+        }
+
+        // Try to create the table and get the description
+        let (table_name, description) = match self.create_table_for_file(file) {
+            Ok(result) => result,
+            Err(e) => {
+                // Check if this is a DES file error (file not found or parse error)
+                let error_msg = format!("{:#}", e);
+                if error_msg.contains("Failed to read DES file") || error_msg.contains("DES file") {
+                    // Collect DES file failure and return Ok(None) to continue processing other files
+                    self.des_file_failures.push(file.id.to_string());
+                    return Ok(None);
+                }
+                // For other errors, record the failure and propagate them
+                self.file_failures.push(FileFailure::new(
+                    file.id,
+                    format!("Failed to create table: {}", error_msg),
+                ));
+                return Err(e);
+            }
+        };
+
+        self.prepare_table(file, &table_name, &description)?;
+        let results = self.insert_records_for_file(file, pb)?;
+        self.finish_file(file, &table_name)?;
+
+        Ok(Some(results))
+    }
+
+    /// Processes a file straight from its ZIP archive.
+    ///
+    /// Reads the `.des` entry to build the schema and streams the `.dat`
+    /// entry into batched inserts, so no extracted files are written to
+    /// disk. Otherwise behaves like `process_file`: a missing or invalid DES
+    /// entry is recorded in `des_file_failures` and the file is skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The file metadata to process
+    /// * `zip_path` - Path to the file's ZIP archive
+    /// * `pb` - Optional progress bar, advanced per committed batch
+    ///
+    /// # Returns
+    ///
+    /// - `Ok(Some(results))` if the file was processed
+    /// - `Ok(None)` if the file was already processed or its DES entry is unusable
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The handler hasn't been initialized
+    /// - The archive cannot be read or has no `.dat` entry
+    /// - Table creation or record insertion fails (excluding foreign key violations)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use ncdac_opi_parser::files::get_file_by_id;
+    /// use std::path::Path;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// let file = get_file_by_id("OFNT1BA1").unwrap();
+    /// // Note: handler must be initialized first in real usage
+    /// // handler.process_file_from_zip(file, Path::new("data/OFNT1BA1.zip"), None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn process_file_from_zip(
+        &mut self,
+        file: &FileMetadata,
+        zip_path: &Path,
+        pb: Option<&ProgressBar>,
+    ) -> Result<Option<ProcessingResults>> {
+        if !self.is_initialized {
+            return Err(anyhow!("DataHandler is not initialized"));
+        }
+
+        if self.processed_files.contains(file.id) {
+            return Ok(None);
+        }
+
+        let zip_file = std::fs::File::open(zip_path)
+            .with_context(|| format!("Failed to open ZIP file: {}", zip_path.display()))?;
+        let mut archive = zip::ZipArchive::new(std::io::BufReader::new(zip_file))
+            .with_context(|| format!("Failed to read ZIP archive: {}", zip_path.display()))?;
+
+        let des_name = find_zip_entry(&archive, &format!("{}.des", file.id));
+        let schema = des_name.and_then(|name| {
+            let mut content = String::new();
+            archive
+                .by_name(&name)
+                .ok()?
+                .read_to_string(&mut content)
+                .ok()?;
+            FileDescription::parse_content(&content).ok()
+        });
+
+        let description = match schema {
+            Some(schema) => FileDescription {
+                filename: file.id.to_string(),
+                schema,
+            },
+            None => {
+                self.des_file_failures.push(file.id.to_string());
+                return Ok(None);
+            }
+        };
+
+        let table_name = to_snake_case(file.name);
+        let created = self.create_table_sql(&table_name, &description).and_then(|sql| {
+            self.database
+                .execute(&sql, [])
+                .with_context(|| format!("Failed to create table {}", table_name))
+        });
+        if let Err(e) = created {
+            self.file_failures
+                .push(FileFailure::new(file.id, format!("Failed to create table: {:#}", e)));
+            return Err(e);
+        }
+
+        self.prepare_table(file, &table_name, &description)?;
+
+        let dat_name = find_zip_entry(&archive, &format!("{}.dat", file.id))
+            .ok_or_else(|| anyhow!("No DAT entry for {} in {}", file.id, zip_path.display()))?;
+        let dat_entry = archive
+            .by_name(&dat_name)
+            .with_context(|| format!("Failed to read ZIP entry: {}", dat_name))?;
+
+        let records = RecordIterator::new(std::io::BufReader::new(dat_entry), description.clone())
+            .with_control_char_policy(self.load.control_chars)
+            .with_empty_to_null(self.load.empty_to_null)
+            .with_raw_fields(
+                self.raw_shadow_columns(&description)?
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+            );
+        let mut results = self.insert_records(file, &table_name, &description, records, pb)?;
+        self.report_reserved_columns(file, &table_name, &description, &mut results);
+        *self.skipped_rows.entry(file.id.to_string()).or_default() += results.deduplicated;
+
+        self.finish_file(file, &table_name)?;
+
+        Ok(Some(results))
+    }
+
+    /// Records a new table's key column and inserts its column descriptions.
+    fn prepare_table(
+        &mut self,
+        file: &FileMetadata,
+        table_name: &str,
+        description: &FileDescription,
+    ) -> Result<()> {
+        if let Some(key_column) = self.key_field(description) {
+            self.load.key_columns.insert(file.id.to_string(), key_column);
+        }
+
+        if Some(table_name) != self.reference_table_name.as_deref() && !self.matches_reference_key(description) {
+            self.load.standalone_files.insert(file.id.to_string());
+        }
+
+        self.insert_column_descriptions(table_name, description)
+    }
+
+    /// Returns the key field of a file.
+    ///
+    /// A field chosen with `set_key_field` or `set_reference_key` wins.
+    /// Otherwise uses the named candidates from `get_primary_key_field` first. For
+    /// files without one, samples the extracted DAT file and picks the first
+    /// field that is unique and non-null (see
+    /// `DataParser::infer_key_candidates`). Returns `None` if neither finds a
+    /// key or the DAT file cannot be read.
+    fn key_field(&self, description: &FileDescription) -> Option<String> {
+        if let Some(key) = self.load.key_overrides.get(&description.filename) {
+            return Some(key.clone());
+        }
+
+        if let Some(key) = get_primary_key_field(&description.schema) {
+            return Some(key.to_string());
+        }
+
+        let mut parser = DataParser::from_description(description.clone(), &self.load.data_dir);
+        parser.set_control_char_policy(self.load.control_chars);
+        parser
+            .infer_key_candidates(KEY_INFERENCE_SAMPLE)
+            .ok()?
+            .into_iter()
+            .next()
+    }
+
+    /// Checks whether a file's key could plausibly reference the reference key.
+    ///
+    /// The key columns have different names across files (CMDORNUM,
+    /// CIDORNUM, ...), so the check compares the key's type and length with
+    /// the reference key instead. When the reference DES cannot be read, as
+    /// when loading straight from a ZIP archive, the key is assumed to match.
+    fn matches_reference_key(&self, description: &FileDescription) -> bool {
+        let (Some(reference_file), Some(reference_field)) = (&self.reference_file, &self.reference_field) else {
+            return true;
+        };
+        let Some(key) = self.key_field(description) else {
+            return true;
+        };
+        let Ok(reference_description) = self.file_description(reference_file.id) else {
+            return true;
+        };
+
+        match reference_description.schema.get(reference_field) {
+            Some(reference_key) => {
+                let key = &description.schema[&key];
+                key.field_type == reference_key.field_type && key.length == reference_key.length
+            }
+            None => true,
+        }
+    }
+
+    /// Returns the IDs of files loaded without a foreign key to the reference table.
+    ///
+    /// A file lands here when its key column's type or length differs from
+    /// the reference key, which means the chosen reference file cannot be
+    /// what it relates to. Such files are still loaded, with a warning in
+    /// their results.
+    pub fn standalone_files(&self) -> &HashSet<String> {
+        &self.load.standalone_files
+    }
+
+    /// Builds configured lookup tables and marks the file as processed.
+    fn finish_file(&mut self, file: &FileMetadata, table_name: &str) -> Result<()> {
+        if let Some(columns) = self.lookup_columns.get(file.id).cloned() {
+            for column in columns {
+                let values = self.distinct_column_values(table_name, &column)?;
+                self.create_lookup_table(file.id, &column, &values)?;
+            }
+        }
+
+        self.processed_files.insert(file.id.to_string());
+
+        Ok(())
+    }
+
+    /// Adds a validation rule for a column of a file.
+    ///
+    /// Rules are evaluated against each record's coerced values as it is
+    /// loaded. A failing value is recorded in `errors` as a
+    /// `Severity::Warning` with its line number, and the row is still
+    /// inserted. Columns are validated against the DES schema when the file
+    /// is loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The file ID (e.g., "OFNT3AA1")
+    /// * `column` - The field code to check
+    /// * `rule` - The rule the value must satisfy
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::{DataHandler, ValidationRule};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.add_validation(
+    ///     "OFNT3AA1",
+    ///     "CMDORNUM",
+    ///     ValidationRule::MatchesRegex(regex::Regex::new(r"^\d{7}$")?),
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_validation(&mut self, file_id: &str, column: &str, rule: ValidationRule) {
+        self.load.validations
+            .entry(file_id.to_string())
+            .or_default()
+            .push((column.to_string(), rule));
+    }
+
+    /// Keeps the untrimmed value of some columns in `<column>_raw` shadow columns.
+    ///
+    /// For audit purposes, each listed column is stored twice: coerced as
+    /// usual, and as the exact slice from the DAT record, padding included,
+    /// in an extra TEXT column. Columns are validated against the DES schema
+    /// when the table is created. Replaces any columns previously set for
+    /// the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The file ID (e.g., "OFNT1BA1")
+    /// * `columns` - The field codes to shadow
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.set_raw_shadow_columns("OFNT1BA1", &["CPCOPBAL"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_raw_shadow_columns(&mut self, file_id: &str, columns: &[&str]) {
+        self.load.raw_shadow_columns.insert(
+            file_id.to_string(),
+            columns.iter().map(|column| column.to_string()).collect(),
+        );
+    }
+
+    /// Returns the raw-shadowed columns of a file, validated against its schema.
+    fn raw_shadow_columns<'a>(&'a self, description: &FileDescription) -> Result<Vec<&'a str>> {
+        let columns: Vec<&str> = self
+            .load
+            .raw_shadow_columns
+            .get(&description.filename)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+
+        if let Some(missing) = columns.iter().find(|column| !description.schema.contains_key(**column)) {
+            return Err(anyhow!(
+                "Raw shadow columns for {} name unknown column {}",
+                description.filename,
+                missing
+            ));
+        }
+
+        Ok(columns)
+    }
+
+    /// Stores Y/N flag columns of a file as INTEGER booleans.
+    ///
+    /// Each listed column is created as INTEGER and its values are coerced
+    /// as they are loaded: `Y` becomes 1, `N` becomes 0, and a blank value
+    /// becomes NULL. Any other value is stored as NULL and recorded in
+    /// `errors` as a `Severity::Warning` with its line number. Columns are
+    /// validated against the DES schema when the table is created. Replaces
+    /// any columns previously set for the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The file ID (e.g., "OFNT3AA1")
+    /// * `columns` - The field codes holding Y/N flags
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.set_boolean_columns("OFNT3AA1", &["ACTIVEFL"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_boolean_columns(&mut self, file_id: &str, columns: &[&str]) {
+        self.load.boolean_columns.insert(
+            file_id.to_string(),
+            columns.iter().map(|column| column.to_string()).collect(),
+        );
+    }
+
+    /// Returns the boolean columns of a file, validated against its schema.
+    fn boolean_columns<'a>(&'a self, description: &FileDescription) -> Result<Vec<&'a str>> {
+        let columns: Vec<&str> = self
+            .load
+            .boolean_columns
+            .get(&description.filename)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+
+        if let Some(missing) = columns.iter().find(|column| !description.schema.contains_key(**column)) {
+            return Err(anyhow!(
+                "Boolean columns for {} name unknown column {}",
+                description.filename,
+                missing
+            ));
+        }
+
+        Ok(columns)
+    }
+
+    /// Sets the leading column order of a file's table.
+    ///
+    /// The listed columns come first, in the given order, followed by the
+    /// remaining columns in DES order. Applies to the CREATE TABLE statement
+    /// and the insert column list. Columns are validated against the DES
+    /// schema when the table is created. Replaces any order previously set
+    /// for the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The file ID (e.g., "OFNT1BA1")
+    /// * `columns` - The field codes to place first
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.set_column_order("OFNT1BA1", &["CMDORNUM", "CPCOPBAL"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_column_order(&mut self, file_id: &str, columns: &[&str]) {
+        self.load.column_orders.insert(
+            file_id.to_string(),
+            columns.iter().map(|column| column.to_string()).collect(),
+        );
+    }
+
+    /// Returns the table columns of a file in creation order.
+    ///
+    /// Columns configured with `set_column_order` lead, followed by the rest
+    /// of the schema in DES order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a configured column is not in the schema or is
+    /// listed twice.
+    fn column_order<'a>(&self, description: &'a FileDescription) -> Result<Vec<&'a str>> {
+        let mut columns: Vec<&'a str> = Vec::with_capacity(description.schema.len());
+
+        for column in self.load.column_orders.get(&description.filename).into_iter().flatten() {
+            let (code, _) = description.schema.get_key_value(column).ok_or_else(|| {
+                anyhow!(
+                    "Column order for {} names unknown column {}",
+                    description.filename,
+                    column
+                )
+            })?;
+            if columns.contains(&code.as_str()) {
+                return Err(anyhow!(
+                    "Column order for {} lists {} more than once",
+                    description.filename,
+                    column
+                ));
+            }
+            columns.push(code);
+        }
+
+        for (code, _) in description.ordered_fields() {
+            if !columns.contains(&code) {
+                columns.push(code);
+            }
+        }
+
+        Ok(columns)
+    }
+
+    /// Configures code columns of a file to get lookup tables.
+    ///
+    /// After the file's records are inserted by `process_file`, a
+    /// `<column>_lookup` table is created for each column, holding the
+    /// distinct non-null values found in the data. Replaces any columns
+    /// previously configured for the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The file ID (e.g., "OFNT3AA1")
+    /// * `columns` - The field codes to build lookup tables for
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.set_lookup_columns("OFNT3AA1", &["CMRACCOD", "CMSEXCOD"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_lookup_columns(&mut self, file_id: &str, columns: &[&str]) {
+        self.lookup_columns.insert(
+            file_id.to_string(),
+            columns.iter().map(|column| column.to_string()).collect(),
+        );
+    }
+
+    /// Creates a lookup table of distinct codes for a column.
+    ///
+    /// The table is named `<column>_lookup` (snake_case) and has a `code`
+    /// primary key plus an empty `description` column for analysts to fill
+    /// in later. Existing codes are kept, so several files sharing a code
+    /// column contribute to one table. The source column is recorded in
+    /// `column_descriptions`.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The file ID the codes were taken from
+    /// * `column` - The field code the lookup table is for
+    /// * `values` - The codes to insert
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table cannot be created or the codes cannot
+    /// be inserted.
+    pub fn create_lookup_table(&mut self, file_id: &str, column: &str, values: &[String]) -> Result<()> {
+        let table_name = format!("{}_lookup", to_snake_case(column));
+        let lookup_table = self.qualified(&table_name);
+        let descriptions_table = self.qualified("column_descriptions");
+
+        let tx = self
+            .database
+            .transaction()
+            .context("Failed to begin lookup table transaction")?;
+
+        tx.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} (code TEXT PRIMARY KEY, description TEXT)",
+                lookup_table
+            ),
+            [],
+        )
+        .with_context(|| format!("Failed to create lookup table {}", table_name))?;
+
+        {
+            let mut stmt = tx
+                .prepare(&format!(
+                    "INSERT OR IGNORE INTO {} (code) VALUES (?)",
+                    lookup_table
+                ))
+                .context("Failed to prepare lookup INSERT statement")?;
+
+            for value in values {
+                stmt.execute([value])
+                    .with_context(|| format!("Failed to insert code {} into {}", value, table_name))?;
+            }
+        }
+
+        tx.execute(
+            &format!(
+                "INSERT INTO {table} (table_name, column_name, description)
+                 SELECT ?1, 'code', ?2
+                 WHERE NOT EXISTS (
+                     SELECT 1 FROM {table} WHERE table_name = ?1 AND description = ?2
+                 )",
+                table = descriptions_table
+            ),
+            [table_name.as_str(), &format!("Distinct {} codes from {}", column, file_id)],
+        )
+        .context("Failed to describe lookup table")?;
+
+        tx.commit().context("Failed to commit lookup table transaction")?;
+
+        Ok(())
+    }
+
+    /// Returns the distinct non-null values of a column, sorted ascending.
+    fn distinct_column_values(&self, table_name: &str, column: &str) -> Result<Vec<String>> {
+        let sql = format!(
+            "SELECT DISTINCT CAST({column} AS TEXT) FROM {table} WHERE {column} IS NOT NULL ORDER BY 1",
+            column = quote_identifier(&self.column_name(column)),
+            table = self.qualified(table_name)
+        );
+
+        let mut stmt = self
+            .database
+            .prepare(&sql)
+            .with_context(|| format!("Failed to read {}.{}", table_name, column))?;
+
+        let values = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+
+        Ok(values)
+    }
+
+    /// Drops the table and column descriptions created for a file.
+    ///
+    /// Used to discard partially inserted rows from a failed attempt before
+    /// the file is processed again. Missing tables are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The file metadata whose table should be dropped
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DROP or DELETE statement fails.
+    pub fn drop_table_for_file(&mut self, file: &FileMetadata) -> Result<()> {
+        let table_name = to_snake_case(file.name);
+
+        self.database
+            .execute(&format!("DROP TABLE IF EXISTS {}", self.qualified(&table_name)), [])
+            .with_context(|| format!("Failed to drop table {}", table_name))?;
+
+        self.database
+            .execute(
+                &format!("DELETE FROM {} WHERE table_name = ?", self.qualified("column_descriptions")),
+                [&table_name],
+            )
+            .with_context(|| format!("Failed to delete column descriptions for {}", table_name))?;
+
+        self.processed_files.remove(file.id);
+        self.load.key_columns.remove(file.id);
+        self.load.standalone_files.remove(file.id);
+        self.skipped_rows.remove(file.id);
+
+        Ok(())
+    }
+
+    /// Reloads a dependent file's table without readers seeing a partial table.
+    ///
+    /// The file is loaded into a staging table, `<table>_new`, while the
+    /// existing table stays in place. Once the load succeeds, a single
+    /// transaction drops the old table, renames the staging table, and
+    /// replaces the table's column descriptions, so other connections see
+    /// either the old or the new contents. If the load fails, the staging
+    /// table is dropped and the old table is left untouched.
+    ///
+    /// Only dependent tables can be refreshed this way. Dropping the
+    /// reference table would violate the foreign keys of every dependent
+    /// table, so the reference file is refreshed by rebuilding the database.
+    /// Dependent tables are not referenced by other tables, so dropping one
+    /// and renaming its replacement needs no foreign key handling.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The dependent file to reload
+    /// * `pb` - Optional progress bar, advanced per committed batch
+    ///
+    /// # Returns
+    ///
+    /// The processing results of the new load.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if:
+    /// - The handler hasn't been initialized
+    /// - `file` is the reference file
+    /// - The DES or DAT file cannot be read, or record insertion fails
+    /// - The swap transaction fails (the old table is kept)
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use ncdac_opi_parser::files::get_file_by_id;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.set_reuse_existing_reference(true);
+    /// handler.init(get_file_by_id("OFNT3AA1").unwrap(), None)?;
+    ///
+    /// let results = handler.refresh_file_atomic(get_file_by_id("OFNT1BA1").unwrap(), None)?;
+    /// println!("{} records reloaded", results.processed);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn refresh_file_atomic(&mut self, file: &FileMetadata, pb: Option<&ProgressBar>) -> Result<ProcessingResults> {
+        if !self.is_initialized {
+            return Err(anyhow!("DataHandler is not initialized"));
+        }
+
+        let table_name = to_snake_case(file.name);
+        if Some(table_name.as_str()) == self.reference_table_name.as_deref() {
+            return Err(anyhow!(
+                "Cannot refresh reference table {} in place: dependent tables reference it",
+                table_name
+            ));
+        }
+
+        let staging_name = format!("{}{}", table_name, STAGING_SUFFIX);
+        let staging_table = self.qualified(&staging_name);
+        let description = self.file_description(file.id)?;
+
+        // A staging table left by an interrupted refresh is discarded
+        self.database
+            .execute(&format!("DROP TABLE IF EXISTS {}", staging_table), [])
+            .with_context(|| format!("Failed to drop staging table {}", staging_name))?;
+        let sql = self.create_table_sql(&staging_name, &description)?;
+        self.database
+            .execute(&sql, [])
+            .with_context(|| format!("Failed to create staging table {}", staging_name))?;
+
+        if let Some(key_column) = self.key_field(&description) {
+            self.load.key_columns.insert(file.id.to_string(), key_column);
+        }
+        if self.matches_reference_key(&description) {
+            self.load.standalone_files.remove(file.id);
+        } else {
+            self.load.standalone_files.insert(file.id.to_string());
+        }
+
+        let results = match self.insert_records_into(file, &staging_name, pb) {
+            Ok(results) => results,
+            Err(e) => {
+                let _ = self.database.execute(&format!("DROP TABLE IF EXISTS {}", staging_table), []);
+                return Err(e.context(format!("Failed to refresh {}; the existing table was kept", table_name)));
+            }
+        };
+
+        let rows = self.column_description_rows(&description)?;
+        let descriptions_table = self.qualified("column_descriptions");
+        let old_table = self.qualified(&table_name);
+
+        let tx = self.database.transaction()
+            .with_context(|| format!("Failed to begin swap of {}", table_name))?;
+        tx.execute(&format!("DROP TABLE IF EXISTS {}", old_table), [])
+            .with_context(|| format!("Failed to drop table {}", table_name))?;
+        tx.execute(
+            &format!("ALTER TABLE {} RENAME TO {}", staging_table, quote_identifier(&table_name)),
+            [],
+        )
+        .with_context(|| format!("Failed to rename {} to {}", staging_name, table_name))?;
+        tx.execute(
+            &format!("DELETE FROM {} WHERE table_name = ?", descriptions_table),
+            [&table_name],
+        )
+        .with_context(|| format!("Failed to delete column descriptions for {}", table_name))?;
+        write_column_descriptions(&tx, &descriptions_table, &table_name, &rows)?;
+        tx.commit().with_context(|| format!("Failed to commit swap of {}", table_name))?;
+
+        self.processed_files.remove(file.id);
+        self.finish_file(file, &table_name)?;
+
+        Ok(results)
+    }
+
+    /// Verifies the referential and structural integrity of the database.
+    ///
+    /// Runs `PRAGMA foreign_key_check` and `PRAGMA integrity_check` and
+    /// returns their findings. Intended as a final quality gate after all
+    /// files have been loaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either PRAGMA fails to execute.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = DataHandler::new("database.db")?;
+    /// let report = handler.verify_integrity()?;
+    /// if !report.is_ok() {
+    ///     println!("{} orphaned rows", report.foreign_key_violations.len());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn verify_integrity(&self) -> Result<IntegrityReport> {
+        let mut stmt = self
+            .database
+            .prepare(&pragma_sql(self.schema.as_deref(), "foreign_key_check"))
+            .context("Failed to run PRAGMA foreign_key_check")?;
+
+        let foreign_key_violations = stmt
+            .query_map([], |row| {
+                Ok(ForeignKeyViolation {
+                    table_name: row.get(0)?,
+                    rowid: row.get(1)?,
+                    parent_table: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read foreign key check results")?;
+
+        let mut stmt = self
+            .database
+            .prepare(&pragma_sql(self.schema.as_deref(), "integrity_check"))
+            .context("Failed to run PRAGMA integrity_check")?;
+
+        let integrity_errors = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read integrity check results")?
+            .into_iter()
+            .filter(|message| message != "ok")
+            .collect();
+
+        Ok(IntegrityReport {
+            foreign_key_violations,
+            integrity_errors,
+        })
+    }
+
+    /// Checks that every source record of the loaded files is accounted for.
+    ///
+    /// For each file with a table, compares the lines of its DAT file in
+    /// `data_dir` (see `count_lines`) with the table's row count plus the records
+    /// rejected (errors of `Severity::Error` in `errors`) and skipped as
+    /// duplicates by this handler. Any discrepancy points to rows dropped
+    /// without a recorded error. Files without a table are skipped.
+    ///
+    /// Rejections and skips are only known for files loaded by this handler
+    /// (errors of pipeline workers are merged into `errors`, but their
+    /// duplicate counts are not).
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The files to check
+    /// * `data_dir` - The data directory containing the extracted DAT files
+    ///
+    /// # Returns
+    ///
+    /// One `CountCheck` per file with a table, in the order given.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a DAT file cannot be read or a table cannot be
+    /// counted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use ncdac_opi_parser::files::FILES;
+    /// use std::path::Path;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = DataHandler::new("database.db")?;
+    /// for check in handler.verify_counts(&FILES, Path::new("data"))? {
+    ///     if !check.is_ok() {
+    ///         eprintln!("{}", check);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn verify_counts(&self, files: &[FileMetadata], data_dir: &Path) -> Result<Vec<CountCheck>> {
+        let mut checks = Vec::new();
+
+        for file in files {
+            let table_name = to_snake_case(file.name);
+            if table_info_in(&self.database, self.schema.as_deref(), &table_name).is_err() {
+                continue;
+            }
+
+            let dat_path = extracted_file_path(data_dir, file.id, "dat");
+            let source_records = count_lines(&dat_path)
+                .with_context(|| format!("Failed to count records of {}", file.id))?;
+            let table_rows: i64 = self
+                .database
+                .query_row(&format!("SELECT COUNT(*) FROM {}", self.qualified(&table_name)), [], |row| row.get(0))
+                .with_context(|| format!("Failed to count rows of {}", table_name))?;
+            let rejected = self
+                .errors
+                .iter()
+                .filter(|error| error.file_id == file.id && error.severity == Severity::Error)
+                .count();
+
+            checks.push(CountCheck {
+                file_id: file.id.to_string(),
+                table_name,
+                source_records,
+                table_rows: table_rows as u64,
+                rejected: rejected as u64,
+                skipped: self.skipped_rows.get(file.id).copied().unwrap_or(0) as u64,
+            });
+        }
+
+        Ok(checks)
+    }
+
+    /// Returns whether the handler has been initialized.
+    pub fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+
+    /// Returns a reference to the reference file metadata.
+    pub fn reference_file(&self) -> Option<&FileMetadata> {
+        self.reference_file.as_ref()
+    }
+
+    /// Returns a reference to the reference table name.
+    pub fn reference_table_name(&self) -> Option<&str> {
+        self.reference_table_name.as_deref()
+    }
+
+    /// Returns a reference to the reference field name.
+    pub fn reference_field(&self) -> Option<&str> {
+        self.reference_field.as_deref()
+    }
+
+    /// Returns the set of processed file IDs.
+    pub fn processed_files(&self) -> &HashSet<String> {
+        &self.processed_files
+    }
+
+    /// Returns the key relationships of every processed table, sorted by table name.
+    ///
+    /// Built from the handler's own state, so no DES files are read. The
+    /// reference table and standalone tables (see `standalone_files`) report
+    /// only their key column; every other table also reports the reference
+    /// table and field its key column points to.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = DataHandler::new("database.db")?;
+    /// for relationship in handler.relationships() {
+    ///     println!("{}.{} -> {:?}", relationship.table, relationship.key_column, relationship.target_table);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn relationships(&self) -> Vec<Relationship> {
+        let reference_id = self.reference_file.as_ref().map(|file| file.id);
+
+        let mut relationships: Vec<Relationship> = self
+            .processed_files
+            .iter()
+            .filter_map(|file_id| {
+                let file = crate::files::get_file_by_id(file_id)?;
+                let key_column = self.load.key_columns.get(file_id)?.clone();
+                let standalone = Some(file.id) == reference_id || self.load.standalone_files.contains(file_id);
+
+                Some(Relationship {
+                    file_id: file_id.clone(),
+                    table: to_snake_case(file.name),
+                    key_column,
+                    target_table: if standalone { None } else { self.reference_table_name.clone() },
+                    target_column: if standalone { None } else { self.reference_field.clone() },
+                })
+            })
+            .collect();
+
+        relationships.sort_by(|a, b| a.table.cmp(&b.table));
+        relationships
+    }
+
+    /// Recommends indexes for the processed tables without creating them.
+    ///
+    /// Suggests an index on every foreign key column, since nearly every
+    /// query joins back to the reference table, and on every configured
+    /// lookup column. Review the statements and apply the useful ones.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = DataHandler::new("database.db")?;
+    /// for suggestion in handler.suggest_indexes() {
+    ///     println!("{};  -- {}", suggestion.statement, suggestion.reason);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn suggest_indexes(&self) -> Vec<IndexSuggestion> {
+        let mut suggestions = Vec::new();
+
+        for relationship in self.relationships() {
+            if let Some(target_table) = &relationship.target_table {
+                suggestions.push(IndexSuggestion::new(
+                    &relationship.table,
+                    &relationship.key_column,
+                    format!("foreign key to {}", target_table),
+                ));
+            }
+
+            for column in self.lookup_columns.get(&relationship.file_id).into_iter().flatten() {
+                if *column != relationship.key_column {
+                    suggestions.push(IndexSuggestion::new(
+                        &relationship.table,
+                        &self.column_name(column),
+                        format!("lookup column joined to {}_lookup", to_snake_case(column)),
+                    ));
+                }
+            }
+        }
+
+        suggestions
+    }
+
+    /// Creates an index on one or more columns of a table.
+    ///
+    /// The index is named `idx_<table>_<column>_...` after the snake_case
+    /// column names, like the statements from `suggest_indexes`, and is
+    /// created with `IF NOT EXISTS`, so calling this again is harmless.
+    /// Every column is validated against the table schema first.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The table to index
+    /// * `columns` - The columns to index, in index order
+    /// * `unique` - Whether to create a `UNIQUE` index
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table does not exist, `columns` is empty or
+    /// names a column the table lacks, or the index cannot be created (for
+    /// a unique index, because the column values are not unique).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = DataHandler::new("database.db")?;
+    /// handler.create_index("financial_obligation", &["CMDORNUM", "CPSTATUS"], false)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn create_index(&self, table: &str, columns: &[&str], unique: bool) -> Result<()> {
+        if columns.is_empty() {
+            return Err(anyhow!("No columns given to index on {}", table));
+        }
+
+        let existing: HashSet<String> = table_info_in(&self.database, self.schema.as_deref(), table)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+
+        if let Some(missing) = columns.iter().find(|column| !existing.contains(**column)) {
+            return Err(anyhow!("Table {} has no column {}", table, missing));
+        }
+
+        let index_name = format!(
+            "idx_{}_{}",
+            table,
+            columns.iter().map(|column| to_snake_case(column)).collect::<Vec<_>>().join("_")
+        );
+        let sql = format!(
+            "CREATE {}INDEX IF NOT EXISTS {} ON {} ({})",
+            if unique { "UNIQUE " } else { "" },
+            self.qualified(&index_name),
+            quote_identifier(table),
+            columns
+                .iter()
+                .map(|column| quote_identifier(column))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        self.database
+            .execute(&sql, [])
+            .with_context(|| format!("Failed to create index {}", index_name))?;
+
+        Ok(())
+    }
+
+    /// Declares an index for `finalize` to create on a file's table.
+    ///
+    /// Columns are field codes and follow the same renaming as the table,
+    /// so a reserved-word field is indexed under its suffixed name. Several
+    /// indexes may be declared for one file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The file ID (e.g., "OFNT1BA1")
+    /// * `columns` - The field codes to index, in index order
+    /// * `unique` - Whether to create a `UNIQUE` index
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut handler = DataHandler::new("database.db")?;
+    /// handler.add_index("OFNT1BA1", &["CPSTATUS"], false);
+    /// // ... process files ...
+    /// handler.finalize()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_index(&mut self, file_id: &str, columns: &[&str], unique: bool) {
+        self.indexes
+            .entry(file_id.to_string())
+            .or_default()
+            .push((columns.iter().map(|column| column.to_string()).collect(), unique));
+    }
+
+    /// Creates the indexes declared with `add_index`.
+    ///
+    /// Run once all files are loaded, since building an index after the
+    /// bulk insert is far cheaper than maintaining it row by row. Files
+    /// whose table does not exist, for example because they were not
+    /// processed in this run, are skipped.
+    ///
+    /// # Returns
+    ///
+    /// The number of indexes created or already present.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a declared index cannot be created.
+    pub fn finalize(&self) -> Result<usize> {
+        let mut created = 0;
+
+        for (file_id, indexes) in &self.indexes {
+            let Some(file) = crate::files::get_file_by_id(file_id) else {
+                continue;
+            };
+            let table_name = to_snake_case(file.name);
+            if table_info_in(&self.database, self.schema.as_deref(), &table_name).is_err() {
+                continue;
+            }
+
+            for (columns, unique) in indexes {
+                let columns: Vec<String> = columns.iter().map(|column| self.column_name(column).into_owned()).collect();
+                let columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+                self.create_index(&table_name, &columns, *unique)
+                    .with_context(|| format!("Failed to create declared index for {}", file_id))?;
+                created += 1;
+            }
+        }
+
+        Ok(created)
+    }
+
+    /// Sets how many rows are written between explicit flushes during export.
+    ///
+    /// Flushing pushes buffered output through to the underlying writer at a
+    /// steady rate, so memory stays flat on multi-gigabyte exports and a
+    /// slow consumer such as a pipe applies backpressure. A value of `0`
+    /// flushes only once, after the last row. Defaults to
+    /// `DEFAULT_EXPORT_FLUSH_ROWS`.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - Number of rows between flushes
+    pub fn set_export_flush_interval(&mut self, rows: usize) {
+        self.export_flush_interval = rows;
+    }
+
+    /// Returns the number of rows written between flushes during export.
+    pub fn export_flush_interval(&self) -> usize {
+        self.export_flush_interval
+    }
+
+    /// Sets the text written for NULL values in CSV exports.
+    ///
+    /// Defaults to an empty field. Use `\N` for PostgreSQL `COPY ... CSV
+    /// NULL '\N'`, or `NULL` for tools that expect it spelled out. The
+    /// representation is written unquoted, and any non-NULL value that
+    /// equals it is quoted, so the two stay distinguishable on import.
+    ///
+    /// # Arguments
+    ///
+    /// * `null_repr` - The text to write for NULL
+    pub fn set_csv_null_repr(&mut self, null_repr: &str) {
+        self.csv_null_repr = null_repr.to_string();
+    }
+
+    /// Returns the text written for NULL values in CSV exports.
+    pub fn csv_null_repr(&self) -> &str {
+        &self.csv_null_repr
+    }
+
+    /// Streams selected columns of a table to CSV.
+    ///
+    /// Writes a header row with the column names followed by one row per
+    /// record, with columns in the order given. Every column is validated
+    /// against the table schema before anything is written. NULLs are
+    /// written as `csv_null_repr` (an empty field by default), and fields
+    /// containing commas, quotes, or line breaks, or equal to the NULL
+    /// representation, are quoted per RFC 4180. Rows are written as they are read, so large
+    /// tables are never held in memory, and the writer is flushed every
+    /// `export_flush_interval` rows.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - The table to export
+    /// * `columns` - The columns to include, in output order
+    /// * `writer` - Destination for the CSV text
+    ///
+    /// # Returns
+    ///
+    /// The number of data rows written (excluding the header).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table does not exist, `columns` is empty or
+    /// names a column the table lacks, or the query or write fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use std::fs::File;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = DataHandler::new("database.db")?;
+    /// let output = File::create("balances.csv")?;
+    /// let rows = handler.export_columns_to_csv(
+    ///     "financial_obligation",
+    ///     &["CMDORNUM", "CPCOPBAL"],
+    ///     output,
+    /// )?;
+    /// println!("Exported {} rows", rows);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn export_columns_to_csv<W: Write>(
+        &self,
+        table: &str,
+        columns: &[&str],
+        writer: W,
+    ) -> Result<usize> {
+        if columns.is_empty() {
+            return Err(anyhow!("No columns given to export from {}", table));
+        }
+
+        let existing: HashSet<String> = table_info_in(&self.database, self.schema.as_deref(), table)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+
+        if let Some(missing) = columns.iter().find(|column| !existing.contains(**column)) {
+            return Err(anyhow!("Table {} has no column {}", table, missing));
+        }
+
+        let sql = format!(
+            "SELECT {} FROM {}",
+            columns
+                .iter()
+                .map(|column| quote_identifier(column))
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.qualified(table)
+        );
+
+        let mut stmt = self
+            .database
+            .prepare(&sql)
+            .with_context(|| format!("Failed to query {} for export", table))?;
+
+        let mut writer = std::io::BufWriter::new(writer);
+        write_csv_row(
+            &mut writer,
+            columns.iter().map(|column| Some(column.to_string())),
+            &self.csv_null_repr,
+        )?;
+
+        let mut rows = stmt.query([])?;
+        let mut written = 0;
+
+        while let Some(row) = rows.next()? {
+            let mut values = Vec::with_capacity(columns.len());
+            for i in 0..columns.len() {
+                values.push(match row.get_ref(i)? {
+                    rusqlite::types::ValueRef::Null => None,
+                    rusqlite::types::ValueRef::Integer(value) => Some(value.to_string()),
+                    rusqlite::types::ValueRef::Real(value) => Some(value.to_string()),
+                    rusqlite::types::ValueRef::Text(bytes) | rusqlite::types::ValueRef::Blob(bytes) => {
+                        Some(String::from_utf8_lossy(bytes).into_owned())
+                    }
+                });
+            }
+            write_csv_row(&mut writer, values, &self.csv_null_repr)?;
+            written += 1;
+
+            if self.export_flush_interval > 0 && written % self.export_flush_interval == 0 {
+                writer.flush().context("Failed to flush CSV output")?;
+            }
+        }
+
+        writer.flush().context("Failed to flush CSV output")?;
+
+        Ok(written)
+    }
+
+    /// Writes the whole database as a SQL script, like `sqlite3 .dump`.
+    ///
+    /// The script disables foreign key checks, then inside one transaction
+    /// creates every table in creation order, inserts all of its rows, and
+    /// finally recreates indexes, views and triggers. Text is emitted as
+    /// single-quoted literals with embedded quotes doubled, BLOBs as `X'..'`
+    /// hex literals, and REAL values always carry a decimal point so they
+    /// keep their storage class. Rows are streamed one at a time and the
+    /// writer is flushed every `export_flush_interval` rows, so large
+    /// databases are never held in memory. With an attached schema only
+    /// that schema is dumped.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Destination for the SQL text
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the schema or a table cannot be read, or the
+    /// write fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use std::fs::File;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = DataHandler::new("database.db")?;
+    /// handler.dump_sql(File::create("database.sql")?)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn dump_sql<W: Write>(&self, writer: W) -> Result<()> {
+        let master = self.qualified("sqlite_master");
+
+        let tables: Vec<(String, String)> = {
+            let mut stmt = self
+                .database
+                .prepare(&format!(
+                    "SELECT name, sql FROM {} WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY rowid",
+                    master
+                ))
+                .context("Failed to read database schema")?;
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<_>>()?
+        };
+
+        let others: Vec<String> = {
+            let mut stmt = self
+                .database
+                .prepare(&format!(
+                    "SELECT sql FROM {} WHERE type IN ('index', 'view', 'trigger') AND sql IS NOT NULL ORDER BY rowid",
+                    master
+                ))
+                .context("Failed to read database schema")?;
+            stmt.query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?
+        };
+
+        let mut writer = std::io::BufWriter::new(writer);
+        let mut written = 0;
+
+        writeln!(writer, "PRAGMA foreign_keys=OFF;\nBEGIN TRANSACTION;")
+            .context("Failed to write SQL dump")?;
+
+        for (table, ddl) in &tables {
+            writeln!(writer, "{};", ddl).context("Failed to write SQL dump")?;
+
+            let mut stmt = self
+                .database
+                .prepare(&format!("SELECT * FROM {}", self.qualified(table)))
+                .with_context(|| format!("Failed to query {} for dump", table))?;
+            let column_count = stmt.column_count();
+            let insert = format!("INSERT INTO {} VALUES(", quote_identifier(table));
+
+            let mut rows = stmt.query([])?;
+            while let Some(row) = rows.next()? {
+                let mut values = Vec::with_capacity(column_count);
+                for i in 0..column_count {
+                    values.push(sql_literal(row.get_ref(i)?));
+                }
+                writeln!(writer, "{}{});", insert, values.join(","))
+                    .context("Failed to write SQL dump")?;
+                written += 1;
+
+                if self.export_flush_interval > 0 && written % self.export_flush_interval == 0 {
+                    writer.flush().context("Failed to flush SQL dump")?;
+                }
+            }
+        }
+
+        for sql in &others {
+            writeln!(writer, "{};", sql).context("Failed to write SQL dump")?;
+        }
+
+        writeln!(writer, "COMMIT;").context("Failed to write SQL dump")?;
+        writer.flush().context("Failed to flush SQL dump")?;
+
+        Ok(())
+    }
+
+    /// Returns a reference to the underlying SQLite connection.
+    ///
+    /// This is primarily used for PRAGMA configuration in concurrent processing scenarios.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let handler = DataHandler::new("database.db")?;
+    /// let conn = handler.connection();
+    /// // Configure PRAGMA settings on the connection
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connection(&self) -> &Connection {
+        &self.database
+    }
+
+    /// Initializes this handler with reference metadata from another handler.
+    ///
+    /// This is used to set up worker handlers in parallel processing scenarios.
+    /// Each worker needs to know about the reference table to properly create
+    /// foreign key constraints.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference_file` - The reference file metadata
+    /// * `reference_table_name` - The name of the reference table
+    /// * `reference_field` - The primary key field name of the reference table
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::data_handler::DataHandler;
+    /// use ncdac_opi_parser::files::get_file_by_id;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let main_handler = DataHandler::new("database.db")?;
+    /// let mut worker_handler = DataHandler::new("database.db")?;
+    ///
+    /// // Copy reference metadata from main handler to worker
+    /// if let (Some(ref_file), Some(ref_table), Some(ref_field)) = (
+    ///     main_handler.reference_file(),
+    ///     main_handler.reference_table_name(),
+    ///     main_handler.reference_field()
+    /// ) {
+    ///     worker_handler.init_from_reference(ref_file, ref_table, ref_field);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn init_from_reference(
+        &mut self,
+        reference_file: &FileMetadata,
+        reference_table_name: &str,
+        reference_field: &str,
+    ) {
+        self.reference_file = Some(*reference_file);
+        self.reference_table_name = Some(reference_table_name.to_string());
+        self.reference_field = Some(reference_field.to_string());
+        self.is_initialized = true;
+    }
+
+    /// Returns a formatted warning message for files that failed due to missing or invalid DES files.
+    ///
+    /// Returns None if no DES file failures occurred.
+    pub fn report_des_file_failures(&self) -> Option<String> {
+        if self.des_file_failures.is_empty() {
+            return None;
+        }
+
+        let file_list = self.des_file_failures.join(", ");
+        Some(format!(
+            "⚠️  The following files were skipped due to missing or invalid DES files: {}\n   Tables were not created for these files.",
+            file_list
+        ))
+    }
+
+    /// Returns a formatted "Failed files" section listing each failed file and why.
+    ///
+    /// Returns None if no files failed.
+    pub fn report_file_failures(&self) -> Option<String> {
+        if self.file_failures.is_empty() {
+            return None;
+        }
+
+        let lines: Vec<String> = self
+            .file_failures
+            .iter()
+            .map(|failure| format!("   - {}", failure))
+            .collect();
+        Some(format!("❌ Failed files:\n{}", lines.join("\n")))
+    }
+
+}
+
+/// Finds a ZIP entry by file name, ignoring case and any directory prefix.
+fn find_zip_entry<R: std::io::Read + std::io::Seek>(
+    archive: &zip::ZipArchive<R>,
+    file_name: &str,
+) -> Option<String> {
+    let file_name = file_name.to_lowercase();
+
+    archive
+        .file_names()
+        .find(|name| {
+            let name = name.to_lowercase();
+            name == file_name || name.ends_with(&format!("/{}", file_name))
+        })
+        .map(str::to_string)
+}
+
+/// Creates the column_descriptions table under the given quoted name.
+fn create_column_descriptions_table(conn: &Connection, table: &str) -> rusqlite::Result<usize> {
+    conn.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                table_name TEXT NOT NULL,
+                column_name TEXT NOT NULL,
+                description TEXT NOT NULL
+            )",
+            table
+        ),
+        [],
+    )
+}
+
+/// Quotes a table name, prefixed with `schema` when it is an attached database.
+fn qualified_name(schema: Option<&str>, table: &str) -> String {
+    match schema {
+        Some(schema) => format!("{}.{}", quote_identifier(schema), quote_identifier(table)),
+        None => quote_identifier(table),
+    }
+}
+
+/// Inserts `(column, description)` rows for a table into `descriptions_table`.
+fn write_column_descriptions(
+    conn: &Connection,
+    descriptions_table: &str,
+    table_name: &str,
+    rows: &[(String, String)],
+) -> Result<()> {
+    let mut stmt = conn.prepare(&format!(
+        "INSERT INTO {} (table_name, column_name, description) VALUES (?, ?, ?)",
+        descriptions_table
+    )).context("Failed to prepare INSERT statement for column descriptions")?;
+
+    for (column_name, column_description) in rows {
+        stmt.execute([table_name, column_name.as_str(), column_description.as_str()])
+            .with_context(|| {
+                format!("Failed to insert description for {}.{}", table_name, column_name)
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Builds a PRAGMA statement, scoped to `schema` when one is given.
+fn pragma_sql(schema: Option<&str>, pragma: &str) -> String {
+    match schema {
+        Some(schema) => format!("PRAGMA {}.{}", quote_identifier(schema), pragma),
+        None => format!("PRAGMA {}", pragma),
+    }
+}
+
+/// Builds a parameterized INSERT statement with quoted identifiers.
+///
+/// `table` must already be quoted (and schema-qualified if needed).
+fn build_insert_sql(table: &str, columns: &[String]) -> String {
+    let placeholders = columns.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
+    let column_list = columns
+        .iter()
+        .map(|column| quote_identifier(column))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        table,
+        column_list,
+        placeholders
+    )
+}
+
+/// A row whose non-key columns differ between two databases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowChange {
+    /// The primary key value of the row
+    pub key: String,
+    /// The columns whose values differ, in table order
+    pub changed_columns: Vec<String>,
+}
+
+/// Differences in a table between a previous run and the current one.
+///
+/// Produced by [`changelog`]. Rows are matched by primary key; keys are
+/// listed in ascending order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changelog {
+    /// The table that was compared
+    pub table_name: String,
+    /// Keys present only in the new database
+    pub added: Vec<String>,
+    /// Keys present only in the old database
+    pub removed: Vec<String>,
+    /// Rows present in both databases with differing values
+    pub modified: Vec<RowChange>,
+}
+
+impl Changelog {
+    /// Returns whether the table is identical in both databases.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Compares a table between two databases and classifies row changes.
+///
+/// Rows are matched by the table's primary key, so this applies to tables
+/// with a single-column PRIMARY KEY such as the reference table. Both tables
+/// are streamed in key order and merged, so memory use does not grow with
+/// table size. Only columns present in both databases are compared.
+///
+/// # Arguments
+///
+/// * `old_db` - Connection to the database from the previous run
+/// * `new_db` - Connection to the database from the current run
+/// * `table` - The table to compare
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The table does not exist in either database
+/// - The table does not have a single-column primary key
+/// - A query fails
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::data_handler::changelog;
+/// use rusqlite::Connection;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let old_db = Connection::open("yesterday.db")?;
+/// let new_db = Connection::open("today.db")?;
+///
+/// let changes = changelog(&old_db, &new_db, "offender_profile")?;
+/// println!(
+///     "{} added, {} removed, {} modified",
+///     changes.added.len(),
+///     changes.removed.len(),
+///     changes.modified.len()
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn changelog(old_db: &Connection, new_db: &Connection, table: &str) -> Result<Changelog> {
+    let new_info = table_info(new_db, table)?;
+    let old_info = table_info(old_db, table)?;
+
+    let key_columns: Vec<&String> = new_info
+        .iter()
+        .filter(|(_, is_key)| *is_key)
+        .map(|(name, _)| name)
+        .collect();
+
+    let key = match key_columns.as_slice() {
+        [key] => (*key).clone(),
+        _ => {
+            return Err(anyhow!(
+                "Table {} must have a single-column primary key to compute a changelog",
+                table
+            ))
+        }
+    };
+
+    let columns: Vec<String> = new_info
+        .iter()
+        .map(|(name, _)| name)
+        .filter(|name| **name != key && old_info.iter().any(|(old, _)| old == *name))
+        .cloned()
+        .collect();
+
+    let select_list = std::iter::once(&key)
+        .chain(columns.iter())
+        .map(|column| quote_identifier(column))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let quoted_key = quote_identifier(&key);
+    let sql = format!(
+        "SELECT {} FROM {} WHERE {} IS NOT NULL ORDER BY {}",
+        select_list,
+        quote_identifier(table),
+        quoted_key,
+        quoted_key
+    );
+
+    let mut old_stmt = old_db
+        .prepare(&sql)
+        .with_context(|| format!("Failed to query {} in old database", table))?;
+    let mut new_stmt = new_db
+        .prepare(&sql)
+        .with_context(|| format!("Failed to query {} in new database", table))?;
+    let mut old_rows = old_stmt.query([])?;
+    let mut new_rows = new_stmt.query([])?;
+
+    let mut result = Changelog {
+        table_name: table.to_string(),
+        ..Default::default()
+    };
+
+    let mut old_row = next_keyed_row(&mut old_rows, columns.len())?;
+    let mut new_row = next_keyed_row(&mut new_rows, columns.len())?;
+
+    loop {
+        match (old_row.take(), new_row.take()) {
+            (None, None) => break,
+            (Some((old_key, _)), None) => {
+                result.removed.push(old_key);
+                old_row = next_keyed_row(&mut old_rows, columns.len())?;
+            }
+            (None, Some((new_key, _))) => {
+                result.added.push(new_key);
+                new_row = next_keyed_row(&mut new_rows, columns.len())?;
+            }
+            (Some((old_key, old_values)), Some((new_key, new_values))) => {
+                match old_key.cmp(&new_key) {
+                    std::cmp::Ordering::Less => {
+                        result.removed.push(old_key);
+                        old_row = next_keyed_row(&mut old_rows, columns.len())?;
+                        new_row = Some((new_key, new_values));
+                    }
+                    std::cmp::Ordering::Greater => {
+                        result.added.push(new_key);
+                        new_row = next_keyed_row(&mut new_rows, columns.len())?;
+                        old_row = Some((old_key, old_values));
+                    }
+                    std::cmp::Ordering::Equal => {
+                        let changed_columns: Vec<String> = columns
+                            .iter()
+                            .zip(old_values.iter().zip(new_values.iter()))
+                            .filter(|(_, (old, new))| old != new)
+                            .map(|(column, _)| column.clone())
+                            .collect();
+
+                        if !changed_columns.is_empty() {
+                            result.modified.push(RowChange {
+                                key: new_key,
+                                changed_columns,
+                            });
+                        }
+
+                        old_row = next_keyed_row(&mut old_rows, columns.len())?;
+                        new_row = next_keyed_row(&mut new_rows, columns.len())?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Copies a table from one database file into another.
+///
+/// Attaches the source to a connection on the destination and runs
+/// `INSERT INTO main.table SELECT * FROM src.table` in one transaction, so
+/// rows stream inside SQLite without passing through CSV or memory. If the
+/// destination lacks the table, it is first created from the source's DDL.
+/// Existing rows in the destination are kept. Foreign keys are not enforced
+/// during the copy. Paths may be SQLite URIs, such as a shared-cache
+/// in-memory database.
+///
+/// # Arguments
+///
+/// * `src_path` - Database to copy from
+/// * `dst_path` - Database to copy into (created if missing)
+/// * `table` - The table to copy
+///
+/// # Returns
+///
+/// The number of rows copied.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Either database cannot be opened
+/// - The table does not exist in the source
+/// - The destination table's columns don't line up with the source's
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::data_handler::copy_table;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let rows = copy_table("full.db", "subset.db", "offender_profile")?;
+/// println!("Copied {} rows", rows);
+/// # Ok(())
+/// # }
+/// ```
+pub fn copy_table(src_path: &str, dst_path: &str, table: &str) -> Result<u64> {
+    let mut conn = Connection::open(dst_path)
+        .with_context(|| format!("Failed to open database: {}", dst_path))?;
+    conn.execute("ATTACH DATABASE ?1 AS src", [src_path])
+        .with_context(|| format!("Failed to attach database: {}", src_path))?;
+
+    let ddl: String = conn
+        .query_row(
+            "SELECT sql FROM src.sqlite_master WHERE type = 'table' AND name = ?1",
+            [table],
+            |row| row.get(0),
+        )
+        .optional()?
+        .ok_or_else(|| anyhow!("Table {} does not exist in {}", table, src_path))?;
+
+    let exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM main.sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get(0),
+    )?;
+
+    let tx = conn.transaction().context("Failed to begin transaction")?;
+    if !exists {
+        tx.execute_batch(&ddl)
+            .with_context(|| format!("Failed to create {} in {}", table, dst_path))?;
+    }
+    let copied = tx
+        .execute(
+            &format!(
+                "INSERT INTO main.{0} SELECT * FROM src.{0}",
+                quote_identifier(table)
+            ),
+            [],
+        )
+        .with_context(|| format!("Failed to copy {} from {} to {}", table, src_path, dst_path))?;
+    tx.commit().context("Failed to commit transaction")?;
+
+    conn.execute("DETACH DATABASE src", [])
+        .context("Failed to detach source database")?;
+
+    Ok(copied as u64)
+}
+
+/// A table copied into the output of [`merge_databases`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergedTable {
+    /// The table name
+    pub table: String,
+    /// The input database the table came from
+    pub source: PathBuf,
+    /// Number of rows copied
+    pub rows: u64,
+}
+
+/// A table skipped by [`merge_databases`] because its name was already taken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableCollision {
+    /// The table name
+    pub table: String,
+    /// The input database whose table was skipped
+    pub source: PathBuf,
+    /// The database that already supplied the table (an earlier input, or
+    /// the output itself if it already had the table)
+    pub kept_from: PathBuf,
+}
+
+/// Outcome of [`merge_databases`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeReport {
+    /// Tables copied into the output, in input order
+    pub copied: Vec<MergedTable>,
+    /// Tables skipped because of a name collision, in input order
+    pub collisions: Vec<TableCollision>,
+}
+
+/// Combines tables from several databases into one.
+///
+/// Built for databases loaded against different reference files (for
+/// example one keyed on CMDORNUM and one on CIDORNUM). Each input's tables
+/// are copied into `output` with [`copy_table`] in input order. A table
+/// whose name is already present in the output, from an earlier input or
+/// from before the merge, is not copied and is reported as a collision.
+/// The `column_descriptions` of each input are not treated as a table:
+/// their rows for the copied tables are appended to the output's.
+///
+/// # Arguments
+///
+/// * `inputs` - Databases to merge, in priority order
+/// * `output` - Database to merge into (created if missing)
+///
+/// # Returns
+///
+/// A `MergeReport` of the tables copied and the collisions skipped.
+///
+/// # Errors
+///
+/// Returns an error if a path is not valid UTF-8, a database cannot be
+/// opened, or a table fails to copy.
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::data_handler::merge_databases;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let report = merge_databases(
+///     &[Path::new("by_offender.db"), Path::new("by_sentence.db")],
+///     Path::new("combined.db"),
+/// )?;
+/// for collision in &report.collisions {
+///     println!("Skipped {} from {}", collision.table, collision.source.display());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn merge_databases(inputs: &[&Path], output: &Path) -> Result<MergeReport> {
+    let output_path = output
+        .to_str()
+        .ok_or_else(|| anyhow!("Database path is not valid UTF-8: {}", output.display()))?;
+    let conn = Connection::open(output_path)
+        .with_context(|| format!("Failed to open database: {}", output_path))?;
+    create_column_descriptions_table(&conn, &quote_identifier("column_descriptions"))
+        .context("Failed to create column_descriptions table")?;
+
+    let mut owners: HashMap<String, PathBuf> = table_names(&conn, "main")?
+        .into_iter()
+        .map(|table| (table, output.to_path_buf()))
+        .collect();
+    let mut report = MergeReport::default();
+
+    for input in inputs {
+        let input_path = input
+            .to_str()
+            .ok_or_else(|| anyhow!("Database path is not valid UTF-8: {}", input.display()))?;
+        conn.execute("ATTACH DATABASE ?1 AS src", [input_path])
+            .with_context(|| format!("Failed to attach database: {}", input_path))?;
+        let tables = table_names(&conn, "src");
+        let has_descriptions: Result<bool> = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM src.sqlite_master WHERE type = 'table' AND name = 'column_descriptions'",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(Into::into);
+        conn.execute("DETACH DATABASE src", [])
+            .context("Failed to detach source database")?;
+
+        let mut copied = Vec::new();
+        for table in tables? {
+            if let Some(owner) = owners.get(&table) {
+                report.collisions.push(TableCollision {
+                    table,
+                    source: input.to_path_buf(),
+                    kept_from: owner.clone(),
+                });
+                continue;
+            }
+
+            let rows = copy_table(input_path, output_path, &table)?;
+            owners.insert(table.clone(), input.to_path_buf());
+            copied.push(table.clone());
+            report.copied.push(MergedTable {
+                table,
+                source: input.to_path_buf(),
+                rows,
+            });
+        }
+
+        if has_descriptions? && !copied.is_empty() {
+            conn.execute("ATTACH DATABASE ?1 AS src", [input_path])
+                .with_context(|| format!("Failed to attach database: {}", input_path))?;
+            let placeholders = copied.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
+            let appended = conn.execute(
+                &format!(
+                    "INSERT INTO main.column_descriptions (table_name, column_name, description)
+                     SELECT table_name, column_name, description FROM src.column_descriptions
+                     WHERE table_name IN ({})",
+                    placeholders
+                ),
+                rusqlite::params_from_iter(copied.iter()),
+            );
+            conn.execute("DETACH DATABASE src", [])
+                .context("Failed to detach source database")?;
+            appended.with_context(|| format!("Failed to merge column descriptions from {}", input_path))?;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Lists the user tables of a schema other than `column_descriptions`, in creation order.
+fn table_names(conn: &Connection, schema: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name FROM {}.sqlite_master
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name != 'column_descriptions'
+         ORDER BY rowid",
+        quote_identifier(schema)
+    ))?;
+    let names = stmt.query_map([], |row| row.get(0))?;
+
+    Ok(names.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Times loading generated records into an in-memory table at each batch size.
+///
+/// Makes the numbers behind `BATCH_SIZE` reproducible. For every size, a
+/// fresh in-memory database gets an offender-style table (a seven-digit
+/// key, a name, a date and a decimal amount), and `synthetic_rows`
+/// generated records are inserted through the same batch commit path as a
+/// real load, committing a transaction every `size` rows.
+///
+/// # Arguments
+///
+/// * `synthetic_rows` - Number of records to insert at each batch size
+/// * `sizes` - The batch sizes to time
+///
+/// # Returns
+///
+/// One `(batch size, elapsed time)` pair per entry in `sizes`, in order.
+///
+/// # Errors
+///
+/// Returns an error if a batch size is zero or an insert fails.
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::data_handler::benchmark_batch_sizes;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// for (size, elapsed) in benchmark_batch_sizes(100_000, &[100, 250, 1000])? {
+///     println!("{:>5} rows/batch: {:?}", size, elapsed);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn benchmark_batch_sizes(synthetic_rows: usize, sizes: &[usize]) -> Result<Vec<(usize, Duration)>> {
+    const TABLE: &str = "benchmark";
+    let file = FileMetadata::new("BENCHMARK", "Benchmark", "");
+    let columns: Vec<String> = ["CMDORNUM", "CMNAME", "CMDATE", "CMAMOUNT"]
+        .iter()
+        .map(|column| column.to_string())
+        .collect();
+    let insert_sql = build_insert_sql(&quote_identifier(TABLE), &columns);
+
+    let mut timings = Vec::with_capacity(sizes.len());
+    for &size in sizes {
+        if size == 0 {
+            return Err(anyhow!("Batch size must be at least 1"));
+        }
+
+        let mut handler = DataHandler::new(":memory:")?;
+        handler.database.execute(
+            &format!(
+                "CREATE TABLE {} (CMDORNUM TEXT PRIMARY KEY, CMNAME TEXT, CMDATE TEXT, CMAMOUNT REAL)",
+                quote_identifier(TABLE)
+            ),
+            [],
+        )?;
+
+        let start = Instant::now();
+        let mut batch: Vec<(Vec<Option<String>>, usize)> = Vec::with_capacity(size);
+        for row in 0..synthetic_rows {
+            batch.push((
+                vec![
+                    Some(format!("{:07}", row)),
+                    Some(format!("OFFENDER NAME {}", row)),
+                    Some(format!("{:04}-{:02}-{:02}", 1950 + row % 50, 1 + row % 12, 1 + row % 28)),
+                    Some(format!("{}.{:02}", row % 10_000, row % 100)),
+                ],
+                row + 1,
+            ));
+
+            if batch.len() >= size {
+                handler.commit_batch(&insert_sql, &columns, &batch, &file, TABLE)?;
+                batch.clear();
+            }
+        }
+        if !batch.is_empty() {
+            handler.commit_batch(&insert_sql, &columns, &batch, &file, TABLE)?;
+        }
+
+        timings.push((size, start.elapsed()));
+    }
+
+    Ok(timings)
+}
+
+/// Reads the next row of a `SELECT key, columns...` query into owned values.
+fn next_keyed_row(
+    rows: &mut rusqlite::Rows<'_>,
+    column_count: usize,
+) -> Result<Option<(String, Vec<rusqlite::types::Value>)>> {
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+
+    let key: String = row.get(0)?;
+    let values = (1..=column_count)
+        .map(|index| row.get::<_, rusqlite::types::Value>(index))
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    Ok(Some((key, values)))
+}
+
+/// Explains a lock error on open, which usually means another process holds
+/// the database or a crashed run left a journal behind.
+fn locked_database_error(
+    error: rusqlite::Error,
+    database_path: &str,
+    journal_state: JournalState,
+) -> anyhow::Error {
+    let locked = matches!(
+        error.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    );
+
+    if !locked {
+        return error.into();
+    }
+
+    let leftover = match journal_state {
+        JournalState::Clean => String::new(),
+        JournalState::RollbackJournal => format!(" A leftover {}-journal was found.", database_path),
+        JournalState::Wal => format!(" A leftover {}-wal was found.", database_path),
+    };
+
+    anyhow!(
+        "Database {} may be in use or needs recovery.{} Close any other process using it and \
+         try again; if none is running, reopening will recover the journal once the lock clears.",
+        database_path,
+        leftover
+    )
+    .context(error)
+}
+
+/// Builds the error for a reference record whose key is already in its table.
+fn duplicate_key_error(
+    file: &FileMetadata,
+    table_name: &str,
+    line_number: usize,
+    column: String,
+    key: String,
+) -> ErrorDetails {
+    let message = format!(
+        "Duplicate key inserting into {}\n  File: {} ({})\n  Line: {}\n  Key: {} = {}",
+        table_name, file.id, file.name, line_number, column, key
+    );
+
+    ErrorDetails::new(
+        file.id.to_string(),
+        table_name.to_string(),
+        message,
+        format!("Key {} is already in {}", key, table_name),
+    )
+    .with_record(line_number, Some(column), Some(key))
+    .with_kind(ErrorKind::DuplicateKey)
+}
+
+/// Writes one CSV row, writing `None` as `null_repr` and quoting fields
+/// that contain commas, quotes, or line breaks or that equal `null_repr`.
+fn write_csv_row<W: Write>(
+    writer: &mut W,
+    fields: impl IntoIterator<Item = Option<String>>,
+    null_repr: &str,
+) -> Result<()> {
+    let line = fields
+        .into_iter()
+        .map(|field| match field {
+            None => null_repr.to_string(),
+            Some(field) if field == null_repr || field.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            }
+            Some(field) => field,
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    writeln!(writer, "{}", line).context("Failed to write CSV row")
+}
+
+/// Renders a value as a SQL literal that reads back with the same storage class.
+fn sql_literal(value: rusqlite::types::ValueRef<'_>) -> String {
+    use rusqlite::types::ValueRef;
+
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(value) => value.to_string(),
+        ValueRef::Real(value) if value.is_infinite() => {
+            if value > 0.0 { "1e999".to_string() } else { "-1e999".to_string() }
+        }
+        ValueRef::Real(value) => {
+            let text = format!("{:?}", value);
+            if text.contains(['.', 'e', 'E']) { text } else { format!("{}.0", text) }
+        }
+        ValueRef::Text(bytes) => {
+            format!("'{}'", String::from_utf8_lossy(bytes).replace('\'', "''"))
+        }
+        ValueRef::Blob(bytes) => {
+            let hex: String = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            format!("X'{}'", hex)
+        }
+    }
+}
+
+/// Returns the columns of a table as `(name, is_primary_key)` pairs in table order.
+///
+/// # Errors
+///
+/// Returns an error if the table does not exist.
+pub(crate) fn table_info(conn: &Connection, table: &str) -> Result<Vec<(String, bool)>> {
+    table_info_in(conn, None, table)
+}
+
+/// Like `table_info`, but reads the table from an attached `schema` if given.
+///
+/// # Errors
+///
+/// Returns an error if the table does not exist in that schema.
+pub(crate) fn table_info_in(conn: &Connection, schema: Option<&str>, table: &str) -> Result<Vec<(String, bool)>> {
+    let mut stmt = conn
+        .prepare(&pragma_sql(schema, &format!("table_info({})", quote_identifier(table))))
+        .with_context(|| format!("Failed to read schema for table {}", table))?;
+
+    let columns = stmt
+        .query_map([], |row| {
+            let name: String = row.get(1)?;
+            let pk: i32 = row.get(5)?;
+            Ok((name, pk > 0))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    if columns.is_empty() {
+        return Err(anyhow!("Table {} does not exist", table));
+    }
+
+    Ok(columns)
+}
+
+/// Maps a DES field type to a SQLite type.
+///
+/// - DECIMAL → REAL
+/// - All others → TEXT
+///
+/// This matches the behavior of the Node.js implementation.
+///
+/// # Examples
+///
+/// ```
+/// use ncdac_opi_parser::data_handler::map_type_to_sqlite;
+///
+/// assert_eq!(map_type_to_sqlite("DECIMAL"), "REAL");
+/// assert_eq!(map_type_to_sqlite("CHAR"), "TEXT");
+/// assert_eq!(map_type_to_sqlite("DATE"), "TEXT");
+/// assert_eq!(map_type_to_sqlite("TIME"), "TEXT");
+/// ```
+pub fn map_type_to_sqlite(field_type: &str) -> &'static str {
+    map_type_to_sqlite_with(field_type, DecimalStorage::Real)
+}
+
+/// Maps a DES field type to a SQLite type under a DECIMAL storage mode.
+///
+/// - DECIMAL → REAL (`DecimalStorage::Real`) or TEXT (`DecimalStorage::Exact`)
+/// - All others → TEXT
+///
+/// # Examples
+///
+/// ```
+/// use ncdac_opi_parser::data_handler::{map_type_to_sqlite_with, DecimalStorage};
+///
+/// assert_eq!(map_type_to_sqlite_with("DECIMAL", DecimalStorage::Real), "REAL");
+/// assert_eq!(map_type_to_sqlite_with("DECIMAL", DecimalStorage::Exact), "TEXT");
+/// assert_eq!(map_type_to_sqlite_with("CHAR", DecimalStorage::Exact), "TEXT");
+/// ```
+pub fn map_type_to_sqlite_with(field_type: &str, storage: DecimalStorage) -> &'static str {
+    match (field_type, storage) {
+        ("DECIMAL", DecimalStorage::Real) => "REAL",
+        _ => "TEXT",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{write_fixture, write_test_zip};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_map_type_to_sqlite() {
+        assert_eq!(map_type_to_sqlite("DECIMAL"), "REAL");
+        assert_eq!(map_type_to_sqlite("CHAR"), "TEXT");
+        assert_eq!(map_type_to_sqlite("DATE"), "TEXT");
+        assert_eq!(map_type_to_sqlite("TIME"), "TEXT");
+        assert_eq!(map_type_to_sqlite("UNKNOWN"), "TEXT");
+    }
+
+    #[test]
+    fn test_data_handler_new() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let handler = DataHandler::new(path)?;
+        assert!(!handler.is_initialized());
+        assert!(handler.reference_file().is_none());
+        assert!(handler.reference_table_name().is_none());
+        assert!(handler.reference_field().is_none());
+        assert_eq!(handler.processed_files().len(), 0);
+        assert_eq!(handler.errors.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_handler_foreign_keys_enabled() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let handler = DataHandler::new(path)?;
+
+        let fk_enabled: i32 = handler
+            .database
+            .pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
+
+        assert_eq!(fk_enabled, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_page_size_applied_to_fresh_database() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("fresh.db");
+
+        let options = DatabaseOptions {
+            page_size: 8192,
+            cache_size: -2048,
+        };
+        let handler = DataHandler::with_options(path.to_str().unwrap(), &options)?;
+
+        let page_size: u32 = handler
+            .database
+            .pragma_query_value(None, "page_size", |row| row.get(0))?;
+        let cache_size: i64 = handler
+            .database
+            .pragma_query_value(None, "cache_size", |row| row.get(0))?;
+
+        assert_eq!(page_size, 8192);
+        assert_eq!(cache_size, -2048);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_descriptions_table_created() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let handler = DataHandler::new(path)?;
+
+        let table_exists: i32 = handler.database.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='column_descriptions'",
+            [],
+            |row| row.get(0),
+        )?;
+
+        assert_eq!(table_exists, 1, "column_descriptions table should exist");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_descriptions_table_schema() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let handler = DataHandler::new(path)?;
+
+        handler.database.execute(
+            "INSERT INTO column_descriptions (table_name, column_name, description) VALUES (?, ?, ?)",
+            ["test_table", "test_column", "Test description"],
+        )?;
+
+        let (table_name, column_name, description): (String, String, String) = handler.database.query_row(
+            "SELECT table_name, column_name, description FROM column_descriptions WHERE table_name = ?",
+            ["test_table"],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+
+        assert_eq!(table_name, "test_table");
+        assert_eq!(column_name, "test_column");
+        assert_eq!(description, "Test description");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_column_descriptions_query() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let handler = DataHandler::new(path)?;
+
+        handler.database.execute(
+            "INSERT INTO column_descriptions (table_name, column_name, description) VALUES (?, ?, ?)",
+            ["offender_profile", "CMDORNUM", "Offender DOC ID Number"],
+        )?;
+        handler.database.execute(
+            "INSERT INTO column_descriptions (table_name, column_name, description) VALUES (?, ?, ?)",
+            ["offender_profile", "CMLNAME", "Offender Last Name"],
+        )?;
+
+        let description: String = handler.database.query_row(
+            "SELECT description FROM column_descriptions WHERE table_name = ? AND column_name = ?",
+            ["offender_profile", "CMDORNUM"],
+            |row| row.get(0),
+        )?;
+
+        assert_eq!(description, "Offender DOC ID Number");
+
+        Ok(())
+    }
+
+
+    #[test]
+    fn test_des_file_failures_reporting_empty() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let handler = DataHandler::new(path)?;
+
+        assert!(handler.report_des_file_failures().is_none(), "Should return None when no DES file failures");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_des_file_failures_reporting_with_failures() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let mut handler = DataHandler::new(path)?;
+        handler.des_file_failures.push("FILE1".to_string());
+        handler.des_file_failures.push("FILE2".to_string());
+
+        let report = handler.report_des_file_failures();
+        assert!(report.is_some(), "Should return Some when DES file failures exist");
+
+        let report_text = report.unwrap();
+        assert!(report_text.contains("FILE1"), "Report should contain FILE1");
+        assert!(report_text.contains("FILE2"), "Report should contain FILE2");
+        assert!(report_text.contains("missing or invalid DES files"), "Report should contain warning message");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_details() {
+        let error = ErrorDetails::new(
+            "OFNT1BA1".to_string(),
+            "test_table".to_string(),
+            "Test error message".to_string(),
+            "SQLite error".to_string(),
+        );
+
+        assert_eq!(error.file_id, "OFNT1BA1");
+        assert_eq!(error.table_name, "test_table");
+        assert_eq!(error.message, "Test error message");
+        assert_eq!(error.error_message, "SQLite error");
+    }
+
+    #[test]
+    fn test_processing_results() {
+        let errors = vec![ErrorDetails::new(
+            "TEST".to_string(),
+            "table".to_string(),
+            "msg".to_string(),
+            "err".to_string(),
+        )];
+
+        let results = ProcessingResults::new(100, errors.clone());
+
+        assert_eq!(results.processed, 100);
+        assert_eq!(results.errors.len(), 1);
+        assert_eq!(results.errors[0].file_id, "TEST");
+    }
+
+    #[test]
+    fn test_table_creation_failure_is_listed() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, des, dat) in [
+            ("OFNT3AA1", "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n", "0000001\n"),
+            ("OFNT1BA1", "CPPREFIX      COP COMMITMENT PREFIX              CHAR      1       2\n", ""),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
+
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        assert!(handler.report_file_failures().is_none());
+
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let keyless_file = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+        handler.init(reference_file, None)?;
+        assert!(handler.process_file(keyless_file, None).is_err());
+
+        assert_eq!(handler.file_failures.len(), 1);
+        assert_eq!(handler.file_failures[0].file_id, "OFNT1BA1");
+        assert!(handler.file_failures[0].reason.contains("expected key field"));
+
+        let report = handler.report_file_failures().unwrap();
+        assert!(report.contains("Failed files"));
+        assert!(report.contains("OFNT1BA1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_file_without_init() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let mut handler = DataHandler::new(path)?;
+
+        let file = FileMetadata::new(
+            "OFNT3AA1",
+            "Offender Profile",
+            "https://example.com/OFNT3AA1.zip",
+        );
+        let result = handler.process_file(&file, None);
+
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("not initialized"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_file_sets_synchronous_full() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let mut handler = DataHandler::new(path)?;
+
+        let reference_file = FileMetadata::new(
+            "OFNT3AA1",
+            "Offender Profile",
+            "https://example.com/OFNT3AA1.zip",
+        );
+
+        let _ = handler.init(&reference_file, None);
+
+        let sync_mode: i32 = handler
+            .database
+            .pragma_query_value(None, "synchronous", |row| row.get(0))?;
+
+        assert_eq!(sync_mode, 2, "Reference table should use PRAGMA synchronous=FULL");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_init_reuses_existing_reference_table() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, dat) in [("OFNT3AA1", "0000001\n0000002\n"), ("OFNT1BA1", "0000002\n")] {
+            write_fixture(
+                temp_dir.path(),
+                file_id,
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+                dat,
+            );
+        }
+        let db_path = temp_dir.path().join("out.db");
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+
+        let mut first = DataHandler::new(db_path.to_str().unwrap())?;
+        first.set_data_dir(temp_dir.path());
+        first.init(reference_file, None)?;
+        drop(first);
+
+        std::fs::write(
+            temp_dir.path().join("OFNT3AA1").join("OFNT3AA1.dat"),
+            "0000001\n0000002\n0000003\n",
+        )?;
+
+        let mut handler = DataHandler::new(db_path.to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_reuse_existing_reference(true);
+        let results = handler.init(reference_file, None)?;
+
+        assert!(handler.is_initialized());
+        assert_eq!(results.processed, 0);
+        assert!(handler.processed_files().contains("OFNT3AA1"));
+
+        let rows: i64 =
+            handler.database.query_row("SELECT COUNT(*) FROM offender_profile", [], |row| row.get(0))?;
+        assert_eq!(rows, 2, "existing reference rows should be kept as-is");
+
+        let dependent_file = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+        let results = handler.process_file(dependent_file, None)?.unwrap();
+        assert!(results.errors.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_intra_file_workers_match_sequential_load() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let reference_dat: String = (1..=10).map(|id| format!("{:07}\n", id)).collect();
+        let dependent_dat: String = (1..=12)
+            .map(|id| format!("{:07}{:03}\n", id, id))
+            .chain(std::iter::once("\n".to_string()))
+            .collect();
+        for (file_id, des, dat) in [
+            (
+                "OFNT3AA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+                reference_dat,
+            ),
+            (
+                "OFNT1BA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 CIDORNUM      SENTENCE COMPONENT NUMBER          CHAR      8       3\n",
+                dependent_dat,
+            ),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let dependent_file = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+
+        let parser = DataParser::from_data_dir("OFNT1BA1", temp_dir.path())?;
+        let ranges = parser.split_ranges(3)?;
+        assert_eq!(ranges.len(), 3);
+        assert!(ranges.windows(2).all(|pair| pair[0].end == pair[1].start));
+
+        let mut counts = Vec::new();
+        for workers in [1, 3] {
+            let db_path = temp_dir.path().join(format!("out{}.db", workers));
+            let mut handler = DataHandler::new(db_path.to_str().unwrap())?;
+            handler.set_data_dir(temp_dir.path());
+            handler.set_intra_file_workers(workers);
+            handler.init(reference_file, None)?;
+            let results = handler.process_file(dependent_file, None)?.unwrap();
+
+            let rows: i64 = handler
+                .database
+                .query_row("SELECT COUNT(*) FROM financial_obligation", [], |row| row.get(0))?;
+            let references: i64 = handler
+                .database
+                .query_row("SELECT COUNT(*) FROM offender_profile", [], |row| row.get(0))?;
+            // The orphaned keys 11 and 12 are in the last range
+            let mut lines: Vec<Option<usize>> = results.errors.iter().map(|error| error.line_number).collect();
+            lines.sort();
+            counts.push((rows, references, results.processed, lines));
+        }
+
+        assert_eq!(counts[0], (10, 10, 12, vec![Some(11), Some(12)]));
+        assert_eq!(counts[0], counts[1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_reference_load_matches_serial() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        // Repeated keys within one range and across ranges, in both directions
+        let dat: String = (1..=40)
+            .map(|line| {
+                let key = match line {
+                    12 => 35,
+                    20 => 21,
+                    30 => 3,
+                    _ => line,
+                };
+                format!("{:07}{:03}\n", key, line)
+            })
+            .collect();
+        write_fixture(
+            temp_dir.path(),
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CMSEXCOD      OFFENDER GENDER CODE               CHAR      8       3\n",
+            dat,
+        );
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+
+        let mut loads = Vec::new();
+        for workers in [1, 4] {
+            let db_path = temp_dir.path().join(format!("reference{}.db", workers));
+            let mut handler = DataHandler::new(db_path.to_str().unwrap())?;
+            handler.set_data_dir(temp_dir.path());
+            handler.set_reference_workers(workers);
+            let results = handler.init(reference_file, None)?;
+
+            let rows: Vec<(String, String)> = handler
+                .database
+                .prepare("SELECT CMDORNUM, CMSEXCOD FROM offender_profile ORDER BY CMDORNUM")?
+                .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<_>>()?;
+            let mut errors: Vec<(Option<usize>, ErrorKind, String)> = results
+                .errors
+                .iter()
+                .map(|error| (error.line_number, error.kind, error.message.clone()))
+                .collect();
+            errors.sort_by_key(|(line, _, _)| *line);
+            loads.push((results.processed, rows, errors));
+        }
+
+        let (processed, rows, errors) = &loads[0];
+        assert_eq!(*processed, 40);
+        assert_eq!(rows.len(), 37);
+        assert!(rows.contains(&("0000035".to_string(), "012".to_string())));
+        assert_eq!(
+            errors.iter().map(|(line, kind, _)| (*line, *kind)).collect::<Vec<_>>(),
+            vec![
+                (Some(21), ErrorKind::DuplicateKey),
+                (Some(30), ErrorKind::DuplicateKey),
+                (Some(35), ErrorKind::DuplicateKey),
+            ]
+        );
+        assert_eq!(loads[0], loads[1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_blank_key_is_classified_as_malformed() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, des, dat) in [
+            (
+                "OFNT3AA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+                "0000001\n0000002\n",
+            ),
+            (
+                "OFNT1BA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 CIDORNUM      SENTENCE COMPONENT NUMBER          CHAR      8       3\n",
+                "0000001001\n       002\n12345AB003\n0000009004\n",
+            ),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
+
+        let temp_file = NamedTempFile::new()?;
+        let mut handler = DataHandler::new(temp_file.path().to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_key_validation(true);
+        handler.set_key_pattern(Some(regex::Regex::new(r"^\d{7}$")?));
+        handler.init(crate::files::get_file_by_id("OFNT3AA1").unwrap(), None)?;
+
+        let results = handler
+            .process_file(crate::files::get_file_by_id("OFNT1BA1").unwrap(), None)?
+            .unwrap();
+
+        let kinds: Vec<(Option<usize>, ErrorKind)> = results
+            .errors
+            .iter()
+            .map(|error| (error.line_number, error.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (Some(2), ErrorKind::MalformedKey),
+                (Some(3), ErrorKind::MalformedKey),
+                (Some(4), ErrorKind::ForeignKeyViolation),
+            ]
+        );
+        assert_eq!(results.errors[0].key_value, None);
+        assert!(results.errors[0].message.contains("Malformed key"));
+
+        let rows: i64 = handler
+            .database
+            .query_row("SELECT COUNT(*) FROM financial_obligation", [], |row| row.get(0))?;
+        assert_eq!(rows, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_field_override_picks_non_default_candidate() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let des = "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                   CIDORNUM      SENTENCE COMPONENT NUMBER          CHAR      8       7\n";
+        for (file_id, dat) in [
+            ("OFNT3AA1", "0000001000000A\n0000001000000B\n"),
+            ("OFNT1BA1", "000000A000000A\n000000B000000B\n"),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
+
+        let temp_file = NamedTempFile::new()?;
+        let mut handler = DataHandler::new(temp_file.path().to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_reference_key("OFNT3AA1", "CIDORNUM");
+        handler.set_key_field("OFNT1BA1", "CIDORNUM");
+        handler.init(crate::files::get_file_by_id("OFNT3AA1").unwrap(), None)?;
+        let results = handler
+            .process_file(crate::files::get_file_by_id("OFNT1BA1").unwrap(), None)?
+            .unwrap();
+
+        // CMDORNUM repeats in the reference file, so only CIDORNUM can be its key
+        assert_eq!(handler.reference_field(), Some("CIDORNUM"));
+        assert!(results.errors.is_empty());
+
+        let primary_key: Vec<(String, bool)> = table_info(&handler.database, "offender_profile")?
+            .into_iter()
+            .filter(|(_, is_key)| *is_key)
+            .collect();
+        assert_eq!(primary_key, vec![("CIDORNUM".to_string(), true)]);
+
+        let (from, to): (String, String) = handler.database.query_row(
+            "SELECT \"from\", \"to\" FROM pragma_foreign_key_list('financial_obligation')",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert_eq!((from.as_str(), to.as_str()), ("CIDORNUM", "CIDORNUM"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reserved_word_field_is_renamed() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, des, dat) in [
+            ("OFNT3AA1", "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n", "0000001\n"),
+            (
+                "OFNT1BA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 ORDER         COURT ORDER NUMBER                 CHAR      8       3\n",
+                "0000001A01\n0000001B02\n",
+            ),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
+
+        let temp_file = NamedTempFile::new()?;
+        let mut handler = DataHandler::new(temp_file.path().to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_rename_reserved_columns(true);
+        handler.init(crate::files::get_file_by_id("OFNT3AA1").unwrap(), None)?;
+        let results = handler
+            .process_file(crate::files::get_file_by_id("OFNT1BA1").unwrap(), None)?
+            .unwrap();
+
+        assert_eq!(results.processed, 2);
+        assert_eq!(results.errors.len(), 1);
+        assert_eq!(results.errors[0].severity, Severity::Warning);
+        assert!(results.errors[0].message.contains("stored as column ORDER_col"));
+
+        let columns: Vec<String> = table_info(&handler.database, "financial_obligation")?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(columns, vec!["CMDORNUM", "ORDER_col"]);
+
+        let orders: Vec<String> = handler
+            .database
+            .prepare("SELECT ORDER_col FROM financial_obligation ORDER BY ORDER_col")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        assert_eq!(orders, vec!["A01", "B02"]);
+
+        let described: String = handler.database.query_row(
+            "SELECT column_name FROM column_descriptions WHERE description = 'COURT ORDER NUMBER'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(described, "ORDER_col");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_key_stores_find_the_same_orphans() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let des = "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n";
+        for (file_id, dat) in [
+            ("OFNT3AA1", "0000001\n0000002\n0000003\n"),
+            ("OFNT1BA1", "0000001\n0000009\n0000002\n       \n0000008\n"),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
+
+        let temp_file = NamedTempFile::new()?;
+        let mut handler = DataHandler::new(temp_file.path().to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.init(crate::files::get_file_by_id("OFNT3AA1").unwrap(), None)?;
+        let dependent = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+
+        let mut found = Vec::new();
+        for store in [ReferenceKeyStore::InMemory, ReferenceKeyStore::OnDisk] {
+            handler.set_reference_key_store(store);
+            assert_eq!(handler.reference_keys()?.len()?, 3);
+
+            let orphans: Vec<(Option<usize>, Option<String>)> = handler
+                .precheck_foreign_keys(dependent)?
+                .into_iter()
+                .inspect(|orphan| assert_eq!(orphan.kind, ErrorKind::ForeignKeyViolation))
+                .map(|orphan| (orphan.line_number, orphan.key_value))
+                .collect();
+            found.push(orphans);
+        }
+
+        assert_eq!(found[0], found[1]);
+        assert_eq!(
+            found[0],
+            vec![(Some(2), Some("0000009".to_string())), (Some(4), Some("0000008".to_string()))]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_row_deduplication_skips_exact_duplicates() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, des, dat) in [
+            (
+                "OFNT3AA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+                "0000001\n0000002\n",
+            ),
+            (
+                "OFNT1BA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 CIDORNUM      SENTENCE COMPONENT NUMBER          CHAR      8       3\n",
+                "0000001001\n0000001001\n0000002001\n0000001001\n0000001002\n",
+            ),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
+
+        for (mode, inserted, deduplicated) in [
+            (RowDeduplication::Off, 5, 0),
+            (RowDeduplication::InMemory, 3, 2),
+            (RowDeduplication::OnDisk, 3, 2),
+            (RowDeduplication::PerBatch, 3, 2),
+        ] {
+            let temp_file = NamedTempFile::new()?;
+            let mut handler = DataHandler::new(temp_file.path().to_str().unwrap())?;
+            handler.set_data_dir(temp_dir.path());
+            handler.set_row_deduplication(mode);
+            handler.init(crate::files::get_file_by_id("OFNT3AA1").unwrap(), None)?;
+
+            let results = handler
+                .process_file(crate::files::get_file_by_id("OFNT1BA1").unwrap(), None)?
+                .unwrap();
+            assert_eq!(results.processed, inserted, "{:?}", mode);
+            assert_eq!(results.deduplicated, deduplicated, "{:?}", mode);
+
+            let distinct: i64 = handler.database.query_row(
+                "SELECT COUNT(*) FROM (SELECT DISTINCT CMDORNUM, CIDORNUM FROM financial_obligation)",
+                [],
+                |row| row.get(0),
+            )?;
+            let count: i64 =
+                handler.database.query_row("SELECT COUNT(*) FROM financial_obligation", [], |row| row.get(0))?;
+            assert_eq!(count, inserted as i64, "{:?}", mode);
+            assert_eq!(distinct, 3);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_refresh_file_atomic_replaces_table() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let des = "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                   CIDORNUM      SENTENCE COMPONENT NUMBER          CHAR      8       3\n";
+        write_fixture(temp_dir.path(), "OFNT3AA1", des.lines().next().unwrap(), "0000001\n0000002\n0000003\n");
+        let dependent_dir = write_fixture(temp_dir.path(), "OFNT1BA1", des, "0000001001\n0000002001\n");
+
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+        let mut handler = DataHandler::new(path)?;
+        handler.set_data_dir(temp_dir.path());
+        handler.init(crate::files::get_file_by_id("OFNT3AA1").unwrap(), None)?;
+        let dependent = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+        handler.process_file(dependent, None)?;
+
+        let rows = |conn: &Connection| -> Result<Vec<String>> {
+            let mut stmt = conn.prepare(
+                "SELECT CMDORNUM || CIDORNUM FROM financial_obligation ORDER BY 1",
+            )?;
+            let rows = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+            Ok(rows)
+        };
+        let reader = Connection::open(path)?;
+        assert_eq!(rows(&reader)?, vec!["0000001001", "0000002001"]);
+
+        std::fs::write(dependent_dir.join("OFNT1BA1.dat"), "0000003001\n0000003002\n0000009001\n")?;
+        let results = handler.refresh_file_atomic(dependent, None)?;
+        assert_eq!(results.processed, 3);
+        assert_eq!(results.errors.len(), 1);
+
+        assert_eq!(rows(&reader)?, vec!["0000003001", "0000003002"]);
+        let staging: i64 = reader.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE name = 'financial_obligation_new'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(staging, 0);
+        let descriptions: i64 = reader.query_row(
+            "SELECT COUNT(*) FROM column_descriptions WHERE table_name = 'financial_obligation'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(descriptions, 2);
+
+        // A failed refresh keeps the current table
+        handler.add_validation("OFNT1BA1", "MISSING", ValidationRule::NonNull);
+        assert!(handler.refresh_file_atomic(dependent, None).is_err());
+        assert_eq!(rows(&reader)?, vec!["0000003001", "0000003002"]);
+
+        let reference = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        assert!(handler.refresh_file_atomic(reference, None).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_counts_flags_dropped_row() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, des, dat) in [
+            (
+                "OFNT3AA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+                "0000001\n0000002\n",
+            ),
+            (
+                "OFNT1BA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 CIDORNUM      SENTENCE COMPONENT NUMBER          CHAR      8       3\n",
+                "0000001001\n0000001001\n0000002001\n0000009001\n0000002002\n",
+            ),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
+
+        let temp_file = NamedTempFile::new()?;
+        let mut handler = DataHandler::new(temp_file.path().to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_row_deduplication(RowDeduplication::InMemory);
+        let reference = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let dependent = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+        handler.init(reference, None)?;
+        handler.process_file(dependent, None)?;
+
+        let files = [*reference, *dependent, *crate::files::get_file_by_id("INMT4AA1").unwrap()];
+        let checks = handler.verify_counts(&files, temp_dir.path())?;
+        assert_eq!(checks.len(), 2);
+        assert!(checks.iter().all(CountCheck::is_ok), "{:?}", checks);
+        assert_eq!(
+            (checks[1].source_records, checks[1].table_rows, checks[1].rejected, checks[1].skipped),
+            (5, 3, 1, 1)
+        );
+
+        handler
+            .database
+            .execute("DELETE FROM financial_obligation WHERE CIDORNUM = '002'", [])?;
+        let checks = handler.verify_counts(&files, temp_dir.path())?;
+        assert!(checks[0].is_ok());
+        assert!(!checks[1].is_ok());
+        assert_eq!(checks[1].discrepancy(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_file_initialization_state() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let mut handler = DataHandler::new(path)?;
+
+        assert!(!handler.is_initialized(), "Handler should not be initialized before init()");
+        assert!(handler.reference_file().is_none());
+        assert!(handler.reference_table_name().is_none());
+        assert!(handler.reference_field().is_none());
+
+        let reference_file = FileMetadata::new(
+            "OFNT3AA1",
+            "Offender Profile",
+            "https://example.com/OFNT3AA1.zip",
+        );
+
+        handler.init_from_reference(&reference_file, "offender_profile", "CMDORNUM");
+
+        assert!(handler.is_initialized(), "Handler should be marked as initialized after init_from_reference() call");
+        assert!(handler.reference_file().is_some());
+        assert!(handler.reference_table_name().is_some());
+        assert!(handler.reference_field().is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_file_must_complete_before_other_processing() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let mut handler = DataHandler::new(path)?;
+
+        let other_file = FileMetadata::new(
+            "OFNT1BA1",
+            "Offender Address",
+            "https://example.com/OFNT1BA1.zip",
+        );
+
+        let result = handler.process_file(&other_file, None);
+
+        assert!(result.is_err(), "process_file() should fail if handler not initialized");
+        assert!(result.unwrap_err().to_string().contains("not initialized"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reference_file_pragma_persists() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let mut handler = DataHandler::new(path)?;
+
+        let sync_mode_before: i32 = handler
+            .database
+            .pragma_query_value(None, "synchronous", |row| row.get(0))?;
+
+        let reference_file = FileMetadata::new(
+            "OFNT3AA1",
+            "Offender Profile",
+            "https://example.com/OFNT3AA1.zip",
+        );
+
+        let _ = handler.init(&reference_file, None);
+
+        let sync_mode_after: i32 = handler
+            .database
+            .pragma_query_value(None, "synchronous", |row| row.get(0))?;
+
+        assert_eq!(sync_mode_after, 2, "PRAGMA synchronous should be FULL after init()");
+
+        if sync_mode_before != 2 {
+            assert_ne!(sync_mode_before, sync_mode_after, "PRAGMA should have changed");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_data_handler_connection_accessor() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let handler = DataHandler::new(path)?;
+
+        let conn = handler.connection();
+
+        let fk_enabled: i32 = conn.pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
+        assert_eq!(fk_enabled, 1);
+
+        Ok(())
+    }
+
+
+    #[test]
+    fn test_error_collection_does_not_stop_processing() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let mut handler = DataHandler::new(path)?;
+
+        handler.database.execute(
+            "CREATE TABLE reference_table (id TEXT PRIMARY KEY)",
+            [],
+        )?;
+
+        handler.database.execute(
+            "INSERT INTO reference_table (id) VALUES ('VALID_ID')",
+            [],
+        )?;
+
+        handler.database.execute(
+            "CREATE TABLE dependent_table (id TEXT, ref_id TEXT, FOREIGN KEY (ref_id) REFERENCES reference_table(id))",
+            [],
+        )?;
+
+        handler.is_initialized = true;
+        handler.reference_table_name = Some("reference_table".to_string());
+        handler.reference_field = Some("id".to_string());
+
+        let test_file = FileMetadata::new(
+            "TEST",
+            "Test Table",
+            "https://example.com/TEST.zip",
+        );
+
+        let batch = vec![
+            (vec![Some("1".to_string()), Some("VALID_ID".to_string())], 1),
+            (vec![Some("2".to_string()), Some("INVALID_ID".to_string())], 2),
+            (vec![Some("3".to_string()), Some("VALID_ID".to_string())], 3),
+        ];
+
+        let insert_sql = "INSERT INTO dependent_table (id, ref_id) VALUES (?, ?)";
+        let columns = vec!["id".to_string(), "ref_id".to_string()];
+        let errors =
+            handler.commit_batch(insert_sql, &columns, &batch, &test_file, "dependent_table")?;
+
+        assert_eq!(errors.len(), 1, "Should collect FK violation error");
+        assert_eq!(errors[0].file_id, "TEST");
+
+        let count: i32 = handler.database.query_row(
+            "SELECT COUNT(*) FROM dependent_table",
+            [],
+            |row| row.get(0),
+        )?;
+
+        assert_eq!(count, 2, "Should have inserted 2 valid records despite 1 FK violation");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_null_counts_for_blank_column() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        write_fixture(
+            temp_dir.path(),
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CMSUFFIX      NAME SUFFIX                        CHAR      8       3\n",
+            "0000001   \n0000002???\n0000003\n",
+        );
+
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let results = handler.init(reference_file, None)?;
+
+        assert_eq!(results.processed, 3);
+        assert_eq!(results.null_counts.get("CMSUFFIX"), Some(&3));
+        assert_eq!(results.null_counts.get("CMDORNUM"), Some(&0));
+        assert_eq!(results.fully_null_columns(), vec!["CMSUFFIX"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_numeric_stats_for_decimal_column() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        write_fixture(
+            temp_dir.path(),
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CPCOPBAL      COP BALANCE                        DECIMAL   8       7\n",
+            "0000001  12.50\n0000002 -2.50\n0000003\n0000004 100.00\n",
+        );
+
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let results = handler.init(reference_file, None)?;
+
+        let stats = results.numeric_stats["CPCOPBAL"];
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.min, -2.5);
+        assert_eq!(stats.max, 100.0);
+        assert_eq!(stats.mean(), Some(110.0 / 3.0));
+        assert!(!results.numeric_stats.contains_key("CMDORNUM"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lookup_table_from_configured_column() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        write_fixture(
+            temp_dir.path(),
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CMSEXCOD      OFFENDER GENDER CODE               CHAR      8       6\n",
+            "0000001MALE  \n0000002FEMALE\n0000003MALE  \n0000004      \n",
+        );
+
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_lookup_columns("OFNT3AA1", &["CMSEXCOD"]);
+
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        handler.init(reference_file, None)?;
+
+        let codes: Vec<String> = handler
+            .database
+            .prepare("SELECT code FROM cmsexcod_lookup ORDER BY code")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        assert_eq!(codes, vec!["FEMALE", "MALE"]);
+
+        handler.create_lookup_table("OFNT3AA1", "CMSEXCOD", &["MALE".to_string(), "UNKNOWN".to_string()])?;
+
+        let count: i64 =
+            handler.database.query_row("SELECT COUNT(*) FROM cmsexcod_lookup", [], |row| row.get(0))?;
+        assert_eq!(count, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_leftover_rollback_journal_is_reported() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let db_path = temp_dir.path().join("out.db");
+        Connection::open(&db_path)?.execute_batch("CREATE TABLE kept (id TEXT)")?;
+
+        assert_eq!(JournalState::detect(&db_path), JournalState::Clean);
+
+        std::fs::write(temp_dir.path().join("out.db-journal"), vec![0u8; 512])?;
+        assert_eq!(JournalState::detect(&db_path), JournalState::RollbackJournal);
+
+        let handler = DataHandler::new(db_path.to_str().unwrap())?;
+        assert_eq!(handler.journal_state(), JournalState::RollbackJournal);
+
+        let count: i64 =
+            handler.database.query_row("SELECT COUNT(*) FROM kept", [], |row| row.get(0))?;
+        assert_eq!(count, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_leftover_wal_can_be_checkpointed() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let db_path = temp_dir.path().join("out.db");
+        let wal_path = temp_dir.path().join("out.db-wal");
+
+        let writer = Connection::open(&db_path)?;
+        writer.pragma_update(None, "journal_mode", "WAL")?;
+        writer.execute_batch("CREATE TABLE kept (id TEXT); INSERT INTO kept VALUES ('1');")?;
+        assert!(std::fs::metadata(&wal_path)?.len() > 0);
+
+        let handler = DataHandler::new(db_path.to_str().unwrap())?;
+        assert_eq!(handler.journal_state(), JournalState::Wal);
+
+        handler.checkpoint_wal()?;
+        assert_eq!(std::fs::metadata(&wal_path)?.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exact_decimal_storage_round_trips() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        write_fixture(
+            temp_dir.path(),
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CPCOPBAL      COP BALANCE                        DECIMAL   8       21\n",
+            "0000001 12345678901234567.89\n",
+        );
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+
+        let balance = |storage: DecimalStorage, name: &str| -> Result<String> {
+            let mut handler = DataHandler::new(temp_dir.path().join(name).to_str().unwrap())?;
+            handler.set_data_dir(temp_dir.path());
+            handler.set_decimal_storage(storage);
+            handler.init(reference_file, None)?;
+            Ok(handler.database.query_row(
+                "SELECT CAST(CPCOPBAL AS TEXT) FROM offender_profile",
+                [],
+                |row| row.get(0),
+            )?)
+        };
+
+        assert_eq!(balance(DecimalStorage::Exact, "exact.db")?, "12345678901234567.89");
+        assert_ne!(balance(DecimalStorage::Real, "real.db")?, "12345678901234567.89");
 
-        let file_list = self.des_file_failures.join(", ");
-        Some(format!(
-            "⚠️  The following files were skipped due to missing or invalid DES files: {}\n   Tables were not created for these files.",
-            file_list
-        ))
+        Ok(())
     }
 
-}
+    #[test]
+    fn test_boolean_columns_store_flags_as_integers() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        write_fixture(
+            temp_dir.path(),
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             ACTIVEFL      ACTIVE FLAG                        CHAR      8       1\n",
+            "0000001Y\n0000002N\n0000003 \n0000004X\n",
+        );
 
-/// Maps a DES field type to a SQLite type.
-///
-/// - DECIMAL → REAL
-/// - All others → TEXT
-///
-/// This matches the behavior of the Node.js implementation.
-///
-/// # Examples
-///
-/// ```
-/// use ncdac_opi_parser::data_handler::map_type_to_sqlite;
-///
-/// assert_eq!(map_type_to_sqlite("DECIMAL"), "REAL");
-/// assert_eq!(map_type_to_sqlite("CHAR"), "TEXT");
-/// assert_eq!(map_type_to_sqlite("DATE"), "TEXT");
-/// assert_eq!(map_type_to_sqlite("TIME"), "TEXT");
-/// ```
-pub fn map_type_to_sqlite(field_type: &str) -> &'static str {
-    match field_type {
-        "DECIMAL" => "REAL",
-        _ => "TEXT",
-    }
-}
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_boolean_columns("OFNT3AA1", &["ACTIVEFL"]);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::NamedTempFile;
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let results = handler.init(reference_file, None)?;
 
-    #[test]
-    fn test_map_type_to_sqlite() {
-        assert_eq!(map_type_to_sqlite("DECIMAL"), "REAL");
-        assert_eq!(map_type_to_sqlite("CHAR"), "TEXT");
-        assert_eq!(map_type_to_sqlite("DATE"), "TEXT");
-        assert_eq!(map_type_to_sqlite("TIME"), "TEXT");
-        assert_eq!(map_type_to_sqlite("UNKNOWN"), "TEXT");
+        assert_eq!(results.processed, 4);
+        assert_eq!(results.errors.len(), 1);
+        assert_eq!(results.errors[0].severity, Severity::Warning);
+        assert_eq!(results.errors[0].line_number, Some(4));
+        assert!(results.errors[0].error_message.contains("'X'"));
+
+        let column_type: String = handler.database.query_row(
+            "SELECT type FROM pragma_table_info('offender_profile') WHERE name = 'ACTIVEFL'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(column_type, "INTEGER");
+
+        let mut stmt = handler
+            .database
+            .prepare("SELECT ACTIVEFL FROM offender_profile ORDER BY CMDORNUM")?;
+        let flags: Vec<Option<i64>> = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        assert_eq!(flags, vec![Some(1), Some(0), None, None]);
+
+        Ok(())
     }
 
     #[test]
-    fn test_data_handler_new() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
+    fn test_validation_failure_recorded_as_warning() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        write_fixture(
+            temp_dir.path(),
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CMSEXCOD      OFFENDER GENDER CODE               CHAR      8       6\n",
+            "0000001MALE  \n00000X2FEMALE\n0000003      \n",
+        );
 
-        let handler = DataHandler::new(path)?;
-        assert!(!handler.is_initialized());
-        assert!(handler.reference_file().is_none());
-        assert!(handler.reference_table_name().is_none());
-        assert!(handler.reference_field().is_none());
-        assert_eq!(handler.processed_files().len(), 0);
-        assert_eq!(handler.errors.len(), 0);
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.add_validation(
+            "OFNT3AA1",
+            "CMDORNUM",
+            ValidationRule::MatchesRegex(regex::Regex::new(r"^\d{7}$")?),
+        );
+        handler.add_validation("OFNT3AA1", "CMSEXCOD", ValidationRule::NonNull);
+
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let results = handler.init(reference_file, None)?;
+
+        assert_eq!(results.processed, 3);
+        assert_eq!(results.errors.len(), 2);
+
+        let regex_failure = &results.errors[0];
+        assert_eq!(regex_failure.severity, Severity::Warning);
+        assert_eq!(regex_failure.line_number, Some(2));
+        assert_eq!(regex_failure.key_value.as_deref(), Some("00000X2"));
+        assert!(regex_failure.error_message.contains("does not match"));
+        assert_eq!(results.errors[1].line_number, Some(3));
+
+        let count: i64 =
+            handler.database.query_row("SELECT COUNT(*) FROM offender_profile", [], |row| row.get(0))?;
+        assert_eq!(count, 3);
+
+        handler.set_strict(true);
+        assert!(handler.check_strict().is_ok());
 
         Ok(())
     }
 
     #[test]
-    fn test_data_handler_foreign_keys_enabled() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
+    fn test_raw_shadow_column_keeps_padding() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        write_fixture(
+            temp_dir.path(),
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             NOTES         NOTES                              CHAR      8       10\n",
+            "0000001  a note  \n0000002          \n",
+        );
 
-        let handler = DataHandler::new(path)?;
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_raw_shadow_columns("OFNT3AA1", &["NOTES"]);
 
-        let fk_enabled: i32 = handler
-            .database
-            .pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        handler.init(reference_file, None)?;
 
-        assert_eq!(fk_enabled, 1);
+        let rows: Vec<(Option<String>, String)> = handler
+            .database
+            .prepare("SELECT NOTES, NOTES_raw FROM offender_profile ORDER BY CMDORNUM")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+
+        assert_eq!(
+            rows,
+            vec![
+                (Some("a note".to_string()), "  a note  ".to_string()),
+                (None, "          ".to_string()),
+            ]
+        );
 
         Ok(())
     }
 
     #[test]
-    fn test_column_descriptions_table_created() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
+    fn test_column_order_applied_to_table() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        write_fixture(
+            temp_dir.path(),
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CMSEXCOD      OFFENDER GENDER CODE               CHAR      8       6\n\
+             CMRACCOD      OFFENDER RACE CODE                 CHAR      14      6\n\
+             CMBIRDAT      OFFENDER BIRTH DATE                DATE      20      10\n",
+            "0000001MALE  WHITE 1980-01-01\n",
+        );
 
-        let handler = DataHandler::new(path)?;
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_column_order("OFNT3AA1", &["CMBIRDAT", "CMDORNUM"]);
 
-        let table_exists: i32 = handler.database.query_row(
-            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='column_descriptions'",
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        handler.init(reference_file, None)?;
+
+        let columns: Vec<String> = table_info(&handler.database, "offender_profile")?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(columns, vec!["CMBIRDAT", "CMDORNUM", "CMSEXCOD", "CMRACCOD"]);
+
+        let race: String = handler.database.query_row(
+            "SELECT CMRACCOD FROM offender_profile WHERE CMDORNUM = '0000001'",
             [],
             |row| row.get(0),
         )?;
+        assert_eq!(race, "WHITE");
 
-        assert_eq!(table_exists, 1, "column_descriptions table should exist");
+        let mut invalid = DataHandler::new(temp_dir.path().join("invalid.db").to_str().unwrap())?;
+        invalid.set_data_dir(temp_dir.path());
+        invalid.set_column_order("OFNT3AA1", &["NOPE"]);
+        let error = invalid.init(reference_file, None).unwrap_err();
+        assert!(format!("{:#}", error).contains("NOPE"));
 
         Ok(())
     }
 
     #[test]
-    fn test_column_descriptions_table_schema() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
+    fn test_from_connection_loads_into_caller_connection() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        write_fixture(
+            temp_dir.path(),
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+            "0000001\n0000002\n",
+        );
 
-        let handler = DataHandler::new(path)?;
+        let conn = Connection::open_in_memory()?;
+        conn.execute_batch("CREATE TABLE app_notes (note TEXT)")?;
 
-        handler.database.execute(
-            "INSERT INTO column_descriptions (table_name, column_name, description) VALUES (?, ?, ?)",
-            ["test_table", "test_column", "Test description"],
-        )?;
+        let mut handler = DataHandler::from_connection(conn)?;
+        handler.set_data_dir(temp_dir.path());
 
-        let (table_name, column_name, description): (String, String, String) = handler.database.query_row(
-            "SELECT table_name, column_name, description FROM column_descriptions WHERE table_name = ?",
-            ["test_table"],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
-        )?;
+        let foreign_keys: i32 = handler.database.query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+        assert_eq!(foreign_keys, 1);
 
-        assert_eq!(table_name, "test_table");
-        assert_eq!(column_name, "test_column");
-        assert_eq!(description, "Test description");
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        handler.init(reference_file, None)?;
+
+        let rows: i64 =
+            handler.database.query_row("SELECT COUNT(*) FROM offender_profile", [], |row| row.get(0))?;
+        assert_eq!(rows, 2);
+        handler.database.execute("INSERT INTO app_notes VALUES ('kept')", [])?;
 
         Ok(())
     }
 
     #[test]
-    fn test_column_descriptions_query() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
+    fn test_unrelated_key_loaded_standalone() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, des, dat) in [
+            (
+                "OFNT3AA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+                "0000001\n",
+            ),
+            (
+                "OFNT1BA1",
+                "CIDORNUM      ACCOUNT NUMBER                     CHAR      1       10\n",
+                "A000000001\nA000000002\n",
+            ),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
 
-        let handler = DataHandler::new(path)?;
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
 
-        handler.database.execute(
-            "INSERT INTO column_descriptions (table_name, column_name, description) VALUES (?, ?, ?)",
-            ["offender_profile", "CMDORNUM", "Offender DOC ID Number"],
-        )?;
-        handler.database.execute(
-            "INSERT INTO column_descriptions (table_name, column_name, description) VALUES (?, ?, ?)",
-            ["offender_profile", "CMLNAME", "Offender Last Name"],
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let dependent_file = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+        handler.init(reference_file, None)?;
+        let results = handler.process_file(dependent_file, None)?.unwrap();
+
+        assert!(handler.standalone_files().contains("OFNT1BA1"));
+        assert_eq!(results.errors.len(), 1);
+        assert_eq!(results.errors[0].severity, Severity::Warning);
+
+        let table = to_snake_case(dependent_file.name);
+        let rows: i64 = handler.database.query_row(
+            &format!("SELECT COUNT(*) FROM {}", quote_identifier(&table)),
+            [],
+            |row| row.get(0),
         )?;
+        assert_eq!(rows, 2);
 
-        let description: String = handler.database.query_row(
-            "SELECT description FROM column_descriptions WHERE table_name = ? AND column_name = ?",
-            ["offender_profile", "CMDORNUM"],
+        let foreign_keys: i64 = handler.database.query_row(
+            "SELECT COUNT(*) FROM pragma_foreign_key_list(?1)",
+            [&table],
             |row| row.get(0),
         )?;
+        assert_eq!(foreign_keys, 0);
 
-        assert_eq!(description, "Offender DOC ID Number");
+        let dependent = handler
+            .relationships()
+            .into_iter()
+            .find(|r| r.file_id == "OFNT1BA1")
+            .unwrap();
+        assert_eq!(dependent.target_table, None);
 
         Ok(())
     }
 
-
     #[test]
-    fn test_des_file_failures_reporting_empty() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
+    fn test_tables_created_in_attached_schema() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, dat) in [("OFNT3AA1", "0000001\n"), ("OFNT1BA1", "0000001\n0000002\n")] {
+            write_fixture(
+                temp_dir.path(),
+                file_id,
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+                dat,
+            );
+        }
 
-        let handler = DataHandler::new(path)?;
+        let mut handler = DataHandler::new_with_schema(
+            temp_dir.path().join("main.db").to_str().unwrap(),
+            temp_dir.path().join("snapshot.db").to_str().unwrap(),
+            "snapshot",
+        )?;
+        handler.set_data_dir(temp_dir.path());
+        assert_eq!(handler.schema(), Some("snapshot"));
+
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let dependent_file = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+        handler.init(reference_file, None)?;
+        let results = handler.process_file(dependent_file, None)?.unwrap();
+        assert_eq!(results.processed, 2);
+        assert_eq!(results.errors.len(), 1, "orphan key should violate the in-schema foreign key");
+
+        let in_main: i64 = handler.database.query_row(
+            "SELECT COUNT(*) FROM main.sqlite_master WHERE name = 'offender_profile'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(in_main, 0);
 
-        assert!(handler.report_des_file_failures().is_none(), "Should return None when no DES file failures");
+        let rows: i64 = handler.database.query_row(
+            "SELECT COUNT(*) FROM snapshot.offender_profile",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(rows, 1);
+
+        let descriptions: i64 = handler.database.query_row(
+            "SELECT COUNT(*) FROM snapshot.column_descriptions WHERE table_name = 'offender_profile'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(descriptions, 1);
 
         Ok(())
     }
 
     #[test]
-    fn test_des_file_failures_reporting_with_failures() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
+    fn test_dump_sql_reimports_equivalent_database() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.database.execute_batch(
+            "CREATE TABLE offender_profile (CMDORNUM TEXT PRIMARY KEY, CMSEX TEXT);
+             CREATE TABLE financial_obligation (
+                 CMDORNUM TEXT REFERENCES offender_profile(CMDORNUM),
+                 CPCOPBAL REAL,
+                 CPCOUNT INTEGER,
+                 RAW BLOB
+             );
+             CREATE INDEX idx_fo_cmdornum ON financial_obligation (CMDORNUM);
+             INSERT INTO offender_profile VALUES ('0000001', 'M');
+             INSERT INTO offender_profile VALUES ('0000002', 'O''Neil');
+             INSERT INTO financial_obligation VALUES ('0000001', 125.5, 3, X'00FF');
+             INSERT INTO financial_obligation VALUES ('0000002', 3.0, NULL, NULL);
+             INSERT INTO financial_obligation VALUES ('0000002', 0.1, -7, 'text');",
+        )?;
 
-        let mut handler = DataHandler::new(path)?;
-        handler.des_file_failures.push("FILE1".to_string());
-        handler.des_file_failures.push("FILE2".to_string());
+        let mut output = Vec::new();
+        handler.dump_sql(&mut output)?;
+        let script = String::from_utf8(output)?;
 
-        let report = handler.report_des_file_failures();
-        assert!(report.is_some(), "Should return Some when DES file failures exist");
+        assert!(script.starts_with("PRAGMA foreign_keys=OFF;\nBEGIN TRANSACTION;\n"));
+        assert!(script.ends_with("COMMIT;\n"));
+        assert!(script.contains("'O''Neil'"));
 
-        let report_text = report.unwrap();
-        assert!(report_text.contains("FILE1"), "Report should contain FILE1");
-        assert!(report_text.contains("FILE2"), "Report should contain FILE2");
-        assert!(report_text.contains("missing or invalid DES files"), "Report should contain warning message");
+        let restored = Connection::open_in_memory()?;
+        restored.execute_batch(&script)?;
+
+        let schema = |conn: &Connection| -> Result<Vec<(String, Option<String>)>> {
+            let mut stmt = conn.prepare("SELECT name, sql FROM sqlite_master ORDER BY name")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            Ok(rows.collect::<rusqlite::Result<_>>()?)
+        };
+        assert_eq!(schema(&handler.database)?, schema(&restored)?);
+
+        let contents = |conn: &Connection, table: &str| -> Result<Vec<String>> {
+            let mut stmt = conn.prepare(&format!("SELECT * FROM {} ORDER BY rowid", table))?;
+            let columns = stmt.column_count();
+            let mut rows = stmt.query([])?;
+            let mut out = Vec::new();
+            while let Some(row) = rows.next()? {
+                for i in 0..columns {
+                    let value = row.get_ref(i)?;
+                    out.push(format!("{:?}:{}", value.data_type(), sql_literal(value)));
+                }
+            }
+            Ok(out)
+        };
+        for table in ["offender_profile", "financial_obligation", "column_descriptions"] {
+            assert_eq!(contents(&handler.database, table)?, contents(&restored, table)?);
+        }
 
         Ok(())
     }
 
     #[test]
-    fn test_error_details() {
-        let error = ErrorDetails::new(
-            "OFNT1BA1".to_string(),
-            "test_table".to_string(),
-            "Test error message".to_string(),
-            "SQLite error".to_string(),
-        );
+    fn test_create_declared_multi_column_index() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, des, dat) in [
+            ("OFNT3AA1", "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n", "0000001\n"),
+            (
+                "OFNT1BA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 CPSTATUS      OBLIGATION STATUS                  CHAR      8       1\n",
+                "0000001A\n0000001C\n",
+            ),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
 
-        assert_eq!(error.file_id, "OFNT1BA1");
-        assert_eq!(error.table_name, "test_table");
-        assert_eq!(error.message, "Test error message");
-        assert_eq!(error.error_message, "SQLite error");
+        let temp_file = NamedTempFile::new()?;
+        let mut handler = DataHandler::new(temp_file.path().to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.add_index("OFNT1BA1", &["CPSTATUS", "CMDORNUM"], true);
+        // Never processed, so finalize skips it
+        handler.add_index("INMT4AA1", &["CMDORNUM"], false);
+        handler.init(crate::files::get_file_by_id("OFNT3AA1").unwrap(), None)?;
+        handler.process_file(crate::files::get_file_by_id("OFNT1BA1").unwrap(), None)?;
+
+        assert_eq!(handler.finalize()?, 1);
+
+        let index = "idx_financial_obligation_cpstatus_cmdornum";
+        let columns: Vec<String> = handler
+            .database
+            .prepare(&format!("PRAGMA index_info({})", index))?
+            .query_map([], |row| row.get(2))?
+            .collect::<rusqlite::Result<_>>()?;
+        assert_eq!(columns, vec!["CPSTATUS", "CMDORNUM"]);
+
+        let unique: bool = handler.database.query_row(
+            "SELECT \"unique\" FROM pragma_index_list('financial_obligation') WHERE name = ?",
+            [index],
+            |row| row.get(0),
+        )?;
+        assert!(unique);
+
+        assert!(handler.create_index("financial_obligation", &["NOPE"], false).is_err());
+        assert!(handler.create_index("financial_obligation", &[], false).is_err());
+
+        Ok(())
     }
 
     #[test]
-    fn test_processing_results() {
-        let errors = vec![ErrorDetails::new(
-            "TEST".to_string(),
-            "table".to_string(),
-            "msg".to_string(),
-            "err".to_string(),
-        )];
+    fn test_export_columns_to_csv() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.database.execute_batch(
+            "CREATE TABLE financial_obligation (CMDORNUM TEXT, CPPREFIX TEXT, CPCOPBAL REAL);
+             INSERT INTO financial_obligation VALUES ('0000001', 'AB', 125.5);
+             INSERT INTO financial_obligation VALUES ('0000002', 'CD', NULL);
+             INSERT INTO financial_obligation VALUES ('0000003, \"X\"', 'EF', 3.0);",
+        )?;
 
-        let results = ProcessingResults::new(100, errors.clone());
+        let mut output = Vec::new();
+        let rows = handler.export_columns_to_csv(
+            "financial_obligation",
+            &["CPCOPBAL", "CMDORNUM"],
+            &mut output,
+        )?;
 
-        assert_eq!(results.processed, 100);
-        assert_eq!(results.errors.len(), 1);
-        assert_eq!(results.errors[0].file_id, "TEST");
+        assert_eq!(rows, 3);
+        assert_eq!(
+            String::from_utf8(output)?,
+            "CPCOPBAL,CMDORNUM\n125.5,0000001\n,0000002\n3,\"0000003, \"\"X\"\"\"\n"
+        );
+
+        let error = handler
+            .export_columns_to_csv("financial_obligation", &["CMDORNUM", "NOPE"], Vec::new())
+            .unwrap_err();
+        assert!(error.to_string().contains("NOPE"));
+
+        Ok(())
     }
 
     #[test]
-    fn test_process_file_without_init() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
-
-        let mut handler = DataHandler::new(path)?;
+    fn test_export_csv_null_repr() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.database.execute_batch(
+            "CREATE TABLE offender_profile (CMDORNUM TEXT, NCSRNAME TEXT);
+             INSERT INTO offender_profile VALUES ('0000001', NULL);
+             INSERT INTO offender_profile VALUES ('0000002', '');
+             INSERT INTO offender_profile VALUES ('0000003', '\\N');",
+        )?;
 
-        let file = FileMetadata::new(
-            "OFNT3AA1",
-            "Offender Profile",
-            "https://example.com/OFNT3AA1.zip",
+        let mut output = Vec::new();
+        handler.export_columns_to_csv("offender_profile", &["CMDORNUM", "NCSRNAME"], &mut output)?;
+        assert_eq!(
+            String::from_utf8(output)?,
+            "CMDORNUM,NCSRNAME\n0000001,\n0000002,\"\"\n0000003,\\N\n"
         );
-        let result = handler.process_file(&file, None);
 
-        assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
-            .to_string()
-            .contains("not initialized"));
+        handler.set_csv_null_repr("\\N");
+        let mut output = Vec::new();
+        handler.export_columns_to_csv("offender_profile", &["CMDORNUM", "NCSRNAME"], &mut output)?;
+        assert_eq!(
+            String::from_utf8(output)?,
+            "CMDORNUM,NCSRNAME\n0000001,\\N\n0000002,\n0000003,\"\\N\"\n"
+        );
 
         Ok(())
     }
 
     #[test]
-    fn test_reference_file_sets_synchronous_full() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
+    fn test_export_flushes_every_interval() -> Result<()> {
+        struct CountingWriter {
+            bytes: usize,
+            flushes: usize,
+        }
 
-        let mut handler = DataHandler::new(path)?;
+        impl Write for CountingWriter {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.bytes += buf.len();
+                Ok(buf.len())
+            }
 
-        let reference_file = FileMetadata::new(
-            "OFNT3AA1",
-            "Offender Profile",
-            "https://example.com/OFNT3AA1.zip",
-        );
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.flushes += 1;
+                Ok(())
+            }
+        }
 
-        let _ = handler.init(&reference_file, None);
+        let temp_dir = tempfile::TempDir::new()?;
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.database.execute_batch(
+            "CREATE TABLE offender_profile (CMDORNUM TEXT);
+             WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 10)
+             INSERT INTO offender_profile SELECT printf('%07d', i) FROM n;",
+        )?;
+        handler.set_export_flush_interval(3);
 
-        let sync_mode: i32 = handler
-            .database
-            .pragma_query_value(None, "synchronous", |row| row.get(0))?;
+        let mut writer = CountingWriter { bytes: 0, flushes: 0 };
+        let rows = handler.export_columns_to_csv("offender_profile", &["CMDORNUM"], &mut writer)?;
+
+        assert_eq!(rows, 10);
+        assert_eq!(writer.bytes, "CMDORNUM\n".len() + 10 * "0000001\n".len());
+        // After rows 3, 6 and 9, plus the final flush.
+        assert_eq!(writer.flushes, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_column_holds_file_id() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, dat) in [("OFNT3AA1", "0000001\n0000002\n"), ("OFNT1BA1", "0000001\n0000002\n0000001\n")] {
+            write_fixture(
+                temp_dir.path(),
+                file_id,
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+                dat,
+            );
+        }
 
-        assert_eq!(sync_mode, 2, "Reference table should use PRAGMA synchronous=FULL");
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_store_source_id(true);
+
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let dependent_file = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+        let results = handler.init(reference_file, None)?;
+        assert!(!results.null_counts.contains_key(SOURCE_COLUMN));
+        handler.process_file(dependent_file, None)?;
+
+        for file in [reference_file, dependent_file] {
+            let table = to_snake_case(file.name);
+            let sources: Vec<String> = handler
+                .database
+                .prepare(&format!("SELECT _source FROM {}", quote_identifier(&table)))?
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<_>>()?;
+
+            assert!(!sources.is_empty());
+            assert!(sources.iter().all(|source| source == file.id));
+        }
 
         Ok(())
     }
 
     #[test]
-    fn test_reference_file_initialization_state() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
+    fn test_relationships_for_reference_and_dependent() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, des) in [
+            ("OFNT3AA1", "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n"),
+            ("OFNT1BA1", "CIDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n"),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, "0000001\n");
+        }
 
-        let mut handler = DataHandler::new(path)?;
+        let mut handler = DataHandler::new(temp_dir.path().join("out.db").to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
 
-        assert!(!handler.is_initialized(), "Handler should not be initialized before init()");
-        assert!(handler.reference_file().is_none());
-        assert!(handler.reference_table_name().is_none());
-        assert!(handler.reference_field().is_none());
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let dependent_file = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+        handler.init(reference_file, None)?;
+        handler.process_file(dependent_file, None)?;
 
-        let reference_file = FileMetadata::new(
-            "OFNT3AA1",
-            "Offender Profile",
-            "https://example.com/OFNT3AA1.zip",
-        );
+        let relationships = handler.relationships();
+        assert_eq!(relationships.len(), 2);
 
-        handler.init_from_reference(&reference_file, "offender_profile", "CMDORNUM");
+        let reference = relationships.iter().find(|r| r.file_id == "OFNT3AA1").unwrap();
+        assert_eq!(reference.key_column, "CMDORNUM");
+        assert_eq!(reference.target_table, None);
+        assert_eq!(reference.target_column, None);
 
-        assert!(handler.is_initialized(), "Handler should be marked as initialized after init_from_reference() call");
-        assert!(handler.reference_file().is_some());
-        assert!(handler.reference_table_name().is_some());
-        assert!(handler.reference_field().is_some());
+        let dependent = relationships.iter().find(|r| r.file_id == "OFNT1BA1").unwrap();
+        assert_eq!(dependent.table, to_snake_case(dependent_file.name));
+        assert_eq!(dependent.key_column, "CIDORNUM");
+        assert_eq!(dependent.target_table.as_deref(), Some(to_snake_case(reference_file.name).as_str()));
+        assert_eq!(dependent.target_column.as_deref(), Some("CMDORNUM"));
+
+        let suggestions = handler.suggest_indexes();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].table, dependent.table);
+        assert_eq!(suggestions[0].column, "CIDORNUM");
+
+        handler.database.execute(&suggestions[0].statement, [])?;
 
         Ok(())
     }
 
     #[test]
-    fn test_reference_file_must_complete_before_other_processing() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
+    fn test_process_file_from_zip_into_memory() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let des = "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                   CPPREFIX      COP COMMITMENT PREFIX              CHAR      8       2\n";
+
+        let reference_zip = temp_dir.path().join("OFNT3AA1.zip");
+        write_test_zip(
+            &reference_zip,
+            &[("OFNT3AA1.des", des.as_bytes()), ("OFNT3AA1.dat", b"0000001AB\n0000002CD\n")],
+        )?;
+        let dependent_zip = temp_dir.path().join("OFNT1BA1.zip");
+        write_test_zip(
+            &dependent_zip,
+            &[("ofnt1ba1/OFNT1BA1.des", des.as_bytes()), ("ofnt1ba1/OFNT1BA1.dat", b"0000001EF\n0000009GH\n")],
+        )?;
 
-        let mut handler = DataHandler::new(path)?;
+        let reference_file = crate::files::get_file_by_id("OFNT3AA1").unwrap();
+        let dependent_file = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+        let reference_table = to_snake_case(reference_file.name);
 
-        let other_file = FileMetadata::new(
-            "OFNT1BA1",
-            "Offender Address",
-            "https://example.com/OFNT1BA1.zip",
-        );
+        let mut handler = DataHandler::new(":memory:")?;
+        handler.reference_file = Some(*reference_file);
+        handler.reference_table_name = Some(reference_table.clone());
+        handler.reference_field = Some("CMDORNUM".to_string());
+        handler.is_initialized = true;
 
-        let result = handler.process_file(&other_file, None);
+        let reference_results = handler
+            .process_file_from_zip(reference_file, &reference_zip, None)?
+            .unwrap();
+        assert_eq!(reference_results.processed, 2);
+
+        let dependent_results = handler
+            .process_file_from_zip(dependent_file, &dependent_zip, None)?
+            .unwrap();
+        assert_eq!(dependent_results.processed, 2);
+        assert_eq!(dependent_results.errors.len(), 1);
+        assert_eq!(dependent_results.errors[0].key_value.as_deref(), Some("0000009"));
+
+        let prefix: String = handler.database.query_row(
+            &format!(
+                "SELECT CPPREFIX FROM {} WHERE CMDORNUM = '0000001'",
+                quote_identifier(&to_snake_case(dependent_file.name))
+            ),
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(prefix, "EF");
+        assert!(handler.processed_files().contains("OFNT1BA1"));
 
-        assert!(result.is_err(), "process_file() should fail if handler not initialized");
-        assert!(result.unwrap_err().to_string().contains("not initialized"));
+        let entries: Vec<_> = std::fs::read_dir(temp_dir.path())?.collect();
+        assert_eq!(entries.len(), 2, "nothing should be extracted to disk");
 
         Ok(())
     }
 
     #[test]
-    fn test_reference_file_pragma_persists() -> Result<()> {
-        let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
-
-        let mut handler = DataHandler::new(path)?;
-
-        let sync_mode_before: i32 = handler
-            .database
-            .pragma_query_value(None, "synchronous", |row| row.get(0))?;
-
-        let reference_file = FileMetadata::new(
-            "OFNT3AA1",
-            "Offender Profile",
-            "https://example.com/OFNT3AA1.zip",
-        );
+    fn test_process_file_from_zip_without_des() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let zip_path = temp_dir.path().join("OFNT1BA1.zip");
+        write_test_zip(&zip_path, &[("OFNT1BA1.dat", b"0000001\n")])?;
 
-        let _ = handler.init(&reference_file, None);
-
-        let sync_mode_after: i32 = handler
-            .database
-            .pragma_query_value(None, "synchronous", |row| row.get(0))?;
-
-        assert_eq!(sync_mode_after, 2, "PRAGMA synchronous should be FULL after init()");
+        let mut handler = DataHandler::new(":memory:")?;
+        handler.is_initialized = true;
 
-        if sync_mode_before != 2 {
-            assert_ne!(sync_mode_before, sync_mode_after, "PRAGMA should have changed");
-        }
+        let file = crate::files::get_file_by_id("OFNT1BA1").unwrap();
+        assert!(handler.process_file_from_zip(file, &zip_path, None)?.is_none());
+        assert_eq!(handler.des_file_failures, vec!["OFNT1BA1".to_string()]);
 
         Ok(())
     }
 
     #[test]
-    fn test_data_handler_connection_accessor() -> Result<()> {
+    fn test_strict_mode_threshold() -> Result<()> {
         let temp_file = NamedTempFile::new()?;
-        let path = temp_file.path().to_str().unwrap();
+        let mut handler = DataHandler::new(temp_file.path().to_str().unwrap())?;
 
-        let handler = DataHandler::new(path)?;
+        handler.set_strict(true);
+        assert!(handler.check_strict().is_ok());
 
-        let conn = handler.connection();
+        handler.errors.push(ErrorDetails::new(
+            "TEST".to_string(),
+            "test_table".to_string(),
+            "Foreign key violation".to_string(),
+            "FOREIGN KEY constraint failed".to_string(),
+        ));
+        assert!(handler.check_strict().is_err());
 
-        let fk_enabled: i32 = conn.pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
-        assert_eq!(fk_enabled, 1);
+        handler.set_max_violations(1);
+        assert!(handler.check_strict().is_ok());
+
+        handler.set_max_violations(0);
+        handler.set_strict(false);
+        assert!(handler.check_strict().is_ok());
 
         Ok(())
     }
 
-
     #[test]
-    fn test_error_collection_does_not_stop_processing() -> Result<()> {
+    fn test_foreign_key_violation_records_key() -> Result<()> {
         let temp_file = NamedTempFile::new()?;
         let path = temp_file.path().to_str().unwrap();
 
         let mut handler = DataHandler::new(path)?;
 
-        handler.database.execute(
-            "CREATE TABLE reference_table (id TEXT PRIMARY KEY)",
-            [],
-        )?;
-
-        handler.database.execute(
-            "INSERT INTO reference_table (id) VALUES ('VALID_ID')",
-            [],
-        )?;
-
-        handler.database.execute(
-            "CREATE TABLE dependent_table (id TEXT, ref_id TEXT, FOREIGN KEY (ref_id) REFERENCES reference_table(id))",
-            [],
+        handler.database.execute_batch(
+            "CREATE TABLE offenders (CMDORNUM TEXT PRIMARY KEY);
+             INSERT INTO offenders (CMDORNUM) VALUES ('0000001');
+             CREATE TABLE sentences (CIDORNUM TEXT, CMDORNUM TEXT,
+                 FOREIGN KEY (CMDORNUM) REFERENCES offenders(CMDORNUM));",
         )?;
 
         handler.is_initialized = true;
-        handler.reference_table_name = Some("reference_table".to_string());
-        handler.reference_field = Some("id".to_string());
+        handler.reference_table_name = Some("offenders".to_string());
+        handler.reference_field = Some("CMDORNUM".to_string());
 
-        let test_file = FileMetadata::new(
-            "TEST",
-            "Test Table",
-            "https://example.com/TEST.zip",
-        );
+        let test_file = FileMetadata::new("TEST", "Sentences", "https://example.com/TEST.zip");
 
         let batch = vec![
-            (vec![Some("1".to_string()), Some("VALID_ID".to_string())], 1),
-            (vec![Some("2".to_string()), Some("INVALID_ID".to_string())], 2),
-            (vec![Some("3".to_string()), Some("VALID_ID".to_string())], 3),
+            (vec![Some("001".to_string()), Some("0000001".to_string())], 1),
+            (vec![Some("002".to_string()), Some("0000009".to_string())], 2),
         ];
 
-        let insert_sql = "INSERT INTO dependent_table (id, ref_id) VALUES (?, ?)";
-        let errors = handler.commit_batch(&insert_sql, &batch, &test_file, "dependent_table")?;
-
-        assert_eq!(errors.len(), 1, "Should collect FK violation error");
-        assert_eq!(errors[0].file_id, "TEST");
-
-        let count: i32 = handler.database.query_row(
-            "SELECT COUNT(*) FROM dependent_table",
-            [],
-            |row| row.get(0),
-        )?;
+        let insert_sql = "INSERT INTO sentences (CIDORNUM, CMDORNUM) VALUES (?, ?)";
+        let columns = vec!["CIDORNUM".to_string(), "CMDORNUM".to_string()];
+        let errors = handler.commit_batch(insert_sql, &columns, &batch, &test_file, "sentences")?;
 
-        assert_eq!(count, 2, "Should have inserted 2 valid records despite 1 FK violation");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line_number, Some(2));
+        assert_eq!(errors[0].key_column.as_deref(), Some("CMDORNUM"));
+        assert_eq!(errors[0].key_value.as_deref(), Some("0000009"));
 
         Ok(())
     }
@@ -1234,19 +6882,30 @@ mod tests {
 
     #[test]
     fn test_database_connection_cleanup() -> Result<()> {
-        use crate::concurrency::create_worker_handler;
+        use crate::concurrency::{create_worker_handler, WorkerConfig};
 
         let temp_file = NamedTempFile::new()?;
         let path = temp_file.path().to_str().unwrap();
 
         for _ in 0..5 {
-            let handler = create_worker_handler(path)?;
+            let handler = create_worker_handler(path, &WorkerConfig::default())?;
             let _: i32 = handler.connection().pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
         }
 
         Ok(())
     }
 
+    #[test]
+    fn test_benchmark_batch_sizes_times_each_size() -> Result<()> {
+        let timings = benchmark_batch_sizes(600, &[1, 250, 1000])?;
+
+        let sizes: Vec<usize> = timings.iter().map(|(size, _)| *size).collect();
+        assert_eq!(sizes, vec![1, 250, 1000]);
+        assert!(benchmark_batch_sizes(10, &[0]).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_batch_size_constant_unchanged() {
         assert_eq!(BATCH_SIZE, 250, "BATCH_SIZE must remain 250 (optimized value)");
@@ -1254,7 +6913,7 @@ mod tests {
 
     #[test]
     fn test_foreign_key_enforcement_across_connections() -> Result<()> {
-        use crate::concurrency::create_worker_handler;
+        use crate::concurrency::{create_worker_handler, WorkerConfig};
 
         let temp_file = NamedTempFile::new()?;
         let path = temp_file.path().to_str().unwrap();
@@ -1269,7 +6928,7 @@ mod tests {
             [],
         )?;
 
-        let worker = create_worker_handler(path)?;
+        let worker = create_worker_handler(path, &WorkerConfig::default())?;
 
         let fk_enabled: i32 = worker.connection()
             .pragma_query_value(None, "foreign_keys", |row| row.get(0))?;
@@ -1322,15 +6981,273 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_generated_sql_quotes_malicious_identifiers() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let mut handler = DataHandler::new(path)?;
+        handler.database.execute("CREATE TABLE victim (id TEXT)", [])?;
+        handler.is_initialized = true;
+        handler.reference_table_name = Some("offender_profile".to_string());
+        handler.reference_field = Some("CMDORNUM".to_string());
+
+        let evil_field = "EVIL\"TEXT);DROP/**/TABLE/**/victim;--";
+        let content = format!(
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             {}      MALICIOUS FIELD                    CHAR      8       2",
+            evil_field
+        );
+        let description = FileDescription {
+            filename: "TEST".to_string(),
+            schema: FileDescription::parse_content(&content)?,
+        };
+        assert!(description.schema.contains_key(evil_field));
+
+        let create_sql = handler.create_table_sql("offender_profile", &description)?;
+        handler.database.execute(&create_sql, [])?;
+
+        let columns: Vec<String> = table_info(&handler.database, "offender_profile")?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert!(columns.contains(&evil_field.to_string()));
+
+        let insert_sql = build_insert_sql(&quote_identifier("offender_profile"), &["CMDORNUM".to_string(), evil_field.to_string()]);
+        handler.database.execute(&insert_sql, ["0000001", "XX"])?;
+
+        let victim_exists: i32 = handler.database.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='victim'",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(victim_exists, 1, "Malicious identifier must not execute extra statements");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_table_between_in_memory_databases() -> Result<()> {
+        let src_path = "file:copy_table_src?mode=memory&cache=shared";
+        let dst_path = "file:copy_table_dst?mode=memory&cache=shared";
+        // Shared-cache in-memory databases live as long as a connection is open
+        let src = Connection::open(src_path)?;
+        let dst = Connection::open(dst_path)?;
+        src.execute_batch(
+            "CREATE TABLE offender_profile (CMDORNUM TEXT PRIMARY KEY, CMSEX TEXT, CMAGE REAL);
+             INSERT INTO offender_profile VALUES ('0000001', 'M', 31.5);
+             INSERT INTO offender_profile VALUES ('0000002', NULL, NULL);
+             INSERT INTO offender_profile VALUES ('0000003', 'F', 40.0);",
+        )?;
+
+        assert_eq!(copy_table(src_path, dst_path, "offender_profile")?, 3);
+
+        let rows = |conn: &Connection| -> Result<Vec<String>> {
+            let mut stmt = conn.prepare(
+                "SELECT quote(CMDORNUM) || ',' || quote(CMSEX) || ',' || quote(CMAGE)
+                 FROM offender_profile ORDER BY CMDORNUM",
+            )?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            Ok(rows.collect::<rusqlite::Result<_>>()?)
+        };
+        assert_eq!(rows(&src)?, rows(&dst)?);
+
+        let ddl = |conn: &Connection| -> Result<String> {
+            Ok(conn.query_row(
+                "SELECT sql FROM sqlite_master WHERE name = 'offender_profile'",
+                [],
+                |row| row.get(0),
+            )?)
+        };
+        assert_eq!(ddl(&src)?, ddl(&dst)?);
+
+        assert!(copy_table(src_path, dst_path, "missing_table").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_databases_with_disjoint_tables() -> Result<()> {
+        let first_path = "file:merge_first?mode=memory&cache=shared";
+        let second_path = "file:merge_second?mode=memory&cache=shared";
+        let output_path = "file:merge_output?mode=memory&cache=shared";
+        // Shared-cache in-memory databases live as long as a connection is open
+        let first = Connection::open(first_path)?;
+        let second = Connection::open(second_path)?;
+        let output = Connection::open(output_path)?;
+        first.execute_batch(
+            "CREATE TABLE offender_profile (CMDORNUM TEXT PRIMARY KEY, CMSEX TEXT);
+             INSERT INTO offender_profile VALUES ('0000001', 'M');
+             INSERT INTO offender_profile VALUES ('0000002', 'F');
+             CREATE TABLE column_descriptions (table_name TEXT, column_name TEXT, description TEXT);
+             INSERT INTO column_descriptions VALUES ('offender_profile', 'CMSEX', 'SEX');",
+        )?;
+        second.execute_batch(
+            "CREATE TABLE sentence_component (CIDORNUM TEXT PRIMARY KEY, CMDORNUM TEXT);
+             INSERT INTO sentence_component VALUES ('0000010', '0000001');
+             CREATE TABLE column_descriptions (table_name TEXT, column_name TEXT, description TEXT);
+             INSERT INTO column_descriptions VALUES ('sentence_component', 'CIDORNUM', 'COMPONENT');",
+        )?;
+
+        let report = merge_databases(&[Path::new(first_path), Path::new(second_path)], Path::new(output_path))?;
+
+        assert!(report.collisions.is_empty());
+        let copied: Vec<(&str, u64)> = report
+            .copied
+            .iter()
+            .map(|table| (table.table.as_str(), table.rows))
+            .collect();
+        assert_eq!(copied, vec![("offender_profile", 2), ("sentence_component", 1)]);
+
+        assert_eq!(table_names(&output, "main")?, vec!["offender_profile", "sentence_component"]);
+        let descriptions: i64 =
+            output.query_row("SELECT COUNT(*) FROM column_descriptions", [], |row| row.get(0))?;
+        assert_eq!(descriptions, 2);
+
+        // Merging the first input again collides with the table it already supplied
+        let report = merge_databases(&[Path::new(first_path)], Path::new(output_path))?;
+        assert!(report.copied.is_empty());
+        assert_eq!(report.collisions.len(), 1);
+        assert_eq!(report.collisions[0].kept_from, Path::new(output_path));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_changelog_added_and_modified() -> Result<()> {
+        let old_db = Connection::open_in_memory()?;
+        let new_db = Connection::open_in_memory()?;
+
+        for db in [&old_db, &new_db] {
+            db.execute(
+                "CREATE TABLE offender_profile (CMDORNUM TEXT, NAME TEXT, STATUS TEXT, PRIMARY KEY (CMDORNUM))",
+                [],
+            )?;
+            db.execute(
+                "INSERT INTO offender_profile VALUES ('0000001', 'SMITH', 'ACTIVE'), ('0000002', 'JONES', 'ACTIVE')",
+                [],
+            )?;
+        }
+
+        new_db.execute(
+            "UPDATE offender_profile SET STATUS = 'INACTIVE' WHERE CMDORNUM = '0000002'",
+            [],
+        )?;
+        new_db.execute(
+            "INSERT INTO offender_profile VALUES ('0000003', 'BROWN', NULL)",
+            [],
+        )?;
+
+        let changes = changelog(&old_db, &new_db, "offender_profile")?;
+
+        assert_eq!(changes.added, vec!["0000003".to_string()]);
+        assert!(changes.removed.is_empty());
+        assert_eq!(
+            changes.modified,
+            vec![RowChange {
+                key: "0000002".to_string(),
+                changed_columns: vec!["STATUS".to_string()],
+            }]
+        );
+
+        let reverse = changelog(&new_db, &old_db, "offender_profile")?;
+        assert_eq!(reverse.removed, vec!["0000003".to_string()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_changelog_requires_primary_key() -> Result<()> {
+        let old_db = Connection::open_in_memory()?;
+        let new_db = Connection::open_in_memory()?;
+
+        for db in [&old_db, &new_db] {
+            db.execute("CREATE TABLE dependent (CMDORNUM TEXT, VALUE TEXT)", [])?;
+        }
+
+        assert!(changelog(&old_db, &new_db, "dependent").is_err());
+        assert!(changelog(&old_db, &new_db, "missing_table").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_table_for_file_discards_partial_load() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let mut handler = DataHandler::new(path)?;
+        let test_file = FileMetadata::new("TEST", "Test Table", "https://example.com/TEST.zip");
+
+        handler.database.execute("CREATE TABLE test_table (id TEXT)", [])?;
+        handler.database.execute("INSERT INTO test_table (id) VALUES ('1')", [])?;
+        handler.database.execute(
+            "INSERT INTO column_descriptions (table_name, column_name, description) VALUES ('test_table', 'id', 'ID')",
+            [],
+        )?;
+        handler.processed_files.insert("TEST".to_string());
+
+        handler.drop_table_for_file(&test_file)?;
+
+        let remaining: i32 = handler.database.query_row(
+            "SELECT (SELECT COUNT(*) FROM sqlite_master WHERE name = 'test_table')
+                  + (SELECT COUNT(*) FROM column_descriptions WHERE table_name = 'test_table')",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(remaining, 0);
+        assert!(!handler.processed_files().contains("TEST"));
+
+        handler.drop_table_for_file(&test_file)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_integrity_reports_orphan() -> Result<()> {
+        let temp_file = NamedTempFile::new()?;
+        let path = temp_file.path().to_str().unwrap();
+
+        let handler = DataHandler::new(path)?;
+        handler.database.execute("CREATE TABLE ref_table (id TEXT PRIMARY KEY)", [])?;
+        handler.database.execute(
+            "CREATE TABLE dep_table (id TEXT, ref_id TEXT, FOREIGN KEY (ref_id) REFERENCES ref_table(id))",
+            [],
+        )?;
+        handler.database.execute("INSERT INTO ref_table (id) VALUES ('VALID')", [])?;
+        handler.database.execute("INSERT INTO dep_table (id, ref_id) VALUES ('1', 'VALID')", [])?;
+
+        assert!(handler.verify_integrity()?.is_ok());
+
+        handler.database.pragma_update(None, "foreign_keys", "OFF")?;
+        handler.database.execute("INSERT INTO dep_table (id, ref_id) VALUES ('2', 'ORPHAN')", [])?;
+        handler.database.pragma_update(None, "foreign_keys", "ON")?;
+
+        let report = handler.verify_integrity()?;
+
+        assert!(!report.is_ok());
+        assert!(report.integrity_errors.is_empty());
+        assert_eq!(
+            report.foreign_key_violations,
+            vec![ForeignKeyViolation {
+                table_name: "dep_table".to_string(),
+                rowid: Some(2),
+                parent_table: "ref_table".to_string(),
+            }]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_worker_handlers_maintain_isolation() -> Result<()> {
-        use crate::concurrency::create_worker_handler;
+        use crate::concurrency::{create_worker_handler, WorkerConfig};
 
         let temp_file = NamedTempFile::new()?;
         let path = temp_file.path().to_str().unwrap();
 
-        let mut worker1 = create_worker_handler(path)?;
-        let mut worker2 = create_worker_handler(path)?;
+        let mut worker1 = create_worker_handler(path, &WorkerConfig::default())?;
+        let mut worker2 = create_worker_handler(path, &WorkerConfig::default())?;
 
         let ref_file = FileMetadata::new(
             "OFNT3AA1",
@@ -1358,4 +7275,115 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_trailing_bytes_are_reported_as_warning() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, des, dat) in [
+            ("OFNT3AA1", "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n", "0000001\n"),
+            (
+                "OFNT1BA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 CASENUM       CASE NUMBER                        CHAR      8       3\n",
+                "0000001A01XY\n0000001B02XY\n0000001C03\n0000001D04XYZ\n",
+            ),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
+
+        let trailing = DataParser::from_data_dir("OFNT1BA1", temp_dir.path())?
+            .trailing_bytes()?
+            .unwrap();
+        assert_eq!((trailing.records, trailing.total_records), (3, 4));
+        assert_eq!((trailing.common_length, trailing.expected), (2, 10));
+        assert_eq!(DataParser::from_data_dir("OFNT3AA1", temp_dir.path())?.trailing_bytes()?, None);
+
+        let temp_file = NamedTempFile::new()?;
+        let mut handler = DataHandler::new(temp_file.path().to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_report_trailing_bytes(true);
+        handler.init(crate::files::get_file_by_id("OFNT3AA1").unwrap(), None)?;
+        let results = handler
+            .process_file(crate::files::get_file_by_id("OFNT1BA1").unwrap(), None)?
+            .unwrap();
+
+        assert_eq!(results.processed, 4);
+        assert_eq!(results.errors.len(), 1);
+        assert_eq!(results.errors[0].severity, Severity::Warning);
+        assert!(results.errors[0].message.contains("3 of 4 records are longer than the expected 10 bytes"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_des_is_parsed_once_per_file() -> Result<()> {
+        use crate::file_description::DES_PARSE_COUNT;
+
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, des, dat) in [
+            ("OFNT3AA1", "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n", "0000001\n"),
+            (
+                "OFNT1BA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 CASENUM       CASE NUMBER                        CHAR      8       3\n",
+                "0000001A01\n0000001B02\n",
+            ),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
+
+        let temp_file = NamedTempFile::new()?;
+        let mut handler = DataHandler::new(temp_file.path().to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+
+        DES_PARSE_COUNT.with(|count| count.set(0));
+        handler.init(crate::files::get_file_by_id("OFNT3AA1").unwrap(), None)?;
+        assert_eq!(DES_PARSE_COUNT.with(|count| count.get()), 1);
+
+        let results = handler
+            .process_file(crate::files::get_file_by_id("OFNT1BA1").unwrap(), None)?
+            .unwrap();
+        assert_eq!(results.processed, 2);
+        assert_eq!(DES_PARSE_COUNT.with(|count| count.get()), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_recompressed_files() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (file_id, des, dat) in [
+            ("OFNT3AA1", "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n", "0000001\n0000002\n"),
+            (
+                "OFNT1BA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 CASENUM       CASE NUMBER                        CHAR      8       3\n",
+                "0000001A01\n0000002B02\n0000001C03\n0000002D\n",
+            ),
+        ] {
+            write_fixture(temp_dir.path(), file_id, des, dat);
+            crate::files::recompress_extracted(file_id, temp_dir.path())?;
+        }
+
+        let temp_file = NamedTempFile::new()?;
+        let mut handler = DataHandler::new(temp_file.path().to_str().unwrap())?;
+        handler.set_data_dir(temp_dir.path());
+        handler.set_intra_file_workers(2);
+        let reference = handler.init(crate::files::get_file_by_id("OFNT3AA1").unwrap(), None)?;
+        assert_eq!(reference.processed, 2);
+
+        let results = handler
+            .process_file(crate::files::get_file_by_id("OFNT1BA1").unwrap(), None)?
+            .unwrap();
+        assert_eq!(results.processed, 4);
+        assert_eq!(results.errors.len(), 1);
+        assert!(results.errors[0].message.contains("Possible truncation"));
+
+        let rows: i64 = handler
+            .database
+            .query_row("SELECT COUNT(*) FROM financial_obligation", [], |row| row.get(0))?;
+        assert_eq!(rows, 4);
+
+        Ok(())
+    }
 }
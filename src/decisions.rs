@@ -0,0 +1,355 @@
+//! Answers to the CLI's interactive prompts.
+//!
+//! The prompts in the CLI go through the `Decisions` trait rather than
+//! reading stdin directly, so a run can be answered from the terminal
+//! (`TerminalDecisions`), replayed from a file of recorded answers
+//! (`ReplayDecisions`), or recorded for later replay (`RecordingDecisions`).
+//!
+//! A decisions file has one `key: value` line per answer, in the order the
+//! prompts appear. Blank lines and lines starting with `#` are ignored.
+//! A key that is asked more than once (such as `download-failed`) takes its
+//! recorded values in order.
+//!
+//! # Example
+//!
+//! ```
+//! use ncdac_opi_parser::decisions::{Decisions, ReplayDecisions};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let mut decisions = ReplayDecisions::parse(
+//!     "use-default-reference: yes\ndownload-missing: all\n".as_bytes(),
+//! )?;
+//!
+//! assert!(decisions.confirm("use-default-reference", "Use the default?", false)?);
+//! assert_eq!(decisions.input("download-missing", "Your choice (a/s/c)")?, "all");
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{anyhow, Context, Result};
+use dialoguer::{theme::ColorfulTheme, Confirm, MultiSelect, Select};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+/// Source of answers for the CLI's prompts.
+///
+/// Every prompt is identified by a stable `key` used in decisions files;
+/// `prompt` is the text shown to a user at the terminal. Items offered by
+/// `select` and `select_many` are `(value, label)` pairs: the label is
+/// displayed and the value is what gets recorded.
+pub trait Decisions {
+    /// Answers a yes/no question.
+    fn confirm(&mut self, key: &str, prompt: &str, default: bool) -> Result<bool>;
+
+    /// Picks one of `items`, returning its index.
+    fn select(
+        &mut self,
+        key: &str,
+        prompt: &str,
+        items: &[(String, String)],
+        default: usize,
+    ) -> Result<usize>;
+
+    /// Picks any number of `items`, returning their indices.
+    fn select_many(&mut self, key: &str, prompt: &str, items: &[(String, String)]) -> Result<Vec<usize>>;
+
+    /// Answers a lettered menu such as `Your choice (d/q)`.
+    ///
+    /// The answer is returned trimmed and lowercased; interpreting it,
+    /// including what an empty answer means, is up to the caller.
+    fn input(&mut self, key: &str, prompt: &str) -> Result<String>;
+}
+
+impl<D: Decisions + ?Sized> Decisions for Box<D> {
+    fn confirm(&mut self, key: &str, prompt: &str, default: bool) -> Result<bool> {
+        (**self).confirm(key, prompt, default)
+    }
+
+    fn select(
+        &mut self,
+        key: &str,
+        prompt: &str,
+        items: &[(String, String)],
+        default: usize,
+    ) -> Result<usize> {
+        (**self).select(key, prompt, items, default)
+    }
+
+    fn select_many(&mut self, key: &str, prompt: &str, items: &[(String, String)]) -> Result<Vec<usize>> {
+        (**self).select_many(key, prompt, items)
+    }
+
+    fn input(&mut self, key: &str, prompt: &str) -> Result<String> {
+        (**self).input(key, prompt)
+    }
+}
+
+/// Asks the user at the terminal.
+#[derive(Debug, Default)]
+pub struct TerminalDecisions;
+
+impl Decisions for TerminalDecisions {
+    fn confirm(&mut self, _key: &str, prompt: &str, default: bool) -> Result<bool> {
+        Ok(Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(default)
+            .interact()?)
+    }
+
+    fn select(
+        &mut self,
+        _key: &str,
+        prompt: &str,
+        items: &[(String, String)],
+        default: usize,
+    ) -> Result<usize> {
+        let labels: Vec<&str> = items.iter().map(|(_, label)| label.as_str()).collect();
+
+        Ok(Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .items(&labels)
+            .default(default)
+            .interact()?)
+    }
+
+    fn select_many(&mut self, _key: &str, prompt: &str, items: &[(String, String)]) -> Result<Vec<usize>> {
+        let labels: Vec<&str> = items.iter().map(|(_, label)| label.as_str()).collect();
+
+        Ok(MultiSelect::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .items(&labels)
+            .interact()?)
+    }
+
+    fn input(&mut self, _key: &str, prompt: &str) -> Result<String> {
+        print!("\n{}: ", prompt);
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        Ok(input.trim().to_lowercase())
+    }
+}
+
+/// Answers prompts from a decisions file.
+///
+/// Asking for a key with no recorded answer left is an error, so a replayed
+/// run never falls back to the terminal.
+#[derive(Debug, Default)]
+pub struct ReplayDecisions {
+    answers: HashMap<String, VecDeque<String>>,
+}
+
+impl ReplayDecisions {
+    /// Reads recorded answers from `key: value` lines.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input cannot be read or a line is not of the
+    /// form `key: value`.
+    pub fn parse<R: BufRead>(reader: R) -> Result<Self> {
+        let mut answers: HashMap<String, VecDeque<String>> = HashMap::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.context("Failed to read decisions")?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| anyhow!("Expected 'key: value' on line {} of decisions: {}", index + 1, line))?;
+
+            answers
+                .entry(key.trim().to_string())
+                .or_default()
+                .push_back(value.trim().to_string());
+        }
+
+        Ok(Self { answers })
+    }
+
+    /// Reads recorded answers from a decisions file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or parsed.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open decisions file: {}", path.display()))?;
+
+        Self::parse(BufReader::new(file))
+            .with_context(|| format!("Invalid decisions file: {}", path.display()))
+    }
+
+    /// Takes the next recorded answer for `key`.
+    fn next(&mut self, key: &str) -> Result<String> {
+        self.answers
+            .get_mut(key)
+            .and_then(VecDeque::pop_front)
+            .ok_or_else(|| anyhow!("No recorded decision for '{}'", key))
+    }
+}
+
+/// Finds the item whose value is `value`.
+fn find_item(key: &str, items: &[(String, String)], value: &str) -> Result<usize> {
+    items
+        .iter()
+        .position(|(item, _)| item.eq_ignore_ascii_case(value))
+        .ok_or_else(|| anyhow!("Recorded decision for '{}' is not a valid choice: {}", key, value))
+}
+
+impl Decisions for ReplayDecisions {
+    fn confirm(&mut self, key: &str, _prompt: &str, _default: bool) -> Result<bool> {
+        let answer = self.next(key)?;
+
+        match answer.to_lowercase().as_str() {
+            "y" | "yes" | "true" => Ok(true),
+            "n" | "no" | "false" => Ok(false),
+            _ => Err(anyhow!("Recorded decision for '{}' must be yes or no: {}", key, answer)),
+        }
+    }
+
+    fn select(
+        &mut self,
+        key: &str,
+        _prompt: &str,
+        items: &[(String, String)],
+        _default: usize,
+    ) -> Result<usize> {
+        let answer = self.next(key)?;
+        find_item(key, items, &answer)
+    }
+
+    fn select_many(&mut self, key: &str, _prompt: &str, items: &[(String, String)]) -> Result<Vec<usize>> {
+        let answer = self.next(key)?;
+
+        answer
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| find_item(key, items, value))
+            .collect()
+    }
+
+    fn input(&mut self, key: &str, _prompt: &str) -> Result<String> {
+        Ok(self.next(key)?.to_lowercase())
+    }
+}
+
+/// Records the answers given by another provider as a decisions file.
+///
+/// Each answer is written and flushed as soon as it is given, so the
+/// recording is complete even if the run exits early.
+#[derive(Debug)]
+pub struct RecordingDecisions<D, W> {
+    inner: D,
+    writer: W,
+}
+
+impl<D: Decisions, W: Write> RecordingDecisions<D, W> {
+    /// Records the answers from `inner` to `writer`.
+    pub fn new(inner: D, writer: W) -> Self {
+        Self { inner, writer }
+    }
+
+    /// Returns the provider and writer.
+    pub fn into_inner(self) -> (D, W) {
+        (self.inner, self.writer)
+    }
+
+    fn record(&mut self, key: &str, value: &str) -> Result<()> {
+        writeln!(self.writer, "{}: {}", key, value).context("Failed to record decision")?;
+        self.writer.flush().context("Failed to record decision")
+    }
+}
+
+impl<D: Decisions, W: Write> Decisions for RecordingDecisions<D, W> {
+    fn confirm(&mut self, key: &str, prompt: &str, default: bool) -> Result<bool> {
+        let answer = self.inner.confirm(key, prompt, default)?;
+        self.record(key, if answer { "yes" } else { "no" })?;
+        Ok(answer)
+    }
+
+    fn select(
+        &mut self,
+        key: &str,
+        prompt: &str,
+        items: &[(String, String)],
+        default: usize,
+    ) -> Result<usize> {
+        let index = self.inner.select(key, prompt, items, default)?;
+        self.record(key, &items[index].0)?;
+        Ok(index)
+    }
+
+    fn select_many(&mut self, key: &str, prompt: &str, items: &[(String, String)]) -> Result<Vec<usize>> {
+        let indices = self.inner.select_many(key, prompt, items)?;
+        let values: Vec<&str> = indices.iter().map(|&index| items[index].0.as_str()).collect();
+        self.record(key, &values.join(","))?;
+        Ok(indices)
+    }
+
+    fn input(&mut self, key: &str, prompt: &str) -> Result<String> {
+        let answer = self.inner.input(key, prompt)?;
+        self.record(key, &answer)?;
+        Ok(answer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(values: &[&str]) -> Vec<(String, String)> {
+        values
+            .iter()
+            .map(|value| (value.to_string(), format!("{} (label)", value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_record_and_replay_decisions() -> Result<()> {
+        let files = items(&["OFNT3AA1", "OFNT1BA1", "INMT4AA1"]);
+        let script = "# recorded run\n\
+                      use-default-reference: no\n\
+                      reference-file: OFNT1BA1\n\
+                      download-failed: R\n\
+                      download-failed: s\n\
+                      download-selection: OFNT3AA1, INMT4AA1\n";
+
+        let replay = ReplayDecisions::parse(script.as_bytes())?;
+        let mut recording = RecordingDecisions::new(replay, Vec::new());
+
+        assert!(!recording.confirm("use-default-reference", "Use default?", true)?);
+        assert_eq!(recording.select("reference-file", "Select", &files, 0)?, 1);
+        assert_eq!(recording.input("download-failed", "Your choice (r/s)")?, "r");
+        assert_eq!(recording.input("download-failed", "Your choice (r/s)")?, "s");
+        assert_eq!(recording.select_many("download-selection", "Select", &files)?, vec![0, 2]);
+
+        let error = recording.input("download-failed", "Your choice (r/s)").unwrap_err();
+        assert!(error.to_string().contains("download-failed"));
+
+        let (_, recorded) = recording.into_inner();
+        let recorded = String::from_utf8(recorded)?;
+        assert_eq!(
+            recorded,
+            "use-default-reference: no\nreference-file: OFNT1BA1\ndownload-failed: r\n\
+             download-failed: s\ndownload-selection: OFNT3AA1,INMT4AA1\n"
+        );
+
+        // The recording replays to the same answers
+        let mut replay = ReplayDecisions::parse(recorded.as_bytes())?;
+        assert!(!replay.confirm("use-default-reference", "Use default?", true)?);
+        assert_eq!(replay.select("reference-file", "Select", &files, 0)?, 1);
+
+        assert!(ReplayDecisions::parse("no separator".as_bytes()).is_err());
+
+        Ok(())
+    }
+}
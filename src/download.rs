@@ -4,13 +4,25 @@
 //! from the North Carolina Department of Adult Correction website.
 
 use crate::files::FileMetadata;
-use anyhow::{Context, Result};
+use crate::utilities::{extracted_file_path, gzipped_extracted_file, open_extracted_file};
+use anyhow::{anyhow, Context, Result};
 use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
 use reqwest::blocking::Client;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Timeout for a full file download
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Timeout for a HEAD request checking a remote file size
+const HEAD_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// URL for the database structure PDF
 pub const DB_STRUCTURE_PDF_URL: &str = "https://www.doc.state.nc.us/offenders/PublicTables.pdf";
@@ -27,8 +39,69 @@ pub fn download_file(
     dest: &Path,
     file_name: &str,
 ) -> Result<()> {
+    let pb = download_file_with_timeout(url, dest, file_name, DOWNLOAD_TIMEOUT)?;
+    pb.finish_with_message(format!("✓ Downloaded {}", file_name));
+
+    Ok(())
+}
+
+/// Returns the timeout for a request that must finish by `deadline`.
+///
+/// This is `default` capped to the time remaining, or an error if the
+/// deadline has already passed.
+fn request_timeout(default: Duration, deadline: Option<Instant>) -> Result<Duration> {
+    let Some(deadline) = deadline else {
+        return Ok(default);
+    };
+
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        anyhow::bail!("Deadline reached before the request could start");
+    }
+
+    Ok(remaining.min(default))
+}
+
+/// Creates the progress bar for a download of `total_size` bytes.
+///
+/// When the server omits Content-Length the size is unknown, so instead of
+/// a percentage against a made-up total this shows a spinner with the bytes
+/// received and the transfer rate.
+fn download_progress_bar(total_size: Option<u64>) -> Result<ProgressBar> {
+    let pb = match total_size {
+        Some(total_size) => {
+            let pb = ProgressBar::new(total_size);
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")?
+                    .progress_chars("#>-"),
+            );
+            pb
+        }
+        None => {
+            let pb = ProgressBar::no_length();
+            pb.set_style(
+                ProgressStyle::default_spinner()
+                    .template("{msg}\n{spinner:.green} [{elapsed_precise}] {bytes} ({bytes_per_sec})")?,
+            );
+            pb.enable_steady_tick(Duration::from_millis(100));
+            pb
+        }
+    };
+
+    Ok(pb)
+}
+
+/// Downloads `url` to `dest`, returning the progress bar unfinished so the
+/// caller can report the outcome on it.
+fn download_file_with_timeout(
+    url: &str,
+    dest: &Path,
+    file_name: &str,
+    timeout: Duration,
+) -> Result<ProgressBar> {
     let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(300))
+        .timeout(timeout)
         .build()
         .context("Failed to create HTTP client")?;
 
@@ -41,14 +114,7 @@ pub fn download_file(
         anyhow::bail!("HTTP error: {}", response.status());
     }
 
-    let total_size = response.content_length().unwrap_or(100_000_000);
-
-    let pb = ProgressBar::new(total_size);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")?
-            .progress_chars("#>-"),
-    );
+    let pb = download_progress_bar(response.content_length())?;
     pb.set_message(format!("Downloading {}", file_name));
 
     let mut dest_file = File::create(dest)
@@ -74,52 +140,257 @@ pub fn download_file(
         pb.set_position(downloaded);
     }
 
-    pb.finish_with_message(format!("✓ Downloaded {}", file_name));
-
-    Ok(())
+    Ok(pb)
 }
 
 /// Download a data file by its metadata.
 ///
-/// Downloads the file to `./data/{FILE_ID}.zip` relative to the current directory.
+/// Downloads the file to `{data_dir}/{FILE_ID}.zip`, then checks it against
+/// the file's expected SHA-256 when one is known (see `verify_zip_hash`).
+/// The download's progress line says whether the ZIP was verified or, with
+/// no known digest, that verification was skipped.
 ///
 /// # Arguments
 ///
 /// * `file` - The file metadata
 /// * `data_dir` - The data directory path
 pub fn download_data_file(file: &FileMetadata, data_dir: &Path) -> Result<()> {
+    download_data_file_before(file, data_dir, None)
+}
+
+/// Download a data file by its metadata, finishing by a deadline.
+///
+/// Like `download_data_file`, but the request is given only the time left
+/// before `deadline` (at most the usual five minutes), so a stalled
+/// connection cannot outlive a run's overall time limit.
+///
+/// # Arguments
+///
+/// * `file` - The file metadata
+/// * `data_dir` - The data directory path
+/// * `deadline` - When the download must be finished by, if any
+///
+/// # Errors
+///
+/// Returns an error if the deadline has passed, the request times out, the
+/// download fails, or the downloaded ZIP does not match `file.sha256`. A
+/// mismatched ZIP is deleted, so it is not mistaken for a complete download.
+pub fn download_data_file_before(
+    file: &FileMetadata,
+    data_dir: &Path,
+    deadline: Option<Instant>,
+) -> Result<()> {
+    let timeout = request_timeout(DOWNLOAD_TIMEOUT, deadline)
+        .with_context(|| format!("Skipped download of {}", file.id))?;
+
     fs::create_dir_all(data_dir)
         .context(format!("Failed to create directory: {}", data_dir.display()))?;
 
     let dest = data_dir.join(format!("{}.zip", file.id));
 
-    download_file(
-        file.download_url,
-        &dest,
-        &format!("{} ({})", file.name, file.id),
-    )?;
+    let file_name = format!("{} ({})", file.name, file.id);
+    let pb = download_file_with_timeout(file.download_url, &dest, &file_name, timeout)?;
+
+    if file.sha256.is_none() {
+        pb.finish_with_message(format!(
+            "✓ Downloaded {} (no digest for {}, skipping verification)",
+            file_name, file.id
+        ));
+        return Ok(());
+    }
+
+    if !verify_zip_hash(file, data_dir)? {
+        pb.abandon_with_message(format!("✗ Downloaded {} does not match its SHA-256", file_name));
+        fs::remove_file(&dest)
+            .with_context(|| format!("Failed to delete corrupt download: {}", dest.display()))?;
+        return Err(anyhow!(
+            "Downloaded {} does not match its expected SHA-256; the file was deleted",
+            dest.display()
+        ));
+    }
+
+    pb.finish_with_message(format!("✓ Downloaded {} (SHA-256 verified)", file_name));
 
     Ok(())
 }
 
+/// Check a downloaded ZIP against the file's expected SHA-256.
+///
+/// Size checks against HTTP HEAD catch truncated downloads but not
+/// corrupted ones; the digest catches both. Files without a known digest
+/// (`file.sha256` is `None`) are not checked, and the ZIP is not read.
+///
+/// # Arguments
+///
+/// * `file` - The file metadata
+/// * `data_dir` - The data directory containing `{FILE_ID}.zip`
+///
+/// # Returns
+///
+/// `true` if the ZIP matches `file.sha256` or no digest is known, `false`
+/// if the digests differ.
+///
+/// # Errors
+///
+/// Returns an error if a digest is known and the ZIP cannot be read.
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::download::verify_zip_hash;
+/// use ncdac_opi_parser::files::get_file_by_id;
+/// use std::path::Path;
+///
+/// let file = get_file_by_id("OFNT3AA1").unwrap();
+/// if !verify_zip_hash(file, Path::new("./data"))? {
+///     eprintln!("{}.zip is corrupt", file.id);
+/// }
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn verify_zip_hash(file: &FileMetadata, data_dir: &Path) -> Result<bool> {
+    let Some(expected) = file.sha256 else {
+        return Ok(true);
+    };
+
+    let actual = sha256_file(&data_dir.join(format!("{}.zip", file.id)))?;
+    Ok(actual.eq_ignore_ascii_case(expected))
+}
+
+/// Download a data file and extract it in one step, discarding the ZIP.
+///
+/// For minimal disk usage: only the extracted `.des` and `.dat` files are
+/// left behind. See `download_and_extract_with` to keep the archive.
+///
+/// # Arguments
+///
+/// * `file` - The file metadata
+/// * `data_dir` - The data directory path
+///
+/// # Returns
+///
+/// The directory the archive was extracted to, `{data_dir}/{FILE_ID}`.
+///
+/// # Errors
+///
+/// Returns an error if the download or the extraction fails.
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::download::download_and_extract;
+/// use ncdac_opi_parser::files::get_file_by_id;
+/// use std::path::Path;
+///
+/// let file = get_file_by_id("OFNT3AA1").unwrap();
+/// let extracted = download_and_extract(file, Path::new("./data"))?;
+/// println!("Extracted to {}", extracted.display());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn download_and_extract(file: &FileMetadata, data_dir: &Path) -> Result<PathBuf> {
+    download_and_extract_with(file, data_dir, false)
+}
+
+/// Download a data file and extract it in one step.
+///
+/// A ZIP's central directory sits at the end of the archive, so it cannot
+/// be extracted while it is still arriving. The archive is therefore
+/// buffered to `{data_dir}/{FILE_ID}.zip` and extracted as soon as the
+/// download completes. Unless `keep_zip` is set, the ZIP is then deleted,
+/// including when extraction fails, so only one copy of the data stays on
+/// disk once the call returns.
+///
+/// # Arguments
+///
+/// * `file` - The file metadata
+/// * `data_dir` - The data directory path
+/// * `keep_zip` - Whether to keep the downloaded ZIP after extraction
+///
+/// # Returns
+///
+/// The directory the archive was extracted to, `{data_dir}/{FILE_ID}`.
+///
+/// # Errors
+///
+/// Returns an error if the download or the extraction fails, or the ZIP
+/// cannot be deleted afterwards.
+pub fn download_and_extract_with(file: &FileMetadata, data_dir: &Path, keep_zip: bool) -> Result<PathBuf> {
+    download_data_file(file, data_dir)?;
+
+    let zip_path = data_dir.join(format!("{}.zip", file.id));
+    let extracted = crate::unzip::calculate_total_uncompressed_bytes(std::slice::from_ref(file), data_dir)
+        .and_then(|total| {
+            let pb = Arc::new(download_progress_bar(Some(total))?);
+            pb.set_message(format!("Extracting {} ({})", file.name, file.id));
+            let extracted = crate::unzip::decompress_into(file.id, file.name, data_dir, &pb)?;
+            pb.finish_with_message(format!("✓ Extracted {} ({})", file.name, file.id));
+            Ok(extracted)
+        });
+
+    if !keep_zip {
+        fs::remove_file(&zip_path)
+            .with_context(|| format!("Failed to delete ZIP file: {}", zip_path.display()))?;
+    }
+
+    extracted
+}
+
 /// Download the database structure PDF.
 ///
+/// Skips the download if `PublicTables.pdf` already exists in `data_dir`
+/// with a nonzero size. Use `download_db_structure_pdf_from` to override the
+/// URL or force a fresh download.
+///
 /// # Arguments
 ///
 /// * `data_dir` - The data directory path
 pub fn download_db_structure_pdf(data_dir: &Path) -> Result<()> {
-    fs::create_dir_all(data_dir)
-        .context(format!("Failed to create directory: {}", data_dir.display()))?;
+    download_db_structure_pdf_from(data_dir, None, false)?;
+    Ok(())
+}
 
+/// Download the database structure PDF from an optional alternate URL.
+///
+/// Useful if NC DAC moves the PDF before `DB_STRUCTURE_PDF_URL` is updated.
+/// Like the data file status checks, an existing nonzero-size local copy is
+/// treated as complete and left alone unless `force` is set.
+///
+/// # Arguments
+///
+/// * `data_dir` - The data directory path
+/// * `url` - The URL to download from, or `None` for `DB_STRUCTURE_PDF_URL`
+/// * `force` - Download even if a local copy exists
+///
+/// # Returns
+///
+/// `true` if the PDF was downloaded, `false` if the local copy was kept.
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be created or the download fails.
+pub fn download_db_structure_pdf_from(
+    data_dir: &Path,
+    url: Option<&str>,
+    force: bool,
+) -> Result<bool> {
     let dest = data_dir.join("PublicTables.pdf");
 
+    let cached = fs::metadata(&dest)
+        .map(|metadata| metadata.is_file() && metadata.len() > 0)
+        .unwrap_or(false);
+    if cached && !force {
+        return Ok(false);
+    }
+
+    fs::create_dir_all(data_dir)
+        .context(format!("Failed to create directory: {}", data_dir.display()))?;
+
     download_file(
-        DB_STRUCTURE_PDF_URL,
+        url.unwrap_or(DB_STRUCTURE_PDF_URL),
         &dest,
         "Database Structure (PDF)",
     )?;
 
-    Ok(())
+    Ok(true)
 }
 
 /// Get the expected file size from the remote server using HTTP HEAD request.
@@ -127,13 +398,15 @@ pub fn download_db_structure_pdf(data_dir: &Path) -> Result<()> {
 /// # Arguments
 ///
 /// * `url` - The URL to check
+/// * `deadline` - When the request must be finished by, if any
 ///
 /// # Returns
 ///
 /// The expected file size in bytes, or None if it cannot be determined
-fn get_remote_file_size(url: &str) -> Option<u64> {
+/// (including when the deadline has passed)
+fn get_remote_file_size(url: &str, deadline: Option<Instant>) -> Option<u64> {
     let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
+        .timeout(request_timeout(HEAD_TIMEOUT, deadline).ok()?)
         .redirect(reqwest::redirect::Policy::limited(10))
         .build()
         .ok()?;
@@ -142,19 +415,15 @@ fn get_remote_file_size(url: &str) -> Option<u64> {
 
     // Manually parse Content-Length header instead of using response.content_length()
     // because reqwest sometimes returns 0 even when the header is present
-    if let Some(content_length_header) = response.headers().get("content-length") {
-        if let Ok(content_length_str) = content_length_header.to_str() {
-            if let Ok(size) = content_length_str.parse::<u64>() {
-                return Some(size);
-            }
-        }
-    }
-
-    None
+    response
+        .headers()
+        .get("content-length")
+        .and_then(|header| header.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
 }
 
 /// File download status
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FileStatus {
     /// File exists and has correct size
     Complete,
@@ -178,6 +447,83 @@ pub enum FileStatus {
 ///
 /// The file's download status
 pub fn get_file_status(file: &FileMetadata, data_dir: &Path) -> FileStatus {
+    get_file_status_with_size(file, data_dir, None)
+}
+
+/// Check the download status of a data file against a known size.
+///
+/// When `expected_size` is given (e.g., from a manifest of known sizes), the
+/// local file is compared against it and no HTTP request is made. This keeps
+/// size verification working where HEAD requests are blocked. Otherwise this
+/// behaves like `get_file_status`.
+///
+/// # Arguments
+///
+/// * `file` - The file metadata
+/// * `data_dir` - The data directory path
+/// * `expected_size` - The expected ZIP size in bytes, if already known
+///
+/// # Returns
+///
+/// The file's download status
+pub fn get_file_status_with_size(
+    file: &FileMetadata,
+    data_dir: &Path,
+    expected_size: Option<u64>,
+) -> FileStatus {
+    file_status(file, data_dir, expected_size, None)
+}
+
+/// Check the download status of a data file, finishing by a deadline.
+///
+/// Like `get_file_status`, but the HEAD request is given only the time left
+/// before `deadline`. Once the deadline has passed no request is made and,
+/// as when the server cannot be reached, an existing file is assumed complete.
+///
+/// # Arguments
+///
+/// * `file` - The file metadata
+/// * `data_dir` - The data directory path
+/// * `deadline` - When the check must be finished by, if any
+///
+/// # Returns
+///
+/// The file's download status
+pub fn get_file_status_before(
+    file: &FileMetadata,
+    data_dir: &Path,
+    deadline: Option<Instant>,
+) -> FileStatus {
+    file_status(file, data_dir, None, deadline)
+}
+
+/// Check the download status of a data file without contacting the server.
+///
+/// For offline runs: an existing ZIP file is trusted to be complete, so the
+/// result is only ever `Complete` or `Missing`.
+///
+/// # Arguments
+///
+/// * `file` - The file metadata
+/// * `data_dir` - The data directory path
+///
+/// # Returns
+///
+/// The file's download status
+pub fn get_local_file_status(file: &FileMetadata, data_dir: &Path) -> FileStatus {
+    if data_dir.join(format!("{}.zip", file.id)).exists() {
+        FileStatus::Complete
+    } else {
+        FileStatus::Missing
+    }
+}
+
+fn file_status(
+    file: &FileMetadata,
+    data_dir: &Path,
+    expected_size: Option<u64>,
+    deadline: Option<Instant>,
+) -> FileStatus {
     let path = data_dir.join(format!("{}.zip", file.id));
 
     if !path.exists() {
@@ -189,7 +535,7 @@ pub fn get_file_status(file: &FileMetadata, data_dir: &Path) -> FileStatus {
         Err(_) => return FileStatus::Missing,
     };
 
-    match get_remote_file_size(file.download_url) {
+    match expected_size.or_else(|| get_remote_file_size(file.download_url, deadline)) {
         Some(expected_size) => {
             if local_size == expected_size {
                 FileStatus::Complete
@@ -223,11 +569,23 @@ pub fn is_file_downloaded(file: &FileMetadata, data_dir: &Path) -> bool {
 }
 
 
-/// Get the data directory path.
+/// Get the default data directory path.
+///
+/// The same as `utilities::data_directory()`: `./data/` relative to the
+/// current working directory unless the CLI's `--data-dir` flag overrides it.
+///
+/// # Example
 ///
-/// Returns `./data/` relative to the current working directory.
+/// ```
+/// # #![allow(deprecated)]
+/// use ncdac_opi_parser::download::get_data_dir;
+/// use ncdac_opi_parser::utilities::data_directory;
+///
+/// assert_eq!(get_data_dir(), data_directory());
+/// ```
+#[deprecated(since = "0.1.0", note = "use `utilities::data_directory` instead")]
 pub fn get_data_dir() -> PathBuf {
-    PathBuf::from("./data")
+    crate::utilities::data_directory()
 }
 
 /// Get expected file sizes from a ZIP archive.
@@ -260,6 +618,7 @@ fn get_expected_sizes_from_zip(zip_path: &Path) -> Option<HashMap<String, u64>>
 ///
 /// This is a fast check that only verifies file existence, not integrity.
 /// Use `are_decompressed_files_valid()` if you need to validate hashes.
+/// A gzipped copy left by `files::recompress_extracted` counts as the file.
 ///
 /// # Arguments
 ///
@@ -270,17 +629,19 @@ fn get_expected_sizes_from_zip(zip_path: &Path) -> Option<HashMap<String, u64>>
 ///
 /// `true` if both .des and .dat files exist, `false` otherwise
 pub fn decompressed_files_exist(file: &FileMetadata, data_dir: &Path) -> bool {
-    let file_dir = data_dir.join(file.id);
-    let des_path = file_dir.join(format!("{}.des", file.id));
-    let dat_path = file_dir.join(format!("{}.dat", file.id));
-
-    des_path.exists() && dat_path.exists()
+    ["des", "dat"].iter().all(|extension| {
+        extracted_file_path(data_dir, file.id, extension).exists()
+            || gzipped_extracted_file(data_dir, file.id, extension).is_some()
+    })
 }
 
 /// Check if decompressed files (.des and .dat) are valid.
 ///
-/// Validates that both .des and .dat files exist and have the correct sizes
-/// by comparing against the expected sizes from the ZIP archive.
+/// Validates that both .des and .dat files exist and that every entry in the
+/// ZIP archive was extracted with the correct size. A directory left behind by
+/// an interrupted extraction fails this check and will be re-extracted.
+/// Without the ZIP archive, the .dat file is checked against
+/// `FileMetadata::dat_sha256` when it is known.
 ///
 /// # Arguments
 ///
@@ -289,44 +650,63 @@ pub fn decompressed_files_exist(file: &FileMetadata, data_dir: &Path) -> bool {
 ///
 /// # Returns
 ///
-/// `true` if both .des and .dat files exist and have correct sizes, `false` otherwise
+/// `true` if the extraction is complete, `false` otherwise
 pub fn are_decompressed_files_valid(file: &FileMetadata, data_dir: &Path) -> bool {
+    verify_decompressed_files(file, data_dir, file.dat_sha256)
+}
+
+/// Check if decompressed files are complete, optionally verifying the .dat digest.
+///
+/// When the ZIP archive is present, every non-directory entry must exist in
+/// `{data_dir}/{file_id}/` with the uncompressed size recorded in the archive.
+/// An entry kept only as a gzipped copy (`{entry}.gz`) is checked against
+/// the size in its gzip footer, which holds the size modulo 2^32.
+///
+/// When the ZIP archive is absent, sizes cannot be checked. If
+/// `expected_dat_sha256` is given, the .dat file's SHA-256 must match it;
+/// otherwise the files are assumed valid (the ZIP may have been deleted
+/// after extraction).
+///
+/// # Arguments
+///
+/// * `file` - The file metadata
+/// * `data_dir` - The data directory path
+/// * `expected_dat_sha256` - Optional hex-encoded SHA-256 of the .dat file
+///
+/// # Returns
+///
+/// `true` if the extraction is complete, `false` otherwise
+pub fn verify_decompressed_files(
+    file: &FileMetadata,
+    data_dir: &Path,
+    expected_dat_sha256: Option<&str>,
+) -> bool {
     if !decompressed_files_exist(file, data_dir) {
         return false;
     }
 
     let file_dir = data_dir.join(file.id);
-    let des_path = file_dir.join(format!("{}.des", file.id));
-    let dat_path = file_dir.join(format!("{}.dat", file.id));
-
     let zip_path = data_dir.join(format!("{}.zip", file.id));
+
     let expected_sizes = match get_expected_sizes_from_zip(&zip_path) {
         Some(sizes) => sizes,
         None => {
-            // If we can't read the ZIP, assume decompressed files are valid
-            // This handles cases where ZIP was deleted after extraction
-            return true;
+            return match expected_dat_sha256 {
+                Some(expected) => matches!(
+                    open_extracted_file(data_dir, file.id, "dat").and_then(sha256_reader),
+                    Ok(actual) if actual.eq_ignore_ascii_case(expected)
+                ),
+                None => true,
+            };
         }
     };
 
-    let des_filename = format!("{}.des", file.id);
-    if let Some(&expected_des_size) = expected_sizes.get(&des_filename) {
-        if let Ok(metadata) = fs::metadata(&des_path) {
-            if metadata.len() != expected_des_size {
-                return false;
-            }
-        } else {
-            return false;
+    for (entry_name, expected_size) in &expected_sizes {
+        if entry_name.ends_with('/') {
+            continue;
         }
-    }
 
-    let dat_filename = format!("{}.dat", file.id);
-    if let Some(&expected_dat_size) = expected_sizes.get(&dat_filename) {
-        if let Ok(metadata) = fs::metadata(&dat_path) {
-            if metadata.len() != expected_dat_size {
-                return false;
-            }
-        } else {
+        if !extracted_entry_has_size(&file_dir, entry_name, *expected_size) {
             return false;
         }
     }
@@ -334,6 +714,85 @@ pub fn are_decompressed_files_valid(file: &FileMetadata, data_dir: &Path) -> boo
     true
 }
 
+/// Returns the path of an extracted file, or of its gzipped copy if only that exists.
+fn extracted_or_gzipped(data_dir: &Path, file_id: &str, extension: &str) -> PathBuf {
+    gzipped_extracted_file(data_dir, file_id, extension)
+        .unwrap_or_else(|| extracted_file_path(data_dir, file_id, extension))
+}
+
+/// Returns the path of an extracted ZIP entry's gzipped copy, if only the copy exists.
+fn gzipped_entry(file_dir: &Path, entry_name: &str) -> Option<PathBuf> {
+    if file_dir.join(entry_name).exists() {
+        return None;
+    }
+
+    Some(file_dir.join(format!("{}.gz", entry_name))).filter(|path| path.exists())
+}
+
+/// Checks an extracted ZIP entry's size, reading it from the gzip footer of
+/// a gzipped copy.
+fn extracted_entry_has_size(file_dir: &Path, entry_name: &str, expected_size: u64) -> bool {
+    let Some(gz_path) = gzipped_entry(file_dir, entry_name) else {
+        return matches!(fs::metadata(file_dir.join(entry_name)), Ok(metadata) if metadata.len() == expected_size);
+    };
+
+    // The last four bytes of a gzip stream are its uncompressed size mod 2^32
+    let footer = || -> io::Result<u32> {
+        let mut gz = File::open(&gz_path)?;
+        gz.seek(SeekFrom::End(-4))?;
+        let mut isize = [0; 4];
+        gz.read_exact(&mut isize)?;
+        Ok(u32::from_le_bytes(isize))
+    };
+    matches!(footer(), Ok(size) if size == expected_size as u32)
+}
+
+/// Opens an extracted ZIP entry, decompressing a gzipped copy while reading.
+fn open_extracted_entry(file_dir: &Path, entry_name: &str) -> io::Result<Box<dyn Read>> {
+    match gzipped_entry(file_dir, entry_name) {
+        Some(gz_path) => Ok(Box::new(flate2::read::GzDecoder::new(File::open(gz_path)?))),
+        None => Ok(Box::new(File::open(file_dir.join(entry_name))?)),
+    }
+}
+
+/// Compute the hex-encoded SHA-256 digest of a file.
+///
+/// # Arguments
+///
+/// * `path` - The file to hash
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read.
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open file for hashing: {}", path.display()))?;
+
+    sha256_reader(file).with_context(|| format!("Failed to read file for hashing: {}", path.display()))
+}
+
+/// Compute the hex-encoded SHA-256 digest of everything `reader` yields.
+fn sha256_reader(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 8192];
+
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+
+        if bytes_read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
 /// Categorization of files by their download status
 #[derive(Debug, Default)]
 pub struct FilesStatus {
@@ -364,18 +823,214 @@ pub struct FilesStatus {
 ///
 /// `FilesStatus` containing vectors of missing, incomplete, and unverifiable file IDs
 pub fn categorize_files(files: &[FileMetadata], data_dir: &Path) -> FilesStatus {
+    categorize_files_before(files, data_dir, None)
+}
+
+/// Categorize files by their availability status, finishing by a deadline.
+///
+/// Like `categorize_files`, but each HEAD request is limited to the time
+/// left before `deadline` (see `get_file_status_before`).
+///
+/// # Arguments
+///
+/// * `files` - Array of file metadata to check
+/// * `data_dir` - The data directory path
+/// * `deadline` - When the checks must be finished by, if any
+///
+/// # Returns
+///
+/// `FilesStatus` containing vectors of missing, incomplete, and unverifiable file IDs
+pub fn categorize_files_before(
+    files: &[FileMetadata],
+    data_dir: &Path,
+    deadline: Option<Instant>,
+) -> FilesStatus {
+    categorize_files_with(
+        files,
+        data_dir,
+        |file| get_file_status_before(file, data_dir, deadline),
+        |file| are_decompressed_files_valid(file, data_dir),
+    )
+}
+
+/// Categorize files by their availability status using only local files.
+///
+/// Like `categorize_files`, but no HTTP requests are made: an existing ZIP
+/// is trusted to be complete (see `get_local_file_status`), so files are
+/// never reported as incomplete. Suited to fully staged offline runs.
+///
+/// # Arguments
+///
+/// * `files` - Array of file metadata to check
+/// * `data_dir` - The data directory path
+///
+/// # Returns
+///
+/// `FilesStatus` containing vectors of missing and unverifiable file IDs
+pub fn categorize_files_offline(files: &[FileMetadata], data_dir: &Path) -> FilesStatus {
+    categorize_files_with(
+        files,
+        data_dir,
+        |file| get_local_file_status(file, data_dir),
+        |file| are_decompressed_files_valid(file, data_dir),
+    )
+}
+
+/// Categorize files by their download status, reusing earlier results.
+///
+/// Like `categorize_files`, but the remote ZIP size and the extraction
+/// check are remembered in `cache`, so a tool that checks status
+/// repeatedly (such as a watch mode) neither re-sends HEAD requests nor
+/// re-reads archives for files that have not changed. See `StatusCache`
+/// for when a result is reused.
+///
+/// # Arguments
+///
+/// * `files` - Array of file metadata to check
+/// * `data_dir` - The data directory path
+/// * `cache` - Results from earlier calls, updated in place
+///
+/// # Returns
+///
+/// `FilesStatus` containing vectors of missing, incomplete, and unverifiable file IDs
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::download::{categorize_files_cached, StatusCache};
+/// use ncdac_opi_parser::files::FILES;
+/// use std::path::Path;
+///
+/// let cache = StatusCache::new();
+/// loop {
+///     let status = categorize_files_cached(&FILES, Path::new("./data"), &cache);
+///     println!("{} missing", status.missing.len());
+///     std::thread::sleep(std::time::Duration::from_secs(60));
+/// }
+/// ```
+pub fn categorize_files_cached(files: &[FileMetadata], data_dir: &Path, cache: &StatusCache) -> FilesStatus {
+    categorize_files_with(
+        files,
+        data_dir,
+        |file| cache.file_status(file, data_dir),
+        |file| cache.decompressed_files_valid(file, data_dir),
+    )
+}
+
+/// Modification time and length of a file, used to tell whether it changed.
+type FileStamp = (SystemTime, u64);
+
+/// Stamps of a file's ZIP, `.des` and `.dat`, `None` where a file is absent.
+type ExtractionStamps = [Option<FileStamp>; 3];
+
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Remembered results of the expensive checks made by `categorize_files`.
+///
+/// A remote ZIP size is reused while the local ZIP keeps the same
+/// modification time and length, and an extraction check is reused while
+/// the ZIP, `.des` and `.dat` files all do. Any change to one of those
+/// files invalidates the result. Failed HEAD requests are not remembered,
+/// so the server is asked again on the next call.
+#[derive(Debug, Default)]
+pub struct StatusCache {
+    /// Remote size by ZIP path, with the local ZIP's stamp when it was fetched
+    remote_sizes: Mutex<HashMap<PathBuf, (FileStamp, u64)>>,
+    /// Extraction validity by file directory, with the ZIP, DES and DAT stamps
+    extractions: Mutex<HashMap<PathBuf, (ExtractionStamps, bool)>>,
+    /// Number of checks that could not be answered from the cache
+    misses: AtomicUsize,
+}
+
+impl StatusCache {
+    /// Creates an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns how many checks were actually performed rather than reused.
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Forgets every remembered result.
+    pub fn clear(&self) {
+        self.remote_sizes.lock().unwrap().clear();
+        self.extractions.lock().unwrap().clear();
+    }
+
+    /// Like `get_file_status`, reusing the remote size for an unchanged ZIP.
+    fn file_status(&self, file: &FileMetadata, data_dir: &Path) -> FileStatus {
+        let zip_path = data_dir.join(format!("{}.zip", file.id));
+        let Some(stamp) = file_stamp(&zip_path) else {
+            return FileStatus::Missing;
+        };
+
+        let cached = self
+            .remote_sizes
+            .lock()
+            .unwrap()
+            .get(&zip_path)
+            .filter(|(cached_stamp, _)| *cached_stamp == stamp)
+            .map(|(_, size)| *size);
+
+        let remote_size = cached.or_else(|| {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            let size = get_remote_file_size(file.download_url, None)?;
+            self.remote_sizes.lock().unwrap().insert(zip_path, (stamp, size));
+            Some(size)
+        });
+
+        match remote_size {
+            Some(size) => file_status(file, data_dir, Some(size), None),
+            // As in `file_status`, an unreachable server means the file is assumed complete
+            None => FileStatus::Complete,
+        }
+    }
+
+    /// Like `are_decompressed_files_valid`, reused while no file changed.
+    fn decompressed_files_valid(&self, file: &FileMetadata, data_dir: &Path) -> bool {
+        let stamps = [
+            file_stamp(&data_dir.join(format!("{}.zip", file.id))),
+            file_stamp(&extracted_or_gzipped(data_dir, file.id, "des")),
+            file_stamp(&extracted_or_gzipped(data_dir, file.id, "dat")),
+        ];
+        let key = data_dir.join(file.id);
+
+        if let Some((cached_stamps, valid)) = self.extractions.lock().unwrap().get(&key)
+            && *cached_stamps == stamps
+        {
+            return *valid;
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let valid = are_decompressed_files_valid(file, data_dir);
+        self.extractions.lock().unwrap().insert(key, (stamps, valid));
+        valid
+    }
+}
+
+fn categorize_files_with(
+    files: &[FileMetadata],
+    data_dir: &Path,
+    zip_status: impl Fn(&FileMetadata) -> FileStatus,
+    extraction_valid: impl Fn(&FileMetadata) -> bool,
+) -> FilesStatus {
     let mut status = FilesStatus::default();
 
     for file in files {
         let des_dat_exist = decompressed_files_exist(file, data_dir);
-        let zip_status = get_file_status(file, data_dir);
+        let zip_status = zip_status(file);
 
         if des_dat_exist && zip_status == FileStatus::Missing {
             status.unverifiable.push(file.id.to_string());
             continue;
         }
 
-        if are_decompressed_files_valid(file, data_dir) {
+        if extraction_valid(file) {
             continue;
         }
 
@@ -416,15 +1071,641 @@ pub fn get_missing_files(files: &[FileMetadata], data_dir: &Path) -> Vec<String>
     all_missing
 }
 
+/// Estimate the number of bytes a download of the given files would fetch.
+///
+/// Files are categorized as by `categorize_files`, and only missing and
+/// incomplete files are counted: their sizes are read from the server with
+/// concurrent HTTP HEAD requests. Incomplete files count at their full size,
+/// since they are downloaded again from the start. Files already available
+/// locally are not requested.
+///
+/// # Arguments
+///
+/// * `files` - Array of file metadata to check
+/// * `data_dir` - The data directory path
+///
+/// # Returns
+///
+/// The total size in bytes of the files that would be downloaded
+///
+/// # Errors
+///
+/// Returns an error if the server does not report the size of a file that
+/// needs downloading.
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::download::estimate_total_download;
+/// use ncdac_opi_parser::files::FILES;
+/// use ncdac_opi_parser::utilities::data_directory;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let bytes = estimate_total_download(&FILES, &data_directory())?;
+/// println!("This will download {:.1} GB", bytes as f64 / 1e9);
+/// # Ok(())
+/// # }
+/// ```
+pub fn estimate_total_download(files: &[FileMetadata], data_dir: &Path) -> Result<u64> {
+    let status = categorize_files(files, data_dir);
+    let needed: Vec<&FileMetadata> = files
+        .iter()
+        .filter(|file| {
+            status.missing.iter().chain(&status.incomplete).any(|id| id == file.id)
+        })
+        .collect();
+
+    let sizes = needed
+        .par_iter()
+        .map(|file| {
+            get_remote_file_size(file.download_url, None)
+                .ok_or_else(|| anyhow!("Could not determine the download size of {}", file.id))
+        })
+        .collect::<Result<Vec<u64>>>()?;
+
+    Ok(sizes.into_iter().sum())
+}
+
+/// Order files for download so the reference file comes first.
+///
+/// The reference file is needed before any other file can be loaded, so
+/// fetching it first lets processing start as early as possible. The
+/// remaining files keep their relative order.
+///
+/// # Arguments
+///
+/// * `files` - The files to download
+/// * `reference_id` - ID of the reference file
+///
+/// # Returns
+///
+/// The same files, reordered.
+///
+/// # Example
+///
+/// ```
+/// use ncdac_opi_parser::download::ordered_by_priority;
+/// use ncdac_opi_parser::files::FILES;
+///
+/// let ordered = ordered_by_priority(&FILES, "INMT4AA1");
+/// assert_eq!(ordered[0].id, "INMT4AA1");
+/// assert_eq!(ordered.len(), FILES.len());
+/// ```
+pub fn ordered_by_priority(files: &[FileMetadata], reference_id: &str) -> Vec<FileMetadata> {
+    ordered_by_priority_with(files, reference_id, &[])
+}
+
+/// Order files for download: the reference file, then `priority`, then the rest.
+///
+/// Like `ordered_by_priority`, with user-chosen files moved up to follow
+/// the reference file in the order they are listed in `priority`. IDs in
+/// `priority` that are not among `files` are ignored.
+///
+/// # Arguments
+///
+/// * `files` - The files to download
+/// * `reference_id` - ID of the reference file
+/// * `priority` - IDs of files to download next, most urgent first
+///
+/// # Returns
+///
+/// The same files, reordered.
+pub fn ordered_by_priority_with(
+    files: &[FileMetadata],
+    reference_id: &str,
+    priority: &[&str],
+) -> Vec<FileMetadata> {
+    let rank = |file: &FileMetadata| {
+        if file.id == reference_id {
+            0
+        } else {
+            priority
+                .iter()
+                .position(|id| *id == file.id)
+                .map_or(priority.len() + 1, |index| index + 1)
+        }
+    };
+
+    let mut ordered = files.to_vec();
+    ordered.sort_by_key(rank);
+    ordered
+}
+
+/// Outcome of verifying one file's local data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyStatus {
+    /// The ZIP's checksums hold and any extracted files match it
+    Passed,
+    /// The ZIP is corrupt or an extracted file differs from it
+    Failed(String),
+    /// Extracted files exist but there is no ZIP to check them against
+    Unverifiable,
+    /// Neither the ZIP nor extracted files are present
+    Missing,
+}
+
+/// Result of verifying one file, as returned by `verify_all`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyResult {
+    /// The file ID (e.g., "OFNT3AA1")
+    pub file_id: String,
+    /// What verification found
+    pub status: VerifyStatus,
+}
+
+impl VerifyResult {
+    /// Whether the file was present and failed verification.
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status, VerifyStatus::Failed(_))
+    }
+}
+
+/// Verify the integrity of downloaded and extracted files without loading them.
+///
+/// For each file with a ZIP in `data_dir`, every entry is read in full so
+/// the CRC-32 stored in the archive is checked. If the file has been
+/// extracted, each extracted entry must also match the archive byte for
+/// byte. No network requests are made.
+///
+/// # Arguments
+///
+/// * `files` - The files to verify
+/// * `data_dir` - The data directory path
+///
+/// # Returns
+///
+/// One `VerifyResult` per file, in the order given.
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::download::verify_all;
+/// use ncdac_opi_parser::files::FILES;
+/// use ncdac_opi_parser::utilities::data_directory;
+///
+/// for result in verify_all(&FILES, &data_directory()) {
+///     println!("{}: {:?}", result.file_id, result.status);
+/// }
+/// ```
+pub fn verify_all(files: &[FileMetadata], data_dir: &Path) -> Vec<VerifyResult> {
+    files
+        .iter()
+        .map(|file| VerifyResult {
+            file_id: file.id.to_string(),
+            status: verify_file(file, data_dir),
+        })
+        .collect()
+}
+
+/// Verify one file's ZIP and extracted files (see `verify_all`).
+fn verify_file(file: &FileMetadata, data_dir: &Path) -> VerifyStatus {
+    let zip_path = data_dir.join(format!("{}.zip", file.id));
+    let file_dir = data_dir.join(file.id);
+
+    if !zip_path.exists() {
+        return if decompressed_files_exist(file, data_dir) {
+            VerifyStatus::Unverifiable
+        } else {
+            VerifyStatus::Missing
+        };
+    }
+
+    match verify_archive(&zip_path, &file_dir) {
+        Ok(()) => VerifyStatus::Passed,
+        Err(e) => VerifyStatus::Failed(format!("{:#}", e)),
+    }
+}
+
+/// Read every entry of a ZIP archive, comparing it with `file_dir` if extracted.
+fn verify_archive(zip_path: &Path, file_dir: &Path) -> Result<()> {
+    let archive_file = File::open(zip_path)
+        .with_context(|| format!("Failed to open ZIP file: {}", zip_path.display()))?;
+    let mut archive = zip::ZipArchive::new(archive_file)
+        .with_context(|| format!("Failed to read ZIP archive: {}", zip_path.display()))?;
+    let extracted = file_dir.is_dir();
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+
+        if !extracted {
+            io::copy(&mut entry, &mut io::sink())
+                .with_context(|| format!("Checksum failed for {}", name))?;
+            continue;
+        }
+
+        let mut local = open_extracted_entry(file_dir, &name)
+            .with_context(|| format!("Extracted file is missing: {}", name))?;
+        let mut expected = vec![0; 8192];
+        let mut actual = vec![0; 8192];
+
+        loop {
+            let bytes_read = entry
+                .read(&mut expected)
+                .with_context(|| format!("Checksum failed for {}", name))?;
+            if bytes_read == 0 {
+                if local.read(&mut actual[..1])? != 0 {
+                    return Err(anyhow!("Extracted file is longer than the archive entry: {}", name));
+                }
+                break;
+            }
+
+            local
+                .read_exact(&mut actual[..bytes_read])
+                .with_context(|| format!("Extracted file is shorter than the archive entry: {}", name))?;
+            if expected[..bytes_read] != actual[..bytes_read] {
+                return Err(anyhow!("Extracted file does not match the archive: {}", name));
+            }
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
+    use crate::test_support::{write_fixture, write_test_zip};
 
     #[test]
+    #[allow(deprecated)]
     fn test_get_data_dir() {
-        let data_dir = get_data_dir();
-        assert_eq!(data_dir, PathBuf::from("./data"));
+        let _guard = crate::utilities::DataDirectoryGuard::new(None);
+        assert_eq!(get_data_dir(), crate::utilities::data_directory());
+        assert!(get_data_dir().ends_with("data"));
+    }
+
+    #[test]
+    fn test_truncated_dat_with_zip_is_reextracted() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+        let file = FileMetadata::new("TESTPART", "Test Partial", "https://example.com/TESTPART.zip");
+
+        let dat_content = vec![b'R'; 4096];
+        write_test_zip(
+            &data_dir.join("TESTPART.zip"),
+            &[("TESTPART.des", b"descriptor"), ("TESTPART.dat", &dat_content)],
+        )
+        .unwrap();
+
+        let pb = std::sync::Arc::new(ProgressBar::hidden());
+        crate::unzip::decompress_into(file.id, file.name, data_dir, &pb).unwrap();
+        assert!(are_decompressed_files_valid(&file, data_dir));
+
+        let dat_path = data_dir.join("TESTPART").join("TESTPART.dat");
+        fs::write(&dat_path, &dat_content[..1000]).unwrap();
+        assert!(!are_decompressed_files_valid(&file, data_dir));
+
+        crate::unzip::decompress_into(file.id, file.name, data_dir, &pb).unwrap();
+        assert!(are_decompressed_files_valid(&file, data_dir));
+        assert_eq!(fs::read(&dat_path).unwrap(), dat_content);
+    }
+
+    #[test]
+    fn test_missing_zip_entry_is_invalid() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+        let file = FileMetadata::new("TESTMISS", "Test Missing", "https://example.com/TESTMISS.zip");
+
+        write_test_zip(
+            &data_dir.join("TESTMISS.zip"),
+            &[("TESTMISS.des", b"d"), ("TESTMISS.dat", b"r"), ("README.txt", b"notes")],
+        )
+        .unwrap();
+
+        write_fixture(data_dir, "TESTMISS", "d", "r");
+
+        assert!(!are_decompressed_files_valid(&file, data_dir));
+    }
+
+    #[test]
+    fn test_verify_dat_sha256_without_zip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+        let file = FileMetadata::new("TESTHASH", "Test Hash", "https://example.com/TESTHASH.zip");
+
+        let file_dir = write_fixture(data_dir, "TESTHASH", "d", "abc");
+
+        let abc_sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert!(verify_decompressed_files(&file, data_dir, Some(abc_sha256)));
+        assert!(!verify_decompressed_files(&file, data_dir, Some("00")));
+        assert!(verify_decompressed_files(&file, data_dir, None));
+
+        // The stored digest is checked when deciding whether to re-extract
+        assert!(are_decompressed_files_valid(&file.with_dat_sha256(abc_sha256), data_dir));
+        fs::write(file_dir.join("TESTHASH.dat"), b"ab").unwrap();
+        assert!(!are_decompressed_files_valid(&file.with_dat_sha256(abc_sha256), data_dir));
+        assert!(are_decompressed_files_valid(&file, data_dir));
+    }
+
+    #[test]
+    fn test_file_status_with_expected_size() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file = FileMetadata::new("SIZE01", "Size Test", "http://127.0.0.1:9/SIZE01.zip");
+        std::fs::write(temp_dir.path().join("SIZE01.zip"), b"truncated").unwrap();
+
+        assert_eq!(
+            get_file_status_with_size(&file, temp_dir.path(), Some(1024)),
+            FileStatus::Incomplete
+        );
+        assert_eq!(
+            get_file_status_with_size(&file, temp_dir.path(), Some(9)),
+            FileStatus::Complete
+        );
+        assert_eq!(
+            get_file_status_with_size(&file, &temp_dir.path().join("missing"), Some(9)),
+            FileStatus::Missing
+        );
+    }
+
+    #[test]
+    fn test_categorize_files_offline_makes_no_requests() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+
+        // Any HEAD request would land on this listener
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let url: &'static str = Box::leak(
+            format!("http://{}/OFFLINE.zip", listener.local_addr().unwrap()).into_boxed_str(),
+        );
+
+        let staged = FileMetadata::new("OFFLN1", "Staged", url);
+        let zipped = FileMetadata::new("OFFLN2", "Zip Only", url);
+        let absent = FileMetadata::new("OFFLN3", "Absent", url);
+
+        write_fixture(data_dir, "OFFLN1", "des", "dat");
+        fs::write(data_dir.join("OFFLN2.zip"), b"zip").unwrap();
+
+        let status = categorize_files_offline(&[staged, zipped, absent], data_dir);
+
+        assert_eq!(status.missing, vec!["OFFLN3".to_string()]);
+        assert!(status.incomplete.is_empty());
+        assert_eq!(status.unverifiable, vec!["OFFLN1".to_string()]);
+        assert_eq!(
+            listener.accept().unwrap_err().kind(),
+            std::io::ErrorKind::WouldBlock,
+            "offline categorization must not contact the server"
+        );
+    }
+
+    /// Serves `body` once over HTTP on a local port and returns its URL.
+    fn serve_once(body: &'static [u8]) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/PublicTables.pdf", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+        });
+
+        url
+    }
+
+    /// Answers one HEAD request on a local port with `size` as the
+    /// Content-Length and returns the URL.
+    fn serve_head(file_id: &str, size: u64) -> &'static str {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/{}.zip", listener.local_addr().unwrap(), file_id);
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            assert!(request.starts_with(b"HEAD "));
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                size
+            )
+            .unwrap();
+        });
+
+        Box::leak(url.into_boxed_str())
+    }
+
+    #[test]
+    fn test_estimate_total_download_sums_missing_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+
+        let first = FileMetadata::new("EST1", "First", serve_head("EST1", 1_500_000_000));
+        let second = FileMetadata::new("EST2", "Second", serve_head("EST2", 800_000_000));
+        // Extracted without its ZIP: not downloaded, so its URL is never requested
+        let staged = FileMetadata::new("EST3", "Staged", "http://127.0.0.1:9/EST3.zip");
+        write_fixture(data_dir, "EST3", "des", "dat");
+
+        let total = estimate_total_download(&[first, second, staged], data_dir).unwrap();
+        assert_eq!(total, 2_300_000_000);
+
+        let unreachable = FileMetadata::new("EST4", "Unreachable", "http://127.0.0.1:9/EST4.zip");
+        assert!(estimate_total_download(&[unreachable], data_dir).is_err());
+    }
+
+    #[test]
+    fn test_categorize_files_cached_skips_unchanged_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+        let zip_path = data_dir.join("CACHE1.zip");
+        write_test_zip(&zip_path, &[("CACHE1.des", b"des"), ("CACHE1.dat", b"dat")]).unwrap();
+        let zip_size = fs::metadata(&zip_path).unwrap().len();
+
+        let file_dir = write_fixture(data_dir, "CACHE1", "des", "dat");
+
+        // Only one HEAD request is ever answered
+        let file = FileMetadata::new("CACHE1", "Cached", serve_head("CACHE1", zip_size));
+        let cache = StatusCache::new();
+
+        let status = categorize_files_cached(&[file], data_dir, &cache);
+        assert!(status.missing.is_empty() && status.incomplete.is_empty());
+        assert_eq!(cache.misses(), 2);
+
+        let status = categorize_files_cached(&[file], data_dir, &cache);
+        assert!(status.missing.is_empty() && status.incomplete.is_empty());
+        assert_eq!(cache.misses(), 2, "unchanged files are not checked again");
+
+        // A changed .dat invalidates only the extraction check
+        fs::write(file_dir.join("CACHE1.dat"), b"dat, partly rewritten").unwrap();
+        categorize_files_cached(&[file], data_dir, &cache);
+        assert_eq!(cache.misses(), 3);
+        assert!(!cache.decompressed_files_valid(&file, data_dir));
+        assert_eq!(cache.misses(), 3);
+    }
+
+    #[test]
+    fn test_download_without_content_length() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dest = temp_dir.path().join("NOLEN.zip");
+        let body = b"body without a declared length";
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/NOLEN.zip", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            // No Content-Length: the body ends when the connection closes
+            write!(stream, "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n").unwrap();
+            stream.write_all(body).unwrap();
+        });
+
+        download_file(&url, &dest, "NOLEN").unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), body);
+
+        assert_eq!(download_progress_bar(None).unwrap().length(), None);
+        assert_eq!(download_progress_bar(Some(42)).unwrap().length(), Some(42));
+    }
+
+    #[test]
+    fn test_db_structure_pdf_url_override_and_cache() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let pdf_path = temp_dir.path().join("PublicTables.pdf");
+
+        let url = serve_once(b"%PDF-1.4 test");
+        assert!(download_db_structure_pdf_from(temp_dir.path(), Some(&url), false).unwrap());
+        assert_eq!(fs::read(&pdf_path).unwrap(), b"%PDF-1.4 test");
+
+        // The local copy is kept; the unreachable URL is never requested
+        fs::write(&pdf_path, b"%PDF-1.4 cached").unwrap();
+        let unreachable = "http://127.0.0.1:9/PublicTables.pdf";
+        assert!(!download_db_structure_pdf_from(temp_dir.path(), Some(unreachable), false).unwrap());
+        assert_eq!(fs::read(&pdf_path).unwrap(), b"%PDF-1.4 cached");
+
+        assert!(download_db_structure_pdf_from(temp_dir.path(), Some(unreachable), true).is_err());
+    }
+
+    #[test]
+    fn test_downloads_land_where_the_parser_reads() {
+        use crate::utilities::{data_directory, DataDirectoryGuard};
+
+        let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        archive.start_file("OFNT3AA1.des", options).unwrap();
+        archive
+            .write_all(b"CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n")
+            .unwrap();
+        archive.start_file("OFNT3AA1.dat", options).unwrap();
+        archive.write_all(b"0000001\n").unwrap();
+        let body = archive.finish().unwrap().into_inner();
+
+        let url = serve_once(Box::leak(body.into_boxed_slice()));
+        let file = FileMetadata::new("OFNT3AA1", "Offender Profile", Box::leak(url.into_boxed_str()));
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let staged = temp_dir.path().join("staged-data");
+        let guard = DataDirectoryGuard::new(Some(&staged));
+
+        // Download, decompress and parse all resolve the same directory
+        download_data_file(&file, &data_directory()).unwrap();
+        crate::unzip::unzip_data_file(file.id, file.name).unwrap();
+        let records: Vec<_> = crate::parser::DataParser::new(file.id)
+            .unwrap()
+            .parse()
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        drop(guard);
+
+        assert!(staged.join("OFNT3AA1.zip").exists());
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["CMDORNUM"].as_deref(), Some("0000001"));
+    }
+
+    #[test]
+    fn test_download_and_extract() {
+        let mut archive = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        archive.start_file("STREAM1.des", options).unwrap();
+        archive
+            .write_all(b"CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n")
+            .unwrap();
+        archive.start_file("STREAM1.dat", options).unwrap();
+        archive.write_all(b"0000001\n0000002\n").unwrap();
+        let body: &'static [u8] = Box::leak(archive.finish().unwrap().into_inner().into_boxed_slice());
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+
+        // Discarding the ZIP leaves only the extracted files
+        let file = FileMetadata::new("STREAM1", "Streamed", Box::leak(serve_once(body).into_boxed_str()));
+        let extracted = download_and_extract(&file, data_dir).unwrap();
+        assert_eq!(extracted, data_dir.join("STREAM1"));
+        assert_eq!(fs::read(extracted.join("STREAM1.dat")).unwrap(), b"0000001\n0000002\n");
+        assert!(!data_dir.join("STREAM1.zip").exists());
+
+        let records = crate::parser::DataParser::from_data_dir("STREAM1", data_dir)
+            .unwrap()
+            .parse()
+            .unwrap()
+            .count();
+        assert_eq!(records, 2);
+
+        // Keeping it leaves the archive next to the extracted files
+        fs::remove_dir_all(&extracted).unwrap();
+        let file = FileMetadata::new("STREAM1", "Streamed", Box::leak(serve_once(body).into_boxed_str()));
+        download_and_extract_with(&file, data_dir, true).unwrap();
+        assert!(data_dir.join("STREAM1.zip").exists());
+        assert!(data_dir.join("STREAM1").join("STREAM1.des").exists());
+    }
+
+    #[test]
+    fn test_verify_zip_hash_match_and_mismatch() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+        write_test_zip(&data_dir.join("HASH1.zip"), &[("HASH1.dat", b"0000001\n")]).unwrap();
+        let zip = fs::read(data_dir.join("HASH1.zip")).unwrap();
+        let digest: &'static str = sha256_file(&data_dir.join("HASH1.zip")).unwrap().leak();
+        let wrong: &'static str = "0".repeat(64).leak();
+
+        let unchecked = FileMetadata::new("HASH1", "Hashed", "http://127.0.0.1:9/HASH1.zip");
+        assert!(verify_zip_hash(&unchecked, data_dir).unwrap());
+        assert!(verify_zip_hash(&unchecked.with_sha256(digest), data_dir).unwrap());
+        assert!(!verify_zip_hash(&unchecked.with_sha256(wrong), data_dir).unwrap());
+
+        // A download that does not match is rejected and removed
+        let body: &'static [u8] = zip.leak();
+        let url: &'static str = Box::leak(serve_once(body).into_boxed_str());
+        let file = FileMetadata::new("HASH1", "Hashed", url).with_sha256(wrong);
+        let download_dir = data_dir.join("downloads");
+        let error = download_data_file(&file, &download_dir).unwrap_err();
+        assert!(error.to_string().contains("SHA-256"));
+        assert!(!download_dir.join("HASH1.zip").exists());
+
+        let url: &'static str = Box::leak(serve_once(body).into_boxed_str());
+        let file = FileMetadata::new("HASH1", "Hashed", url).with_sha256(digest);
+        download_data_file(&file, &download_dir).unwrap();
+        assert_eq!(fs::read(download_dir.join("HASH1.zip")).unwrap(), body);
+    }
+
+    #[test]
+    fn test_reference_file_downloads_first() {
+        let files = [
+            FileMetadata::new("APPT7AA1", "Probation/Parole Client", "https://example.com/APPT7AA1.zip"),
+            FileMetadata::new("INMT4AA1", "Inmate Profile", "https://example.com/INMT4AA1.zip"),
+            FileMetadata::new("OFNT3AA1", "Offender Profile", "https://example.com/OFNT3AA1.zip"),
+            FileMetadata::new("OFNT1BA1", "Financial Obligation", "https://example.com/OFNT1BA1.zip"),
+        ];
+        let ids = |files: Vec<FileMetadata>| files.iter().map(|file| file.id).collect::<Vec<_>>();
+
+        assert_eq!(
+            ids(ordered_by_priority(&files, "OFNT3AA1")),
+            vec!["OFNT3AA1", "APPT7AA1", "INMT4AA1", "OFNT1BA1"]
+        );
+        assert_eq!(
+            ids(ordered_by_priority_with(&files, "OFNT3AA1", &["OFNT1BA1", "MISSING"])),
+            vec!["OFNT3AA1", "OFNT1BA1", "APPT7AA1", "INMT4AA1"]
+        );
     }
 
     #[test]
@@ -432,4 +1713,37 @@ mod tests {
         assert!(DB_STRUCTURE_PDF_URL.starts_with("https://"));
         assert!(DB_STRUCTURE_PDF_URL.contains("PublicTables.pdf"));
     }
+
+    #[test]
+    fn test_verify_all_reports_mismatched_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+        let good = FileMetadata::new("TESTGOOD", "Test Good", "https://example.com/TESTGOOD.zip");
+        let bad = FileMetadata::new("TESTBAD", "Test Bad", "https://example.com/TESTBAD.zip");
+        let absent = FileMetadata::new("TESTNONE", "Test None", "https://example.com/TESTNONE.zip");
+
+        let pb = std::sync::Arc::new(ProgressBar::hidden());
+        for file in [&good, &bad] {
+            write_test_zip(
+                &data_dir.join(format!("{}.zip", file.id)),
+                &[
+                    (&format!("{}.des", file.id), b"descriptor"),
+                    (&format!("{}.dat", file.id), b"0000001RECORD"),
+                ],
+            )
+            .unwrap();
+            crate::unzip::decompress_into(file.id, file.name, data_dir, &pb).unwrap();
+        }
+
+        // Same size, different content: only a content check catches this
+        fs::write(data_dir.join("TESTBAD").join("TESTBAD.dat"), b"0000002RECORD").unwrap();
+
+        let results = verify_all(&[good, bad, absent], data_dir);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].status, VerifyStatus::Passed);
+        assert!(results[1].is_failed());
+        assert!(matches!(&results[1].status, VerifyStatus::Failed(reason) if reason.contains("TESTBAD.dat")));
+        assert_eq!(results[2].status, VerifyStatus::Missing);
+    }
 }
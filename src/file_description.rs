@@ -1,9 +1,12 @@
-use anyhow::{Context, Result};
+use crate::files::get_file_by_id;
+use crate::utilities::{extracted_file_path, open_extracted_file, to_snake_case};
+use anyhow::{anyhow, Context, Result};
 use once_cell::sync::Lazy;
 use regex::Regex;
-use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::Path;
 
 /// Represents a field definition from a DES descriptor file.
 ///
@@ -19,6 +22,9 @@ pub struct FieldDefinition {
     pub length: usize,
     /// The human-readable description of the field
     pub description: String,
+    /// Whether `start` counts back from the end of the record instead of
+    /// forward from its beginning (written as a negative start in the DES)
+    pub from_end: bool,
 }
 
 impl FieldDefinition {
@@ -36,9 +42,19 @@ impl FieldDefinition {
             start,
             length,
             description,
+            from_end: false,
         }
     }
 
+    /// Makes the field end-relative.
+    ///
+    /// The field then begins `start` characters before the end of the
+    /// record, so `start == length` selects the last `length` characters.
+    pub fn end_relative(mut self) -> Self {
+        self.from_end = true;
+        self
+    }
+
     /// Returns the end position (inclusive) of this field.
     pub fn end(&self) -> usize {
         self.start + self.length - 1
@@ -48,6 +64,39 @@ impl FieldDefinition {
     pub fn zero_indexed_start(&self) -> usize {
         self.start.saturating_sub(1)
     }
+
+    /// Returns this field's raw slice of a record line.
+    ///
+    /// DES positions count bytes, so the line is sliced as bytes. A
+    /// multibyte character cut by a field boundary is replaced with U+FFFD
+    /// rather than dropping the whole value; a slice that falls on
+    /// character boundaries is borrowed unchanged. Positions past the end
+    /// of the line are clipped, so a short line yields a partial or empty
+    /// slice rather than an error. End-relative fields are resolved
+    /// against the length of `line`.
+    pub fn slice<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let bytes = line.as_bytes();
+        let slice_start = if self.from_end {
+            bytes.len().saturating_sub(self.start)
+        } else {
+            self.zero_indexed_start().min(bytes.len())
+        };
+        let slice_end = (slice_start + self.length).min(bytes.len());
+
+        String::from_utf8_lossy(&bytes[slice_start..slice_end])
+    }
+
+    /// Returns whether this field starts past the end of a record line.
+    ///
+    /// `slice` yields an empty value for such a field, which is
+    /// indistinguishable from a blank one once coerced.
+    pub fn starts_beyond(&self, line: &str) -> bool {
+        if self.from_end {
+            self.start > line.len()
+        } else {
+            self.zero_indexed_start() >= line.len()
+        }
+    }
 }
 
 /// Parses and holds the schema definition for a DES descriptor file.
@@ -62,6 +111,9 @@ impl FieldDefinition {
 /// CPPREFIX      COP COMMITMENT PREFIX              CHAR      8       2
 /// CPPAYSEQ      COP ACCOUNT SEQUENCE NUMBER        CHAR      10      3
 /// ```
+///
+/// A negative start position (e.g., `-8`) makes a field end-relative; see
+/// `FieldDefinition::end_relative`.
 #[derive(Debug, Clone)]
 pub struct FileDescription {
     /// The filename (without extension) of the descriptor
@@ -70,23 +122,60 @@ pub struct FileDescription {
     pub schema: HashMap<String, FieldDefinition>,
 }
 
+/// Field types that appear in NC DAC DES files.
+///
+/// `parse_content` accepts any uppercase type; anything outside this list is
+/// stored as TEXT, so `FileDescription::validate_types` reports it.
+pub const KNOWN_FIELD_TYPES: [&str; 4] = ["CHAR", "DECIMAL", "DATE", "TIME"];
+
+/// A field whose type is not in `KNOWN_FIELD_TYPES`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeIssue {
+    /// The field code with the unknown type
+    pub field_code: String,
+    /// The type as written in the DES file
+    pub field_type: String,
+}
+
+impl std::fmt::Display for TypeIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "field {} has unknown type {} (expected one of {})",
+            self.field_code,
+            self.field_type,
+            KNOWN_FIELD_TYPES.join(", ")
+        )
+    }
+}
+
 /// Regex pattern for parsing DES file lines.
 ///
+/// The line is anchored on its last three columns (type, start, length),
+/// so the separators around the description may be any width. Some DES
+/// files leave a single space between a long description and the type.
+///
 /// Pattern breakdown:
 /// - `^(\S+)` - Field code (non-whitespace characters at start)
-/// - `\s{2,}` - At least 2 spaces separator
-/// - `(.+?)` - Description (non-greedy, captured but not used)
-/// - `\s{2,}` - At least 2 spaces separator
+/// - `\s+` - Whitespace separator
+/// - `(.+?)` - Description (everything up to the last three columns)
+/// - `\s+` - Whitespace separator
 /// - `([A-Z]+)` - Type (uppercase letters: CHAR, DECIMAL, DATE, TIME, etc.)
 /// - `\s+` - One or more spaces
-/// - `(\d+)` - Start position (digits)
+/// - `(-?\d+)` - Start position (digits, negative for end-relative)
 /// - `\s+` - One or more spaces
-/// - `(\d+)` - Length (digits)
+/// - `(\d+)$` - Length (digits), ending the line
 static DES_LINE_REGEX: Lazy<Regex> = Lazy::new(|| {
-    Regex::new(r"^(\S+)\s{2,}(.+?)\s{2,}([A-Z]+)\s+(\d+)\s+(\d+)")
+    Regex::new(r"^(\S+)\s+(.+?)\s+([A-Z]+)\s+(-?\d+)\s+(\d+)$")
         .expect("Invalid DES line regex pattern")
 });
 
+#[cfg(test)]
+thread_local! {
+    /// Number of DES files read on this thread, for tests that check caching
+    pub(crate) static DES_PARSE_COUNT: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
 impl FileDescription {
     /// Creates a new FileDescription by parsing the corresponding DES file.
     ///
@@ -119,41 +208,53 @@ impl FileDescription {
     /// # Ok::<(), anyhow::Error>(())
     /// ```
     pub fn new(filename: &str) -> Result<Self> {
-        let schema = Self::parse(filename)?;
+        Self::from_data_dir(filename, &crate::utilities::data_directory())
+    }
+
+    /// Creates a new FileDescription from a DES file in an explicit data directory.
+    ///
+    /// The DES file is expected at `{data_dir}/{filename}/{filename}.des`.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - The base filename (e.g., "OFNT1BA1")
+    /// * `data_dir` - The data directory containing the extracted files
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DES file cannot be read or parsed.
+    pub fn from_data_dir(filename: &str, data_dir: &Path) -> Result<Self> {
+        let schema = Self::parse(filename, data_dir)?;
         Ok(Self {
             filename: filename.to_string(),
             schema,
         })
     }
 
-    /// Gets the data directory path.
-    ///
-    /// Returns the path to the data directory, which is `./data` relative
-    /// to the project directory.
-    fn get_data_directory() -> PathBuf {
-        // Use the utilities module's data_directory function for consistency
-        crate::utilities::data_directory()
-    }
-
     /// Parses a DES descriptor file and returns the schema.
     ///
     /// # Arguments
     ///
     /// * `filename` - The base filename (without extension or path)
+    /// * `data_dir` - The data directory containing the extracted files
     ///
     /// # Errors
     ///
     /// Returns an error if the file cannot be read or parsed.
-    fn parse(filename: &str) -> Result<HashMap<String, FieldDefinition>> {
-        let data_dir = Self::get_data_directory();
-        let descriptor_path = data_dir.join(filename).join(format!("{filename}.des"));
-
-        let descriptor = fs::read_to_string(&descriptor_path).with_context(|| {
-            format!(
-                "Failed to read DES file: {}",
-                descriptor_path.display()
-            )
-        })?;
+    fn parse(filename: &str, data_dir: &Path) -> Result<HashMap<String, FieldDefinition>> {
+        let descriptor_path = extracted_file_path(data_dir, filename, "des");
+        #[cfg(test)]
+        DES_PARSE_COUNT.with(|count| count.set(count.get() + 1));
+
+        let mut descriptor = String::new();
+        open_extracted_file(data_dir, filename, "des")
+            .and_then(|mut file| file.read_to_string(&mut descriptor))
+            .with_context(|| {
+                format!(
+                    "Failed to read DES file: {}",
+                    descriptor_path.display()
+                )
+            })?;
 
         Self::parse_content(&descriptor)
     }
@@ -169,10 +270,17 @@ impl FileDescription {
     /// # Returns
     ///
     /// A HashMap mapping field codes to their definitions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a start position or length cannot be parsed, or
+    /// if a field code is defined more than once. The duplicate error names
+    /// the code with the line and start position of both definitions.
     pub fn parse_content(content: &str) -> Result<HashMap<String, FieldDefinition>> {
-        let mut schema = HashMap::new();
+        let mut schema: HashMap<String, FieldDefinition> = HashMap::new();
+        let mut defined_on: HashMap<String, usize> = HashMap::new();
 
-        for raw_line in content.lines() {
+        for (index, raw_line) in content.lines().enumerate() {
             // Trim trailing whitespace but preserve leading structure
             let line = raw_line.trim_end();
 
@@ -200,9 +308,13 @@ impl FileDescription {
                     .trim()
                     .to_string();
 
-                let start: usize = captures.get(4)
+                let start_text = captures.get(4)
                     .expect("Start position capture group")
-                    .as_str()
+                    .as_str();
+                let from_end = start_text.starts_with('-');
+
+                let start: usize = start_text
+                    .trim_start_matches('-')
                     .parse()
                     .with_context(|| {
                         format!("Failed to parse start position for field {field_code}")
@@ -216,10 +328,24 @@ impl FileDescription {
                         format!("Failed to parse length for field {field_code}")
                     })?;
 
-                schema.insert(
-                    field_code,
-                    FieldDefinition::new(field_type, start, length, description),
-                );
+                let mut field = FieldDefinition::new(field_type, start, length, description);
+                if from_end {
+                    field = field.end_relative();
+                }
+
+                if let Some(previous) = schema.get(&field_code) {
+                    return Err(anyhow!(
+                        "Field {} is defined twice: line {} (start {}) and line {} (start {})",
+                        field_code,
+                        defined_on[&field_code],
+                        previous.start,
+                        index + 1,
+                        field.start
+                    ));
+                }
+
+                defined_on.insert(field_code.clone(), index + 1);
+                schema.insert(field_code, field);
             }
         }
 
@@ -244,6 +370,122 @@ impl FileDescription {
         self.schema.len()
     }
 
+    /// Returns the length of a record in bytes.
+    ///
+    /// This is the end position of the last absolutely positioned field in
+    /// the schema, or 0 if there is none. End-relative fields are ignored,
+    /// since they float with the record's actual length; this is the width
+    /// used to frame records (`RecordMode::FixedLength`) and to detect short
+    /// or over-long ones. See `expected_record_length` for the minimum
+    /// width that still holds every field.
+    pub fn record_length(&self) -> usize {
+        self.schema
+            .values()
+            .filter(|field| !field.from_end)
+            .map(FieldDefinition::end)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns the minimum length in bytes of a record holding every field.
+    ///
+    /// The same as `record_length` unless an end-relative field reaches
+    /// further back from the record end than that, in which case it is the
+    /// field's reach (its `start`). A record shorter than this cuts off at
+    /// least one field.
+    pub fn expected_record_length(&self) -> usize {
+        self.schema
+            .values()
+            .filter(|field| field.from_end)
+            .map(|field| field.start)
+            .fold(self.record_length(), usize::max)
+    }
+
+    /// Checks that a fixed-width record is long enough for the schema.
+    ///
+    /// Blank lines are accepted, since parsers skip them.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The record, without its terminator
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a non-blank line is shorter than
+    /// `expected_record_length` bytes.
+    pub fn validate_record(&self, line: &str) -> Result<()> {
+        let expected = self.expected_record_length();
+        if !line.trim().is_empty() && line.len() < expected {
+            return Err(anyhow!(
+                "Record is {} bytes, shorter than the {} bytes expected by {}",
+                line.len(),
+                expected,
+                self.filename
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the fields in record order.
+    ///
+    /// DES files list fields by position, so this recovers their DES order:
+    /// absolutely positioned fields by start position, then end-relative
+    /// fields from the furthest to the nearest to the record end. Ties are
+    /// broken by field code.
+    pub fn ordered_fields(&self) -> Vec<(&str, &FieldDefinition)> {
+        let mut fields: Vec<(&str, &FieldDefinition)> = self
+            .schema
+            .iter()
+            .map(|(code, field)| (code.as_str(), field))
+            .collect();
+
+        fields.sort_by(|(a_code, a), (b_code, b)| {
+            a.from_end
+                .cmp(&b.from_end)
+                .then_with(|| {
+                    if a.from_end {
+                        b.start.cmp(&a.start)
+                    } else {
+                        a.start.cmp(&b.start)
+                    }
+                })
+                .then_with(|| a_code.cmp(b_code))
+        });
+
+        fields
+    }
+
+    /// Reports fields whose type is not in `KNOWN_FIELD_TYPES`.
+    ///
+    /// Parsing stays lenient, so a typo such as `CAHR` would otherwise
+    /// silently become a TEXT column. Issues are sorted by field code.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::FileDescription;
+    ///
+    /// let desc = FileDescription::new("OFNT1BA1")?;
+    /// for issue in desc.validate_types() {
+    ///     eprintln!("Warning: {}", issue);
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn validate_types(&self) -> Vec<TypeIssue> {
+        let mut issues: Vec<TypeIssue> = self
+            .schema
+            .iter()
+            .filter(|(_, field)| !KNOWN_FIELD_TYPES.contains(&field.field_type.as_str()))
+            .map(|(code, field)| TypeIssue {
+                field_code: code.clone(),
+                field_type: field.field_type.clone(),
+            })
+            .collect();
+
+        issues.sort_by(|a, b| a.field_code.cmp(&b.field_code));
+        issues
+    }
+
     /// Returns an iterator over all field codes in the schema.
     pub fn field_codes(&self) -> impl Iterator<Item = &String> {
         self.schema.keys()
@@ -259,23 +501,156 @@ impl FileDescription {
     /// # Returns
     ///
     /// An Option containing the trimmed field value, or None if the field doesn't exist
-    /// or if the record is too short.
-    pub fn extract_field<'a>(&self, field_code: &str, record: &'a str) -> Option<&'a str> {
+    /// or if the record is too short. The value is borrowed from `record`
+    /// unless a multibyte character straddles the field boundary (see
+    /// `FieldDefinition::slice`).
+    pub fn extract_field<'a>(&self, field_code: &str, record: &'a str) -> Option<Cow<'a, str>> {
         let field_def = self.schema.get(field_code)?;
-        let start = field_def.zero_indexed_start();
-        let end = start + field_def.length;
 
-        if record.len() < end {
+        let available = if field_def.from_end {
+            record.len() >= field_def.start
+        } else {
+            record.len() >= field_def.zero_indexed_start() + field_def.length
+        };
+        if !available {
             return None;
         }
 
-        Some(record[start..end].trim())
+        Some(match field_def.slice(record) {
+            Cow::Borrowed(value) => Cow::Borrowed(value.trim()),
+            Cow::Owned(value) => Cow::Owned(value.trim().to_string()),
+        })
+    }
+
+    /// Renders the schema as a Rust struct definition.
+    ///
+    /// The struct derives `Debug` and has one field per column in record
+    /// order, named by snake-casing the field code and documented with the
+    /// code and its description. DECIMAL fields are typed `Option<f64>` and
+    /// all other fields `Option<String>`, matching how the parser coerces
+    /// empty values to `None`. Field names that are Rust keywords are
+    /// emitted as raw identifiers, or with a trailing underscore for `self`,
+    /// `Self`, `super` and `crate`, which can't be raw. When two field codes
+    /// snake-case to the same name, the later one gets a numeric suffix
+    /// (`cm_dob_2`), and a code with no letters or digits is named after its
+    /// position (`field_6`).
+    ///
+    /// # Arguments
+    ///
+    /// * `struct_name` - The name of the generated struct
+    pub fn rust_struct(&self, struct_name: &str) -> String {
+        let mut code = format!(
+            "/// A record of {}.\n#[derive(Debug)]\npub struct {} {{\n",
+            self.filename, struct_name
+        );
+
+        let mut field_names = HashSet::new();
+        for (position, (field_code, field)) in self.ordered_fields().into_iter().enumerate() {
+            let mut field_name = rust_field_name(field_code, position + 1);
+            let mut suffix = 2;
+            while !field_names.insert(field_name.clone()) {
+                field_name = rust_field_name(&format!("{}_{}", field_code, suffix), position + 1);
+                suffix += 1;
+            }
+
+            let field_type = if field.field_type == "DECIMAL" {
+                "Option<f64>"
+            } else {
+                "Option<String>"
+            };
+            code.push_str(&format!(
+                "    /// {}: {}\n    pub {}: {},\n",
+                field_code,
+                field.description,
+                field_name,
+                field_type
+            ));
+        }
+
+        code.push_str("}\n");
+        code
+    }
+}
+
+/// Generates a Rust struct definition for a file's records.
+///
+/// Reads `{data_dir}/{file_id}/{file_id}.des` and renders it with
+/// `FileDescription::rust_struct`. The struct is named after the file
+/// (e.g., `OffenderProfile` for OFNT3AA1), or after the file ID if it is not
+/// a known file.
+///
+/// # Arguments
+///
+/// * `file_id` - The file ID (e.g., "OFNT3AA1")
+/// * `data_dir` - The data directory containing the extracted files
+///
+/// # Errors
+///
+/// Returns an error if the DES file cannot be read or parsed.
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::file_description::generate_rust_struct;
+/// use std::path::Path;
+///
+/// let code = generate_rust_struct("OFNT3AA1", Path::new("data"))?;
+/// std::fs::write("src/offender_profile.rs", code)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn generate_rust_struct(file_id: &str, data_dir: &Path) -> Result<String> {
+    let description = FileDescription::from_data_dir(file_id, data_dir)?;
+    let name = get_file_by_id(file_id).map_or(file_id, |file| file.name);
+
+    Ok(description.rust_struct(&to_pascal_case(name)))
+}
+
+/// Converts a name such as "Offender Profile" to PascalCase.
+fn to_pascal_case(name: &str) -> String {
+    to_snake_case(name)
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Converts a field code to a valid Rust field name.
+///
+/// A code with no letters or digits becomes `field_{position}`, its
+/// 1-based position in the record.
+fn rust_field_name(field_code: &str, position: usize) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "dyn", "else", "enum", "extern",
+        "false", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "static", "struct", "trait", "true", "try", "type", "unsafe", "use",
+        "where", "while", "yield",
+    ];
+    // Keywords that can't be raw identifiers
+    const RESERVED: &[&str] = &["crate", "self", "Self", "super"];
+
+    let name = to_snake_case(field_code);
+    if name.is_empty() {
+        format!("field_{}", position)
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else if KEYWORDS.contains(&name.as_str()) {
+        format!("r#{}", name)
+    } else if RESERVED.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else {
+        name
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::write_fixture;
 
     #[test]
     fn test_field_definition_basic() {
@@ -288,6 +663,52 @@ mod tests {
         assert_eq!(field.zero_indexed_start(), 0);
     }
 
+    #[test]
+    fn test_generate_rust_struct() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        write_fixture(
+            temp_dir.path(),
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CMAMOUNT      AMOUNT OWED                        DECIMAL   8       9\n\
+             TYPE          RECORD TYPE                        CHAR      17      1\n",
+            "",
+        );
+
+        let code = generate_rust_struct("OFNT3AA1", temp_dir.path())?;
+
+        assert!(code.contains("#[derive(Debug)]\npub struct OffenderProfile {"));
+        assert!(code.contains("    /// CMDORNUM: OFFENDER NC DOC ID NUMBER\n    pub cmdornum: Option<String>,"));
+        assert!(code.contains("    pub cmamount: Option<f64>,"));
+        assert!(code.contains("    pub r#type: Option<String>,"));
+        assert!(code.find("cmdornum") < code.find("cmamount"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rust_struct_field_names_are_unique_and_valid() {
+        let content = "SELF          SELF REFERENCE                     CHAR      1       1\n\
+                       SELF_         SELF REFERENCE COPY                CHAR      2       1\n\
+                       CM-DOB        BIRTH DATE                         DATE      3      10\n\
+                       CM_DOB        BIRTH DATE COPY                    DATE      13     10\n\
+                       CRATE         CRATE NUMBER                       CHAR      23      1\n\
+                       ###           UNNAMED FILLER                     CHAR      24      1\n";
+        let description = FileDescription {
+            filename: "TEST".to_string(),
+            schema: FileDescription::parse_content(content).unwrap(),
+        };
+
+        let code = description.rust_struct("Test");
+
+        assert!(code.contains("    pub self_: Option<String>,"));
+        assert!(code.contains("    pub self_2: Option<String>,"));
+        assert!(code.contains("    pub cm_dob: Option<String>,"));
+        assert!(code.contains("    pub cm_dob_2: Option<String>,"));
+        assert!(code.contains("    pub crate_: Option<String>,"));
+        assert!(code.contains("    /// ###: UNNAMED FILLER\n    pub field_6: Option<String>,"));
+    }
+
     #[test]
     fn test_field_definition_end_calculation() {
         let field = FieldDefinition::new("CHAR".to_string(), 8, 2, "Another description".to_string());
@@ -334,6 +755,29 @@ TMOFUPDT      TIME OF LAST UPDATE                TIME      232     8     "#;
         assert_eq!(schema.get("TMOFUPDT").unwrap().field_type, "TIME");
     }
 
+    #[test]
+    fn test_validate_types_flags_unknown_type() {
+        let content = r#"CMDORNUM      OFFENDER NC DOC ID NUMBER          CAHR      1       7
+CPCOPBAL      COP BALANCE                        DECIMAL   8       11
+DTOFUPDT      DATE OF LAST UPDATE                DATE      19      10     "#;
+
+        let desc = FileDescription {
+            filename: "TEST".to_string(),
+            schema: FileDescription::parse_content(content).unwrap(),
+        };
+
+        let issues = desc.validate_types();
+
+        assert_eq!(
+            issues,
+            vec![TypeIssue {
+                field_code: "CMDORNUM".to_string(),
+                field_type: "CAHR".to_string(),
+            }]
+        );
+        assert!(issues[0].to_string().contains("CAHR"));
+    }
+
     #[test]
     fn test_parse_content_skips_empty_lines() {
         let content = r#"
@@ -359,6 +803,36 @@ Another invalid line without proper format
         assert_eq!(schema.len(), 2);
     }
 
+    #[test]
+    fn test_parse_content_single_space_before_type() {
+        let content = "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                       CPSNTCMP      SENTENCE COMPONENT DESCRIPTION FOR THE COMMITMENT CHAR      8       40\n\
+                       CPPREFIX COP COMMITMENT PREFIX CHAR 48 2\n";
+
+        let schema = FileDescription::parse_content(content).unwrap();
+        assert_eq!(schema.len(), 3);
+
+        let component = &schema["CPSNTCMP"];
+        assert_eq!(component.description, "SENTENCE COMPONENT DESCRIPTION FOR THE COMMITMENT");
+        assert_eq!(component.field_type, "CHAR");
+        assert_eq!((component.start, component.length), (8, 40));
+        assert_eq!(schema["CPPREFIX"].description, "COP COMMITMENT PREFIX");
+    }
+
+    #[test]
+    fn test_parse_content_rejects_duplicate_field_code() {
+        let content = r#"CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7
+CPPREFIX      COP COMMITMENT PREFIX              CHAR      8       2
+CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      10      7
+"#;
+
+        let error = FileDescription::parse_content(content).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Field CMDORNUM is defined twice: line 1 (start 1) and line 3 (start 10)"
+        );
+    }
+
     #[test]
     fn test_get_field() {
         let content = r#"CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7
@@ -401,9 +875,9 @@ CPPAYSEQ      COP ACCOUNT SEQUENCE NUMBER        CHAR      10      3     "#;
 
         let record = "1234567AB123more data here";
 
-        assert_eq!(desc.extract_field("CMDORNUM", record), Some("1234567"));
-        assert_eq!(desc.extract_field("CPPREFIX", record), Some("AB"));
-        assert_eq!(desc.extract_field("CPPAYSEQ", record), Some("123"));
+        assert_eq!(desc.extract_field("CMDORNUM", record).as_deref(), Some("1234567"));
+        assert_eq!(desc.extract_field("CPPREFIX", record).as_deref(), Some("AB"));
+        assert_eq!(desc.extract_field("CPPAYSEQ", record).as_deref(), Some("123"));
     }
 
     #[test]
@@ -417,7 +891,7 @@ CPPAYSEQ      COP ACCOUNT SEQUENCE NUMBER        CHAR      10      3     "#;
 
         // Field value with trailing spaces
         let record = "123    more data";
-        assert_eq!(desc.extract_field("CMDORNUM", record), Some("123"));
+        assert_eq!(desc.extract_field("CMDORNUM", record).as_deref(), Some("123"));
     }
 
     #[test]
@@ -445,4 +919,25 @@ CPPAYSEQ      COP ACCOUNT SEQUENCE NUMBER        CHAR      10      3     "#;
         let record = "1234567";
         assert_eq!(desc.extract_field("NONEXISTENT", record), None);
     }
+
+    #[test]
+    fn test_validate_record_against_expected_length() {
+        let content = "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                       CASENUM       CASE NUMBER                        CHAR      8       3\n\
+                       TRAILER       TRAILER CODE                       CHAR    -12       2\n";
+
+        let desc = FileDescription {
+            filename: "test".to_string(),
+            schema: FileDescription::parse_content(content).unwrap(),
+        };
+
+        // The end-relative field reaches 12 bytes back, past CASENUM's end
+        assert_eq!(desc.record_length(), 10);
+        assert_eq!(desc.expected_record_length(), 12);
+        assert!(desc.validate_record("0000001A01XY").is_ok());
+        assert!(desc.validate_record("   ").is_ok());
+
+        let error = desc.validate_record("0000001A01").unwrap_err();
+        assert_eq!(error.to_string(), "Record is 10 bytes, shorter than the 12 bytes expected by test");
+    }
 }
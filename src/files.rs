@@ -3,6 +3,14 @@
 //! This module provides metadata for the 12 NC DAC file types and a lookup function
 //! to retrieve file information by ID.
 
+use crate::utilities::extracted_file_path;
+use anyhow::{anyhow, Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
 /// Metadata for a NC DAC file type.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct FileMetadata {
@@ -12,6 +20,10 @@ pub struct FileMetadata {
     pub name: &'static str,
     /// Download URL for the ZIP file
     pub download_url: &'static str,
+    /// Expected hex-encoded SHA-256 of the ZIP file, when known
+    pub sha256: Option<&'static str>,
+    /// Expected hex-encoded SHA-256 of the extracted .dat file, when known
+    pub dat_sha256: Option<&'static str>,
 }
 
 impl FileMetadata {
@@ -39,8 +51,51 @@ impl FileMetadata {
             id,
             name,
             download_url,
+            sha256: None,
+            dat_sha256: None,
         }
     }
+
+    /// Sets the expected SHA-256 of the ZIP file.
+    ///
+    /// Downloads of a file with a known digest are verified against it
+    /// (see `download::verify_zip_hash`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ncdac_opi_parser::files::FileMetadata;
+    ///
+    /// let file = FileMetadata::new("OFNT3AA1", "Offender Profile", "https://example.com/OFNT3AA1.zip")
+    ///     .with_sha256("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08");
+    /// assert!(file.sha256.is_some());
+    /// ```
+    #[must_use]
+    pub const fn with_sha256(mut self, sha256: &'static str) -> Self {
+        self.sha256 = Some(sha256);
+        self
+    }
+
+    /// Sets the expected SHA-256 of the extracted .dat file.
+    ///
+    /// When the ZIP archive has been deleted, extracted files are checked
+    /// against this digest instead of the archive's entry sizes (see
+    /// `download::are_decompressed_files_valid`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ncdac_opi_parser::files::FileMetadata;
+    ///
+    /// let file = FileMetadata::new("OFNT3AA1", "Offender Profile", "https://example.com/OFNT3AA1.zip")
+    ///     .with_dat_sha256("9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08");
+    /// assert!(file.dat_sha256.is_some());
+    /// ```
+    #[must_use]
+    pub const fn with_dat_sha256(mut self, dat_sha256: &'static str) -> Self {
+        self.dat_sha256 = Some(dat_sha256);
+        self
+    }
 }
 
 /// Static array containing all NC DAC file metadata.
@@ -136,15 +191,193 @@ pub fn get_file_by_id(id: &str) -> Option<&'static FileMetadata> {
     FILES.iter().find(|file| file.id == id)
 }
 
+/// Returns the IDs of all known files, in `FILES` order.
+///
+/// # Examples
+///
+/// ```
+/// use ncdac_opi_parser::files::all_ids;
+///
+/// assert!(all_ids().contains(&"OFNT3AA1"));
+/// ```
+#[must_use]
+pub fn all_ids() -> Vec<&'static str> {
+    FILES.iter().map(|file| file.id).collect()
+}
+
+/// Returns the names of all known files, in `FILES` order.
+///
+/// # Examples
+///
+/// ```
+/// use ncdac_opi_parser::files::all_names;
+///
+/// assert!(all_names().contains(&"Offender Profile"));
+/// ```
+#[must_use]
+pub fn all_names() -> Vec<&'static str> {
+    FILES.iter().map(|file| file.name).collect()
+}
+
+/// Returns `(id, name)` pairs for all known files, in `FILES` order.
+///
+/// # Examples
+///
+/// ```
+/// use ncdac_opi_parser::files::id_name_pairs;
+///
+/// for (id, name) in id_name_pairs() {
+///     println!("{} - {}", id, name);
+/// }
+/// ```
+#[must_use]
+pub fn id_name_pairs() -> Vec<(&'static str, &'static str)> {
+    FILES.iter().map(|file| (file.id, file.name)).collect()
+}
+
+/// Reads a newline-separated list of file IDs.
+///
+/// Used to pin the exact set of files a run processes. Surrounding
+/// whitespace and blank lines are ignored, and repeated IDs are kept once,
+/// in first-seen order.
+///
+/// # Arguments
+///
+/// * `reader` - Source of the list, one file ID per line
+///
+/// # Errors
+///
+/// Returns an error if the list cannot be read or names an ID that is not
+/// in `FILES`.
+///
+/// # Examples
+///
+/// ```
+/// use ncdac_opi_parser::files::parse_file_list;
+///
+/// let files = parse_file_list("OFNT3AA1\nOFNT1BA1\n".as_bytes()).unwrap();
+/// assert_eq!(files.len(), 2);
+/// ```
+pub fn parse_file_list<R: BufRead>(reader: R) -> Result<Vec<&'static FileMetadata>> {
+    let mut files: Vec<&'static FileMetadata> = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.context("Failed to read file list")?;
+        let id = line.trim();
+        if id.is_empty() {
+            continue;
+        }
+
+        let file = get_file_by_id(id)
+            .ok_or_else(|| anyhow!("Unknown file id on line {} of file list: {}", index + 1, id))?;
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Gzips a file's extracted `.des` and `.dat` files in place.
+///
+/// Each file is compressed to `{name}.gz` alongside it and the original is
+/// removed, so data kept after a run (`--keep-data`) takes far less disk.
+/// The parser reads the compressed files transparently (see
+/// `utilities::open_extracted_file`). Files that are missing, such as ones
+/// already compressed, are skipped.
+///
+/// # Arguments
+///
+/// * `file_id` - The file ID (e.g., "OFNT3AA1")
+/// * `data_dir` - The data directory containing the extracted files
+///
+/// # Returns
+///
+/// The paths of the `.gz` files written.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read, compressed or removed. A
+/// partially written `.gz` is never left under its final name.
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::files::recompress_extracted;
+/// use std::path::Path;
+///
+/// let written = recompress_extracted("OFNT1BA1", Path::new("./data"))?;
+/// println!("Compressed {} files", written.len());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn recompress_extracted(file_id: &str, data_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+
+    for extension in ["des", "dat"] {
+        let path = extracted_file_path(data_dir, file_id, extension);
+        if !path.exists() {
+            continue;
+        }
+
+        let mut gz_path = path.clone().into_os_string();
+        gz_path.push(".gz");
+        let gz_path = PathBuf::from(gz_path);
+        let mut partial_path = gz_path.clone().into_os_string();
+        partial_path.push(".part");
+        let partial_path = PathBuf::from(partial_path);
+
+        let compress = || -> Result<()> {
+            let mut input = File::open(&path)?;
+            let output = BufWriter::new(File::create(&partial_path)?);
+            let mut encoder = GzEncoder::new(output, Compression::default());
+            std::io::copy(&mut input, &mut encoder)?;
+            encoder.finish()?.flush()?;
+            std::fs::rename(&partial_path, &gz_path)?;
+            Ok(())
+        };
+        if let Err(e) = compress() {
+            let _ = std::fs::remove_file(&partial_path);
+            return Err(e.context(format!("Failed to compress {}", path.display())));
+        }
+
+        std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        written.push(gz_path);
+    }
+
+    Ok(written)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::write_fixture;
 
     #[test]
     fn test_files_array_length() {
         assert_eq!(FILES.len(), 12);
     }
 
+    #[test]
+    fn test_id_and_name_helpers() {
+        assert_eq!(all_ids().len(), 12);
+        assert_eq!(all_names().len(), 12);
+        assert_eq!(id_name_pairs().len(), 12);
+
+        assert!(all_ids().contains(&"OFNT1BA1"));
+        assert!(all_names().contains(&"Financial Obligation"));
+        assert!(id_name_pairs().contains(&("OFNT3AA1", "Offender Profile")));
+    }
+
+    #[test]
+    fn test_parse_file_list() {
+        let files = parse_file_list("OFNT1BA1\n\n  INMT4AA1  \nOFNT1BA1\n".as_bytes()).unwrap();
+        let ids: Vec<&str> = files.iter().map(|file| file.id).collect();
+        assert_eq!(ids, vec!["OFNT1BA1", "INMT4AA1"]);
+
+        let error = parse_file_list("OFNT1BA1\nNOPE1234\n".as_bytes()).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown file id on line 2 of file list: NOPE1234");
+    }
+
     #[test]
     fn test_get_file_by_id_found() {
         let file = get_file_by_id("OFNT3AA1");
@@ -222,8 +455,33 @@ mod tests {
     fn test_all_files_have_download_urls() {
         for file in &FILES {
             assert!(file.download_url.starts_with("https://"));
-            assert!(file.download_url.contains(&file.id));
+            assert!(file.download_url.contains(file.id));
             assert!(file.download_url.ends_with(".zip"));
         }
     }
+
+    #[test]
+    fn test_recompress_extracted_is_readable_by_parser() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let file_dir = write_fixture(
+            temp_dir.path(),
+            "OFNT1BA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CASENUM       CASE NUMBER                        CHAR      8       3\n",
+            "0000001A01\n0000002B02\n",
+        );
+
+        let written = recompress_extracted("OFNT1BA1", temp_dir.path())?;
+        assert_eq!(written, vec![file_dir.join("OFNT1BA1.des.gz"), file_dir.join("OFNT1BA1.dat.gz")]);
+        assert!(!file_dir.join("OFNT1BA1.des").exists());
+        assert!(!file_dir.join("OFNT1BA1.dat").exists());
+        assert!(recompress_extracted("OFNT1BA1", temp_dir.path())?.is_empty());
+
+        let parser = crate::parser::DataParser::from_data_dir("OFNT1BA1", temp_dir.path())?;
+        let records: Vec<_> = parser.parse()?.collect::<Result<_>>()?;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1]["CASENUM"].as_deref(), Some("B02"));
+
+        Ok(())
+    }
 }
@@ -5,14 +5,19 @@
 
 pub mod concurrency;
 pub mod data_handler;
+pub mod decisions;
 pub mod download;
 pub mod file_description;
 pub mod files;
 pub mod parser;
+pub mod pipeline;
 pub mod unzip;
 pub mod utilities;
 
-pub use concurrency::{create_worker_handler, ErrorAggregator, set_pragma_synchronous_full, set_pragma_synchronous_normal};
-pub use data_handler::{DataHandler, ErrorDetails, ProcessingResults};
-pub use file_description::{FieldDefinition, FileDescription};
-pub use parser::{DataParser, RecordIterator};
+#[cfg(test)]
+mod test_support;
+
+pub use concurrency::{create_worker_handler, ErrorAggregator, WorkerConfig, set_pragma_synchronous_full, set_pragma_synchronous_normal};
+pub use data_handler::{CountCheck, DatabaseOptions, DataHandler, DecimalStorage, ErrorDetails, ErrorKind, FileFailure, IndexSuggestion, IntegrityReport, JournalState, MergeReport, MergedTable, NumericStats, ProcessingResults, ReferenceKeySet, ReferenceKeyStore, Relationship, RowDeduplication, Severity, TableCollision, ValidationRule};
+pub use file_description::{FieldDefinition, FileDescription, TypeIssue};
+pub use parser::{CoercionExplanation, CoercionRule, ControlCharPolicy, DataParser, FieldCoercion, FieldOverrun, KeyCounts, RecordCoercion, RecordIterator, RecordMode, SetDiff, TrailingBytes, TruncatedRecord};
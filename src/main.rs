@@ -5,24 +5,22 @@
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use dialoguer::{theme::ColorfulTheme, Confirm, MultiSelect, Select};
-use indicatif::{ProgressBar, ProgressStyle};
 use ncdac_opi_parser::{
-    concurrency::{create_worker_handler, DesFailureAggregator, ErrorAggregator},
-    data_handler::DataHandler,
+    concurrency::WorkerConfig,
+    decisions::{Decisions, RecordingDecisions, ReplayDecisions, TerminalDecisions},
     download::{
-        are_decompressed_files_valid, categorize_files, download_data_file, get_data_dir,
-        get_file_status, FileStatus,
+        categorize_files_before, download_data_file_before, verify_all, VerifyStatus,
+    },
+    files::{get_file_by_id, parse_file_list, FileMetadata, FILES},
+    pipeline::{self, CleanupPolicy, DownloadPolicy, PipelineConfig},
+    utilities::{
+        clean_data_directory_in, create_spinner, data_directory, format_count, format_duration,
+        set_data_directory,
     },
-    files::{get_file_by_id, FILES},
-    unzip::{calculate_total_uncompressed_bytes, decompress_with_shared_progress},
-    utilities::{count_lines, delete_data_subdirectory, format_count, format_duration},
 };
-use rayon::prelude::*;
-use std::io::{self, Write};
-use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
 
 /// NC DAC Offender Public Information Parser
 ///
@@ -35,8 +33,13 @@ use std::time::SystemTime;
 #[command(version)]
 struct Cli {
     /// Output SQLite database file path
-    #[arg(short, long)]
-    output: PathBuf,
+    #[arg(short, long, required_unless_present_any = ["clean", "verify_only"])]
+    output: Option<PathBuf>,
+
+    /// Directory to download, extract and read data files in
+    /// [default: ./data]
+    #[arg(long, value_name = "PATH")]
+    data_dir: Option<PathBuf>,
 
     /// Reference file ID to use as foreign key source
     #[arg(short, long, default_value = "OFNT3AA1")]
@@ -45,66 +48,185 @@ struct Cli {
     /// Keep data files after processing
     #[arg(long)]
     keep_data: bool,
-}
 
-/// Creates a spinner with the ora-compatible "bouncingBar" style
-fn create_spinner(message: &str) -> ProgressBar {
-    let spinner = ProgressBar::new_spinner();
-    spinner.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.cyan} {msg}")
-            .expect("Invalid template")
-            .tick_strings(&["[    ]", "[=   ]", "[==  ]", "[=== ]", "[ ===]", "[  ==]", "[   =]", "[    ]", "[   =]", "[  ==]", "[ ===]", "[====]"]),
-    );
-    spinner.set_message(message.to_string());
-    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
-    spinner
+    /// Gzip the kept data files after processing (requires --keep-data)
+    #[arg(long, requires = "keep_data")]
+    compress_data: bool,
+
+    /// Remove extracted data (keeping ZIP files and the PDF) and exit
+    #[arg(long)]
+    clean: bool,
+
+    /// Check the integrity of downloaded and extracted files against their
+    /// ZIP archives, report each file, and exit without processing
+    #[arg(long)]
+    verify_only: bool,
+
+    /// Exit with an error if foreign key violations occur
+    #[arg(long)]
+    strict: bool,
+
+    /// Number of violations tolerated by --strict
+    #[arg(long, default_value_t = 0, requires = "strict")]
+    max_violations: usize,
+
+    /// Abort the run after this many seconds, keeping fully loaded files
+    #[arg(long, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// Process only the file IDs listed in this file, one per line
+    /// (the reference file is always included)
+    #[arg(long, value_name = "PATH")]
+    file_list: Option<PathBuf>,
+
+    /// Use only locally staged data: skip all server checks and downloads,
+    /// failing if a required file is not present
+    #[arg(long)]
+    offline: bool,
+
+    /// Answer prompts from a file of recorded decisions instead of asking
+    #[arg(long, value_name = "PATH")]
+    decisions: Option<PathBuf>,
+
+    /// Record the answers given to prompts in this file for later replay
+    #[arg(long, value_name = "PATH")]
+    record_decisions: Option<PathBuf>,
 }
 
 /// Prompt user to confirm or select a reference file
-fn confirm_reference_file(default_reference: &str) -> Result<String> {
+fn confirm_reference_file(decisions: &mut dyn Decisions, default_reference: &str) -> Result<String> {
     let default_file = get_file_by_id(default_reference)
         .ok_or_else(|| anyhow::anyhow!("Invalid default reference file: {}", default_reference))?;
 
-    let use_default = Confirm::with_theme(&ColorfulTheme::default())
-        .with_prompt(format!(
+    let use_default = decisions.confirm(
+        "use-default-reference",
+        &format!(
             "Use '{}' ({}) as reference file?",
             default_file.name, default_file.id
-        ))
-        .default(true)
-        .interact()?;
+        ),
+        true,
+    )?;
 
     if use_default {
         return Ok(default_reference.to_string());
     }
 
-    let items: Vec<String> = FILES
+    let items: Vec<(String, String)> = FILES
         .iter()
         .map(|f| {
-            if f.id == default_reference {
+            let label = if f.id == default_reference {
                 format!("{} ({}) - default", f.name, f.id)
             } else {
                 format!("{} ({})", f.name, f.id)
-            }
+            };
+            (f.id.to_string(), label)
         })
         .collect();
 
-    let selection = Select::with_theme(&ColorfulTheme::default())
-        .with_prompt("Select reference file")
-        .items(&items)
-        .default(0)
-        .interact()?;
+    let selection = decisions.select("reference-file", "Select reference file", &items, 0)?;
 
     Ok(FILES[selection].id.to_string())
 }
 
+/// Builds the prompt answer provider selected by `--decisions` and `--record-decisions`.
+fn decisions_provider(args: &Cli) -> Result<Box<dyn Decisions>> {
+    let decisions: Box<dyn Decisions> = match &args.decisions {
+        Some(path) => Box::new(ReplayDecisions::from_file(path)?),
+        None => Box::new(TerminalDecisions),
+    };
+
+    match &args.record_decisions {
+        Some(path) => {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("Failed to create decisions file: {}", path.display()))?;
+            Ok(Box::new(RecordingDecisions::new(decisions, file)))
+        }
+        None => Ok(decisions),
+    }
+}
+
+/// Applies `--data-dir`, returning the data directory for the run.
+///
+/// The override is set process-wide so library defaults agree with the
+/// paths passed explicitly below.
+fn data_dir(args: &Cli) -> PathBuf {
+    if let Some(path) = &args.data_dir {
+        set_data_directory(Some(path));
+    }
+    data_directory()
+}
+
+/// Reads the file IDs listed in a `--file-list` file.
+fn read_file_list(path: &Path) -> Result<Vec<&'static FileMetadata>> {
+    let reader = io::BufReader::new(
+        std::fs::File::open(path)
+            .with_context(|| format!("Failed to open file list: {}", path.display()))?,
+    );
+    parse_file_list(reader)
+}
+
+/// Verifies local files for `--verify-only`, printing one line per file.
+///
+/// Returns `false` if any present file failed verification.
+fn verify_only(files: &[FileMetadata], data_dir: &Path) -> bool {
+    println!("🔍 Verifying files in {}", data_dir.display());
+
+    let results = verify_all(files, data_dir);
+    for result in &results {
+        match &result.status {
+            VerifyStatus::Passed => println!("  ✓ {} passed", result.file_id),
+            VerifyStatus::Failed(reason) => println!("  ✗ {} failed: {}", result.file_id, reason),
+            VerifyStatus::Unverifiable => {
+                println!("  ? {} extracted without its ZIP, cannot verify", result.file_id)
+            }
+            VerifyStatus::Missing => println!("  - {} not present", result.file_id),
+        }
+    }
+
+    let failed = results.iter().filter(|result| result.is_failed()).count();
+    if failed > 0 {
+        eprintln!("\n❌ {} of {} files failed verification", failed, results.len());
+        false
+    } else {
+        println!("\n✅ No verification failures");
+        true
+    }
+}
+
 /// Main application entry point
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Cli::parse();
     let epoch = SystemTime::now();
+    let deadline = args
+        .timeout
+        .map(|seconds| Instant::now() + Duration::from_secs(seconds));
+
+    let data_dir = data_dir(&args);
+
+    if args.clean {
+        clean_data_directory_in(&data_dir, true)
+            .context("Failed to clean data directory")?;
+        println!("✓ Removed extracted data from {}", data_dir.display());
+        return Ok(());
+    }
+
+    if args.verify_only {
+        let files = match &args.file_list {
+            Some(path) => read_file_list(path)?.into_iter().copied().collect(),
+            None => FILES.to_vec(),
+        };
+        if !verify_only(&files, &data_dir) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let output = args.output.clone().context("An output path is required")?;
+
+    let mut decisions = decisions_provider(&args)?;
 
-    let reference_id = confirm_reference_file(&args.reference)?;
+    let reference_id = confirm_reference_file(decisions.as_mut(), &args.reference)?;
     println!();
 
     let reference_file = get_file_by_id(&reference_id);
@@ -118,21 +240,44 @@ async fn main() -> Result<()> {
     }
     let reference_file = reference_file.unwrap();
 
-    match handle_downloads(reference_file) {
-        Ok(downloaded) => {
-            if downloaded {
-                println!();
+    let file_list = match &args.file_list {
+        Some(path) => Some(read_file_list(path)?),
+        None => None,
+    };
+
+    let mut config = PipelineConfig {
+        output,
+        reference_id: reference_id.clone(),
+        data_dir: data_dir.clone(),
+        keep_data: args.keep_data,
+        compress_kept_data: args.compress_data,
+        download: if args.offline { DownloadPolicy::Offline } else { DownloadPolicy::Skip },
+        show_progress: true,
+        cleanup: CleanupPolicy::default(),
+        timeout: None,
+        files: file_list,
+        worker: WorkerConfig::default(),
+    };
+
+    if !args.offline {
+        match handle_downloads(decisions.as_mut(), reference_file, &config.selected_files(), &data_dir, deadline) {
+            Ok(downloaded) => {
+                if downloaded {
+                    println!();
+                }
+            }
+            Err(e) => {
+                eprintln!("❌ Download failed");
+                eprintln!("Error: {:#}", e);
+                std::process::exit(1);
             }
-        }
-        Err(e) => {
-            eprintln!("❌ Download failed");
-            eprintln!("Error: {:#}", e);
-            std::process::exit(1);
         }
     }
 
-    let data_handler = match run(&args, reference_file).await {
-        Ok(handler) => handler,
+    config.timeout = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+    let (mut data_handler, report) = match pipeline::run_with_report(config).await {
+        Ok(result) => result,
         Err(e) => {
             eprintln!("❌ Processing failed");
             eprintln!("Error: {:#}", e);
@@ -143,23 +288,31 @@ async fn main() -> Result<()> {
     let total_duration = format_duration(epoch, None)
         .context("Failed to calculate total duration")?;
     println!("✅ Processing complete in {}", total_duration);
+    println!(
+        "   {} records from {} files written to {}",
+        format_count(report.total_records as usize),
+        report.files_processed.len(),
+        report.database_path.display()
+    );
 
     if let Some(des_failures_report) = data_handler.report_des_file_failures() {
         eprintln!("\n{}", des_failures_report);
     }
 
-    if !data_handler.errors.is_empty() {
-        print!(
-            "\n⚠️  {} errors encountered while processing. View them? (y/N): ",
-            data_handler.errors.len()
-        );
-        io::stdout().flush()?;
+    if let Some(failures_report) = data_handler.report_file_failures() {
+        eprintln!("\n{}", failures_report);
+    }
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+    if report.total_errors() > 0 {
+        let answer = decisions.input(
+            "view-errors",
+            &format!(
+                "⚠️  {} errors encountered while processing. View them? (y/N)",
+                report.total_errors()
+            ),
+        )?;
 
-        let answer = input.trim();
-        if answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes") {
+        if answer == "y" || answer == "yes" {
             for (index, error_details) in data_handler.errors.iter().enumerate() {
                 println!(
                     "\n[{}/{}] {}",
@@ -171,6 +324,13 @@ async fn main() -> Result<()> {
         }
     }
 
+    data_handler.set_strict(args.strict);
+    data_handler.set_max_violations(args.max_violations);
+    if let Err(e) = data_handler.check_strict() {
+        eprintln!("\n❌ {:#}", e);
+        std::process::exit(1);
+    }
+
     Ok(())
 }
 
@@ -179,40 +339,36 @@ async fn main() -> Result<()> {
 /// For reference files: prompts to retry or quit on failure
 /// For other files: prompts to retry or skip on failure
 fn download_with_retry(
+    decisions: &mut dyn Decisions,
     file: &ncdac_opi_parser::files::FileMetadata,
-    data_dir: &std::path::Path,
+    data_dir: &Path,
     is_reference: bool,
+    deadline: Option<Instant>,
 ) -> Result<bool> {
     loop {
-        match download_data_file(file, data_dir) {
+        match download_data_file_before(file, data_dir, deadline) {
             Ok(_) => return Ok(true),
             Err(e) => {
                 eprintln!("\n❌ Failed to download {}: {:#}", file.id, e);
 
-                if is_reference {
+                let choice = if is_reference {
                     println!("\nThe reference file is required to proceed.");
                     println!("  [r] Retry download");
                     println!("  [q] Quit");
-                    print!("\nYour choice (r/q): ");
+                    decisions.input("download-failed", "Your choice (r/q)")?
                 } else {
                     println!("\n  [r] Retry download");
                     println!("  [s] Skip this file");
-                    print!("\nYour choice (r/s): ");
-                }
-
-                io::stdout().flush()?;
-
-                let mut input = String::new();
-                io::stdin().read_line(&mut input)?;
-                let choice = input.trim().to_lowercase();
+                    decisions.input("download-failed", "Your choice (r/s)")?
+                };
 
                 match choice.as_str() {
-                    "r" => continue,
-                    "q" if is_reference => {
+                    "r" | "retry" => continue,
+                    "q" | "quit" if is_reference => {
                         eprintln!("Cannot proceed without reference file. Exiting.");
                         std::process::exit(1);
                     }
-                    "s" if !is_reference => return Ok(false),
+                    "s" | "skip" if !is_reference => return Ok(false),
                     _ => {
                         if is_reference {
                             eprintln!("Invalid choice. Please choose 'r' to retry or 'q' to quit.");
@@ -229,11 +385,15 @@ fn download_with_retry(
 /// Handle file downloads based on CLI arguments and missing files.
 ///
 /// Returns `true` if downloads were performed, `false` otherwise.
-fn handle_downloads(reference_file: &ncdac_opi_parser::files::FileMetadata) -> Result<bool> {
-    let data_dir = get_data_dir();
-
+fn handle_downloads(
+    decisions: &mut dyn Decisions,
+    reference_file: &FileMetadata,
+    files: &[FileMetadata],
+    data_dir: &Path,
+    deadline: Option<Instant>,
+) -> Result<bool> {
     let spinner = create_spinner("Checking for available data files...");
-    let file_status = categorize_files(&FILES, &data_dir);
+    let file_status = categorize_files_before(files, data_dir, deadline);
     spinner.finish_and_clear();
 
     if !file_status.unverifiable.is_empty() {
@@ -247,18 +407,13 @@ fn handle_downloads(reference_file: &ncdac_opi_parser::files::FileMetadata) -> R
         println!("\nWould you like to:");
         println!("  [d] Download ZIP files to verify data integrity");
         println!("  [c] Continue without verification (default)");
-        print!("\nYour choice (d/c) [c]: ");
-        io::stdout().flush()?;
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        let choice = input.trim().to_lowercase();
+        let choice = decisions.input("verify-unverifiable", "Your choice (d/c) [c]")?;
 
-        if choice == "d" {
+        if choice == "d" || choice == "download" {
             println!("\n📥 Downloading ZIP files for verification...\n");
             for file_id in &file_status.unverifiable {
                 let file = get_file_by_id(file_id).unwrap();
-                download_with_retry(file, &data_dir, false)?;
+                download_with_retry(decisions, file, data_dir, false, deadline)?;
             }
         } else {
             println!("Continuing without verification.");
@@ -276,17 +431,12 @@ fn handle_downloads(reference_file: &ncdac_opi_parser::files::FileMetadata) -> R
             println!("\nThis file must be downloaded to proceed.");
             println!("  [d] Download now");
             println!("  [q] Quit");
-            print!("\nYour choice (d/q): ");
-            io::stdout().flush()?;
-
-            let mut input = String::new();
-            io::stdin().read_line(&mut input)?;
-            let choice = input.trim().to_lowercase();
+            let choice = decisions.input("download-reference", "Your choice (d/q)")?;
 
             match choice.as_str() {
-                "d" => {
+                "d" | "download" => {
                     println!("\n📥 Downloading {}...\n", reference_file.name);
-                    download_with_retry(reference_file, &data_dir, true)?;
+                    download_with_retry(decisions, reference_file, data_dir, true, deadline)?;
                 }
                 _ => {
                     eprintln!("Cannot proceed without reference file. Exiting.");
@@ -331,19 +481,14 @@ fn handle_downloads(reference_file: &ncdac_opi_parser::files::FileMetadata) -> R
             println!("  [a] Download all (default)");
             println!("  [s] Skip all");
             println!("  [c] Choose which files to download");
-            print!("\nYour choice (a/s/c) [a]: ");
-            io::stdout().flush()?;
-
-            let mut input = String::new();
-            io::stdin().read_line(&mut input)?;
-            let choice = input.trim().to_lowercase();
+            let choice = decisions.input("download-missing", "Your choice (a/s/c) [a]")?;
 
             match choice.as_str() {
-                "s" => {
+                "s" | "skip" => {
                     println!("Skipping optional file downloads.");
                 }
-                "c" => {
-                    let options: Vec<String> = other_problematic
+                "c" | "choose" => {
+                    let options: Vec<(String, String)> = other_problematic
                         .iter()
                         .map(|id| {
                             let file = get_file_by_id(id).unwrap();
@@ -352,21 +497,22 @@ fn handle_downloads(reference_file: &ncdac_opi_parser::files::FileMetadata) -> R
                             } else {
                                 "missing"
                             };
-                            format!("{} ({}) [{}]", file.id, file.name, status)
+                            (file.id.to_string(), format!("{} ({}) [{}]", file.id, file.name, status))
                         })
                         .collect();
 
-                    let selections = MultiSelect::with_theme(&ColorfulTheme::default())
-                        .with_prompt("Select files to download (use Space to select, Enter to confirm)")
-                        .items(&options)
-                        .interact()?;
+                    let selections = decisions.select_many(
+                        "download-selection",
+                        "Select files to download (use Space to select, Enter to confirm)",
+                        &options,
+                    )?;
 
                     if !selections.is_empty() {
                         println!("\n📥 Downloading selected files...\n");
                         for idx in selections {
                             let file_id = other_problematic[idx].as_str();
                             let file = get_file_by_id(file_id).unwrap();
-                            download_with_retry(file, &data_dir, false)?;
+                            download_with_retry(decisions, file, data_dir, false, deadline)?;
                         }
                     }
                 }
@@ -374,7 +520,7 @@ fn handle_downloads(reference_file: &ncdac_opi_parser::files::FileMetadata) -> R
                     println!("\n📥 Downloading all missing/out-of-date files...\n");
                     for file_id in &other_problematic {
                         let file = get_file_by_id(file_id).unwrap();
-                        download_with_retry(file, &data_dir, false)?;
+                        download_with_retry(decisions, file, data_dir, false, deadline)?;
                     }
                 }
             }
@@ -386,292 +532,80 @@ fn handle_downloads(reference_file: &ncdac_opi_parser::files::FileMetadata) -> R
     Ok(false)
 }
 
-/// Main workflow function
-async fn run(
-    args: &Cli,
-    reference_file: &ncdac_opi_parser::files::FileMetadata,
-) -> Result<DataHandler> {
-    let data_dir = get_data_dir();
-
-    let mut already_decompressed = Vec::new();
-    let mut missing_files = Vec::new();
-    let mut incomplete_files = Vec::new();
-    let mut files_to_decompress = Vec::new();
-
-    for file in &FILES {
-        if are_decompressed_files_valid(file, &data_dir) {
-            already_decompressed.push(file.id);
-            continue;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        match get_file_status(file, &data_dir) {
-            FileStatus::Missing => {
-                missing_files.push(file.id);
-                continue;
-            }
-            FileStatus::Incomplete => {
-                incomplete_files.push(file.id);
-                continue;
-            }
-            FileStatus::Complete => {
-                files_to_decompress.push(*file);
-            }
-        }
-    }
+    #[test]
+    fn test_reference_prompt_replays_recorded_decisions() -> Result<()> {
+        let mut decisions =
+            ReplayDecisions::parse("use-default-reference: yes\n".as_bytes())?;
+        assert_eq!(confirm_reference_file(&mut decisions, "OFNT3AA1")?, "OFNT3AA1");
 
-    if files_to_decompress.is_empty() {
-        if !missing_files.is_empty() || !incomplete_files.is_empty() {
-            for file_id in &missing_files {
-                println!(
-                    "\x1b[34mℹ\x1b[0m Skipped {} (ZIP file not available)",
-                    file_id
-                );
-            }
-            for file_id in &incomplete_files {
-                println!(
-                    "\x1b[33m⚠\x1b[0m Skipped {} (ZIP file out-of-date or incomplete)",
-                    file_id
-                );
-            }
-        }
-    } else {
-        let total_bytes = calculate_total_uncompressed_bytes(&files_to_decompress, &data_dir)
-            .context("Failed to calculate total uncompressed bytes")?;
-
-        let total_mb = total_bytes as f64 / 1_048_576.0;
-
-        let shared_pb = Arc::new(ProgressBar::new(total_bytes));
-        shared_pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
-                .unwrap()
-                .progress_chars("#>-"),
-        );
-        shared_pb.set_message(format!(
-            "Decompressing {} files concurrently - {:.1} MB total",
-            files_to_decompress.len(),
-            total_mb
-        ));
-
-        let decompression_start = SystemTime::now();
-
-        let result: Result<()> = files_to_decompress
-            .par_iter()
-            .try_for_each(|file| {
-                decompress_with_shared_progress(file.id, file.name, &shared_pb)?;
-                Ok(())
-            });
-
-        match result {
-            Ok(_) => {
-                let decompression_duration = format_duration(decompression_start, None)
-                    .context("Failed to calculate decompression duration")?;
-
-                shared_pb.finish_with_message(format!(
-                    "✓ Decompressed {} files - {:.1} MB total in {}",
-                    files_to_decompress.len(),
-                    total_mb,
-                    decompression_duration
-                ));
-                println!();
-            }
-            Err(e) => {
-                shared_pb.finish_and_clear();
-                eprintln!("❌ Failed to decompress files");
-                return Err(e);
-            }
-        }
+        let mut decisions = ReplayDecisions::parse(
+            "use-default-reference: no\nreference-file: OFNT1BA1\n".as_bytes(),
+        )?;
+        assert_eq!(confirm_reference_file(&mut decisions, "OFNT3AA1")?, "OFNT1BA1");
 
-        if !missing_files.is_empty() || !incomplete_files.is_empty() {
-            for file_id in &missing_files {
-                println!(
-                    "\x1b[34mℹ\x1b[0m Skipped {} (ZIP file not available)",
-                    file_id
-                );
-            }
-            for file_id in &incomplete_files {
-                println!(
-                    "\x1b[33m⚠\x1b[0m Skipped {} (ZIP file out-of-date or incomplete)",
-                    file_id
-                );
-            }
-        }
-    }
+        // A replayed run never falls back to asking
+        let mut decisions = ReplayDecisions::parse("".as_bytes())?;
+        assert!(confirm_reference_file(&mut decisions, "OFNT3AA1").is_err());
 
-    let mut data_handler = DataHandler::new(
-        args.output
-            .to_str()
-            .context("Invalid output path")?,
-    )
-    .context("Failed to create database handler")?;
-
-    let init_start_time = SystemTime::now();
-
-    let ref_dat_path = data_dir.join(reference_file.id).join(format!("{}.dat", reference_file.id));
-    let ref_line_count = count_lines(&ref_dat_path)
-        .with_context(|| format!("Failed to count lines in {}", ref_dat_path.display()))?;
-
-    let ref_pb = ProgressBar::new(ref_line_count);
-    ref_pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} records ({eta})")
-            .unwrap()
-            .progress_chars("#>-"),
-    );
-    ref_pb.set_message(format!(
-        "Processing reference file ({}) - Inserting {} records into {} table",
-        reference_file.id,
-        format_count(ref_line_count as usize),
-        reference_file.name
-    ));
-
-    let init_results = data_handler
-        .init(reference_file, Some(&ref_pb))
-        .context("Failed to initialize with reference file")?;
-
-    let init_duration = format_duration(init_start_time, None)
-        .context("Failed to calculate initialization duration")?;
-
-    if !init_results.errors.is_empty() {
-        ref_pb.finish_and_clear();
-        println!(
-            "⚠️  {} errors encountered while processing {} reference file.",
-            init_results.errors.len(),
-            reference_file.name
-        );
-    } else {
-        ref_pb.finish_with_message(format!(
-            "✓ Processed reference file ({}) - Inserted {} records into {} table in {}",
-            reference_file.id,
-            format_count(init_results.processed),
-            reference_file.name,
-            init_duration
-        ));
+        Ok(())
     }
 
-    println!("\n📋 Reference file processing complete");
+    /// Serializes tests that set the process-wide data directory.
+    static DATA_DIRECTORY_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
 
-    let files_to_process: Vec<_> = FILES
-        .iter()
-        .filter(|file| {
-            if file.id == reference_file.id {
-                return false;
-            }
-            let file_dir = data_dir.join(file.id);
-            file_dir.exists()
-        })
-        .collect();
+    /// Holds `DATA_DIRECTORY_LOCK` and clears the data directory override on
+    /// drop, even if the test panics.
+    struct DataDirectoryGuard {
+        _lock: std::sync::MutexGuard<'static, ()>,
+    }
 
-    if files_to_process.is_empty() {
-        if !args.keep_data {
-            let spinner = create_spinner("Cleaning up data files...");
-            for file in &FILES {
-                delete_data_subdirectory(file.id)
-                    .await
-                    .with_context(|| format!("Failed to delete data directory for {}", file.id))?;
+    impl DataDirectoryGuard {
+        fn new() -> Self {
+            Self {
+                _lock: DATA_DIRECTORY_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner()),
             }
-            spinner.finish_with_message("Cleaned up data files".to_string());
         }
-        return Ok(data_handler);
     }
 
-    let mut total_records = 0u64;
-    for file in &files_to_process {
-        let dat_path = data_dir.join(file.id).join(format!("{}.dat", file.id));
-        if let Ok(line_count) = count_lines(&dat_path) {
-            total_records += line_count;
+    impl Drop for DataDirectoryGuard {
+        fn drop(&mut self) {
+            set_data_directory(None);
         }
     }
 
-    println!("🚀 Starting parallel processing of {} files", files_to_process.len());
-
-    let combined_pb = Arc::new(ProgressBar::new(total_records));
-    combined_pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{msg}\n{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} records ({eta})")
-            .unwrap()
-            .progress_chars("#>-"),
-    );
-    combined_pb.set_message(format!(
-        "Processing {} files concurrently - {} total records",
-        files_to_process.len(),
-        format_count(total_records as usize)
-    ));
-
-    let error_aggregator = Arc::new(ErrorAggregator::new());
-    let des_failure_aggregator = Arc::new(DesFailureAggregator::new());
-
-    let database_path = args.output.to_str().context("Invalid output path")?;
-    let parallel_start_time = SystemTime::now();
-
-    let ref_file = data_handler.reference_file().copied()
-        .context("Reference file not set before parallel processing")?;
-    let ref_table = data_handler.reference_table_name()
-        .context("Reference table not set before parallel processing")?
-        .to_string();
-    let ref_field = data_handler.reference_field()
-        .context("Reference field not set before parallel processing")?
-        .to_string();
-
-    files_to_process.par_iter().for_each(|file| {
-        let mut worker_handler = match create_worker_handler(database_path) {
-            Ok(handler) => handler,
-            Err(e) => {
-                eprintln!("❌ Failed to create worker handler for {}: {:#}", file.id, e);
-                return;
-            }
-        };
-
-        worker_handler.init_from_reference(&ref_file, &ref_table, &ref_field);
-
-        let pb = Arc::clone(&combined_pb);
-        let agg = Arc::clone(&error_aggregator);
-        let des_agg = Arc::clone(&des_failure_aggregator);
-
-        match worker_handler.process_file(file, Some(&pb)) {
-            Ok(Some(results)) => {
-                if !results.errors.is_empty() {
-                    agg.add_errors(results.errors);
-                }
-            }
-            Ok(None) => {
-                if !worker_handler.des_file_failures.is_empty() {
-                    des_agg.add_failures(worker_handler.des_file_failures.clone());
-                }
-            }
-            Err(e) => {
-                eprintln!("❌ Failed to process file {}: {:#}", file.id, e);
-            }
-        }
-    });
-
-    let parallel_duration = format_duration(parallel_start_time, None)
-        .context("Failed to calculate parallel processing duration")?;
-
-    combined_pb.finish_with_message(format!(
-        "✓ Processed {} files concurrently in {} - {} total records",
-        files_to_process.len(),
-        parallel_duration,
-        format_count(total_records as usize)
-    ));
-
-    println!("✅ Parallel processing complete");
-
-    let all_parallel_errors = error_aggregator.get_errors();
-    data_handler.errors.extend(all_parallel_errors);
-
-    let all_des_failures = des_failure_aggregator.get_failures();
-    data_handler.des_file_failures.extend(all_des_failures);
-
-    if !args.keep_data {
-        let spinner = create_spinner("Cleaning up data files...");
-        for file in &FILES {
-            delete_data_subdirectory(file.id)
-                .await
-                .with_context(|| format!("Failed to delete data directory for {}", file.id))?;
-        }
-        spinner.finish_with_message("Cleaned up data files".to_string());
+    #[test]
+    fn test_verify_only_uses_custom_data_dir() -> Result<()> {
+        use std::io::Write;
+
+        let _guard = DataDirectoryGuard::new();
+        let temp_dir = tempfile::TempDir::new()?;
+        let data_path = temp_dir.path().join("scratch");
+        let args = Cli::try_parse_from([
+            "ncdac-opi-parser",
+            "--verify-only",
+            "--data-dir",
+            data_path.to_str().unwrap(),
+        ])?;
+        assert_eq!(data_dir(&args), data_path);
+        assert_eq!(data_directory(), data_path);
+
+        let file = *get_file_by_id("OFNT3AA1").unwrap();
+        std::fs::create_dir_all(data_path.join(file.id))?;
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(data_path.join("OFNT3AA1.zip"))?);
+        zip.start_file("OFNT3AA1.dat", zip::write::SimpleFileOptions::default())?;
+        zip.write_all(b"0000001")?;
+        zip.finish()?;
+
+        std::fs::write(data_path.join(file.id).join("OFNT3AA1.dat"), b"0000001")?;
+        assert!(verify_only(&[file], &data_dir(&args)));
+
+        std::fs::write(data_path.join(file.id).join("OFNT3AA1.dat"), b"0000002")?;
+        assert!(!verify_only(&[file], &data_dir(&args)));
+
+        Ok(())
     }
-
-    Ok(data_handler)
 }
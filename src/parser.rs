@@ -23,15 +23,18 @@
 //! # }
 //! ```
 
-use crate::file_description::FileDescription;
-use crate::utilities::data_directory;
-use anyhow::{Context, Result};
+use crate::file_description::{FieldDefinition, FileDescription};
+use crate::utilities::{data_directory, extracted_file_path, gzipped_extracted_file, open_extracted_file};
+use anyhow::{anyhow, Context, Result};
 use once_cell::sync::Lazy;
+use rayon::prelude::*;
 use regex::Regex;
-use std::collections::HashMap;
+use std::borrow::{Borrow, Cow};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufRead, BufReader, Lines};
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Take, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 /// Regex pattern for detecting strings that are all question marks.
 ///
@@ -42,7 +45,211 @@ static ALL_QUESTION_MARKS: Lazy<Regex> =
 /// The null date marker used in the data files.
 ///
 /// Date fields with this value should be treated as null/missing.
-const NULL_DATE_MARKER: &str = "0001-01-01";
+pub const NULL_DATE_MARKER: &str = "0001-01-01";
+
+/// Returns whether a raw field value is coerced to NULL by the parser.
+///
+/// This applies exactly the null rules of `DataParser::coerce_value`, so
+/// downstream tools can interpret the database's NULLs consistently:
+/// blank values, the null date marker, and strings of only "?".
+///
+/// # Example
+///
+/// ```
+/// use ncdac_opi_parser::parser::is_null_marker;
+///
+/// assert!(is_null_marker("   "));
+/// assert!(is_null_marker("0001-01-01"));
+/// assert!(is_null_marker(" ??? "));
+/// assert!(!is_null_marker("what?"));
+/// ```
+pub fn is_null_marker(raw_value: &str) -> bool {
+    null_rule(raw_value.trim()).is_some()
+}
+
+/// Returns the null rule matching an already-trimmed value, if any.
+fn null_rule(value: &str) -> Option<CoercionRule> {
+    if value.is_empty() {
+        Some(CoercionRule::EmptyAfterTrim)
+    } else if value == NULL_DATE_MARKER {
+        Some(CoercionRule::NullDate)
+    } else if ALL_QUESTION_MARKS.is_match(value) {
+        Some(CoercionRule::AllQuestionMarks)
+    } else {
+        None
+    }
+}
+
+/// The coercion rule applied to a raw field value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoercionRule {
+    /// The value was blank after trimming → NULL
+    EmptyAfterTrim,
+    /// The value was the null date marker "0001-01-01" → NULL
+    NullDate,
+    /// The value was only "?" characters → NULL
+    AllQuestionMarks,
+    /// No null rule matched; the trimmed value is kept
+    Preserved,
+}
+
+/// Which coercion rule fired for a raw value, and the resulting value.
+///
+/// Returned by `DataParser::explain_coercion`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoercionExplanation {
+    /// The rule that decided the result
+    pub rule: CoercionRule,
+    /// The coerced value, identical to `DataParser::coerce_value`
+    pub value: Option<String>,
+}
+
+/// How one field of a sampled record was coerced.
+///
+/// Part of a `RecordCoercion`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldCoercion {
+    /// The field code
+    pub field: String,
+    /// The value exactly as sliced from the record
+    pub raw: String,
+    /// The rule that decided the result
+    pub rule: CoercionRule,
+    /// The coerced value, as `DataParser::parse` yields it
+    pub value: Option<String>,
+}
+
+/// How every field of one record was coerced.
+///
+/// Returned by `DataParser::sample_coercions`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordCoercion {
+    /// The record's 1-based position among the non-empty records
+    pub record: usize,
+    /// One entry per field, in record order
+    pub fields: Vec<FieldCoercion>,
+}
+
+impl RecordCoercion {
+    /// Returns the fields that a null rule turned into NULL.
+    pub fn nulled(&self) -> impl Iterator<Item = &FieldCoercion> {
+        self.fields.iter().filter(|field| field.value.is_none())
+    }
+}
+
+/// Suffix of the keys holding untrimmed values of raw-shadowed fields.
+///
+/// A field `NOTES` listed in `DataParser::set_raw_fields` also yields a
+/// `NOTES_raw` entry with the exact slice from the record.
+pub const RAW_SUFFIX: &str = "_raw";
+
+/// Parses one record line into coerced field values.
+///
+/// Fixed-width lines are sliced by field position. With a `delimiter`, the
+/// Nth delimited value is assigned to the Nth field in DES order and missing
+/// trailing values become `None`. Fields in `raw_fields` additionally get a
+/// `<code>_raw` entry holding the value exactly as sliced. With
+/// `empty_to_null` off, blank values become `Some("")` instead of `None`.
+fn parse_record(
+    file_description: &FileDescription,
+    line: &str,
+    control_chars: ControlCharPolicy,
+    delimiter: Option<char>,
+    raw_fields: &[String],
+    empty_to_null: bool,
+) -> HashMap<String, Option<String>> {
+    let mut record = HashMap::with_capacity(file_description.schema.len() + raw_fields.len());
+
+    let mut insert = |field_code: &str, raw_value: &str| {
+        let coerced_value = DataParser::coerce_value_with(&control_chars.apply(raw_value), empty_to_null);
+        record.insert(field_code.to_string(), coerced_value);
+
+        if raw_fields.iter().any(|field| field == field_code) {
+            record.insert(format!("{}{}", field_code, RAW_SUFFIX), Some(raw_value.to_string()));
+        }
+    };
+
+    match delimiter {
+        None => {
+            for (field_code, field_def) in &file_description.schema {
+                insert(field_code, &field_def.slice(line));
+            }
+        }
+        Some(delimiter) => {
+            let mut values = line.split(delimiter);
+            for (field_code, _) in file_description.ordered_fields() {
+                insert(field_code, values.next().unwrap_or(""));
+            }
+        }
+    }
+
+    record
+}
+
+/// How records are separated in a DAT file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RecordMode {
+    /// One record per line, terminated by `\n` or `\r\n`
+    #[default]
+    Newline,
+    /// Records of exactly the schema's record length with no terminators
+    FixedLength,
+    /// Records terminated by the given byte (e.g., form feed or 0x1E)
+    Delimiter(u8),
+}
+
+/// How control characters (0x00–0x1F, except tab) in field values are handled.
+///
+/// Applied to each raw field value before coercion. The default preserves
+/// values byte for byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ControlCharPolicy {
+    /// Keep control characters as-is
+    #[default]
+    Preserve,
+    /// Remove control characters
+    Strip,
+    /// Replace each control character with the given character
+    Replace(char),
+}
+
+impl ControlCharPolicy {
+    /// Applies the policy to a raw field value.
+    ///
+    /// Borrows the value unchanged when the policy is `Preserve` or the value
+    /// contains no control characters.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ncdac_opi_parser::parser::ControlCharPolicy;
+    ///
+    /// assert_eq!(ControlCharPolicy::Strip.apply("AB\x07C"), "ABC");
+    /// assert_eq!(ControlCharPolicy::Replace(' ').apply("AB\x07C"), "AB C");
+    /// assert_eq!(ControlCharPolicy::Strip.apply("A\tB"), "A\tB");
+    /// ```
+    pub fn apply<'a>(&self, value: &'a str) -> Cow<'a, str> {
+        let is_control = |c: char| c <= '\x1f' && c != '\t';
+
+        match self {
+            ControlCharPolicy::Preserve => Cow::Borrowed(value),
+            _ if !value.contains(is_control) => Cow::Borrowed(value),
+            ControlCharPolicy::Strip => Cow::Owned(value.chars().filter(|&c| !is_control(c)).collect()),
+            ControlCharPolicy::Replace(replacement) => Cow::Owned(
+                value
+                    .chars()
+                    .map(|c| if is_control(c) { *replacement } else { c })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Reader over a DAT file returned by `DataParser::parse`.
+///
+/// Boxed so that a gzipped `{file_id}.dat.gz` reads the same as a plain
+/// `.dat` (see `utilities::open_extracted_file`).
+pub type DatReader = BufReader<Box<dyn Read + Send>>;
 
 /// Parser for fixed-width DAT files.
 ///
@@ -93,6 +300,20 @@ pub struct DataParser {
     file_id: String,
     /// The parsed schema definition
     file_description: FileDescription,
+    /// The data directory containing the extracted files
+    data_dir: PathBuf,
+    /// How records are separated in the DAT file
+    record_mode: RecordMode,
+    /// How control characters in field values are handled
+    control_chars: ControlCharPolicy,
+    /// Field separator for delimited files (None for fixed-width)
+    delimiter: Option<char>,
+    /// Fields that also yield their untrimmed value under `<code>_raw`
+    raw_fields: Vec<String>,
+    /// Whether blank values are coerced to NULL rather than `""`
+    empty_to_null: bool,
+    /// Whether records shorter than the schema are errors rather than padded
+    strict: bool,
 }
 
 impl DataParser {
@@ -120,11 +341,185 @@ impl DataParser {
     /// # }
     /// ```
     pub fn new(file_id: &str) -> Result<Self> {
-        let file_description = FileDescription::new(file_id)?;
-        Ok(Self {
-            file_id: file_id.to_string(),
+        Self::from_data_dir(file_id, &data_directory())
+    }
+
+    /// Creates a new `DataParser` reading from an explicit data directory.
+    ///
+    /// The DES and DAT files are expected at `{data_dir}/{file_id}/{file_id}.des`
+    /// and `{data_dir}/{file_id}/{file_id}.dat`.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The file identifier (e.g., "OFNT1BA1")
+    /// * `data_dir` - The data directory containing the extracted files
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the descriptor file cannot be read or parsed.
+    pub fn from_data_dir(file_id: &str, data_dir: &Path) -> Result<Self> {
+        let file_description = FileDescription::from_data_dir(file_id, data_dir)?;
+        Ok(Self::from_description(file_description, data_dir))
+    }
+
+    /// Creates a new `DataParser` from an already parsed schema.
+    ///
+    /// Lets callers that have read the DES file reuse it instead of parsing
+    /// it again. The DAT file is expected at
+    /// `{data_dir}/{filename}/{filename}.dat`, where `filename` comes from
+    /// the description.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_description` - The parsed schema of the file
+    /// * `data_dir` - The data directory containing the extracted files
+    pub fn from_description(file_description: FileDescription, data_dir: &Path) -> Self {
+        Self {
+            file_id: file_description.filename.clone(),
             file_description,
-        })
+            data_dir: data_dir.to_path_buf(),
+            record_mode: RecordMode::default(),
+            control_chars: ControlCharPolicy::default(),
+            delimiter: None,
+            raw_fields: Vec::new(),
+            empty_to_null: true,
+            strict: false,
+        }
+    }
+
+    /// Creates a new `DataParser` that rejects short records.
+    ///
+    /// By default a fixed-width record shorter than the schema yields `None`
+    /// for the fields it cuts off. A strict parser's `RecordIterator` yields
+    /// an `Err` for such a record instead, so a mismatched DES and DAT fail
+    /// loudly. Delimited records are not checked.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_id` - The file identifier (e.g., "OFNT1BA1")
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the descriptor file cannot be read or parsed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new_strict("OFNT1BA1")?;
+    /// for record in parser.parse()? {
+    ///     let record = record?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_strict(file_id: &str) -> Result<Self> {
+        let mut parser = Self::new(file_id)?;
+        parser.set_strict(true);
+        Ok(parser)
+    }
+
+    /// Sets whether records shorter than the schema are rejected.
+    ///
+    /// Makes any parser strict, such as one from `from_data_dir` or
+    /// `from_description`; `new_strict` is the shorthand for the global
+    /// data directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `strict` - Whether short fixed-width records are errors
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Returns whether records shorter than the schema are rejected.
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Sets how records are separated in the DAT file.
+    ///
+    /// Defaults to `RecordMode::Newline`. With `RecordMode::FixedLength`,
+    /// each record is read as exactly `FileDescription::record_length` bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `record_mode` - The record separation mode
+    pub fn set_record_mode(&mut self, record_mode: RecordMode) {
+        self.record_mode = record_mode;
+    }
+
+    /// Returns how records are separated in the DAT file.
+    pub fn record_mode(&self) -> RecordMode {
+        self.record_mode
+    }
+
+    /// Sets how control characters in field values are handled.
+    ///
+    /// Defaults to `ControlCharPolicy::Preserve`.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The control character policy
+    pub fn set_control_char_policy(&mut self, policy: ControlCharPolicy) {
+        self.control_chars = policy;
+    }
+
+    /// Returns how control characters in field values are handled.
+    pub fn control_char_policy(&self) -> ControlCharPolicy {
+        self.control_chars
+    }
+
+    /// Switches between fixed-width and delimited parsing.
+    ///
+    /// With `Some(delimiter)`, each line is split on the delimiter and the
+    /// Nth value is mapped to the Nth field in DES order (see
+    /// `FileDescription::ordered_fields`), using the same coercion rules.
+    /// `None` (the default) parses fixed-width records.
+    ///
+    /// # Arguments
+    ///
+    /// * `delimiter` - The field separator, e.g. `Some('|')` or `Some(',')`
+    pub fn set_delimited(&mut self, delimiter: Option<char>) {
+        self.delimiter = delimiter;
+    }
+
+    /// Returns the field separator, or `None` for fixed-width parsing.
+    pub fn delimiter(&self) -> Option<char> {
+        self.delimiter
+    }
+
+    /// Sets fields whose untrimmed values are also returned.
+    ///
+    /// Each parsed record gains a `<code>_raw` entry (see `RAW_SUFFIX`) for
+    /// every listed field, holding the exact slice from the record including
+    /// padding. Codes not in the schema are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The field codes to keep raw copies of
+    pub fn set_raw_fields(&mut self, fields: &[&str]) {
+        self.raw_fields = fields.iter().map(|field| field.to_string()).collect();
+    }
+
+    /// Sets whether blank field values are coerced to NULL.
+    ///
+    /// Defaults to `true`. When `false`, values that are empty after
+    /// trimming become `Some("")`, for consumers that distinguish NULL from
+    /// an empty string. The null date and "?" rules still apply.
+    ///
+    /// # Arguments
+    ///
+    /// * `empty_to_null` - Whether blank values become `None`
+    pub fn set_empty_to_null(&mut self, empty_to_null: bool) {
+        self.empty_to_null = empty_to_null;
+    }
+
+    /// Returns whether blank field values are coerced to NULL.
+    pub fn empty_to_null(&self) -> bool {
+        self.empty_to_null
     }
 
     /// Returns a reference to the file description schema.
@@ -152,6 +547,10 @@ impl DataParser {
     ///
     /// Returns an error if the DAT file cannot be opened.
     ///
+    /// A DAT file gzipped by `files::recompress_extracted` is read
+    /// transparently. Methods that seek within the file, such as
+    /// `split_ranges` and `parse_range`, need the uncompressed file.
+    ///
     /// # Example
     ///
     /// ```no_run
@@ -170,38 +569,69 @@ impl DataParser {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn parse(&self) -> Result<RecordIterator<BufReader<File>>> {
+    pub fn parse(&self) -> Result<RecordIterator<DatReader>> {
         let file_path = self.get_dat_file_path();
 
-        let file = File::open(&file_path).with_context(|| {
+        let file = open_extracted_file(&self.data_dir, &self.file_id, "dat").with_context(|| {
             format!("Failed to open DAT file: {}", file_path.display())
         })?;
 
         let reader = BufReader::new(file);
-        Ok(RecordIterator::new(reader, self.file_description.clone()))
+        Ok(RecordIterator::with_mode(
+            reader,
+            self.file_description.clone(),
+            self.record_mode,
+        )
+        .with_control_char_policy(self.control_chars)
+        .with_delimiter(self.delimiter)
+        .with_raw_fields(self.raw_fields.clone())
+        .with_empty_to_null(self.empty_to_null)
+        .with_strict(self.strict))
     }
 
-    /// Gets the path to the DAT file.
+    /// Returns the first non-empty parsed record of the DAT file.
     ///
-    /// Returns the path: `./data/{file_id}/{file_id}.dat`
-    fn get_dat_file_path(&self) -> PathBuf {
-        data_directory()
-            .join(&self.file_id)
-            .join(format!("{}.dat", self.file_id))
+    /// Only reads as far as the first record, which makes it a cheap way to
+    /// check field alignment before a full load.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(None)` if the file contains no non-empty records.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DAT file cannot be opened or read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT1BA1")?;
+    /// if let Some(record) = parser.first_record()? {
+    ///     println!("{:?}", record.get("CMDORNUM"));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn first_record(&self) -> Result<Option<HashMap<String, Option<String>>>> {
+        self.parse()?.next().transpose()
     }
 
-    /// Parses a single line from the DAT file.
+    /// Parses the DAT file, yielding only records that match a predicate.
     ///
-    /// Extracts all fields defined in the schema and coerces their values
-    /// according to the coercion rules.
+    /// A scripting convenience for ad-hoc extraction without loading into
+    /// SQLite. Records are read lazily, so only matching records are ever
+    /// handed to the caller. Read errors are passed through unfiltered.
     ///
     /// # Arguments
     ///
-    /// * `line` - A line from the DAT file
+    /// * `predicate` - Returns `true` for records to keep
     ///
-    /// # Returns
+    /// # Errors
     ///
-    /// A HashMap mapping field codes to their coerced values.
+    /// Returns an error if the DAT file cannot be opened.
     ///
     /// # Example
     ///
@@ -209,171 +639,1699 @@ impl DataParser {
     /// use ncdac_opi_parser::parser::DataParser;
     ///
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let parser = DataParser::new("OFNT1BA1")?;
-    /// let line = "1234567AB123..."; // Fixed-width record
-    /// let record = parser.parse_line(line);
+    /// let parser = DataParser::new("OFNT3AA1")?;
+    /// let female = parser.parse_filtered(|record| {
+    ///     record.get("CMSEXCOD").and_then(|v| v.as_deref()) == Some("FEMALE")
+    /// })?;
+    /// for record in female {
+    ///     println!("{:?}", record?.get("CMDORNUM"));
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    pub fn parse_line(&self, line: &str) -> HashMap<String, Option<String>> {
-        let mut record = HashMap::new();
-
-        for (field_code, field_def) in &self.file_description.schema {
-            let slice_start = field_def.start.saturating_sub(1);
-            let slice_end = slice_start + field_def.length;
-
-            let raw_value = if line.len() >= slice_end {
-                &line[slice_start..slice_end]
-            } else if line.len() > slice_start {
-                &line[slice_start..]
-            } else {
-                ""
-            };
-
-            let coerced_value = Self::coerce_value(raw_value);
-            record.insert(field_code.clone(), coerced_value);
-        }
-
-        record
+    pub fn parse_filtered<P>(
+        &self,
+        predicate: P,
+    ) -> Result<impl Iterator<Item = Result<HashMap<String, Option<String>>>> + use<P>>
+    where
+        P: Fn(&HashMap<String, Option<String>>) -> bool,
+    {
+        Ok(self.parse()?.filter(move |record| match record {
+            Ok(record) => predicate(record),
+            Err(_) => true,
+        }))
     }
 
-    /// Coerces a raw field value according to the data rules.
+    /// Reads the DAT file as raw records, without parsing them into fields.
     ///
-    /// # Coercion Rules
+    /// Records are split according to the record mode and yielded verbatim,
+    /// minus their terminator. Blank records are skipped, exactly as `parse`
+    /// skips them. Tools that re-emit fixed-width data can parse just the key
+    /// with `parse_line` and write matching records back unchanged, avoiding
+    /// a lossy round trip through value coercion.
     ///
-    /// 1. Trim whitespace from both ends
-    /// 2. Empty strings → None
-    /// 3. "0001-01-01" → None (null date marker)
-    /// 4. Strings of only "?" → None (null markers like "???")
-    /// 5. All other values → Some(value)
+    /// # Errors
+    ///
+    /// Returns an error if the DAT file cannot be opened. Each item is an
+    /// error if the record cannot be read or is not valid UTF-8.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT3AA1")?;
+    /// for line in parser.raw_lines()? {
+    ///     let line = line?;
+    ///     if parser.parse_line(&line).get("CMSEXCOD").and_then(|v| v.as_deref()) == Some("FEMALE") {
+    ///         println!("{}", line);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn raw_lines(&self) -> Result<impl Iterator<Item = Result<String>> + use<>> {
+        let mut records = self.parse()?;
+
+        Ok(std::iter::from_fn(move || loop {
+            match records.read_record() {
+                Ok(Some(line)) if line.trim().is_empty() => continue,
+                Ok(Some(line)) => return Some(Ok(line)),
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }))
+    }
+
+    /// Explains how each field of the first records was coerced.
+    ///
+    /// An interactive troubleshooting aid for a new or misaligned file:
+    /// for each of the first `n` non-empty records it reports every
+    /// field's raw slice, the coercion rule that fired, and the resulting
+    /// value. A date field full of `NullDate` or a name field full of
+    /// `EmptyAfterTrim` usually means the schema positions are off. The
+    /// parser's control character policy and `empty_to_null` setting are
+    /// applied as in `parse`.
     ///
     /// # Arguments
     ///
-    /// * `raw_value` - The raw field value extracted from the fixed-width record
+    /// * `n` - The maximum number of records to sample
     ///
-    /// # Returns
+    /// # Errors
     ///
-    /// `Some(String)` for valid values, `None` for null markers.
+    /// Returns an error if the DAT file cannot be opened or read.
     ///
     /// # Example
     ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT3AA1")?;
+    /// for record in parser.sample_coercions(5)? {
+    ///     for field in record.nulled() {
+    ///         println!("record {}: {} {:?} -> {:?}", record.record, field.field, field.raw, field.rule);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
     /// ```
+    pub fn sample_coercions(&self, n: usize) -> Result<Vec<RecordCoercion>> {
+        let fields = self.file_description.ordered_fields();
+
+        self.raw_lines()?
+            .take(n)
+            .enumerate()
+            .map(|(index, line)| {
+                let line = line?;
+                let mut delimited = self.delimiter.map(|delimiter| line.split(delimiter));
+
+                let fields = fields
+                    .iter()
+                    .map(|(code, field)| {
+                        let raw = match delimited.as_mut() {
+                            Some(values) => Cow::Borrowed(values.next().unwrap_or("")),
+                            None => field.slice(&line),
+                        };
+                        let cleaned = self.control_chars.apply(&raw);
+                        FieldCoercion {
+                            field: code.to_string(),
+                            raw: raw.to_string(),
+                            rule: Self::explain_coercion(&cleaned).rule,
+                            value: Self::coerce_value_with(&cleaned, self.empty_to_null),
+                        }
+                    })
+                    .collect();
+
+                Ok(RecordCoercion { record: index + 1, fields })
+            })
+            .collect()
+    }
+
+    /// Counts the distinct non-null values of a key field in the DAT file.
+    ///
+    /// Streams the file record by record, so memory use is bounded by the
+    /// number of distinct keys rather than the file size.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_field` - The field code to count (e.g., "CMDORNUM")
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is not in the schema or the DAT file
+    /// cannot be read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
     /// use ncdac_opi_parser::parser::DataParser;
     ///
-    /// assert_eq!(DataParser::coerce_value("  123  "), Some("123".to_string()));
-    /// assert_eq!(DataParser::coerce_value(""), None);
-    /// assert_eq!(DataParser::coerce_value("   "), None);
-    /// assert_eq!(DataParser::coerce_value("0001-01-01"), None);
-    /// assert_eq!(DataParser::coerce_value("???"), None);
-    /// assert_eq!(DataParser::coerce_value("valid"), Some("valid".to_string()));
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT3AA1")?;
+    /// let offenders = parser.distinct_key_count("CMDORNUM")?;
+    /// println!("{} unique offenders", offenders);
+    /// # Ok(())
+    /// # }
     /// ```
-    pub fn coerce_value(raw_value: &str) -> Option<String> {
-        let value = raw_value.trim();
+    pub fn distinct_key_count(&self, key_field: &str) -> Result<usize> {
+        Ok(self.key_counts(key_field)?.distinct_keys)
+    }
 
-        if value.is_empty() {
-            return None;
-        }
+    /// Counts total records and distinct non-null values of a key field.
+    ///
+    /// Use this instead of `distinct_key_count` when the duplicate rate is
+    /// also of interest.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_field` - The field code to count (e.g., "CMDORNUM")
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is not in the schema or the DAT file
+    /// cannot be read.
+    pub fn key_counts(&self, key_field: &str) -> Result<KeyCounts> {
+        self.parse()?.key_counts(key_field)
+    }
 
-        if value == NULL_DATE_MARKER {
-            return None;
+    /// Collects the distinct non-null values of a key field.
+    ///
+    /// With `workers` above 1, the DAT file is split into that many
+    /// record-aligned ranges (see `split_ranges`) that are parsed in
+    /// parallel, and the per-range sets are merged. This only pays off for
+    /// large files such as the reference file; the result is the same
+    /// either way.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_field` - The field code to collect (e.g., "CMDORNUM")
+    /// * `workers` - The number of ranges parsed in parallel (0 or 1 for serial)
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is not in the schema or the DAT file
+    /// cannot be read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT3AA1")?;
+    /// let offenders = parser.key_set("CMDORNUM", rayon::current_num_threads())?;
+    /// println!("{} unique offenders", offenders.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn key_set(&self, key_field: &str, workers: usize) -> Result<HashSet<String>> {
+        if workers <= 1 {
+            return self.parse()?.key_set(key_field);
+        }
+        if self.file_description.get_field(key_field).is_none() {
+            anyhow::bail!("Field {} not found in schema for {}", key_field, self.file_id());
+        }
+
+        self.split_ranges(workers)?
+            .into_par_iter()
+            .map(|range| self.parse_range(range)?.key_set(key_field))
+            .try_reduce(HashSet::new, |mut a, mut b| {
+                if a.len() < b.len() {
+                    std::mem::swap(&mut a, &mut b);
+                }
+                a.extend(b);
+                Ok(a)
+            })
+    }
+
+    /// Returns the most common non-null values of a field in the DAT file.
+    ///
+    /// Values are ordered by descending frequency, with ties broken by
+    /// ascending value so the output is deterministic. A field with only a
+    /// handful of distinct values is likely an enum-like code field.
+    ///
+    /// # Arguments
+    ///
+    /// * `field_code` - The field code to tally (e.g., "CMSEXCOD")
+    /// * `top_n` - The maximum number of values to return
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is not in the schema or the DAT file
+    /// cannot be read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT3AA1")?;
+    /// for (value, count) in parser.value_frequencies("CMSEXCOD", 10)? {
+    ///     println!("{}: {}", value, count);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn value_frequencies(&self, field_code: &str, top_n: usize) -> Result<Vec<(String, usize)>> {
+        self.parse()?.value_frequencies(field_code, top_n)
+    }
+
+    /// Returns the fields whose every value in the DAT file coerces to NULL.
+    ///
+    /// Such fields carry no data and are candidates for dropping from the
+    /// schema. Fields are returned in record order. A file with no records
+    /// reports no fields.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DAT file cannot be read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT3AA1")?;
+    /// for field in parser.always_null_fields()? {
+    ///     println!("{} is always empty", field);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn always_null_fields(&self) -> Result<Vec<String>> {
+        self.parse()?.always_null_fields()
+    }
+
+    /// Counts, per field, the records that end before the field starts.
+    ///
+    /// Such a field is sliced as an empty value and silently becomes NULL.
+    /// A field past the end of every record usually means the DES schema
+    /// describes wider records than the DAT file holds; see
+    /// `FieldOverrun::is_always_beyond`. Only fields that overrun at least
+    /// one record are returned, in record order. Delimited files have no
+    /// field positions, so they report no fields.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DAT file cannot be read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT3AA1")?;
+    /// for overrun in parser.field_overruns()? {
+    ///     if overrun.is_always_beyond() {
+    ///         println!("{} starts past the end of every record", overrun.field);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn field_overruns(&self) -> Result<Vec<FieldOverrun>> {
+        self.parse()?.field_overruns()
+    }
+
+    /// Counts records that run past the end of the schema.
+    ///
+    /// The mirror of truncation: bytes after `FileDescription::record_length`
+    /// are never parsed, so records that are consistently longer than the
+    /// schema usually mean a field was appended upstream and the DES is out
+    /// of date. Delimited files, and schemas with a field positioned from
+    /// the end of the record, have no unparsed tail and are never reported.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(None)` if no record is longer than the schema.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DAT file cannot be opened or read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT3AA1")?;
+    /// if let Some(trailing) = parser.trailing_bytes()? {
+    ///     eprintln!("{}", trailing);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn trailing_bytes(&self) -> Result<Option<TrailingBytes>> {
+        self.parse()?.trailing_bytes()
+    }
+
+    /// Finds fields that are unique and non-null across a sample of records.
+    ///
+    /// A fallback for files whose key is not one of the named candidates
+    /// known to `get_primary_key_field`. Only the first `sample` records are
+    /// read, so a field reported here is a strong key candidate rather than
+    /// a guaranteed key. Fields are returned in record order.
+    ///
+    /// # Arguments
+    ///
+    /// * `sample` - The maximum number of records to inspect
+    ///
+    /// # Returns
+    ///
+    /// An empty list if the file has no records.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DAT file cannot be opened or read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT3AA1")?;
+    /// println!("{:?}", parser.infer_key_candidates(10_000)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn infer_key_candidates(&self, sample: usize) -> Result<Vec<String>> {
+        let fields: Vec<&str> = self
+            .file_description
+            .ordered_fields()
+            .into_iter()
+            .map(|(code, _)| code)
+            .collect();
+        let mut seen: Vec<Option<HashSet<String>>> = vec![Some(HashSet::new()); fields.len()];
+        let mut sampled = 0;
+
+        for record in self.parse()?.take(sample) {
+            let record = record?;
+            sampled += 1;
+
+            for (field, values) in fields.iter().zip(seen.iter_mut()) {
+                let unique = match (values.as_mut(), record.get(*field).cloned().flatten()) {
+                    (Some(values), Some(value)) => values.insert(value),
+                    _ => false,
+                };
+                if !unique {
+                    *values = None;
+                }
+            }
+        }
+
+        if sampled == 0 {
+            return Ok(Vec::new());
+        }
+
+        Ok(fields
+            .into_iter()
+            .zip(seen)
+            .filter(|(_, values)| values.is_some())
+            .map(|(field, _)| field.to_string())
+            .collect())
+    }
+
+    /// Checks whether the last record of the DAT file looks truncated.
+    ///
+    /// Only the final non-empty record is compared with
+    /// `FileDescription::record_length`, so a short line elsewhere in the
+    /// file is not reported. Just the tail of the file is read. Delimited
+    /// files have no fixed width and are never reported.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(None)` if the final record is full length or the file is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DAT file cannot be opened or read.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT1BA1")?;
+    /// if let Some(truncated) = parser.truncated_final_record()? {
+    ///     eprintln!("{}", truncated);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn truncated_final_record(&self) -> Result<Option<TruncatedRecord>> {
+        let expected = self.file_description.record_length();
+        if expected == 0 || self.delimiter.is_some() {
+            return Ok(None);
+        }
+
+        if self.is_gzipped() {
+            return self.truncated_final_record_streamed(expected);
+        }
+
+        let mut file = self.open_seekable_dat()?;
+        let file_len = file.metadata()?.len();
+
+        let terminator = match self.record_mode {
+            RecordMode::Newline => b'\n',
+            RecordMode::Delimiter(delimiter) => delimiter,
+            RecordMode::FixedLength => {
+                let remainder = (file_len % expected as u64) as usize;
+                return Ok((remainder > 0).then_some(TruncatedRecord { length: remainder, expected }));
+            }
+        };
+
+        // Long enough to hold the final record plus any trailing blank lines.
+        let window = (expected as u64 * 2 + 1024).min(file_len);
+        file.seek(SeekFrom::End(-(window as i64)))?;
+        let mut tail = Vec::with_capacity(window as usize);
+        file.read_to_end(&mut tail)?;
+
+        let tail = String::from_utf8_lossy(&tail);
+        let mut records = tail.split(terminator as char).rev();
+        let last = records.find(|record| !record.trim().is_empty());
+        // With no terminator before it, the record started before the window
+        // and is already longer than expected.
+        let complete = window == file_len || records.next().is_some();
+
+        Ok(last
+            .filter(|_| complete)
+            .map(|record| record.strip_suffix('\r').unwrap_or(record).len())
+            .filter(|length| *length < expected)
+            .map(|length| TruncatedRecord { length, expected }))
+    }
+
+    /// Like `truncated_final_record`, for a gzipped DAT file.
+    ///
+    /// A gzip stream cannot be read from its end, so the whole file is
+    /// decompressed to find the final record.
+    fn truncated_final_record_streamed(&self, expected: usize) -> Result<Option<TruncatedRecord>> {
+        let file_path = self.get_dat_file_path();
+        let mut reader = BufReader::new(open_extracted_file(&self.data_dir, &self.file_id, "dat").with_context(|| {
+            format!("Failed to open DAT file: {}", file_path.display())
+        })?);
+
+        let terminator = match self.record_mode {
+            RecordMode::Newline => b'\n',
+            RecordMode::Delimiter(delimiter) => delimiter,
+            RecordMode::FixedLength => {
+                let file_len = std::io::copy(&mut reader, &mut std::io::sink())?;
+                let remainder = (file_len % expected as u64) as usize;
+                return Ok((remainder > 0).then_some(TruncatedRecord { length: remainder, expected }));
+            }
+        };
+
+        let mut last = None;
+        for record in reader.split(terminator) {
+            let record = record?;
+            let record = String::from_utf8_lossy(&record);
+            if !record.trim().is_empty() {
+                last = Some(record.strip_suffix('\r').unwrap_or(&record).len());
+            }
+        }
+
+        Ok(last
+            .filter(|length| *length < expected)
+            .map(|length| TruncatedRecord { length, expected }))
+    }
+
+    /// Splits the DAT file into byte ranges that start on record boundaries.
+    ///
+    /// The file is cut into roughly equal parts, and each cut is moved
+    /// forward to just past the next record terminator (or to a multiple of
+    /// the record length in `RecordMode::FixedLength`), so no record spans
+    /// two ranges. Each range can then be read independently with
+    /// `parse_range`. Fewer than `count` ranges are returned when the file
+    /// is too small to split that many ways.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - The number of ranges wanted
+    ///
+    /// # Returns
+    ///
+    /// Contiguous ranges covering the whole file, in file order; empty for
+    /// an empty file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DAT file cannot be opened or read, or if it
+    /// is gzipped (see `is_gzipped`).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT3CE1")?;
+    /// for range in parser.split_ranges(4)? {
+    ///     let records = parser.parse_range(range)?.count();
+    ///     println!("{} records", records);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn split_ranges(&self, count: usize) -> Result<Vec<Range<u64>>> {
+        let file = self.open_seekable_dat()?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut boundaries = vec![0];
+        for part in 1..count.max(1) as u64 {
+            let target = file_len * part / count as u64;
+            let boundary = match self.record_mode {
+                RecordMode::FixedLength => {
+                    let record_length = (self.file_description.record_length() as u64).max(1);
+                    target.div_ceil(record_length) * record_length
+                }
+                RecordMode::Newline | RecordMode::Delimiter(_) => {
+                    let terminator = match self.record_mode {
+                        RecordMode::Delimiter(delimiter) => delimiter,
+                        _ => b'\n',
+                    };
+                    // Scan from the byte before the target, so a target that
+                    // already starts a record stays where it is.
+                    let start = target.saturating_sub(1);
+                    reader.seek(SeekFrom::Start(start))?;
+                    start + reader.skip_until(terminator)? as u64
+                }
+            };
+
+            let boundary = boundary.min(file_len);
+            if boundary > *boundaries.last().unwrap() && boundary < file_len {
+                boundaries.push(boundary);
+            }
+        }
+        boundaries.push(file_len);
+
+        Ok(boundaries
+            .windows(2)
+            .map(|pair| pair[0]..pair[1])
+            .filter(|range| !range.is_empty())
+            .collect())
+    }
+
+    /// Parses the records in one byte range of the DAT file.
+    ///
+    /// Like `parse`, but reads only `range`, which should come from
+    /// `split_ranges` so that it starts and ends on record boundaries.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - The byte range to read
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DAT file cannot be opened or is gzipped, or
+    /// the start of the range cannot be reached.
+    pub fn parse_range(&self, range: Range<u64>) -> Result<RecordIterator<BufReader<Take<File>>>> {
+        let mut file = self.open_seekable_dat()?;
+        file.seek(SeekFrom::Start(range.start))?;
+
+        let reader = BufReader::new(file.take(range.end.saturating_sub(range.start)));
+        Ok(RecordIterator::with_mode(
+            reader,
+            self.file_description.clone(),
+            self.record_mode,
+        )
+        .with_control_char_policy(self.control_chars)
+        .with_delimiter(self.delimiter)
+        .with_raw_fields(self.raw_fields.clone())
+        .with_empty_to_null(self.empty_to_null)
+        .with_strict(self.strict))
+    }
+
+    /// Counts the non-empty records in one byte range of the DAT file.
+    ///
+    /// Cheaper than counting `parse_range`, since fields are not extracted.
+    /// Summing the counts of earlier ranges gives the line number before a
+    /// range's first record, as `parse` would number it.
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - The byte range to read, from `split_ranges`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DAT file cannot be opened or read, or a
+    /// record is not valid UTF-8.
+    pub fn count_range_records(&self, range: Range<u64>) -> Result<usize> {
+        let mut records = self.parse_range(range)?;
+        let mut count = 0;
+        while let Some(line) = records.read_record()? {
+            if !line.trim().is_empty() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Gets the path to the DAT file.
+    ///
+    /// Returns the path: `{data_dir}/{file_id}/{file_id}.dat`
+    fn get_dat_file_path(&self) -> PathBuf {
+        extracted_file_path(&self.data_dir, &self.file_id, "dat")
+    }
+
+    /// Returns whether only a gzipped copy of the DAT file exists.
+    ///
+    /// `parse` reads a gzipped DAT file transparently, but `split_ranges`
+    /// and `parse_range` need the uncompressed file, since a gzip stream
+    /// cannot be seeked.
+    pub fn is_gzipped(&self) -> bool {
+        gzipped_extracted_file(&self.data_dir, &self.file_id, "dat").is_some()
+    }
+
+    /// Opens the uncompressed DAT file for reading by byte offset.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the DAT file cannot be opened, or if only a
+    /// gzipped copy of it exists.
+    fn open_seekable_dat(&self) -> Result<File> {
+        if let Some(gz_path) = gzipped_extracted_file(&self.data_dir, &self.file_id, "dat") {
+            return Err(anyhow!(
+                "Cannot read {} by byte range: decompress it or load it serially",
+                gz_path.display()
+            ));
+        }
+
+        let file_path = self.get_dat_file_path();
+        File::open(&file_path).with_context(|| format!("Failed to open DAT file: {}", file_path.display()))
+    }
+
+    /// Parses a single line from the DAT file.
+    ///
+    /// Extracts all fields defined in the schema and coerces their values
+    /// according to the coercion rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - A line from the DAT file
+    ///
+    /// # Returns
+    ///
+    /// A HashMap mapping field codes to their coerced values.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT1BA1")?;
+    /// let line = "1234567AB123..."; // Fixed-width record
+    /// let record = parser.parse_line(line);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse_line(&self, line: &str) -> HashMap<String, Option<String>> {
+        parse_record(
+            &self.file_description,
+            line,
+            self.control_chars,
+            self.delimiter,
+            &self.raw_fields,
+            self.empty_to_null,
+        )
+    }
+
+    /// Coerces a raw field value according to the data rules.
+    ///
+    /// # Coercion Rules
+    ///
+    /// 1. Trim whitespace from both ends
+    /// 2. Empty strings → None
+    /// 3. "0001-01-01" → None (null date marker)
+    /// 4. Strings of only "?" → None (null markers like "???")
+    /// 5. All other values → Some(value)
+    ///
+    /// # Arguments
+    ///
+    /// * `raw_value` - The raw field value extracted from the fixed-width record
+    ///
+    /// # Returns
+    ///
+    /// `Some(String)` for valid values, `None` for null markers.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// assert_eq!(DataParser::coerce_value("  123  "), Some("123".to_string()));
+    /// assert_eq!(DataParser::coerce_value(""), None);
+    /// assert_eq!(DataParser::coerce_value("   "), None);
+    /// assert_eq!(DataParser::coerce_value("0001-01-01"), None);
+    /// assert_eq!(DataParser::coerce_value("???"), None);
+    /// assert_eq!(DataParser::coerce_value("valid"), Some("valid".to_string()));
+    /// ```
+    pub fn coerce_value(raw_value: &str) -> Option<String> {
+        Self::coerce_value_with(raw_value, true)
+    }
+
+    /// Coerces a raw field value, optionally keeping blank values.
+    ///
+    /// Identical to `coerce_value` when `empty_to_null` is `true`. When it
+    /// is `false`, a value that is empty after trimming becomes `Some("")`.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw_value` - The raw field value extracted from the fixed-width record
+    /// * `empty_to_null` - Whether blank values become `None`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// assert_eq!(DataParser::coerce_value_with("   ", false), Some(String::new()));
+    /// assert_eq!(DataParser::coerce_value_with("???", false), None);
+    /// ```
+    pub fn coerce_value_with(raw_value: &str, empty_to_null: bool) -> Option<String> {
+        let value = raw_value.trim();
+
+        match null_rule(value) {
+            Some(CoercionRule::EmptyAfterTrim) if !empty_to_null => Some(String::new()),
+            Some(_) => None,
+            None => Some(value.to_string()),
+        }
+    }
+
+    /// Explains how `coerce_value` treats a raw field value.
+    ///
+    /// A diagnostic aid for tracking down why a field is unexpectedly NULL.
+    /// Uses the same rules as `coerce_value`, so `value` always equals
+    /// `coerce_value(raw_value)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw_value` - The raw field value extracted from the fixed-width record
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use ncdac_opi_parser::parser::{CoercionRule, DataParser};
+    ///
+    /// let explanation = DataParser::explain_coercion(" ??? ");
+    /// assert_eq!(explanation.rule, CoercionRule::AllQuestionMarks);
+    /// assert_eq!(explanation.value, None);
+    /// ```
+    pub fn explain_coercion(raw_value: &str) -> CoercionExplanation {
+        let value = raw_value.trim();
+
+        match null_rule(value) {
+            Some(rule) => CoercionExplanation { rule, value: None },
+            None => CoercionExplanation {
+                rule: CoercionRule::Preserved,
+                value: Some(value.to_string()),
+            },
+        }
+    }
+
+    /// Writes records back out as fixed-width lines.
+    ///
+    /// The inverse of parsing: each field's value is placed at its DES
+    /// position and padded with spaces to its declared length. DECIMAL
+    /// values are right-aligned and all other values left-aligned; NULLs
+    /// and missing fields are written as spaces, as are gaps between
+    /// fields. End-relative fields are placed against the end of the
+    /// record. Each line is terminated with `\n`.
+    ///
+    /// Parsing trims values, so a record only round-trips byte for byte
+    /// if its values were padded this way in the original file.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - The records to write, keyed by field code
+    /// * `schema` - The schema giving each field's position and length
+    /// * `writer` - Where the lines are written
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a value is longer than its field (values are
+    /// never truncated) or the writer fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use ncdac_opi_parser::parser::DataParser;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let parser = DataParser::new("OFNT3AA1")?;
+    /// let records = parser
+    ///     .parse_filtered(|record| record.get("CMSEXCOD").and_then(Option::as_deref) == Some("FEMALE"))?
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    ///
+    /// let output = std::io::BufWriter::new(std::fs::File::create("filtered.dat")?);
+    /// DataParser::write_fixed_width(&records, parser.schema(), output)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_fixed_width<I, W>(records: I, schema: &FileDescription, mut writer: W) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<HashMap<String, Option<String>>>,
+        W: Write,
+    {
+        let fields = schema.ordered_fields();
+        let width = fields
+            .iter()
+            .map(|(_, field)| if field.from_end { field.start } else { field.end() })
+            .max()
+            .unwrap_or(0);
+
+        let mut line = Vec::with_capacity(width + 1);
+        for (index, record) in records.into_iter().enumerate() {
+            let record = record.borrow();
+            line.clear();
+            line.resize(width, b' ');
+
+            for (code, field) in &fields {
+                let Some(value) = record.get(*code).and_then(Option::as_deref) else {
+                    continue;
+                };
+                if value.len() > field.length {
+                    return Err(anyhow!(
+                        "Value of {} in record {} is {} characters long, but the field holds {}: {}",
+                        code,
+                        index + 1,
+                        value.len(),
+                        field.length,
+                        value
+                    ));
+                }
+
+                let start = if field.from_end {
+                    width - field.start
+                } else {
+                    field.zero_indexed_start()
+                };
+                let offset = if field.field_type == "DECIMAL" {
+                    field.length - value.len()
+                } else {
+                    0
+                };
+                line[start + offset..start + offset + value.len()].copy_from_slice(value.as_bytes());
+            }
+
+            line.push(b'\n');
+            writer.write_all(&line).context("Failed to write fixed-width record")?;
         }
 
-        if ALL_QUESTION_MARKS.is_match(value) {
-            return None;
+        writer.flush().context("Failed to write fixed-width record")
+    }
+}
+
+/// A final record shorter than the schema's record length.
+///
+/// Returned by `DataParser::truncated_final_record`. A short last record
+/// usually means the DAT file was cut off mid-record, for example by an
+/// interrupted download or a full disk during extraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TruncatedRecord {
+    /// Length of the final record, in bytes
+    pub length: usize,
+    /// Record length expected from the schema
+    pub expected: usize,
+}
+
+impl std::fmt::Display for TruncatedRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Final record is {} bytes long, expected {} (file may be truncated)",
+            self.length, self.expected
+        )
+    }
+}
+
+/// How often a field started past the end of a record.
+///
+/// Returned by `DataParser::field_overruns`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldOverrun {
+    /// The field code
+    pub field: String,
+    /// Number of records that end before the field starts
+    pub beyond: usize,
+    /// Number of non-empty records read
+    pub records: usize,
+}
+
+impl FieldOverrun {
+    /// Returns whether the field starts past the end of every record.
+    ///
+    /// Every value of such a field is NULL, which points to a mismatch
+    /// between the schema and the record width rather than missing data.
+    pub fn is_always_beyond(&self) -> bool {
+        self.records > 0 && self.beyond == self.records
+    }
+}
+
+impl std::fmt::Display for FieldOverrun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} starts past the end of {} of {} records",
+            self.field, self.beyond, self.records
+        )?;
+        if self.is_always_beyond() {
+            write!(f, " (schema may not match the record width)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Records that are longer than the schema's record length.
+///
+/// Returned by `DataParser::trailing_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrailingBytes {
+    /// Number of records with bytes past the record length
+    pub records: usize,
+    /// Number of non-empty records read
+    pub total_records: usize,
+    /// The most frequent number of trailing bytes (the shortest on a tie)
+    pub common_length: usize,
+    /// Record length expected from the schema
+    pub expected: usize,
+}
+
+impl std::fmt::Display for TrailingBytes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} records are longer than the expected {} bytes, most often by {} (schema may be missing fields)",
+            self.records, self.total_records, self.expected, self.common_length
+        )
+    }
+}
+
+/// Record and key counts for a DAT file.
+///
+/// Returned by `DataParser::key_counts` for capacity planning and
+/// validation of the reference file before dependents are loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyCounts {
+    /// Number of non-empty records in the file
+    pub total_records: usize,
+    /// Number of distinct non-null key values
+    pub distinct_keys: usize,
+    /// Number of records whose key value coerced to null
+    pub null_keys: usize,
+}
+
+impl KeyCounts {
+    /// Returns the number of records that repeat an already-seen key.
+    pub fn duplicate_records(&self) -> usize {
+        self.total_records - self.null_keys - self.distinct_keys
+    }
+}
+
+/// Overlap between the distinct key values of two DAT files.
+///
+/// Returned by `key_set_difference`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SetDiff {
+    /// Distinct keys found only in the first file
+    pub only_in_a: usize,
+    /// Distinct keys found only in the second file
+    pub only_in_b: usize,
+    /// Distinct keys found in both files
+    pub in_both: usize,
+}
+
+/// Compares the distinct non-null key values of two DAT files.
+///
+/// Streams each file's key column into a set, so memory use is bounded by
+/// the number of distinct keys. Useful before building the database, e.g.
+/// to count offenders in the reference file with no records in a dependent
+/// file (`only_in_a`) or dependent records that would violate the foreign
+/// key (`only_in_b`).
+///
+/// # Arguments
+///
+/// * `file_a` - The first file ID (e.g., "OFNT3AA1")
+/// * `field_a` - The key field in the first file (e.g., "CMDORNUM")
+/// * `file_b` - The second file ID (e.g., "OFNT1BA1")
+/// * `field_b` - The key field in the second file
+/// * `data_dir` - The data directory containing the extracted files
+///
+/// # Errors
+///
+/// Returns an error if either DES or DAT file cannot be read or a field is
+/// not in its file's schema.
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::parser::key_set_difference;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let diff = key_set_difference("OFNT3AA1", "CMDORNUM", "OFNT1BA1", "CMDORNUM", Path::new("./data"))?;
+/// println!("{} offenders without financial obligations", diff.only_in_a);
+/// # Ok(())
+/// # }
+/// ```
+pub fn key_set_difference(
+    file_a: &str,
+    field_a: &str,
+    file_b: &str,
+    field_b: &str,
+    data_dir: &Path,
+) -> Result<SetDiff> {
+    let keys_a = DataParser::from_data_dir(file_a, data_dir)?.parse()?.key_set(field_a)?;
+    let keys_b = DataParser::from_data_dir(file_b, data_dir)?.parse()?.key_set(field_b)?;
+
+    let in_both = keys_a.intersection(&keys_b).count();
+
+    Ok(SetDiff {
+        only_in_a: keys_a.len() - in_both,
+        only_in_b: keys_b.len() - in_both,
+        in_both,
+    })
+}
+
+/// Iterator over records in a DAT file.
+///
+/// This iterator reads records from a buffered reader, split according to a
+/// `RecordMode`, and parses each into a record using the provided schema. It
+/// automatically skips empty records.
+///
+/// The iterator yields `Result<HashMap<String, Option<String>>>` where:
+/// - The `HashMap` keys are field codes from the schema
+/// - The values are `Option<String>` (None for null/missing values)
+/// - The `Result` captures any I/O errors during reading
+///
+/// # Type Parameters
+///
+/// * `R` - A type that implements `BufRead` (typically `BufReader<File>`)
+pub struct RecordIterator<R: BufRead> {
+    reader: R,
+    file_description: FileDescription,
+    record_mode: RecordMode,
+    control_chars: ControlCharPolicy,
+    delimiter: Option<char>,
+    raw_fields: Vec<String>,
+    empty_to_null: bool,
+    strict: bool,
+}
+
+impl<R: BufRead> RecordIterator<R> {
+    /// Creates a new `RecordIterator`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - A buffered reader for the DAT file
+    /// * `file_description` - The schema definition for parsing records
+    pub fn new(reader: R, file_description: FileDescription) -> Self {
+        Self::with_mode(reader, file_description, RecordMode::Newline)
+    }
+
+    /// Creates a new `RecordIterator` with an explicit record mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - A buffered reader for the DAT file
+    /// * `file_description` - The schema definition for parsing records
+    /// * `record_mode` - How records are separated in the reader
+    pub fn with_mode(reader: R, file_description: FileDescription, record_mode: RecordMode) -> Self {
+        Self {
+            reader,
+            file_description,
+            record_mode,
+            control_chars: ControlCharPolicy::default(),
+            delimiter: None,
+            raw_fields: Vec::new(),
+            empty_to_null: true,
+            strict: false,
+        }
+    }
+
+    /// Sets how control characters in field values are handled.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The control character policy
+    pub fn with_control_char_policy(mut self, policy: ControlCharPolicy) -> Self {
+        self.control_chars = policy;
+        self
+    }
+
+    /// Sets the field separator for delimited records (None for fixed-width).
+    ///
+    /// # Arguments
+    ///
+    /// * `delimiter` - The field separator
+    pub fn with_delimiter(mut self, delimiter: Option<char>) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Sets fields whose untrimmed values are also yielded under `<code>_raw`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` - The field codes to keep raw copies of
+    pub fn with_raw_fields(mut self, fields: Vec<String>) -> Self {
+        self.raw_fields = fields;
+        self
+    }
+
+    /// Sets whether blank field values are coerced to NULL (default `true`).
+    ///
+    /// # Arguments
+    ///
+    /// * `empty_to_null` - Whether blank values become `None`
+    pub fn with_empty_to_null(mut self, empty_to_null: bool) -> Self {
+        self.empty_to_null = empty_to_null;
+        self
+    }
+
+    /// Sets whether fixed-width records shorter than the schema are errors
+    /// (default `false`, which yields `None` for the missing fields).
+    ///
+    /// # Arguments
+    ///
+    /// * `strict` - Whether short records yield an `Err`
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Reads the next raw record, without its terminator.
+    ///
+    /// Returns `Ok(None)` at end of input. A trailing partial record in
+    /// `RecordMode::FixedLength` is returned as-is.
+    fn read_record(&mut self) -> Result<Option<String>> {
+        let mut buf = Vec::new();
+
+        match self.record_mode {
+            RecordMode::Newline => {
+                if self.reader.read_until(b'\n', &mut buf)? == 0 {
+                    return Ok(None);
+                }
+                if buf.last() == Some(&b'\n') {
+                    buf.pop();
+                    if buf.last() == Some(&b'\r') {
+                        buf.pop();
+                    }
+                }
+            }
+            RecordMode::Delimiter(delimiter) => {
+                if self.reader.read_until(delimiter, &mut buf)? == 0 {
+                    return Ok(None);
+                }
+                if buf.last() == Some(&delimiter) {
+                    buf.pop();
+                }
+            }
+            RecordMode::FixedLength => {
+                let record_length = self.file_description.record_length() as u64;
+                if record_length == 0 {
+                    return Ok(None);
+                }
+                if (&mut self.reader).take(record_length).read_to_end(&mut buf)? == 0 {
+                    return Ok(None);
+                }
+            }
+        }
+
+        String::from_utf8(buf)
+            .map(Some)
+            .context("Record is not valid UTF-8")
+    }
+}
+
+impl<R: BufRead> Iterator for RecordIterator<R> {
+    type Item = Result<HashMap<String, Option<String>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.read_record() {
+                Ok(Some(line)) => {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+
+                    if self.strict
+                        && self.delimiter.is_none()
+                        && let Err(e) = self.file_description.validate_record(&line)
+                    {
+                        return Some(Err(e));
+                    }
+
+                    let record = self.parse_line(&line);
+                    return Some(Ok(record));
+                }
+                Err(e) => {
+                    return Some(Err(e));
+                }
+                Ok(None) => {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> RecordIterator<R> {
+    /// Consumes the iterator, counting total records and distinct key values.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_field` - The field code to count
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is not in the schema or a line cannot
+    /// be read.
+    pub fn key_counts(self, key_field: &str) -> Result<KeyCounts> {
+        if self.file_description.get_field(key_field).is_none() {
+            anyhow::bail!(
+                "Field {} not found in schema for {}",
+                key_field,
+                self.file_description.filename
+            );
+        }
+
+        let key_field = key_field.to_string();
+        let mut distinct = HashSet::new();
+        let mut total_records = 0;
+        let mut null_keys = 0;
+
+        for record_result in self {
+            let mut record = record_result?;
+            total_records += 1;
+
+            match record.remove(&key_field).flatten() {
+                Some(key) => {
+                    distinct.insert(key);
+                }
+                None => null_keys += 1,
+            }
+        }
+
+        Ok(KeyCounts {
+            total_records,
+            distinct_keys: distinct.len(),
+            null_keys,
+        })
+    }
+
+    /// Consumes the iterator, collecting the distinct non-null values of a key field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is not in the schema or a line cannot
+    /// be read.
+    fn key_set(self, key_field: &str) -> Result<HashSet<String>> {
+        if self.file_description.get_field(key_field).is_none() {
+            anyhow::bail!(
+                "Field {} not found in schema for {}",
+                key_field,
+                self.file_description.filename
+            );
         }
 
-        Some(value.to_string())
+        let mut keys = HashSet::new();
+        for record_result in self {
+            if let Some(key) = record_result?.remove(key_field).flatten() {
+                keys.insert(key);
+            }
+        }
+
+        Ok(keys)
+    }
+
+    /// Consumes the iterator, returning the most common non-null values of a field.
+    ///
+    /// See `DataParser::value_frequencies` for the ordering.
+    ///
+    /// # Arguments
+    ///
+    /// * `field_code` - The field code to tally
+    /// * `top_n` - The maximum number of values to return
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is not in the schema or a record cannot
+    /// be read.
+    pub fn value_frequencies(self, field_code: &str, top_n: usize) -> Result<Vec<(String, usize)>> {
+        if self.file_description.get_field(field_code).is_none() {
+            anyhow::bail!(
+                "Field {} not found in schema for {}",
+                field_code,
+                self.file_description.filename
+            );
+        }
+
+        let field_code = field_code.to_string();
+        let mut frequencies: HashMap<String, usize> = HashMap::new();
+
+        for record_result in self {
+            let mut record = record_result?;
+
+            if let Some(value) = record.remove(&field_code).flatten() {
+                *frequencies.entry(value).or_insert(0) += 1;
+            }
+        }
+
+        let mut frequencies: Vec<(String, usize)> = frequencies.into_iter().collect();
+        frequencies.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        frequencies.truncate(top_n);
+
+        Ok(frequencies)
+    }
+
+    /// Consumes the iterator, returning the fields that are NULL in every record.
+    ///
+    /// See `DataParser::always_null_fields`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a record cannot be read.
+    pub fn always_null_fields(self) -> Result<Vec<String>> {
+        let mut null_fields: Vec<String> = self
+            .file_description
+            .ordered_fields()
+            .into_iter()
+            .map(|(code, _)| code.to_string())
+            .collect();
+        let mut any_records = false;
+
+        for record_result in self {
+            let record = record_result?;
+            any_records = true;
+
+            null_fields.retain(|code| record.get(code).is_none_or(Option::is_none));
+            if null_fields.is_empty() {
+                break;
+            }
+        }
+
+        if !any_records {
+            null_fields.clear();
+        }
+
+        Ok(null_fields)
+    }
+
+    /// Consumes the iterator, counting per field the records that end before it starts.
+    ///
+    /// See `DataParser::field_overruns`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a record cannot be read.
+    pub fn field_overruns(mut self) -> Result<Vec<FieldOverrun>> {
+        if self.delimiter.is_some() {
+            return Ok(Vec::new());
+        }
+
+        let fields: Vec<(String, FieldDefinition)> = self
+            .file_description
+            .ordered_fields()
+            .into_iter()
+            .map(|(code, field)| (code.to_string(), field.clone()))
+            .collect();
+        let mut beyond = vec![0usize; fields.len()];
+        let mut records = 0;
+
+        while let Some(line) = self.read_record()? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            records += 1;
+
+            for (count, (_, field)) in beyond.iter_mut().zip(&fields) {
+                if field.starts_beyond(&line) {
+                    *count += 1;
+                }
+            }
+        }
+
+        Ok(fields
+            .into_iter()
+            .zip(beyond)
+            .filter(|(_, beyond)| *beyond > 0)
+            .map(|((field, _), beyond)| FieldOverrun { field, beyond, records })
+            .collect())
+    }
+
+    /// Consumes the iterator, counting records longer than the schema.
+    ///
+    /// See `DataParser::trailing_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a record cannot be read.
+    pub fn trailing_bytes(mut self) -> Result<Option<TrailingBytes>> {
+        let expected = self.file_description.record_length();
+        let has_from_end = self
+            .file_description
+            .ordered_fields()
+            .iter()
+            .any(|(_, field)| field.from_end);
+        if expected == 0 || has_from_end || self.delimiter.is_some() {
+            return Ok(None);
+        }
+
+        let mut lengths: HashMap<usize, usize> = HashMap::new();
+        let mut total_records = 0;
+
+        while let Some(line) = self.read_record()? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            total_records += 1;
+
+            if line.len() > expected {
+                *lengths.entry(line.len() - expected).or_default() += 1;
+            }
+        }
+
+        let Some((&common_length, _)) = lengths
+            .iter()
+            .max_by(|(a_len, a_count), (b_len, b_count)| a_count.cmp(b_count).then(b_len.cmp(a_len)))
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(TrailingBytes {
+            records: lengths.values().sum(),
+            total_records,
+            common_length,
+            expected,
+        }))
+    }
+
+    /// Parses a single line into a record.
+    ///
+    /// This is an internal helper that extracts all fields according to the schema.
+    fn parse_line(&self, line: &str) -> HashMap<String, Option<String>> {
+        parse_record(
+            &self.file_description,
+            line,
+            self.control_chars,
+            self.delimiter,
+            &self.raw_fields,
+            self.empty_to_null,
+        )
     }
 }
 
-/// Iterator over records in a DAT file.
+/// Streams the records of a DAT file as Arrow `RecordBatch`es.
 ///
-/// This iterator reads lines from a buffered reader and parses each line
-/// into a record using the provided schema. It automatically skips empty lines.
+/// Columns are ordered by their start position in the fixed-width record.
+/// DECIMAL fields become `Float64` columns and all other fields become
+/// `Utf8` columns. Values coerced to `None` (and DECIMAL values that do not
+/// parse as numbers) are recorded as Arrow nulls. A record that fails to
+/// parse ends its batch early: the rows read before it are yielded as a
+/// shorter batch, then the error, then batches continue after it.
 ///
-/// The iterator yields `Result<HashMap<String, Option<String>>>` where:
-/// - The `HashMap` keys are field codes from the schema
-/// - The values are `Option<String>` (None for null/missing values)
-/// - The `Result` captures any I/O errors during reading
+/// Requires the `arrow` feature.
 ///
-/// # Type Parameters
+/// # Arguments
 ///
-/// * `R` - A type that implements `BufRead` (typically `BufReader<File>`)
-pub struct RecordIterator<R: BufRead> {
-    lines: Lines<R>,
-    file_description: FileDescription,
+/// * `file_id` - The file identifier (e.g., "OFNT1BA1")
+/// * `data_dir` - The data directory containing `{file_id}/{file_id}.des` and `.dat`
+/// * `batch_size` - The maximum number of rows per batch
+///
+/// # Errors
+///
+/// Returns an error if `batch_size` is zero or the DES or DAT file cannot be
+/// opened. Read errors while streaming are yielded by the iterator.
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::parser::records_to_arrow;
+/// use std::path::Path;
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// for batch in records_to_arrow("OFNT1BA1", Path::new("./data"), 8192)? {
+///     let batch = batch?;
+///     println!("{} rows", batch.num_rows());
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "arrow")]
+pub fn records_to_arrow(
+    file_id: &str,
+    data_dir: &std::path::Path,
+    batch_size: usize,
+) -> Result<ArrowBatchIterator<DatReader>> {
+    if batch_size == 0 {
+        anyhow::bail!("Arrow batch size must be greater than zero");
+    }
+
+    let file_description = FileDescription::from_data_dir(file_id, data_dir)?;
+    let file_path = extracted_file_path(data_dir, file_id, "dat");
+
+    let file = open_extracted_file(data_dir, file_id, "dat").with_context(|| {
+        format!("Failed to open DAT file: {}", file_path.display())
+    })?;
+
+    Ok(ArrowBatchIterator::new(
+        RecordIterator::new(BufReader::new(file), file_description),
+        batch_size,
+    ))
 }
 
-impl<R: BufRead> RecordIterator<R> {
-    /// Creates a new `RecordIterator`.
+/// Iterator adapter that groups parsed records into Arrow `RecordBatch`es.
+///
+/// Created by [`records_to_arrow`]. Requires the `arrow` feature.
+#[cfg(feature = "arrow")]
+pub struct ArrowBatchIterator<R: BufRead> {
+    records: RecordIterator<R>,
+    fields: Vec<(String, bool)>,
+    schema: arrow_schema::SchemaRef,
+    batch_size: usize,
+    /// A record error held back until the rows read before it are yielded
+    pending_error: Option<anyhow::Error>,
+}
+
+#[cfg(feature = "arrow")]
+impl<R: BufRead> ArrowBatchIterator<R> {
+    /// Creates a new `ArrowBatchIterator` over parsed records.
     ///
     /// # Arguments
     ///
-    /// * `reader` - A buffered reader for the DAT file
-    /// * `file_description` - The schema definition for parsing records
-    pub fn new(reader: R, file_description: FileDescription) -> Self {
+    /// * `records` - The record iterator to batch
+    /// * `batch_size` - The maximum number of rows per batch
+    pub fn new(records: RecordIterator<R>, batch_size: usize) -> Self {
+        let mut definitions: Vec<_> = records.file_description.schema.iter().collect();
+        definitions.sort_by_key(|(code, definition)| (definition.start, (*code).clone()));
+
+        let fields: Vec<(String, bool)> = definitions
+            .iter()
+            .map(|(code, definition)| ((*code).clone(), definition.field_type == "DECIMAL"))
+            .collect();
+
+        let schema = std::sync::Arc::new(arrow_schema::Schema::new(
+            fields
+                .iter()
+                .map(|(code, is_decimal)| {
+                    let data_type = if *is_decimal {
+                        arrow_schema::DataType::Float64
+                    } else {
+                        arrow_schema::DataType::Utf8
+                    };
+                    arrow_schema::Field::new(code, data_type, true)
+                })
+                .collect::<Vec<_>>(),
+        ));
+
         Self {
-            lines: reader.lines(),
-            file_description,
+            records,
+            fields,
+            schema,
+            batch_size: batch_size.max(1),
+            pending_error: None,
         }
     }
+
+    /// Returns the Arrow schema of the batches produced by this iterator.
+    pub fn schema(&self) -> arrow_schema::SchemaRef {
+        std::sync::Arc::clone(&self.schema)
+    }
 }
 
-impl<R: BufRead> Iterator for RecordIterator<R> {
-    type Item = Result<HashMap<String, Option<String>>>;
+#[cfg(feature = "arrow")]
+impl<R: BufRead> Iterator for ArrowBatchIterator<R> {
+    type Item = Result<arrow_array::RecordBatch>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            match self.lines.next() {
-                Some(Ok(line)) => {
-                    if line.trim().is_empty() {
-                        continue;
-                    }
+        use arrow_array::builder::{Float64Builder, StringBuilder};
+        use arrow_array::ArrayRef;
 
-                    let record = self.parse_line(&line);
-                    return Some(Ok(record));
-                }
-                Some(Err(e)) => {
-                    return Some(Err(e.into()));
-                }
-                None => {
-                    return None;
+        if let Some(e) = self.pending_error.take() {
+            return Some(Err(e));
+        }
+
+        // A record error ends the batch early: the rows before it are
+        // yielded first and the error on the following call
+        let mut rows = Vec::with_capacity(self.batch_size);
+        for record_result in self.records.by_ref().take(self.batch_size) {
+            match record_result {
+                Ok(record) => rows.push(record),
+                Err(e) if rows.is_empty() => return Some(Err(e)),
+                Err(e) => {
+                    self.pending_error = Some(e);
+                    break;
                 }
             }
         }
-    }
-}
 
-impl<R: BufRead> RecordIterator<R> {
-    /// Parses a single line into a record.
-    ///
-    /// This is an internal helper that extracts all fields according to the schema.
-    fn parse_line(&self, line: &str) -> HashMap<String, Option<String>> {
-        let mut record = HashMap::new();
-
-        for (field_code, field_def) in &self.file_description.schema {
-            let slice_start = field_def.start.saturating_sub(1);
-            let slice_end = slice_start + field_def.length;
+        if rows.is_empty() {
+            return None;
+        }
 
-            let raw_value = if line.len() >= slice_end {
-                &line[slice_start..slice_end]
-            } else if line.len() > slice_start {
-                &line[slice_start..]
-            } else {
-                ""
-            };
+        let columns: Vec<ArrayRef> = self
+            .fields
+            .iter()
+            .map(|(code, is_decimal)| {
+                let values = rows.iter().map(|row| row.get(code).and_then(|v| v.as_deref()));
 
-            let coerced_value = DataParser::coerce_value(raw_value);
-            record.insert(field_code.clone(), coerced_value);
-        }
+                if *is_decimal {
+                    let mut builder = Float64Builder::with_capacity(rows.len());
+                    for value in values {
+                        builder.append_option(value.and_then(|v| v.parse::<f64>().ok()));
+                    }
+                    std::sync::Arc::new(builder.finish()) as ArrayRef
+                } else {
+                    let mut builder = StringBuilder::new();
+                    for value in values {
+                        builder.append_option(value);
+                    }
+                    std::sync::Arc::new(builder.finish()) as ArrayRef
+                }
+            })
+            .collect();
 
-        record
+        Some(
+            arrow_array::RecordBatch::try_new(self.schema(), columns)
+                .context("Failed to build Arrow record batch"),
+        )
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::write_fixture;
     use std::io::Cursor;
 
     fn create_test_schema() -> FileDescription {
@@ -390,6 +2348,12 @@ NOTES         ADDITIONAL NOTES                   CHAR      23      10"#;
         }
     }
 
+    fn test_parser() -> DataParser {
+        let mut parser = DataParser::from_description(create_test_schema(), &data_directory());
+        parser.set_control_char_policy(ControlCharPolicy::Preserve);
+        parser
+    }
+
     #[test]
     fn test_coerce_value_basic() {
         assert_eq!(
@@ -436,13 +2400,26 @@ NOTES         ADDITIONAL NOTES                   CHAR      23      10"#;
         );
     }
 
+    #[test]
+    fn test_is_null_marker_matches_coerce_value() {
+        let samples = [
+            "", " ", "\t", "0001-01-01", "  0001-01-01  ", "?", "???", "  ???  ",
+            "what?", "a?b", "0001-01-02", "0", "valid", " 12 ", "? ?",
+        ];
+
+        for sample in samples {
+            assert_eq!(
+                is_null_marker(sample),
+                DataParser::coerce_value(sample).is_none(),
+                "is_null_marker disagrees with coerce_value for {:?}",
+                sample
+            );
+        }
+    }
+
     #[test]
     fn test_parse_line_basic() {
-        let file_desc = create_test_schema();
-        let parser = DataParser {
-            file_id: "TEST".to_string(),
-            file_description: file_desc,
-        };
+        let parser = test_parser();
 
         let line = "1234567AB123more data here";
         let record = parser.parse_line(line);
@@ -454,11 +2431,7 @@ NOTES         ADDITIONAL NOTES                   CHAR      23      10"#;
 
     #[test]
     fn test_parse_line_with_whitespace() {
-        let file_desc = create_test_schema();
-        let parser = DataParser {
-            file_id: "TEST".to_string(),
-            file_description: file_desc,
-        };
+        let parser = test_parser();
 
         let line = "123    AB 001       ";
         let record = parser.parse_line(line);
@@ -470,11 +2443,7 @@ NOTES         ADDITIONAL NOTES                   CHAR      23      10"#;
 
     #[test]
     fn test_parse_line_with_null_markers() {
-        let file_desc = create_test_schema();
-        let parser = DataParser {
-            file_id: "TEST".to_string(),
-            file_description: file_desc,
-        };
+        let parser = test_parser();
 
         let line = "1234567AB1230001-01-01???       ";
         let record = parser.parse_line(line);
@@ -488,11 +2457,7 @@ NOTES         ADDITIONAL NOTES                   CHAR      23      10"#;
 
     #[test]
     fn test_parse_line_short_line() {
-        let file_desc = create_test_schema();
-        let parser = DataParser {
-            file_id: "TEST".to_string(),
-            file_description: file_desc,
-        };
+        let parser = test_parser();
 
         let line = "123";
         let record = parser.parse_line(line);
@@ -540,6 +2505,421 @@ NOTES         ADDITIONAL NOTES                   CHAR      23      10"#;
         assert!(iterator.next().is_none());
     }
 
+    #[test]
+    fn test_multibyte_character_straddling_field_boundary() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        // "é" is two bytes, 12 and 13, so it straddles NCSRNAME and NCSRSUFX;
+        // the second record ends partway through NCSRSUFX
+        write_fixture(
+            temp_dir.path(),
+            "UTF801",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             NCSRNAME      OFFENDER NAME                      CHAR      8       5\n\
+             NCSRSUFX      OFFENDER NAME SUFFIX               CHAR     13       4\n",
+            "0000001RENEéJR \n0000002JOSÉ X\n",
+        );
+
+        let parser = DataParser::from_data_dir("UTF801", temp_dir.path()).unwrap();
+        let records: Vec<_> = parser.parse().unwrap().collect::<Result<_>>().unwrap();
+
+        assert_eq!(records[0]["CMDORNUM"].as_deref(), Some("0000001"));
+        assert_eq!(records[0]["NCSRNAME"].as_deref(), Some("RENE\u{FFFD}"));
+        assert_eq!(records[0]["NCSRSUFX"].as_deref(), Some("\u{FFFD}JR"));
+        assert_eq!(records[1]["NCSRNAME"].as_deref(), Some("JOSÉ"));
+        assert_eq!(records[1]["NCSRSUFX"].as_deref(), Some("X"));
+
+        let line = "0000001RENEéJR ";
+        assert_eq!(parser.parse_line(line)["NCSRNAME"].as_deref(), Some("RENE\u{FFFD}"));
+    }
+
+    #[test]
+    fn test_strict_parser_rejects_short_records() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        write_fixture(
+            temp_dir.path(),
+            "SHORT01",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CASENUM       CASE NUMBER                        CHAR      8       3\n",
+            "0000001A01\n0000002\n0000003C03\n",
+        );
+
+        let mut parser = DataParser::from_data_dir("SHORT01", temp_dir.path()).unwrap();
+        assert!(!parser.is_strict());
+        let records: Vec<_> = parser.parse().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1]["CASENUM"], None);
+
+        parser.set_strict(true);
+        let results: Vec<_> = parser.parse().unwrap().collect();
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert!(results[1].as_ref().unwrap_err().to_string().contains("Record is 7 bytes"));
+        assert_eq!(results[2].as_ref().unwrap()["CASENUM"].as_deref(), Some("C03"));
+
+        let guard = crate::utilities::DataDirectoryGuard::new(Some(temp_dir.path()));
+        let strict = DataParser::new_strict("SHORT01").unwrap();
+        drop(guard);
+        assert!(strict.is_strict());
+        let results: Vec<_> = strict.parse().unwrap().collect();
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn test_sample_coercions_reports_null_date_rule() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        write_fixture(
+            temp_dir.path(),
+            "SAMPLE01",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CMBIRDAT      OFFENDER BIRTH DATE                DATE      8      10\n\
+             CMSEXCOD      OFFENDER GENDER CODE               CHAR     18       6\n",
+            "00000010001-01-01MALE  \n\n00000021980-05-17??????\n00000031990-01-01FEMALE\n",
+        );
+
+        let parser = DataParser::from_data_dir("SAMPLE01", temp_dir.path()).unwrap();
+        let samples = parser.sample_coercions(2).unwrap();
+        assert_eq!(samples.len(), 2);
+
+        let first: Vec<_> = samples[0].nulled().collect();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].field, "CMBIRDAT");
+        assert_eq!(first[0].raw, "0001-01-01");
+        assert_eq!(first[0].rule, CoercionRule::NullDate);
+
+        // The blank line is skipped, so the second sample is the next record
+        assert_eq!(samples[1].record, 2);
+        let rules: Vec<_> = samples[1].fields.iter().map(|field| (field.field.as_str(), field.rule)).collect();
+        assert_eq!(
+            rules,
+            vec![
+                ("CMDORNUM", CoercionRule::Preserved),
+                ("CMBIRDAT", CoercionRule::Preserved),
+                ("CMSEXCOD", CoercionRule::AllQuestionMarks),
+            ]
+        );
+        assert_eq!(samples[1].fields[1].value.as_deref(), Some("1980-05-17"));
+    }
+
+    #[test]
+    fn test_parallel_key_set_matches_serial() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        // Repeated keys land in different ranges; blank keys are not collected
+        let dat: String = (0..1000)
+            .map(|i| if i % 97 == 0 { "       \n".to_string() } else { format!("{:07}\n", i % 300) })
+            .collect();
+        write_fixture(
+            temp_dir.path(),
+            "KEYS01",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+            dat,
+        );
+
+        let parser = DataParser::from_data_dir("KEYS01", temp_dir.path()).unwrap();
+        let serial = parser.key_set("CMDORNUM", 1).unwrap();
+        let parallel = parser.key_set("CMDORNUM", 4).unwrap();
+
+        assert_eq!(serial.len(), 300);
+        assert_eq!(parallel, serial);
+        assert!(parser.key_set("MISSING", 4).is_err());
+    }
+
+    #[test]
+    fn test_fixed_length_records_without_newlines() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        write_fixture(
+            temp_dir.path(),
+            "FIXED01",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CPPREFIX      COP COMMITMENT PREFIX              CHAR      8       2\n",
+            "0000001AB0000002  0000003CD",
+        );
+
+        let mut parser = DataParser::from_data_dir("FIXED01", temp_dir.path()).unwrap();
+        parser.set_record_mode(RecordMode::FixedLength);
+
+        let records: Vec<_> = parser.parse().unwrap().collect::<Result<_>>().unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].get("CPPREFIX"), Some(&Some("AB".to_string())));
+        assert_eq!(records[1].get("CMDORNUM"), Some(&Some("0000002".to_string())));
+        assert_eq!(records[1].get("CPPREFIX"), Some(&None));
+        assert_eq!(records[2].get("CMDORNUM"), Some(&Some("0000003".to_string())));
+    }
+
+    #[test]
+    fn test_first_record() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let des = "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                   CPPREFIX      COP COMMITMENT PREFIX              CHAR      8       2\n";
+        write_fixture(temp_dir.path(), "FIRST01", des, "\n  \n0000001AB\n0000002CD\n");
+        write_fixture(temp_dir.path(), "EMPTY01", des, "\n");
+
+        let parser = DataParser::from_data_dir("FIRST01", temp_dir.path()).unwrap();
+        let record = parser.first_record().unwrap().unwrap();
+        assert_eq!(record.get("CMDORNUM"), Some(&Some("0000001".to_string())));
+        assert_eq!(record.get("CPPREFIX"), Some(&Some("AB".to_string())));
+
+        let empty = DataParser::from_data_dir("EMPTY01", temp_dir.path()).unwrap();
+        assert!(empty.first_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_infer_key_candidates_finds_unique_field() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        write_fixture(
+            temp_dir.path(),
+            "KEY01",
+            "CASECODE      CASE STATUS                        CHAR      1       2\n\
+             CASENUM       CASE NUMBER                        CHAR      3       5\n\
+             CASENOTE      CASE NOTE                          CHAR      8       3\n",
+            "OPA0001ONE\nOPA0002   \nCLA0003TWO\nOPA0004SIX\n",
+        );
+
+        let parser = DataParser::from_data_dir("KEY01", temp_dir.path()).unwrap();
+        assert_eq!(parser.infer_key_candidates(100).unwrap(), vec!["CASENUM"]);
+        // Every non-null field is trivially unique in a one-record sample.
+        assert_eq!(parser.infer_key_candidates(1).unwrap(), vec!["CASECODE", "CASENUM", "CASENOTE"]);
+    }
+
+    #[test]
+    fn test_truncated_final_record_is_flagged() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for (file_id, dat) in [
+            ("TRUNC01", "0000001AB\n0000002\n0000003AB\n0000004A\n\n"),
+            ("TRUNC02", "0000001AB\n0000002\n0000003AB\r\n"),
+        ] {
+            write_fixture(
+                temp_dir.path(),
+                file_id,
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 CPPREFIX      COP COMMITMENT PREFIX              CHAR      8       2\n",
+                dat,
+            );
+        }
+
+        let truncated = DataParser::from_data_dir("TRUNC01", temp_dir.path()).unwrap();
+        assert_eq!(
+            truncated.truncated_final_record().unwrap(),
+            Some(TruncatedRecord { length: 8, expected: 9 })
+        );
+
+        // A short line before a full-length final record is not reported.
+        let complete = DataParser::from_data_dir("TRUNC02", temp_dir.path()).unwrap();
+        assert_eq!(complete.truncated_final_record().unwrap(), None);
+    }
+
+    #[test]
+    fn test_truncated_final_record_counts_bytes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        // "É" is two bytes, so both records fill the 12-byte schema
+        let file_dir = write_fixture(
+            temp_dir.path(),
+            "TRUNC03",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             NCSRNAME      OFFENDER NAME                      CHAR      8       5\n",
+            "0000001RENEE\n0000002RENÉ\n",
+        );
+
+        let parser = DataParser::from_data_dir("TRUNC03", temp_dir.path()).unwrap();
+        assert_eq!(parser.truncated_final_record().unwrap(), None);
+
+        std::fs::write(file_dir.join("TRUNC03.dat"), "0000001RENEE\n0000002JOÉ\n").unwrap();
+        let truncated = parser.truncated_final_record().unwrap().unwrap();
+        assert_eq!(truncated, TruncatedRecord { length: 11, expected: 12 });
+        assert_eq!(truncated.to_string(), "Final record is 11 bytes long, expected 12 (file may be truncated)");
+    }
+
+    #[test]
+    fn test_raw_lines_match_input_verbatim() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        write_fixture(
+            temp_dir.path(),
+            "RAW01",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CPPREFIX      COP COMMITMENT PREFIX              CHAR      8       4\n",
+            "0000001 AB \n\n   \n0000002????\r\n0000003    \n",
+        );
+
+        let parser = DataParser::from_data_dir("RAW01", temp_dir.path()).unwrap();
+        let lines: Vec<String> = parser.raw_lines().unwrap().collect::<Result<_>>().unwrap();
+
+        assert_eq!(lines, vec!["0000001 AB ", "0000002????", "0000003    "]);
+    }
+
+    #[test]
+    fn test_empty_to_null_option_keeps_blank_fields() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        write_fixture(
+            temp_dir.path(),
+            "BLANK01",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CPPREFIX      COP COMMITMENT PREFIX              CHAR      8       2\n\
+             CPSTATUS      STATUS                             CHAR      10      3\n",
+            "0000001  ???\n",
+        );
+
+        let mut parser = DataParser::from_data_dir("BLANK01", temp_dir.path()).unwrap();
+        let record = parser.first_record().unwrap().unwrap();
+        assert_eq!(record.get("CPPREFIX"), Some(&None));
+
+        parser.set_empty_to_null(false);
+        let record = parser.first_record().unwrap().unwrap();
+        assert_eq!(record.get("CPPREFIX"), Some(&Some(String::new())));
+        assert_eq!(record.get("CPSTATUS"), Some(&None));
+    }
+
+    #[test]
+    fn test_explain_coercion_identifies_rule() {
+        let cases = [
+            ("   ", CoercionRule::EmptyAfterTrim),
+            ("", CoercionRule::EmptyAfterTrim),
+            (" 0001-01-01 ", CoercionRule::NullDate),
+            ("???", CoercionRule::AllQuestionMarks),
+            ("  123  ", CoercionRule::Preserved),
+            ("what?", CoercionRule::Preserved),
+            ("0001-01-02", CoercionRule::Preserved),
+        ];
+
+        for (raw, rule) in cases {
+            let explanation = DataParser::explain_coercion(raw);
+            assert_eq!(explanation.rule, rule, "rule for {:?}", raw);
+            assert_eq!(explanation.value, DataParser::coerce_value(raw), "value for {:?}", raw);
+        }
+
+        assert_eq!(DataParser::explain_coercion("  123  ").value, Some("123".to_string()));
+    }
+
+    #[test]
+    fn test_key_set_difference() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        for (file_id, dat) in [
+            ("SETA01", "0000001\n0000002\n0000003\n0000003\n???????\n"),
+            ("SETB01", "0000002001\n0000003001\n0000003002\n0000004001\n"),
+        ] {
+            let des = if file_id == "SETA01" {
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n"
+            } else {
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 CIDORNUM      SENTENCE COMPONENT NUMBER          CHAR      8       3\n"
+            };
+            write_fixture(temp_dir.path(), file_id, des, dat);
+        }
+
+        let diff =
+            key_set_difference("SETA01", "CMDORNUM", "SETB01", "CMDORNUM", temp_dir.path()).unwrap();
+
+        assert_eq!(
+            diff,
+            SetDiff {
+                only_in_a: 1,
+                only_in_b: 1,
+                in_both: 2,
+            }
+        );
+
+        assert!(key_set_difference("SETA01", "NOPE", "SETB01", "CMDORNUM", temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_parse_filtered_keeps_matching_records() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        write_fixture(
+            temp_dir.path(),
+            "FILTER01",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CPPREFIX      COP COMMITMENT PREFIX              CHAR      8       2\n",
+            "0000001AB\n0000002CD\n0000003AB\n0000004  \n",
+        );
+
+        let parser = DataParser::from_data_dir("FILTER01", temp_dir.path()).unwrap();
+        let ids: Vec<String> = parser
+            .parse_filtered(|record| {
+                record.get("CPPREFIX").and_then(|v| v.as_deref()) == Some("AB")
+            })
+            .unwrap()
+            .map(|record| record.unwrap()["CMDORNUM"].clone().unwrap())
+            .collect();
+
+        assert_eq!(ids, vec!["0000001", "0000003"]);
+    }
+
+    #[test]
+    fn test_control_characters_cleaned_only_when_enabled() {
+        let file_desc = create_test_schema();
+        let data = "1234567AB123\x07\x01        NOTE\x07TEXT\n";
+
+        let reader = BufReader::new(Cursor::new(data));
+        let record = RecordIterator::new(reader, file_desc.clone()).next().unwrap().unwrap();
+        assert_eq!(record.get("NOTES"), Some(&Some("NOTE\x07TEXT".to_string())));
+
+        let reader = BufReader::new(Cursor::new(data));
+        let record = RecordIterator::new(reader, file_desc.clone())
+            .with_control_char_policy(ControlCharPolicy::Strip)
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.get("NOTES"), Some(&Some("NOTETEXT".to_string())));
+        assert_eq!(record.get("DTOFUPDT"), Some(&None));
+
+        let reader = BufReader::new(Cursor::new(data));
+        let record = RecordIterator::new(reader, file_desc)
+            .with_control_char_policy(ControlCharPolicy::Replace(' '))
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.get("NOTES"), Some(&Some("NOTE TEXT".to_string())));
+    }
+
+    #[test]
+    fn test_end_relative_field() {
+        let content = "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                       TRAILER       TRAILING CODE                      CHAR      -4      4\n";
+        let file_desc = FileDescription {
+            filename: "TEST".to_string(),
+            schema: FileDescription::parse_content(content).unwrap(),
+        };
+        assert!(file_desc.get_field("TRAILER").unwrap().from_end);
+
+        let data = "0000001XYZABCD\n0000002LONGER MIDDLEWXYZ\n0000003AB\n";
+        let records: Vec<_> = RecordIterator::new(BufReader::new(Cursor::new(data)), file_desc)
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(records[0].get("TRAILER"), Some(&Some("ABCD".to_string())));
+        assert_eq!(records[1].get("TRAILER"), Some(&Some("WXYZ".to_string())));
+        assert_eq!(records[2].get("TRAILER"), Some(&Some("03AB".to_string())));
+        assert_eq!(records[1].get("CMDORNUM"), Some(&Some("0000002".to_string())));
+    }
+
+    #[test]
+    fn test_pipe_delimited_matches_fixed_width() {
+        let mut parser = test_parser();
+
+        let fixed = parser.parse_line("1234567AB1232024-01-15NOTES HERE");
+
+        parser.set_delimited(Some('|'));
+        let delimited = parser.parse_line("1234567|AB|123|2024-01-15|NOTES HERE");
+        assert_eq!(delimited, fixed);
+
+        let short = parser.parse_line("7654321| CD |???");
+        assert_eq!(short.get("CPPREFIX"), Some(&Some("CD".to_string())));
+        assert_eq!(short.get("CPPAYSEQ"), Some(&None));
+        assert_eq!(short.get("NOTES"), Some(&None));
+    }
+
+    #[test]
+    fn test_delimiter_records() {
+        let file_desc = create_test_schema();
+        let reader = BufReader::new(Cursor::new("1234567AB123\x1e7654321CD456\x1e"));
+
+        let records: Vec<_> = RecordIterator::with_mode(reader, file_desc, RecordMode::Delimiter(0x1e))
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].get("CPPAYSEQ"), Some(&Some("456".to_string())));
+    }
+
     #[test]
     fn test_record_iterator_empty_file() {
         let file_desc = create_test_schema();
@@ -590,6 +2970,185 @@ NOTES         ADDITIONAL NOTES                   CHAR      23      10"#;
         );
     }
 
+    #[test]
+    fn test_key_counts_with_duplicates() {
+        let file_desc = create_test_schema();
+        let data = "1234567AB123\n7654321CD456\n1234567EF789\n???????GH000\n7654321IJ111\n";
+        let reader = BufReader::new(Cursor::new(data));
+
+        let counts = RecordIterator::new(reader, file_desc)
+            .key_counts("CMDORNUM")
+            .unwrap();
+
+        assert_eq!(counts.total_records, 5);
+        assert_eq!(counts.distinct_keys, 2);
+        assert_eq!(counts.null_keys, 1);
+        assert_eq!(counts.duplicate_records(), 2);
+    }
+
+    #[test]
+    fn test_always_null_fields() {
+        let file_desc = create_test_schema();
+        let data = "0000001  001          NOTE ONE  \n\
+                    0000002  0020001-01-01          \n\
+                    0000003  003????????????????????\n";
+
+        let reader = BufReader::new(Cursor::new(data));
+        let fields = RecordIterator::new(reader, file_desc.clone())
+            .always_null_fields()
+            .unwrap();
+        assert_eq!(fields, vec!["CPPREFIX".to_string(), "DTOFUPDT".to_string()]);
+
+        let reader = BufReader::new(Cursor::new(""));
+        assert!(RecordIterator::new(reader, file_desc).always_null_fields().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_field_overruns_flag_field_past_every_line() {
+        let file_desc = create_test_schema();
+        // No line reaches NOTES (position 23); the second holds only the key
+        let data = "00000010100120240101\n0000002\n0000003AB0032024010199\n";
+
+        let reader = BufReader::new(Cursor::new(data));
+        let overruns = RecordIterator::new(reader, file_desc).field_overruns().unwrap();
+
+        assert_eq!(overruns.len(), 4);
+        let notes = overruns.iter().find(|overrun| overrun.field == "NOTES").unwrap();
+        assert_eq!(notes.beyond, 3);
+        assert_eq!(notes.records, 3);
+        assert!(notes.is_always_beyond());
+
+        let prefix = overruns.iter().find(|overrun| overrun.field == "CPPREFIX").unwrap();
+        assert_eq!(prefix.beyond, 1);
+        assert!(!prefix.is_always_beyond());
+    }
+
+    #[test]
+    fn test_write_fixed_width_round_trips_records() {
+        let file_desc = create_test_schema();
+        let data = "0000001AB0012024-01-15NOTE ONE  \n\
+                    0000002  002          NOTE TWO  \n";
+
+        let reader = BufReader::new(Cursor::new(data));
+        let records: Vec<_> = RecordIterator::new(reader, file_desc.clone())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(records[1]["CPPREFIX"], None);
+
+        let mut output = Vec::new();
+        DataParser::write_fixed_width(&records, &file_desc, &mut output).unwrap();
+        assert_eq!(output, data.as_bytes());
+
+        let mut too_long = records[0].clone();
+        too_long.insert("CPPREFIX".to_string(), Some("ABC".to_string()));
+        let error = DataParser::write_fixed_width([too_long], &file_desc, Vec::new()).unwrap_err();
+        assert!(error.to_string().contains("CPPREFIX"));
+    }
+
+    #[test]
+    fn test_value_frequencies_ordering() {
+        let file_desc = create_test_schema();
+        let data = "0000001AB\n0000002CD\n0000003AB\n0000004  \n0000005EF\n0000006CD\n0000007AB\n";
+        let reader = BufReader::new(Cursor::new(data));
+
+        let frequencies = RecordIterator::new(reader, file_desc.clone())
+            .value_frequencies("CPPREFIX", 10)
+            .unwrap();
+
+        assert_eq!(
+            frequencies,
+            vec![
+                ("AB".to_string(), 3),
+                ("CD".to_string(), 2),
+                ("EF".to_string(), 1),
+            ]
+        );
+
+        let reader = BufReader::new(Cursor::new(data));
+        let top = RecordIterator::new(reader, file_desc)
+            .value_frequencies("CPPREFIX", 1)
+            .unwrap();
+        assert_eq!(top, vec![("AB".to_string(), 3)]);
+    }
+
+    #[test]
+    fn test_key_counts_unknown_field() {
+        let file_desc = create_test_schema();
+        let reader = BufReader::new(Cursor::new("1234567AB123\n"));
+
+        let result = RecordIterator::new(reader, file_desc).key_counts("NOPE");
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_records_to_arrow_batches() {
+        use arrow_array::{Array, Float64Array, StringArray};
+        use arrow_schema::DataType;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        write_fixture(
+            temp_dir.path(),
+            "ARROW01",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CPCOPBAL      COP BALANCE                        DECIMAL   8       6\n",
+            "0000001012.50\n0000002??????\n0000003000100\n",
+        );
+
+        let batches: Vec<_> = records_to_arrow("ARROW01", temp_dir.path(), 2)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].num_rows(), 2);
+        assert_eq!(batches[1].num_rows(), 1);
+
+        let schema = batches[0].schema();
+        assert_eq!(schema.field(0).name(), "CMDORNUM");
+        assert_eq!(schema.field(0).data_type(), &DataType::Utf8);
+        assert_eq!(schema.field(1).name(), "CPCOPBAL");
+        assert_eq!(schema.field(1).data_type(), &DataType::Float64);
+
+        let ids = batches[0].column(0).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(ids.value(1), "0000002");
+
+        let balances = batches[0].column(1).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(balances.value(0), 12.5);
+        assert!(balances.is_null(1));
+    }
+
+    #[cfg(feature = "arrow")]
+    #[test]
+    fn test_arrow_batches_keep_rows_before_record_error() {
+        use arrow_array::{Array, Float64Array};
+
+        let description = FileDescription {
+            filename: "ARROW02".to_string(),
+            schema: FileDescription::parse_content(
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+                 CPCOPBAL      COP BALANCE                        DECIMAL   8       6\n",
+            )
+            .unwrap(),
+        };
+        // The second record is short, which a strict iterator rejects; the
+        // fourth record's balance does not parse as a number
+        let dat = "0000001012.50\n0000002\n0000003000100\n00000041A.B00\n";
+        let records = RecordIterator::new(Cursor::new(dat), description).with_strict(true);
+        let mut batches = ArrowBatchIterator::new(records, 3);
+
+        assert_eq!(batches.next().unwrap().unwrap().num_rows(), 1);
+        assert!(batches.next().unwrap().is_err());
+
+        let batch = batches.next().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        let balances = batch.column(1).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert_eq!(balances.value(0), 100.0);
+        assert!(balances.is_null(1));
+
+        assert!(batches.next().is_none());
+    }
+
     #[test]
     fn test_data_parser_new() {
         let result = DataParser::new("NONEXISTENT_FILE_12345");
@@ -598,11 +3157,7 @@ NOTES         ADDITIONAL NOTES                   CHAR      23      10"#;
 
     #[test]
     fn test_data_parser_accessors() {
-        let file_desc = create_test_schema();
-        let parser = DataParser {
-            file_id: "TEST".to_string(),
-            file_description: file_desc,
-        };
+        let parser = test_parser();
 
         assert_eq!(parser.file_id(), "TEST");
         assert_eq!(parser.schema().filename, "TEST");
@@ -0,0 +1,1090 @@
+//! End-to-end processing pipeline.
+//!
+//! This module exposes the download → decompress → load → cleanup flow used by
+//! the CLI as a single library call. Interactive decisions are replaced by
+//! values supplied up front in [`PipelineConfig`], so the pipeline can be
+//! embedded in other tools and run unattended.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use ncdac_opi_parser::pipeline::{self, DownloadPolicy, PipelineConfig};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), anyhow::Error> {
+//! let mut config = PipelineConfig::new("database.db");
+//! config.download = DownloadPolicy::Missing;
+//!
+//! let handler = pipeline::run(config).await?;
+//! println!("{} errors", handler.errors.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::concurrency::{
+    create_worker_handler, process_with_serial_retry, DesFailureAggregator, ErrorAggregator,
+    WorkerConfig,
+};
+use crate::data_handler::{DataHandler, FileFailure, JournalState, ProcessingResults};
+use crate::download::{
+    are_decompressed_files_valid, categorize_files_before, categorize_files_offline,
+    download_data_file_before, get_file_status_before, get_local_file_status, ordered_by_priority,
+    FileStatus,
+};
+use crate::files::{get_file_by_id, recompress_extracted, FileMetadata, FILES};
+use crate::unzip::{MAX_ENTRY_COMPRESSION_RATIO, calculate_archive_stats, decompress_into_counting};
+use crate::utilities::{
+    count_lines, create_spinner, data_directory, delete_data_subdirectory_in, extracted_file_path, format_count,
+    format_duration,
+};
+use anyhow::{anyhow, Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How the pipeline handles data files that are missing or out of date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DownloadPolicy {
+    /// Never download; process whatever is already in the data directory
+    #[default]
+    Skip,
+    /// Download missing or incomplete files without prompting
+    Missing,
+    /// Never contact the server: trust local extracted data and ZIP files,
+    /// and fail if a required file is not present locally. The reference
+    /// file and every file in `PipelineConfig::files` are required.
+    Offline,
+}
+
+/// What the pipeline cleans up when a run fails.
+///
+/// The default leaves everything in place, so a failed run can be inspected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CleanupPolicy {
+    /// Delete the partially written output database
+    pub on_error_delete_db: bool,
+    /// Keep extracted data for debugging (ignored when `keep_data` is set)
+    pub on_error_keep_data: bool,
+}
+
+impl Default for CleanupPolicy {
+    fn default() -> Self {
+        Self {
+            on_error_delete_db: false,
+            on_error_keep_data: true,
+        }
+    }
+}
+
+/// Configuration for a full pipeline run.
+#[derive(Debug, Clone)]
+pub struct PipelineConfig {
+    /// Output SQLite database file path
+    pub output: PathBuf,
+    /// Reference file ID to use as foreign key source
+    pub reference_id: String,
+    /// Directory containing the ZIP files and extracted data
+    pub data_dir: PathBuf,
+    /// Keep extracted data files after processing
+    pub keep_data: bool,
+    /// Gzip the kept `.des` and `.dat` files after processing (only with `keep_data`)
+    pub compress_kept_data: bool,
+    /// How missing or out-of-date files are handled
+    pub download: DownloadPolicy,
+    /// Show progress bars and status messages on the terminal
+    pub show_progress: bool,
+    /// What to clean up when the run fails
+    pub cleanup: CleanupPolicy,
+    /// Wall-clock limit for the whole run, including downloads
+    pub timeout: Option<Duration>,
+    /// Files to download, decompress and load; `None` processes every file
+    /// in `FILES`. The reference file is always included.
+    pub files: Option<Vec<&'static FileMetadata>>,
+    /// Connection tuning shared by the parallel worker handlers
+    pub worker: WorkerConfig,
+}
+
+/// Error returned when a run exceeds `PipelineConfig::timeout`.
+///
+/// Files listed in `completed` were fully loaded before the limit was
+/// reached and remain in the output database, unless the cleanup policy
+/// deletes it. Retrieve it with `anyhow::Error::downcast_ref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedOut {
+    /// The configured time limit
+    pub timeout: Duration,
+    /// IDs of the files fully loaded before the limit, in completion order
+    pub completed: Vec<String>,
+}
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Time limit reached; ")?;
+        if self.completed.is_empty() {
+            write!(f, "no files were fully loaded")
+        } else {
+            write!(
+                f,
+                "{} file(s) fully loaded: {}",
+                self.completed.len(),
+                self.completed.join(", ")
+            )
+        }
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Returns true once `deadline` has passed.
+fn deadline_passed(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+/// Summary of a completed pipeline run.
+///
+/// Returned by [`run_with_report`] alongside the handler so embedders can
+/// report on a run without inspecting the handler's fields. Serializes to
+/// JSON (or any other serde format) for machine-readable output.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    /// Wall-clock time of the whole run, including downloads and cleanup
+    pub duration: Duration,
+    /// IDs of the files that were fully loaded, reference file first
+    pub files_processed: Vec<String>,
+    /// Number of records read from the loaded files, including rejected rows
+    pub total_records: u64,
+    /// Number of rejected rows per file ID; files without errors are omitted
+    pub errors_by_file: BTreeMap<String, usize>,
+    /// Files whose tables could not be (fully) loaded
+    pub failed_files: Vec<FileFailure>,
+    /// Path of the output database
+    pub database_path: PathBuf,
+}
+
+impl RunReport {
+    /// Total number of rejected rows across all files.
+    pub fn total_errors(&self) -> usize {
+        self.errors_by_file.values().sum()
+    }
+}
+
+/// Files loaded so far and the records they inserted.
+///
+/// Shared with the load workers so a timed-out run can still report which
+/// files finished.
+#[derive(Debug, Default)]
+struct LoadTally {
+    completed: Mutex<Vec<String>>,
+    records: AtomicU64,
+}
+
+impl LoadTally {
+    /// Records a fully loaded file and the number of records read from it.
+    fn record(&self, file_id: &str, processed: usize) {
+        self.completed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(file_id.to_string());
+        self.records.fetch_add(processed as u64, Ordering::Relaxed);
+    }
+
+    /// Takes the IDs of the files recorded so far.
+    fn take_completed(&self) -> Vec<String> {
+        std::mem::take(&mut *self.completed.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl PipelineConfig {
+    /// Creates a configuration with the CLI defaults.
+    ///
+    /// Uses OFNT3AA1 as the reference file, `./data` as the data directory,
+    /// removes extracted data after processing, never downloads, shows
+    /// progress, leaves everything in place if the run fails, has no time
+    /// limit, and processes every file.
+    ///
+    /// # Arguments
+    ///
+    /// * `output` - Output SQLite database file path
+    pub fn new(output: impl Into<PathBuf>) -> Self {
+        Self {
+            output: output.into(),
+            reference_id: "OFNT3AA1".to_string(),
+            data_dir: data_directory(),
+            keep_data: false,
+            compress_kept_data: false,
+            download: DownloadPolicy::Skip,
+            show_progress: true,
+            cleanup: CleanupPolicy::default(),
+            timeout: None,
+            files: None,
+            worker: WorkerConfig::default(),
+        }
+    }
+
+    /// Returns the files this run processes, in `FILES` order.
+    ///
+    /// Every file when `files` is `None`; otherwise the listed files plus
+    /// the reference file.
+    pub fn selected_files(&self) -> Vec<FileMetadata> {
+        FILES
+            .iter()
+            .filter(|file| match &self.files {
+                None => true,
+                Some(files) => file.id == self.reference_id || files.iter().any(|f| f.id == file.id),
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Prints a status line if progress output is enabled.
+    fn status(&self, message: &str) {
+        if self.show_progress {
+            println!("{}", message);
+        }
+    }
+
+    /// Creates a styled progress bar, or a hidden one if progress output is disabled.
+    fn progress_bar(&self, len: u64, template: &str) -> ProgressBar {
+        if !self.show_progress {
+            return ProgressBar::hidden();
+        }
+
+        let pb = ProgressBar::new(len);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template(template)
+                .expect("Invalid template")
+                .progress_chars("#>-"),
+        );
+        pb
+    }
+}
+
+const RECORDS_TEMPLATE: &str =
+    "{msg}\n{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} records ({eta})";
+
+const BYTES_TEMPLATE: &str =
+    "{msg}\n{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})";
+
+/// Runs the full pipeline and returns the handler for the output database.
+///
+/// Steps:
+/// 1. Downloads missing or incomplete files according to `config.download`
+/// 2. Decompresses ZIP files whose extracted data is missing or invalid
+/// 3. Loads the reference file, then all other available files in parallel
+/// 4. Removes extracted data unless `config.keep_data` is set, or gzips it
+///    when `config.compress_kept_data` is also set
+///
+/// Per-row errors and DES failures are collected on the returned handler.
+/// If a step fails, `config.cleanup` decides whether the partial database
+/// and the extracted data are removed before the error is returned.
+///
+/// With `config.timeout` set, every step stops once the limit is reached:
+/// HTTP requests are cut short, no further archives are extracted, and
+/// loading stops at the next batch. Files that were interrupted are dropped
+/// and the run fails with a [`TimedOut`] error listing the files that were
+/// fully loaded.
+///
+/// # Arguments
+///
+/// * `config` - The pipeline configuration
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - The reference file ID is unknown or its data is unavailable
+/// - A download, decompression, or reference load fails
+/// - The run exceeds `config.timeout`
+/// - Cleanup of the data directory fails
+pub async fn run(config: PipelineConfig) -> Result<DataHandler> {
+    run_with_report(config).await.map(|(data_handler, _)| data_handler)
+}
+
+/// Runs the full pipeline like [`run`], also returning a [`RunReport`].
+///
+/// # Arguments
+///
+/// * `config` - The pipeline configuration
+///
+/// # Returns
+///
+/// The handler for the output database and a summary of the run.
+///
+/// # Errors
+///
+/// Returns the same errors as [`run`].
+///
+/// # Example
+///
+/// ```no_run
+/// use ncdac_opi_parser::pipeline::{self, PipelineConfig};
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), anyhow::Error> {
+/// let (_, report) = pipeline::run_with_report(PipelineConfig::new("database.db")).await?;
+/// println!("{} records from {} files", report.total_records, report.files_processed.len());
+/// # Ok(())
+/// # }
+/// ```
+pub async fn run_with_report(config: PipelineConfig) -> Result<(DataHandler, RunReport)> {
+    let start = Instant::now();
+    let reference_file = get_file_by_id(&config.reference_id)
+        .ok_or_else(|| anyhow!("Unknown reference file id: {}", config.reference_id))?;
+
+    let deadline = config.timeout.map(|timeout| Instant::now() + timeout);
+    let tally = LoadTally::default();
+
+    let result = match config.download {
+        DownloadPolicy::Missing => download_missing(&config, deadline),
+        DownloadPolicy::Offline => check_offline(&config, reference_file),
+        DownloadPolicy::Skip => Ok(()),
+    }
+    .and_then(|()| decompress_available(&config, deadline))
+    .and_then(|line_counts| load(&config, reference_file, deadline, &tally, &line_counts));
+
+    let result = match (result, config.timeout) {
+        (Err(e), Some(timeout)) if deadline_passed(deadline) => Err(e.context(TimedOut {
+            timeout,
+            completed: tally.take_completed(),
+        })),
+        (result, _) => result,
+    };
+
+    let data_handler = match result {
+        Ok(data_handler) => data_handler,
+        Err(e) => {
+            cleanup_after_error(&config).await;
+            return Err(e);
+        }
+    };
+
+    if !config.keep_data {
+        cleanup(&config).await?;
+    } else if config.compress_kept_data {
+        for file in config.selected_files() {
+            recompress_extracted(file.id, &config.data_dir)?;
+        }
+    }
+
+    let mut errors_by_file = BTreeMap::new();
+    for error in &data_handler.errors {
+        *errors_by_file.entry(error.file_id.clone()).or_insert(0) += 1;
+    }
+
+    let report = RunReport {
+        duration: start.elapsed(),
+        total_records: tally.records.load(Ordering::Relaxed),
+        files_processed: tally.take_completed(),
+        errors_by_file,
+        failed_files: data_handler.file_failures.clone(),
+        database_path: config.output.clone(),
+    };
+
+    Ok((data_handler, report))
+}
+
+/// Applies the configured cleanup policy after a failed run.
+///
+/// Cleanup failures are reported as status lines (so they are silent when
+/// `show_progress` is off) but don't replace the original error.
+async fn cleanup_after_error(config: &PipelineConfig) {
+    if config.cleanup.on_error_delete_db {
+        for suffix in ["", "-wal", "-shm", "-journal"] {
+            let mut path = config.output.clone().into_os_string();
+            path.push(suffix);
+
+            match std::fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    config.status(&format!(
+                        "⚠️  Failed to delete {}: {}",
+                        PathBuf::from(path).display(),
+                        e
+                    ));
+                }
+            }
+        }
+    }
+
+    if !config.keep_data
+        && !config.cleanup.on_error_keep_data
+        && let Err(e) = cleanup(config).await
+    {
+        config.status(&format!("⚠️  {:#}", e));
+    }
+}
+
+/// Downloads every missing or incomplete file without prompting.
+///
+/// The reference file is downloaded first (see `ordered_by_priority`).
+fn download_missing(config: &PipelineConfig, deadline: Option<Instant>) -> Result<()> {
+    let file_status = categorize_files_before(&config.selected_files(), &config.data_dir, deadline);
+
+    let needed = file_status
+        .missing
+        .iter()
+        .chain(file_status.incomplete.iter())
+        .map(|file_id| get_file_by_id(file_id).copied().ok_or_else(|| anyhow!("Unknown file id: {}", file_id)))
+        .collect::<Result<Vec<_>>>()?;
+
+    for file in ordered_by_priority(&needed, &config.reference_id) {
+        download_data_file_before(&file, &config.data_dir, deadline)
+            .with_context(|| format!("Failed to download {}", file.id))?;
+    }
+
+    Ok(())
+}
+
+/// Fails if a required file has neither extracted data nor a ZIP locally.
+///
+/// Required files are the reference file and, when `files` is set, every
+/// listed file. No network requests are made.
+fn check_offline(config: &PipelineConfig, reference_file: &FileMetadata) -> Result<()> {
+    let status = categorize_files_offline(&config.selected_files(), &config.data_dir);
+
+    let absent: Vec<&str> = status
+        .missing
+        .iter()
+        .map(String::as_str)
+        .filter(|id| *id == reference_file.id || config.files.is_some())
+        .collect();
+
+    if !absent.is_empty() {
+        return Err(anyhow!(
+            "Offline mode: required files not present in {}: {}",
+            config.data_dir.display(),
+            absent.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Decompresses every file whose ZIP is complete but whose extracted data is not valid.
+///
+/// Returns the `.dat` line counts taken during extraction, keyed by file
+/// ID, so loading can size its progress bars without recounting.
+fn decompress_available(config: &PipelineConfig, deadline: Option<Instant>) -> Result<HashMap<String, u64>> {
+    let data_dir = &config.data_dir;
+    let mut line_counts = HashMap::new();
+
+    let mut missing_files = Vec::new();
+    let mut incomplete_files = Vec::new();
+    let mut files_to_decompress = Vec::new();
+
+    for file in &config.selected_files() {
+        if are_decompressed_files_valid(file, data_dir) {
+            continue;
+        }
+
+        let status = if config.download == DownloadPolicy::Offline {
+            get_local_file_status(file, data_dir)
+        } else {
+            get_file_status_before(file, data_dir, deadline)
+        };
+
+        match status {
+            FileStatus::Missing => missing_files.push(file.id),
+            FileStatus::Incomplete => incomplete_files.push(file.id),
+            FileStatus::Complete => files_to_decompress.push(*file),
+        }
+    }
+
+    if !files_to_decompress.is_empty() {
+        let stats = calculate_archive_stats(&files_to_decompress, data_dir)
+            .context("Failed to calculate total uncompressed bytes")?;
+        stats.check_entry_ratio(MAX_ENTRY_COMPRESSION_RATIO)?;
+        let total_bytes = stats.uncompressed;
+
+        let total_mb = total_bytes as f64 / 1_048_576.0;
+
+        let shared_pb = Arc::new(config.progress_bar(total_bytes, BYTES_TEMPLATE));
+        shared_pb.set_message(format!(
+            "Decompressing {} files concurrently - {:.1} MB total",
+            files_to_decompress.len(),
+            total_mb
+        ));
+
+        let decompression_start = SystemTime::now();
+
+        let result: Result<Vec<(String, Option<u64>)>> = files_to_decompress
+            .par_iter()
+            .map(|file| {
+                if deadline_passed(deadline) {
+                    return Err(anyhow!("Deadline reached before {} was decompressed", file.id));
+                }
+                let decompressed = decompress_into_counting(file.id, file.name, data_dir, &shared_pb)?;
+                Ok((file.id.to_string(), decompressed.dat_lines))
+            })
+            .collect();
+
+        match result {
+            Ok(counts) => {
+                line_counts.extend(counts.into_iter().filter_map(|(id, lines)| Some((id, lines?))));
+            }
+            Err(e) => {
+                shared_pb.finish_and_clear();
+                return Err(e.context("Failed to decompress files"));
+            }
+        }
+
+        let decompression_duration = format_duration(decompression_start, None)
+            .context("Failed to calculate decompression duration")?;
+
+        shared_pb.finish_with_message(format!(
+            "✓ Decompressed {} files - {:.1} MB total in {}",
+            files_to_decompress.len(),
+            total_mb,
+            decompression_duration
+        ));
+        config.status("");
+    }
+
+    for file_id in &missing_files {
+        config.status(&format!(
+            "\x1b[34mℹ\x1b[0m Skipped {} (ZIP file not available)",
+            file_id
+        ));
+    }
+    for file_id in &incomplete_files {
+        config.status(&format!(
+            "\x1b[33m⚠\x1b[0m Skipped {} (ZIP file out-of-date or incomplete)",
+            file_id
+        ));
+    }
+
+    Ok(line_counts)
+}
+
+/// Loads the reference file, then all other extracted files in parallel.
+///
+/// Each fully loaded file is recorded in `tally`. Progress bars
+/// are sized from `line_counts`, falling back to counting the lines of
+/// files that were not extracted in this run.
+fn load(
+    config: &PipelineConfig,
+    reference_file: &FileMetadata,
+    deadline: Option<Instant>,
+    tally: &LoadTally,
+    line_counts: &HashMap<String, u64>,
+) -> Result<DataHandler> {
+    let data_dir = &config.data_dir;
+    let database_path = config.output.to_str().context("Invalid output path")?;
+    let worker_config = &config.worker;
+
+    let mut data_handler =
+        DataHandler::new(database_path).context("Failed to create database handler")?;
+    data_handler.set_data_dir(data_dir);
+    data_handler.set_deadline(deadline);
+    data_handler.set_worker_config(*worker_config);
+
+    if data_handler.journal_state() == JournalState::Wal {
+        data_handler
+            .checkpoint_wal()
+            .context("Failed to recover leftover write-ahead log")?;
+    }
+
+    let init_start_time = SystemTime::now();
+
+    let ref_dat_path = extracted_file_path(data_dir, reference_file.id, "dat");
+    let ref_line_count = match line_counts.get(reference_file.id) {
+        Some(&count) => count,
+        None => count_lines(&ref_dat_path)
+            .with_context(|| format!("Failed to count lines in {}", ref_dat_path.display()))?,
+    };
+
+    let ref_pb = config.progress_bar(ref_line_count, RECORDS_TEMPLATE);
+    ref_pb.set_message(format!(
+        "Processing reference file ({}) - Inserting {} records into {} table",
+        reference_file.id,
+        format_count(ref_line_count as usize),
+        reference_file.name
+    ));
+
+    let init_results = data_handler
+        .init(reference_file, Some(&ref_pb))
+        .context("Failed to initialize with reference file")?;
+    tally.record(reference_file.id, init_results.processed);
+
+    let init_duration = format_duration(init_start_time, None)
+        .context("Failed to calculate initialization duration")?;
+
+    warn_fully_null_columns(&ref_pb, reference_file.id, &init_results);
+
+    if !init_results.errors.is_empty() {
+        ref_pb.finish_and_clear();
+        config.status(&format!(
+            "⚠️  {} errors encountered while processing {} reference file.",
+            init_results.errors.len(),
+            reference_file.name
+        ));
+    } else {
+        ref_pb.finish_with_message(format!(
+            "✓ Processed reference file ({}) - Inserted {} records into {} table in {}",
+            reference_file.id,
+            format_count(init_results.processed),
+            reference_file.name,
+            init_duration
+        ));
+    }
+
+    config.status("\n📋 Reference file processing complete");
+
+    let selected_files = config.selected_files();
+    let files_to_process: Vec<_> = selected_files
+        .iter()
+        .filter(|file| file.id != reference_file.id && data_dir.join(file.id).exists())
+        .collect();
+
+    if files_to_process.is_empty() {
+        return Ok(data_handler);
+    }
+
+    let mut total_records = 0u64;
+    for file in &files_to_process {
+        if let Some(&line_count) = line_counts.get(file.id) {
+            total_records += line_count;
+            continue;
+        }
+        let dat_path = extracted_file_path(data_dir, file.id, "dat");
+        if let Ok(line_count) = count_lines(&dat_path) {
+            total_records += line_count;
+        }
+    }
+
+    config.status(&format!(
+        "🚀 Starting parallel processing of {} files",
+        files_to_process.len()
+    ));
+
+    let combined_pb = Arc::new(config.progress_bar(total_records, RECORDS_TEMPLATE));
+    combined_pb.set_message(format!(
+        "Processing {} files concurrently - {} total records",
+        files_to_process.len(),
+        format_count(total_records as usize)
+    ));
+
+    let error_aggregator = Arc::new(ErrorAggregator::new());
+    let des_failure_aggregator = Arc::new(DesFailureAggregator::new());
+
+    let parallel_start_time = SystemTime::now();
+
+    let ref_file = data_handler
+        .reference_file()
+        .copied()
+        .context("Reference file not set before parallel processing")?;
+    let ref_table = data_handler
+        .reference_table_name()
+        .context("Reference table not set before parallel processing")?
+        .to_string();
+    let ref_field = data_handler
+        .reference_field()
+        .context("Reference field not set before parallel processing")?
+        .to_string();
+
+    let skipped = AtomicUsize::new(0);
+    // Progress from loaded files only, so a failed attempt's records can be
+    // taken back off the bar before they are read again
+    let committed_progress = AtomicU64::new(0);
+
+    let permanently_failed = process_with_serial_retry(&files_to_process, |file, attempt| {
+        let mut worker_handler = create_worker_handler(database_path, worker_config)
+            .with_context(|| format!("Failed to create worker handler for {}", file.id))?;
+
+        worker_handler.set_data_dir(data_dir);
+        worker_handler.set_deadline(deadline);
+        worker_handler.set_worker_config(*worker_config);
+        worker_handler.init_from_reference(&ref_file, &ref_table, &ref_field);
+
+        if attempt > 1 {
+            worker_handler
+                .drop_table_for_file(file)
+                .with_context(|| format!("Failed to reset {} before retry", file.id))?;
+            // Retries run one at a time after the parallel pass, so the bar
+            // holds only loaded files' progress plus that of failed attempts
+            combined_pb.set_position(committed_progress.load(Ordering::Relaxed));
+        }
+
+        if deadline_passed(deadline) {
+            skipped.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let pb = Arc::clone(&combined_pb);
+        let agg = Arc::clone(&error_aggregator);
+        let des_agg = Arc::clone(&des_failure_aggregator);
+
+        match worker_handler.process_file(file, Some(&pb))? {
+            Some(results) => {
+                committed_progress.fetch_add((results.processed + results.deduplicated) as u64, Ordering::Relaxed);
+                tally.record(file.id, results.processed);
+                warn_fully_null_columns(&pb, file.id, &results);
+
+                if !results.errors.is_empty() {
+                    agg.add_errors(results.errors);
+                }
+            }
+            None => {
+                if !worker_handler.des_file_failures.is_empty() {
+                    des_agg.add_failures(worker_handler.des_file_failures.clone());
+                }
+            }
+        }
+
+        Ok(())
+    });
+
+    let timed_out =
+        deadline_passed(deadline) && (skipped.into_inner() > 0 || !permanently_failed.is_empty());
+    if timed_out {
+        combined_pb.finish_and_clear();
+        return Err(anyhow!("Loading stopped at the deadline"));
+    }
+
+    if !permanently_failed.is_empty() {
+        combined_pb.set_position(committed_progress.into_inner());
+    }
+
+    for (file, e) in &permanently_failed {
+        data_handler
+            .file_failures
+            .push(FileFailure::new(file.id, format!("Failed after retry: {:#}", e)));
+    }
+
+    let parallel_duration = format_duration(parallel_start_time, None)
+        .context("Failed to calculate parallel processing duration")?;
+
+    combined_pb.finish_with_message(format!(
+        "✓ Processed {} files concurrently in {} - {} total records",
+        files_to_process.len(),
+        parallel_duration,
+        format_count(total_records as usize)
+    ));
+
+    config.status("✅ Parallel processing complete");
+
+    data_handler.errors.extend(error_aggregator.get_errors());
+    data_handler
+        .des_file_failures
+        .extend(des_failure_aggregator.get_failures());
+
+    Ok(data_handler)
+}
+
+/// Warns about columns in which every value coerced to NULL.
+///
+/// Printed through the progress bar so the message is suppressed along with
+/// the bar when progress output is disabled.
+fn warn_fully_null_columns(pb: &ProgressBar, file_id: &str, results: &ProcessingResults) {
+    for column in results.fully_null_columns() {
+        pb.println(format!(
+            "\x1b[33m⚠\x1b[0m Column {} in {} is entirely NULL (possible misaligned field)",
+            column, file_id
+        ));
+    }
+}
+
+/// Removes the extracted data subdirectory of every known file.
+async fn cleanup(config: &PipelineConfig) -> Result<()> {
+    let spinner = if config.show_progress {
+        create_spinner("Cleaning up data files...")
+    } else {
+        ProgressBar::hidden()
+    };
+
+    for file in &FILES {
+        delete_data_subdirectory_in(&config.data_dir, file.id)
+            .await
+            .with_context(|| format!("Failed to delete data directory for {}", file.id))?;
+    }
+
+    spinner.finish_with_message("Cleaned up data files".to_string());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{write_fixture, write_test_zip};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_run_loads_extracted_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let data_dir = temp_dir.path().join("data");
+
+        write_fixture(
+            &data_dir,
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+            "0000001\n0000002\n",
+        );
+        write_fixture(
+            &data_dir,
+            "OFNT1BA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CIDORNUM      SENTENCE COMPONENT NUMBER          CHAR      8       3\n",
+            "0000001001\n0000002001\n0000009001\n",
+        );
+
+        let mut config = PipelineConfig::new(temp_dir.path().join("out.db"));
+        config.data_dir = data_dir.clone();
+        config.show_progress = false;
+
+        let handler = run(config).await?;
+
+        assert!(handler.is_initialized());
+        assert_eq!(handler.reference_file().unwrap().id, "OFNT3AA1");
+        assert!(!data_dir.join("OFNT3AA1").exists());
+        assert!(!data_dir.join("OFNT1BA1").exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_report_totals_match_loaded_data() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let data_dir = temp_dir.path().join("data");
+        let output = temp_dir.path().join("out.db");
+
+        write_fixture(
+            &data_dir,
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+            "0000001\n0000002\n",
+        );
+        write_fixture(
+            &data_dir,
+            "OFNT1BA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n\
+             CIDORNUM      SENTENCE COMPONENT NUMBER          CHAR      8       3\n",
+            "0000001001\n0000002001\n0000009001\n",
+        );
+
+        let mut config = PipelineConfig::new(&output);
+        config.data_dir = data_dir;
+        config.show_progress = false;
+
+        let (handler, report) = run_with_report(config).await?;
+        drop(handler);
+
+        let conn = rusqlite::Connection::open(&output)?;
+        let mut loaded = 0u64;
+        for table in ["offender_profile", "financial_obligation"] {
+            let count: u64 =
+                conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))?;
+            loaded += count;
+        }
+
+        // The orphaned obligation is read but rejected
+        assert_eq!(report.total_records, loaded + 1);
+        assert_eq!(report.total_records, 5);
+        assert_eq!(report.files_processed[0], "OFNT3AA1");
+        assert!(report.files_processed.contains(&"OFNT1BA1".to_string()));
+        assert_eq!(report.errors_by_file.get("OFNT1BA1"), Some(&1));
+        assert_eq!(report.total_errors(), 1);
+        assert!(report.failed_files.is_empty());
+        assert_eq!(report.database_path, output);
+
+        let json = serde_json::to_value(&report)?;
+        assert_eq!(json["total_records"], 5);
+        assert_eq!(json["errors_by_file"]["OFNT1BA1"], 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_offline_run_uses_staged_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let data_dir = temp_dir.path().join("data");
+
+        write_fixture(
+            &data_dir,
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+            "0000001\n0000002\n",
+        );
+
+        let mut config = PipelineConfig::new(temp_dir.path().join("out.db"));
+        config.data_dir = data_dir.clone();
+        config.show_progress = false;
+        config.keep_data = true;
+        config.download = DownloadPolicy::Offline;
+
+        let handler = run(config.clone()).await?;
+        assert!(handler.is_initialized());
+
+        // An explicitly listed file that is absent locally is an error
+        config.output = temp_dir.path().join("listed.db");
+        config.files = Some(vec![get_file_by_id("OFNT1BA1").unwrap()]);
+        let error = run(config).await.unwrap_err();
+        assert!(error.to_string().contains("OFNT1BA1"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compressed_kept_data_is_reused() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir_all(&data_dir)?;
+        write_test_zip(
+            &data_dir.join("OFNT3AA1.zip"),
+            &[
+                ("OFNT3AA1.des", b"CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n"),
+                ("OFNT3AA1.dat", b"0000001\n0000002\n"),
+            ],
+        )?;
+
+        let mut config = PipelineConfig::new(temp_dir.path().join("first.db"));
+        config.data_dir = data_dir.clone();
+        config.show_progress = false;
+        config.keep_data = true;
+        config.compress_kept_data = true;
+        config.download = DownloadPolicy::Offline;
+        run(config.clone()).await?;
+
+        let dat_gz = data_dir.join("OFNT3AA1").join("OFNT3AA1.dat.gz");
+        let compressed_at = std::fs::metadata(&dat_gz)?.modified()?;
+        assert!(!data_dir.join("OFNT3AA1").join("OFNT3AA1.dat").exists());
+
+        // The gzipped copies satisfy the next run, so nothing is extracted again
+        config.output = temp_dir.path().join("second.db");
+        let handler = run(config.clone()).await?;
+        assert!(handler.is_initialized());
+        assert_eq!(std::fs::metadata(&dat_gz)?.modified()?, compressed_at);
+        assert!(!data_dir.join("OFNT3AA1").join("OFNT3AA1.dat").exists());
+        let reference = [*get_file_by_id("OFNT3AA1").unwrap()];
+        let verified = crate::download::verify_all(&reference, &data_dir);
+        assert_eq!(verified[0].status, crate::download::VerifyStatus::Passed);
+
+        // Without the ZIP they are still found rather than reported missing
+        std::fs::remove_file(data_dir.join("OFNT3AA1.zip"))?;
+        config.output = temp_dir.path().join("third.db");
+        let (_, report) = run_with_report(config).await?;
+        assert_eq!(report.total_records, 2);
+        assert_eq!(std::fs::metadata(&dat_gz)?.modified()?, compressed_at);
+        let verified = crate::download::verify_all(&reference, &data_dir);
+        assert_eq!(verified[0].status, crate::download::VerifyStatus::Unverifiable);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_timeout_aborts_run() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let data_dir = temp_dir.path().join("data");
+
+        write_fixture(
+            &data_dir,
+            "OFNT3AA1",
+            "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+            "0000001\n0000002\n",
+        );
+
+        let mut config = PipelineConfig::new(temp_dir.path().join("out.db"));
+        config.data_dir = data_dir.clone();
+        config.show_progress = false;
+        config.keep_data = true;
+        config.timeout = Some(Duration::ZERO);
+
+        let error = run(config.clone()).await.unwrap_err();
+        let timed_out = error.downcast_ref::<TimedOut>().expect("TimedOut error");
+        assert!(timed_out.completed.is_empty());
+        assert!(error.to_string().contains("no files were fully loaded"));
+
+        config.output = temp_dir.path().join("complete.db");
+        config.timeout = Some(Duration::from_secs(600));
+        let handler = run(config).await?;
+        assert!(handler.is_initialized());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_timeout_reports_completed_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let data_dir = temp_dir.path().join("data");
+        let des = "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n";
+
+        // An empty reference file commits no batches, so it loads even though
+        // the deadline has passed; the dependent file is then skipped
+        write_fixture(&data_dir, "OFNT3AA1", des, "");
+        write_fixture(&data_dir, "OFNT1BA1", des, "0000001\n0000002\n");
+
+        let mut config = PipelineConfig::new(temp_dir.path().join("out.db"));
+        config.data_dir = data_dir;
+        config.show_progress = false;
+        config.keep_data = true;
+        config.timeout = Some(Duration::ZERO);
+
+        let error = run(config).await.unwrap_err();
+        let timed_out = error.downcast_ref::<TimedOut>().expect("TimedOut error");
+        assert_eq!(timed_out.completed, vec!["OFNT3AA1".to_string()]);
+        assert!(error.to_string().contains("OFNT3AA1"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_policy_on_error() {
+        for cleanup in [
+            CleanupPolicy::default(),
+            CleanupPolicy {
+                on_error_delete_db: true,
+                on_error_keep_data: false,
+            },
+        ] {
+            let temp_dir = TempDir::new().unwrap();
+            let data_dir = temp_dir.path().join("data");
+            let output = temp_dir.path().join("out.db");
+
+            // A DES file without its DAT file fails after the database is created
+            let file_dir = write_fixture(
+                &data_dir,
+                "OFNT3AA1",
+                "CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n",
+                "",
+            );
+            std::fs::remove_file(file_dir.join("OFNT3AA1.dat")).unwrap();
+
+            let mut config = PipelineConfig::new(&output);
+            config.data_dir = data_dir.clone();
+            config.show_progress = false;
+            config.cleanup = cleanup;
+
+            assert!(run(config).await.is_err());
+            assert_eq!(output.exists(), !cleanup.on_error_delete_db);
+            assert_eq!(file_dir.exists(), cleanup.on_error_keep_data);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_requires_reference_data() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut config = PipelineConfig::new(temp_dir.path().join("out.db"));
+        config.data_dir = temp_dir.path().join("data");
+        config.show_progress = false;
+
+        assert!(run(config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_rejects_unknown_reference() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut config = PipelineConfig::new(temp_dir.path().join("out.db"));
+        config.reference_id = "NOPE".to_string();
+        config.show_progress = false;
+
+        assert!(run(config).await.is_err());
+    }
+}
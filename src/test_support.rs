@@ -0,0 +1,38 @@
+//! Fixtures shared by the unit tests of several modules.
+
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use zip::write::{SimpleFileOptions, ZipWriter};
+
+/// Writes `{data_dir}/{file_id}/{file_id}.des` and `.dat`, as extraction would.
+///
+/// Returns the file's directory, `{data_dir}/{file_id}`.
+pub(crate) fn write_fixture(data_dir: &Path, file_id: &str, des: &str, dat: impl AsRef<[u8]>) -> PathBuf {
+    let file_dir = data_dir.join(file_id);
+    std::fs::create_dir_all(&file_dir).unwrap();
+    std::fs::write(file_dir.join(format!("{}.des", file_id)), des).unwrap();
+    std::fs::write(file_dir.join(format!("{}.dat", file_id)), dat).unwrap();
+    file_dir
+}
+
+/// Writes a ZIP archive at `zip_path` holding each `(name, content)` entry.
+pub(crate) fn write_test_zip(zip_path: &Path, entries: &[(&str, &[u8])]) -> Result<()> {
+    let file = File::create(zip_path)
+        .with_context(|| format!("Failed to create test ZIP: {}", zip_path.display()))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default();
+
+    for (name, content) in entries {
+        zip.start_file(*name, options)
+            .with_context(|| format!("Failed to start ZIP entry: {}", name))?;
+        zip.write_all(content)
+            .with_context(|| format!("Failed to write ZIP entry: {}", name))?;
+    }
+
+    zip.finish()
+        .context("Failed to finalize ZIP archive")?;
+    Ok(())
+}
@@ -44,11 +44,19 @@
 
 use anyhow::{Context, Result};
 use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
 use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::io::{BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+/// Size of the buffer used to copy decompressed bytes to disk.
+///
+/// Large `.dat` entries are hundreds of megabytes; a 256 KiB buffer cuts the
+/// number of read/write calls (and progress bar updates) by 32x compared to
+/// 8 KiB while staying small enough to allocate per worker thread.
+const EXTRACT_BUFFER_SIZE: usize = 256 * 1024;
+
 /// Check if a path exists
 fn path_exists(path: &Path) -> bool {
     path.exists()
@@ -131,6 +139,25 @@ fn ensure_destination(destination_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Check whether a file entry was already fully extracted
+///
+/// Output files are written sequentially, so an interrupted extraction leaves
+/// a file shorter than the entry. A regular file with exactly the entry's
+/// uncompressed size is therefore complete.
+///
+/// # Arguments
+/// * `file` - The ZIP file entry
+/// * `destination_dir` - The base directory for extraction
+fn is_entry_extracted(file: &zip::read::ZipFile, destination_dir: &Path) -> bool {
+    if file.is_dir() || file.name().is_empty() {
+        return false;
+    }
+
+    fs::symlink_metadata(destination_dir.join(file.name()))
+        .map(|metadata| metadata.is_file() && metadata.len() == file.size())
+        .unwrap_or(false)
+}
+
 /// Extract a single entry from the ZIP archive to disk
 ///
 /// # Arguments
@@ -147,6 +174,42 @@ fn extract_entry(
     file: &mut zip::read::ZipFile,
     destination_dir: &Path,
     pb: &Arc<ProgressBar>,
+) -> Result<u64> {
+    extract_entry_with(file, destination_dir, pb, None)
+}
+
+/// Counts lines in a byte stream the way `utilities::count_lines` does.
+///
+/// Every newline ends a line, and trailing bytes after the last newline
+/// count as one more line.
+#[derive(Debug, Default)]
+struct LineCounter {
+    newlines: u64,
+    last_byte: Option<u8>,
+}
+
+impl LineCounter {
+    fn update(&mut self, bytes: &[u8]) {
+        self.newlines += bytes.iter().filter(|&&byte| byte == b'\n').count() as u64;
+        if let Some(&byte) = bytes.last() {
+            self.last_byte = Some(byte);
+        }
+    }
+
+    fn lines(&self) -> u64 {
+        match self.last_byte {
+            Some(byte) if byte != b'\n' => self.newlines + 1,
+            _ => self.newlines,
+        }
+    }
+}
+
+/// Extract a single entry, feeding its bytes to `lines` if given
+fn extract_entry_with(
+    file: &mut zip::read::ZipFile,
+    destination_dir: &Path,
+    pb: &Arc<ProgressBar>,
+    mut lines: Option<&mut LineCounter>,
 ) -> Result<u64> {
     let entry_name = file.name().to_string();
 
@@ -167,11 +230,12 @@ fn extract_entry(
             .with_context(|| format!("Failed to create parent directory: {}", parent.display()))?;
     }
 
-    let mut output_file = File::create(&file_path)
+    let output_file = File::create(&file_path)
         .with_context(|| format!("Failed to create file: {}", file_path.display()))?;
+    let mut output_file = BufWriter::with_capacity(EXTRACT_BUFFER_SIZE, output_file);
 
     let mut total_written = 0u64;
-    let mut buffer = vec![0; 8192];
+    let mut buffer = vec![0; EXTRACT_BUFFER_SIZE];
 
     loop {
         let bytes_read = file
@@ -185,11 +249,19 @@ fn extract_entry(
         output_file
             .write_all(&buffer[..bytes_read])
             .with_context(|| format!("Failed to write file: {}", file_path.display()))?;
+        if let Some(counter) = lines.as_deref_mut() {
+            counter.update(&buffer[..bytes_read]);
+        }
 
         total_written += bytes_read as u64;
         pb.inc(bytes_read as u64);
     }
 
+    output_file
+        .flush()
+        .with_context(|| format!("Failed to write file: {}", file_path.display()))?;
+    drop(output_file);
+
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
@@ -235,39 +307,176 @@ pub fn decompress_with_shared_progress(
     file_name: &str,
     shared_pb: &Arc<ProgressBar>,
 ) -> Result<PathBuf> {
-    let data_dir = crate::utilities::data_directory();
+    decompress_into(
+        file_id,
+        file_name,
+        &crate::utilities::data_directory(),
+        shared_pb,
+    )
+}
 
-    let zip_path = resolve_zip_path(file_id, &data_dir)
+/// Decompress a ZIP file from an explicit data directory
+///
+/// Behaves like `decompress_with_shared_progress`, but reads `{file_id}.zip`
+/// from and extracts into `data_dir` instead of the default data directory.
+///
+/// Extraction resumes where an interrupted run left off: an entry whose
+/// output file already exists with the entry's uncompressed size is skipped
+/// (its bytes still count towards the progress bar), and any other existing
+/// output, such as a partially written `.dat`, is overwritten.
+///
+/// Archives with several entries are extracted in parallel: each worker opens
+/// its own handle on the archive, so entries are inflated concurrently
+/// instead of one after another.
+///
+/// # Arguments
+/// * `file_id` - The identifier for the file (without .zip extension)
+/// * `file_name` - Human-readable name for error messages
+/// * `data_dir` - The directory containing the ZIP file
+/// * `shared_pb` - Arc-wrapped ProgressBar shared across parallel workers
+///
+/// # Returns
+/// The path to the extraction directory on success
+///
+/// # Errors
+/// * Returns errors if the ZIP file cannot be found or opened
+/// * Returns errors if extraction fails
+pub fn decompress_into(
+    file_id: &str,
+    file_name: &str,
+    data_dir: &Path,
+    shared_pb: &Arc<ProgressBar>,
+) -> Result<PathBuf> {
+    decompress_entries(file_id, file_name, data_dir, shared_pb, false).map(|decompressed| decompressed.path)
+}
+
+/// An extracted archive and the line count of its `.dat` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decompressed {
+    /// The extraction directory
+    pub path: PathBuf,
+    /// Lines in `{file_id}.dat`, as `utilities::count_lines` would report,
+    /// or `None` if the archive has no such entry
+    pub dat_lines: Option<u64>,
+}
+
+/// Decompress a ZIP file, counting the lines of its `.dat` entry on the way
+///
+/// Behaves like `decompress_into`, but newlines in the `{file_id}.dat`
+/// entry are counted as its bytes are written, so sizing a progress bar
+/// needs no second pass over the extracted file. A `.dat` already
+/// extracted by an earlier run is skipped as usual and counted from disk.
+///
+/// # Arguments
+/// * `file_id` - The identifier for the file (without .zip extension)
+/// * `file_name` - Human-readable name for error messages
+/// * `data_dir` - The directory containing the ZIP file
+/// * `shared_pb` - Arc-wrapped ProgressBar shared across parallel workers
+///
+/// # Returns
+/// The extraction directory and the `.dat` line count
+///
+/// # Errors
+/// * Returns errors if the ZIP file cannot be found or opened
+/// * Returns errors if extraction or counting fails
+///
+/// # Example
+/// ```no_run
+/// use ncdac_opi_parser::unzip::decompress_into_counting;
+/// use indicatif::ProgressBar;
+/// use std::path::Path;
+/// use std::sync::Arc;
+///
+/// let pb = Arc::new(ProgressBar::hidden());
+/// let decompressed = decompress_into_counting("OFNT3AA1", "Offender Profile", Path::new("data"), &pb)?;
+/// println!("{:?} records", decompressed.dat_lines);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn decompress_into_counting(
+    file_id: &str,
+    file_name: &str,
+    data_dir: &Path,
+    shared_pb: &Arc<ProgressBar>,
+) -> Result<Decompressed> {
+    decompress_entries(file_id, file_name, data_dir, shared_pb, true)
+}
+
+/// Shared body of `decompress_into` and `decompress_into_counting`
+fn decompress_entries(
+    file_id: &str,
+    file_name: &str,
+    data_dir: &Path,
+    shared_pb: &Arc<ProgressBar>,
+    count_dat_lines: bool,
+) -> Result<Decompressed> {
+    let zip_path = resolve_zip_path(file_id, data_dir)
         .with_context(|| format!("Failed to locate ZIP file for {}", file_id))?;
 
     let destination_dir = data_dir.join(file_id);
 
-    ensure_destination(&destination_dir)?;
-
-    let file = File::open(&zip_path)
-        .with_context(|| format!("Failed to open ZIP file: {}", zip_path.display()))?;
+    fs::create_dir_all(&destination_dir).with_context(|| {
+        format!(
+            "Failed to create destination directory: {}",
+            destination_dir.display()
+        )
+    })?;
 
-    let mut archive = zip::ZipArchive::new(file)
-        .with_context(|| format!("Failed to read ZIP archive: {}", zip_path.display()))?;
+    let entry_count = open_archive(&zip_path)?.len();
+    let dat_name = format!("{}.dat", file_id);
+
+    let dat_lines = (0..entry_count)
+        .into_par_iter()
+        .map_init(
+            || open_archive(&zip_path),
+            |archive, i| -> Result<Option<u64>> {
+                let archive = archive.as_mut().map_err(|e| anyhow::anyhow!("{:#}", e))?;
+
+                let mut file = archive
+                    .by_index(i)
+                    .with_context(|| format!("Failed to read ZIP entry at index {}", i))?;
+                let is_dat = count_dat_lines && file.name().eq_ignore_ascii_case(&dat_name);
+
+                if is_entry_extracted(&file, &destination_dir) {
+                    shared_pb.inc(file.size());
+                    return if is_dat {
+                        crate::utilities::count_lines(&destination_dir.join(file.name())).map(Some)
+                    } else {
+                        Ok(None)
+                    };
+                }
 
-    let entry_count = archive.len();
+                let mut counter = LineCounter::default();
+                extract_entry_with(&mut file, &destination_dir, shared_pb, is_dat.then_some(&mut counter))
+                    .with_context(|| {
+                        format!(
+                            "Failed to extract entry '{}' from {} ({})",
+                            file.name(),
+                            file_name,
+                            file_id
+                        )
+                    })?;
+
+                Ok(is_dat.then(|| counter.lines()))
+            },
+        )
+        .try_reduce(|| None, |a, b| Ok(a.or(b)))?;
 
-    for i in 0..entry_count {
-        let mut file = archive
-            .by_index(i)
-            .with_context(|| format!("Failed to read ZIP entry at index {}", i))?;
+    Ok(Decompressed {
+        path: destination_dir,
+        dat_lines,
+    })
+}
 
-        extract_entry(&mut file, &destination_dir, shared_pb).with_context(|| {
-            format!(
-                "Failed to extract entry '{}' from {} ({})",
-                file.name(),
-                file_name,
-                file_id
-            )
-        })?;
-    }
+/// Open a ZIP archive for reading
+///
+/// # Errors
+/// Returns errors if the file cannot be opened or is not a valid ZIP archive
+fn open_archive(zip_path: &Path) -> Result<zip::ZipArchive<File>> {
+    let file = File::open(zip_path)
+        .with_context(|| format!("Failed to open ZIP file: {}", zip_path.display()))?;
 
-    Ok(destination_dir)
+    zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read ZIP archive: {}", zip_path.display()))
 }
 
 /// Extract a ZIP data file to the data directory
@@ -343,6 +552,68 @@ pub fn unzip_data_file(file_id: &str, file_name: &str) -> Result<PathBuf> {
     Ok(destination_dir)
 }
 
+/// Highest compressed-to-uncompressed ratio accepted for a single ZIP entry.
+///
+/// Fixed-width DAT files compress well because of their space padding, but
+/// nowhere near this. Entries beyond it are treated as a likely zip bomb (or
+/// a corrupt header) and rejected before anything is written to disk.
+pub const MAX_ENTRY_COMPRESSION_RATIO: f64 = 200.0;
+
+/// Size statistics for one or more ZIP archives, read from their headers.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ArchiveStats {
+    /// Total compressed bytes across all entries
+    pub compressed: u64,
+    /// Total uncompressed bytes across all entries
+    pub uncompressed: u64,
+    /// Highest uncompressed/compressed ratio of any single entry
+    pub max_entry_ratio: f64,
+}
+
+impl ArchiveStats {
+    /// Returns the overall compression ratio (uncompressed / compressed).
+    ///
+    /// Returns 0.0 when nothing is compressed.
+    pub fn ratio(&self) -> f64 {
+        if self.compressed == 0 {
+            0.0
+        } else {
+            self.uncompressed as f64 / self.compressed as f64
+        }
+    }
+
+    /// Fails if any entry's compression ratio exceeds `max_ratio`.
+    ///
+    /// # Arguments
+    /// * `max_ratio` - Highest ratio accepted, typically `MAX_ENTRY_COMPRESSION_RATIO`
+    ///
+    /// # Errors
+    /// Returns an error naming the offending ratio when the limit is exceeded
+    pub fn check_entry_ratio(&self, max_ratio: f64) -> Result<()> {
+        if self.max_entry_ratio > max_ratio {
+            anyhow::bail!(
+                "Suspicious archive: an entry expands {:.0}x (limit {:.0}x); \
+                 the ZIP may be corrupt or a decompression bomb",
+                self.max_entry_ratio,
+                max_ratio
+            );
+        }
+        Ok(())
+    }
+
+    fn add_entry(&mut self, compressed: u64, uncompressed: u64) {
+        self.compressed += compressed;
+        self.uncompressed += uncompressed;
+
+        if compressed > 0 {
+            let ratio = uncompressed as f64 / compressed as f64;
+            if ratio > self.max_entry_ratio {
+                self.max_entry_ratio = ratio;
+            }
+        }
+    }
+}
+
 /// Calculate the total uncompressed bytes across all ZIP files
 ///
 /// This function opens each ZIP file in the provided list, sums the uncompressed
@@ -374,7 +645,43 @@ pub fn calculate_total_uncompressed_bytes(
     files: &[crate::files::FileMetadata],
     data_dir: &Path,
 ) -> Result<u64> {
-    let mut total_bytes = 0u64;
+    Ok(calculate_archive_stats(files, data_dir)?.uncompressed)
+}
+
+/// Calculate compressed and uncompressed sizes across all ZIP files
+///
+/// Like `calculate_total_uncompressed_bytes`, but also reports the compressed
+/// size and the highest per-entry compression ratio so callers can reject
+/// suspicious archives (see `ArchiveStats::check_entry_ratio`) before
+/// extracting them.
+///
+/// # Arguments
+/// * `files` - Slice of FileMetadata for files to inspect
+/// * `data_dir` - The data directory containing the ZIP files
+///
+/// # Returns
+/// Combined `ArchiveStats` for all archives
+///
+/// # Errors
+/// * Returns error if any ZIP file cannot be found, opened, or read
+/// * Error context identifies which file failed
+///
+/// # Example
+/// ```no_run
+/// use ncdac_opi_parser::unzip::{calculate_archive_stats, MAX_ENTRY_COMPRESSION_RATIO};
+/// use ncdac_opi_parser::files::FILES;
+/// use std::path::Path;
+///
+/// let stats = calculate_archive_stats(&FILES, Path::new("./data"))?;
+/// stats.check_entry_ratio(MAX_ENTRY_COMPRESSION_RATIO)?;
+/// println!("{} -> {} bytes ({:.1}x)", stats.compressed, stats.uncompressed, stats.ratio());
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn calculate_archive_stats(
+    files: &[crate::files::FileMetadata],
+    data_dir: &Path,
+) -> Result<ArchiveStats> {
+    let mut stats = ArchiveStats::default();
 
     for file_metadata in files {
         let file_id = file_metadata.id;
@@ -405,20 +712,21 @@ pub fn calculate_total_uncompressed_bytes(
         })?;
 
         for i in 0..archive.len() {
-            if let Ok(entry) = archive.by_index(i) {
-                total_bytes += entry.size();
+            if let Ok(entry) = archive.by_index_raw(i) {
+                stats.add_entry(entry.compressed_size(), entry.size());
             }
         }
 
         // ZIP archive is automatically closed when it goes out of scope
     }
 
-    Ok(total_bytes)
+    Ok(stats)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::write_test_zip;
     use std::fs::File;
     use std::io::Write;
     use tempfile::TempDir;
@@ -465,22 +773,59 @@ mod tests {
         fs::remove_dir_all(&temp_dir).unwrap();
     }
 
-    fn create_test_zip(zip_path: &Path, files: &[(&str, &[u8])]) -> Result<()> {
-        let file = File::create(zip_path)
-            .with_context(|| format!("Failed to create test ZIP: {}", zip_path.display()))?;
-        let mut zip = ZipWriter::new(file);
-        let options = SimpleFileOptions::default();
+    #[test]
+    fn test_decompress_counts_dat_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+        let pb = Arc::new(ProgressBar::hidden());
 
-        for (name, content) in files {
-            zip.start_file(*name, options)
-                .with_context(|| format!("Failed to start ZIP entry: {}", name))?;
-            zip.write_all(content)
-                .with_context(|| format!("Failed to write ZIP entry: {}", name))?;
-        }
+        // Blank lines and an unterminated final record both count
+        let dat: Vec<u8> = (0..2000)
+            .flat_map(|i| format!("{:07}RECORD\n", i).into_bytes())
+            .chain(b"\n0002000LAST".iter().copied())
+            .collect();
+        write_test_zip(
+            &data_dir.join("TESTCNT.zip"),
+            &[("TESTCNT.des", b"CMDORNUM\n"), ("TESTCNT.dat", &dat)],
+        )
+        .unwrap();
 
-        zip.finish()
-            .context("Failed to finalize ZIP archive")?;
-        Ok(())
+        let decompressed = decompress_into_counting("TESTCNT", "Count Test", data_dir, &pb).unwrap();
+        let dat_path = decompressed.path.join("TESTCNT.dat");
+        assert_eq!(decompressed.dat_lines, Some(crate::utilities::count_lines(&dat_path).unwrap()));
+        assert_eq!(decompressed.dat_lines, Some(2002));
+
+        // A resumed extraction counts the already extracted file instead
+        let resumed = decompress_into_counting("TESTCNT", "Count Test", data_dir, &pb).unwrap();
+        assert_eq!(resumed.dat_lines, Some(2002));
+
+        write_test_zip(&data_dir.join("TESTNODAT.zip"), &[("README.txt", b"notes")]).unwrap();
+        let decompressed = decompress_into_counting("TESTNODAT", "No Dat", data_dir, &pb).unwrap();
+        assert_eq!(decompressed.dat_lines, None);
+    }
+
+    #[test]
+    fn test_lowercase_entries_are_found_by_parser() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+        let pb = Arc::new(ProgressBar::hidden());
+
+        write_test_zip(
+            &data_dir.join("TESTCASE.zip"),
+            &[
+                ("testcase.des", b"CMDORNUM      OFFENDER NC DOC ID NUMBER          CHAR      1       7\n"),
+                ("testcase.dat", b"0000001\n0000002\n"),
+            ],
+        )
+        .unwrap();
+
+        let decompressed = decompress_into_counting("TESTCASE", "Case Test", data_dir, &pb).unwrap();
+        assert_eq!(decompressed.dat_lines, Some(2));
+
+        let parser = crate::parser::DataParser::from_data_dir("TESTCASE", data_dir).unwrap();
+        let records: Vec<_> = parser.parse().unwrap().collect::<Result<_>>().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1]["CMDORNUM"].as_deref(), Some("0000002"));
     }
 
     #[test]
@@ -491,7 +836,7 @@ mod tests {
 
         let zip_path = data_dir.join(format!("{}.zip", file_id));
         let test_content = b"Hello, this is test content!";
-        create_test_zip(&zip_path, &[("test.txt", test_content)]).unwrap();
+        write_test_zip(&zip_path, &[("test.txt", test_content)]).unwrap();
 
         let total_size = test_content.len() as u64;
         let pb = Arc::new(ProgressBar::new(total_size));
@@ -522,6 +867,79 @@ mod tests {
         assert_eq!(pb.position(), test_content.len() as u64);
     }
 
+    #[test]
+    fn test_decompress_skips_already_extracted_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+
+        let file_id = "RESUME01";
+        let done = vec![b'D'; 4096];
+        let partial = vec![b'P'; 8192];
+        let fresh = vec![b'F'; 2048];
+        write_test_zip(
+            &data_dir.join(format!("{}.zip", file_id)),
+            &[("done.dat", &done), ("partial.dat", &partial), ("fresh.des", &fresh)],
+        )
+        .unwrap();
+
+        // A previous run finished one entry and was interrupted mid-way through another
+        let destination_dir = data_dir.join(file_id);
+        fs::create_dir_all(&destination_dir).unwrap();
+        fs::write(destination_dir.join("done.dat"), vec![b'X'; done.len()]).unwrap();
+        fs::write(destination_dir.join("partial.dat"), &partial[..1000]).unwrap();
+
+        let pb = Arc::new(ProgressBar::hidden());
+        decompress_into(file_id, "Resume Test", data_dir, &pb).unwrap();
+
+        // Same size, so the pre-existing output was kept rather than re-extracted
+        assert_eq!(fs::read(destination_dir.join("done.dat")).unwrap(), vec![b'X'; done.len()]);
+        assert_eq!(fs::read(destination_dir.join("partial.dat")).unwrap(), partial);
+        assert_eq!(fs::read(destination_dir.join("fresh.des")).unwrap(), fresh);
+        assert_eq!(pb.position(), (done.len() + partial.len() + fresh.len()) as u64);
+    }
+
+    #[test]
+    fn test_parallel_entry_extraction_is_byte_identical() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+        let file_id = "TESTPAR";
+
+        // Deterministic pseudo-random content, large enough to span many
+        // buffer fills per entry
+        let mut state = 0x2545_f491_u32;
+        let entries: Vec<(String, Vec<u8>)> = (0..6)
+            .map(|index| {
+                let size = EXTRACT_BUFFER_SIZE * 3 + index * 977;
+                let content = (0..size)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 17;
+                        state ^= state << 5;
+                        (state % 26) as u8 + b'A'
+                    })
+                    .collect();
+                (format!("part{}/{}.dat", index % 2, index), content)
+            })
+            .collect();
+
+        let zip_entries: Vec<(&str, &[u8])> = entries
+            .iter()
+            .map(|(name, content)| (name.as_str(), content.as_slice()))
+            .collect();
+        write_test_zip(&data_dir.join(format!("{}.zip", file_id)), &zip_entries).unwrap();
+
+        let total: u64 = entries.iter().map(|(_, content)| content.len() as u64).sum();
+        let pb = Arc::new(ProgressBar::hidden());
+
+        let destination = decompress_into(file_id, "Parallel Test", data_dir, &pb).unwrap();
+
+        for (name, content) in &entries {
+            let extracted = fs::read(destination.join(name)).unwrap();
+            assert!(extracted == *content, "{} differs after extraction", name);
+        }
+        assert_eq!(pb.position(), total);
+    }
+
     #[test]
     fn test_decompress_preserves_file_sizes() {
         let temp_dir = TempDir::new().unwrap();
@@ -533,7 +951,7 @@ mod tests {
         let medium_file = b"This is a medium sized file with more content.";
         let large_file = vec![b'X'; 1000]; // 1000 bytes
 
-        create_test_zip(
+        write_test_zip(
             &zip_path,
             &[
                 ("small.txt", small_file),
@@ -671,7 +1089,7 @@ mod tests {
         let content1 = b"Hello World!";
         let content2 = vec![b'A'; 500];
 
-        create_test_zip(
+        write_test_zip(
             &zip_path,
             &[("file1.txt", content1), ("file2.dat", &content2)],
         )
@@ -698,7 +1116,7 @@ mod tests {
         let zip1_path = data_dir.join(format!("{}.zip", file1_id));
         let content1a = b"First file, first content";
         let content1b = vec![b'B'; 200];
-        create_test_zip(
+        write_test_zip(
             &zip1_path,
             &[("a.txt", content1a), ("b.dat", &content1b)],
         )
@@ -708,7 +1126,7 @@ mod tests {
         let zip2_path = data_dir.join(format!("{}.zip", file2_id));
         let content2a = b"Second file content";
         let content2b = vec![b'C'; 300];
-        create_test_zip(
+        write_test_zip(
             &zip2_path,
             &[("c.txt", content2a), ("d.dat", &content2b)],
         )
@@ -717,7 +1135,7 @@ mod tests {
         let file3_id = "TEST_MULTI_3";
         let zip3_path = data_dir.join(format!("{}.zip", file3_id));
         let content3 = vec![b'X'; 1000];
-        create_test_zip(&zip3_path, &[("large.bin", &content3)]).unwrap();
+        write_test_zip(&zip3_path, &[("large.bin", &content3)]).unwrap();
 
         let files = vec![
             crate::files::FileMetadata::new(file1_id, "Test 1", "https://example.com/1.zip"),
@@ -754,6 +1172,34 @@ mod tests {
         assert!(error_message.contains("Missing File"));
     }
 
+    #[test]
+    fn test_archive_stats_flags_high_ratio_entry() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+
+        let file_id = "TEST_RATIO";
+        let zip_path = data_dir.join(format!("{}.zip", file_id));
+        let bomb = vec![b'0'; 4 * 1024 * 1024];
+        write_test_zip(&zip_path, &[("small.txt", b"Hello World!"), ("bomb.dat", &bomb)])
+            .unwrap();
+
+        let file_metadata = crate::files::FileMetadata::new(
+            file_id,
+            "Test File",
+            "https://example.com/test.zip",
+        );
+
+        let stats = calculate_archive_stats(&[file_metadata], data_dir).unwrap();
+
+        assert_eq!(stats.uncompressed, bomb.len() as u64 + 12);
+        assert!(stats.compressed < stats.uncompressed);
+        assert!(stats.max_entry_ratio > MAX_ENTRY_COMPRESSION_RATIO);
+
+        let error = stats.check_entry_ratio(MAX_ENTRY_COMPRESSION_RATIO).unwrap_err();
+        assert!(error.to_string().contains("Suspicious archive"));
+        assert!(stats.check_entry_ratio(f64::INFINITY).is_ok());
+    }
+
     #[test]
     fn test_calculate_total_bytes_empty_list() {
         let temp_dir = TempDir::new().unwrap();
@@ -782,7 +1228,7 @@ mod tests {
 
         for (file_id, content) in file_ids.iter().zip(&test_contents) {
             let zip_path = data_dir.join(format!("{}.zip", file_id));
-            create_test_zip(&zip_path, &[(format!("{}.txt", file_id).as_str(), content)]).unwrap();
+            write_test_zip(&zip_path, &[(format!("{}.txt", file_id).as_str(), content)]).unwrap();
             total_size += content.len() as u64;
         }
 
@@ -831,7 +1277,7 @@ mod tests {
         let missing_id = "MISSING_ZIP";
 
         let zip_path = data_dir.join(format!("{}.zip", valid_id));
-        create_test_zip(&zip_path, &[("test.txt", b"valid content")]).unwrap();
+        write_test_zip(&zip_path, &[("test.txt", b"valid content")]).unwrap();
 
         let file_ids = vec![valid_id, missing_id];
         let shared_pb = Arc::new(ProgressBar::new(1000));
@@ -873,7 +1319,7 @@ mod tests {
 
         for (file_id, content) in file_ids.iter().zip(&contents) {
             let zip_path = data_dir.join(format!("{}.zip", file_id));
-            create_test_zip(&zip_path, &[(format!("{}.dat", file_id).as_str(), content)]).unwrap();
+            write_test_zip(&zip_path, &[(format!("{}.dat", file_id).as_str(), content)]).unwrap();
         }
 
         let total_size: u64 = contents.iter().map(|c| c.len() as u64).sum();
@@ -909,7 +1355,7 @@ mod tests {
 
         for (file_id, test_data) in file_ids.iter().zip(&[&test_data_1[..], &test_data_2[..]]) {
             let zip_path = data_dir.join(format!("{}.zip", file_id));
-            create_test_zip(&zip_path, &[(format!("{}.bin", file_id).as_str(), test_data)])
+            write_test_zip(&zip_path, &[(format!("{}.bin", file_id).as_str(), test_data)])
                 .unwrap();
         }
 
@@ -947,23 +1393,23 @@ mod tests {
         let file_ids = vec!["MULTI_FILE_1", "MULTI_FILE_2"];
 
         let zip1_path = data_dir.join(format!("{}.zip", file_ids[0]));
-        create_test_zip(
+        write_test_zip(
             &zip1_path,
             &[
                 ("file_a.txt", b"Content A"),
                 ("file_b.txt", b"Content B"),
-                ("file_c.dat", &vec![b'X'; 100]),
+                ("file_c.dat", &[b'X'; 100]),
             ],
         )
         .unwrap();
 
         let zip2_path = data_dir.join(format!("{}.zip", file_ids[1]));
-        create_test_zip(
+        write_test_zip(
             &zip2_path,
             &[
                 ("file_d.txt", b"Content D"),
                 ("file_e.txt", b"Content E"),
-                ("file_f.dat", &vec![b'Y'; 200]),
+                ("file_f.dat", &[b'Y'; 200]),
             ],
         )
         .unwrap();
@@ -1025,7 +1471,7 @@ mod tests {
         let zip_path = data_dir.join(format!("{}.zip", file_id));
         let test_content = b"Single file decompression test";
 
-        create_test_zip(&zip_path, &[("single.txt", test_content)]).unwrap();
+        write_test_zip(&zip_path, &[("single.txt", test_content)]).unwrap();
 
         let total_size = test_content.len() as u64;
         let shared_pb = Arc::new(ProgressBar::new(total_size));
@@ -1055,7 +1501,7 @@ mod tests {
         let zip_path = data_dir.join(format!("{}.zip", file_id));
         let large_content = vec![b'Z'; 1_048_576]; // 1MB
 
-        create_test_zip(&zip_path, &[("large.bin", &large_content)]).unwrap();
+        write_test_zip(&zip_path, &[("large.bin", &large_content)]).unwrap();
 
         let total_size = large_content.len() as u64;
         let shared_pb = Arc::new(ProgressBar::new(total_size));
@@ -1090,13 +1536,13 @@ mod tests {
         let large_content = vec![b'L'; 100_000];
 
         let zip1_path = data_dir.join(format!("{}.zip", file_ids[0]));
-        create_test_zip(&zip1_path, &[("tiny.dat", &tiny_content)]).unwrap();
+        write_test_zip(&zip1_path, &[("tiny.dat", &tiny_content)]).unwrap();
 
         let zip2_path = data_dir.join(format!("{}.zip", file_ids[1]));
-        create_test_zip(&zip2_path, &[("medium.dat", &medium_content)]).unwrap();
+        write_test_zip(&zip2_path, &[("medium.dat", &medium_content)]).unwrap();
 
         let zip3_path = data_dir.join(format!("{}.zip", file_ids[2]));
-        create_test_zip(&zip3_path, &[("large.dat", &large_content)]).unwrap();
+        write_test_zip(&zip3_path, &[("large.dat", &large_content)]).unwrap();
 
         let total_size = (tiny_content.len() + medium_content.len() + large_content.len()) as u64;
         let shared_pb = Arc::new(ProgressBar::new(total_size));
@@ -4,15 +4,25 @@
 //! schema inspection, and data directory operations.
 
 use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Read};
 use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 use std::time::SystemTime;
 
+/// Data directory set with `set_data_directory`, if any.
+static DATA_DIRECTORY_OVERRIDE: RwLock<Option<PathBuf>> = RwLock::new(None);
+
 /// Returns the path to the data directory.
 ///
-/// The data directory is located at "./data" relative to the project directory.
+/// This is the one place the data directory is resolved: downloads,
+/// decompression, parsing and cleanup all default to it. It is the
+/// directory set with `set_data_directory`, or "./data" relative to the
+/// current working directory if none is set.
 ///
 /// # Examples
 ///
@@ -23,7 +33,169 @@ use std::time::SystemTime;
 /// assert!(data_dir.ends_with("data"));
 /// ```
 pub fn data_directory() -> PathBuf {
-    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("data")
+    DATA_DIRECTORY_OVERRIDE
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("./data"))
+}
+
+/// Sets the data directory returned by `data_directory` for this process.
+///
+/// The CLI's `--data-dir` flag calls this before anything else, so every
+/// step that falls back to `data_directory` uses the same directory.
+/// Passing `None` restores the default "./data".
+///
+/// # Arguments
+///
+/// * `path` - The data directory to use, or `None` for the default
+///
+/// # Examples
+///
+/// ```
+/// use ncdac_opi_parser::utilities::{data_directory, set_data_directory};
+/// use std::path::Path;
+///
+/// set_data_directory(Some(Path::new("/scratch/ncdac-data")));
+/// assert_eq!(data_directory(), Path::new("/scratch/ncdac-data"));
+///
+/// set_data_directory(None);
+/// assert!(data_directory().ends_with("data"));
+/// ```
+pub fn set_data_directory(path: Option<&Path>) {
+    *DATA_DIRECTORY_OVERRIDE
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = path.map(Path::to_path_buf);
+}
+
+/// Serializes tests that set or read the process-wide data directory.
+#[cfg(test)]
+static DATA_DIRECTORY_TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Holds the data directory lock for a test, clearing any override on drop.
+///
+/// Dropping restores the default even when the test panics, so a failed
+/// assertion cannot leak the override into later tests.
+#[cfg(test)]
+pub(crate) struct DataDirectoryGuard {
+    _lock: std::sync::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl DataDirectoryGuard {
+    /// Locks the data directory, overriding it with `path` if given.
+    pub(crate) fn new(path: Option<&Path>) -> Self {
+        let lock = DATA_DIRECTORY_TEST_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        set_data_directory(path);
+        Self { _lock: lock }
+    }
+}
+
+#[cfg(test)]
+impl Drop for DataDirectoryGuard {
+    fn drop(&mut self) {
+        set_data_directory(None);
+    }
+}
+
+/// Returns the path of an extracted file, such as `{data_dir}/{file_id}/{file_id}.dat`.
+///
+/// Archives do not always use the case of the file ID for their entries, so
+/// when the exact-case path does not exist, the file's directory and then
+/// the file itself are looked up case-insensitively, as `.zip` archives
+/// are. If nothing matches, the exact-case path is returned so callers
+/// report it in their errors.
+///
+/// # Arguments
+///
+/// * `data_dir` - The data directory containing the extracted files
+/// * `file_id` - The file ID (e.g., "OFNT3AA1")
+/// * `extension` - The file extension without the dot (e.g., "dat")
+///
+/// # Examples
+///
+/// ```
+/// use ncdac_opi_parser::utilities::extracted_file_path;
+/// use std::path::Path;
+///
+/// let path = extracted_file_path(Path::new("missing"), "OFNT3AA1", "des");
+/// assert_eq!(path, Path::new("missing/OFNT3AA1/OFNT3AA1.des"));
+/// ```
+pub fn extracted_file_path(data_dir: &Path, file_id: &str, extension: &str) -> PathBuf {
+    let file_name = format!("{}.{}", file_id, extension);
+    let exact = data_dir.join(file_id).join(&file_name);
+    if exact.exists() {
+        return exact;
+    }
+
+    let file_dir = find_case_insensitive(data_dir, file_id).unwrap_or_else(|| data_dir.join(file_id));
+    find_case_insensitive(&file_dir, &file_name).unwrap_or(exact)
+}
+
+/// Opens an extracted file for reading, such as `{data_dir}/{file_id}/{file_id}.dat`.
+///
+/// The file is located with `extracted_file_path`. When it is missing but a
+/// gzipped copy (`{file_id}.dat.gz`, as written by
+/// `files::recompress_extracted`) exists, the copy is decompressed while
+/// reading, so compressed and uncompressed data read the same.
+///
+/// # Arguments
+///
+/// * `data_dir` - The data directory containing the extracted files
+/// * `file_id` - The file ID (e.g., "OFNT3AA1")
+/// * `extension` - The file extension without the dot (e.g., "dat")
+///
+/// # Errors
+///
+/// Returns the error from opening the uncompressed path if neither file
+/// can be opened.
+pub fn open_extracted_file(
+    data_dir: &Path,
+    file_id: &str,
+    extension: &str,
+) -> std::io::Result<Box<dyn Read + Send>> {
+    if let Some(gz_path) = gzipped_extracted_file(data_dir, file_id, extension) {
+        return Ok(Box::new(GzDecoder::new(File::open(gz_path)?)));
+    }
+
+    Ok(Box::new(File::open(extracted_file_path(data_dir, file_id, extension))?))
+}
+
+/// Returns the gzipped copy of an extracted file when only the copy exists.
+///
+/// `open_extracted_file` reads such a copy transparently, but a gzip stream
+/// cannot be seeked, so callers that read by byte offset check this first.
+///
+/// # Arguments
+///
+/// * `data_dir` - The data directory containing the extracted files
+/// * `file_id` - The file ID (e.g., "OFNT3AA1")
+/// * `extension` - The file extension without the dot (e.g., "dat")
+///
+/// # Returns
+///
+/// The path of `{file_id}.{extension}.gz`, or `None` if the uncompressed
+/// file exists or there is no gzipped copy.
+pub fn gzipped_extracted_file(data_dir: &Path, file_id: &str, extension: &str) -> Option<PathBuf> {
+    if extracted_file_path(data_dir, file_id, extension).exists() {
+        return None;
+    }
+
+    Some(extracted_file_path(data_dir, file_id, &format!("{}.gz", extension))).filter(|path| path.exists())
+}
+
+/// Finds an entry of `dir` whose name equals `name` ignoring ASCII case.
+fn find_case_insensitive(dir: &Path, name: &str) -> Option<PathBuf> {
+    let exact = dir.join(name);
+    if exact.exists() {
+        return Some(exact);
+    }
+
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| entry.file_name().to_str().is_some_and(|entry_name| entry_name.eq_ignore_ascii_case(name)))
+        .map(|entry| entry.path())
 }
 
 /// Converts a string to snake_case.
@@ -71,6 +243,91 @@ pub fn to_snake_case(s: &str) -> String {
     result.trim_matches('_').to_string()
 }
 
+/// Quotes an SQL identifier for safe interpolation into a statement.
+///
+/// Wraps the name in double quotes and doubles any embedded double quotes,
+/// so table and column names derived from file metadata or DES field codes
+/// cannot break out of the identifier.
+///
+/// # Examples
+///
+/// ```
+/// use ncdac_opi_parser::utilities::quote_identifier;
+///
+/// assert_eq!(quote_identifier("offender_profile"), "\"offender_profile\"");
+/// assert_eq!(quote_identifier("BAD\"NAME"), "\"BAD\"\"NAME\"");
+/// ```
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// SQLite keywords, in alphabetical order.
+///
+/// From <https://www.sqlite.org/lang_keywords.html>.
+const SQLITE_KEYWORDS: [&str; 147] = [
+    "ABORT", "ACTION", "ADD", "AFTER", "ALL", "ALTER", "ALWAYS", "ANALYZE", "AND", "AS", "ASC",
+    "ATTACH", "AUTOINCREMENT", "BEFORE", "BEGIN", "BETWEEN", "BY", "CASCADE", "CASE", "CAST",
+    "CHECK", "COLLATE", "COLUMN", "COMMIT", "CONFLICT", "CONSTRAINT", "CREATE", "CROSS",
+    "CURRENT", "CURRENT_DATE", "CURRENT_TIME", "CURRENT_TIMESTAMP", "DATABASE", "DEFAULT",
+    "DEFERRABLE", "DEFERRED", "DELETE", "DESC", "DETACH", "DISTINCT", "DO", "DROP", "EACH",
+    "ELSE", "END", "ESCAPE", "EXCEPT", "EXCLUDE", "EXCLUSIVE", "EXISTS", "EXPLAIN", "FAIL",
+    "FILTER", "FIRST", "FOLLOWING", "FOR", "FOREIGN", "FROM", "FULL", "GENERATED", "GLOB",
+    "GROUP", "GROUPS", "HAVING", "IF", "IGNORE", "IMMEDIATE", "IN", "INDEX", "INDEXED",
+    "INITIALLY", "INNER", "INSERT", "INSTEAD", "INTERSECT", "INTO", "IS", "ISNULL", "JOIN",
+    "KEY", "LAST", "LEFT", "LIKE", "LIMIT", "MATCH", "MATERIALIZED", "NATURAL", "NO", "NOT",
+    "NOTHING", "NOTNULL", "NULL", "NULLS", "OF", "OFFSET", "ON", "OR", "ORDER", "OTHERS",
+    "OUTER", "OVER", "PARTITION", "PLAN", "PRAGMA", "PRECEDING", "PRIMARY", "QUERY", "RAISE",
+    "RANGE", "RECURSIVE", "REFERENCES", "REGEXP", "REINDEX", "RELEASE", "RENAME", "REPLACE",
+    "RESTRICT", "RETURNING", "RIGHT", "ROLLBACK", "ROW", "ROWS", "SAVEPOINT", "SELECT", "SET",
+    "TABLE", "TEMP", "TEMPORARY", "THEN", "TIES", "TO", "TRANSACTION", "TRIGGER", "UNBOUNDED",
+    "UNION", "UNIQUE", "UPDATE", "USING", "VACUUM", "VALUES", "VIEW", "VIRTUAL", "WHEN",
+    "WHERE", "WINDOW", "WITH", "WITHOUT",
+];
+
+/// Suffix appended to a column name that collides with an SQLite keyword.
+pub const RESERVED_WORD_SUFFIX: &str = "_col";
+
+/// Checks whether a name is an SQLite keyword, ignoring case.
+///
+/// Keywords work as identifiers only when quoted, so a column named after
+/// one breaks hand-written queries against the database.
+///
+/// # Examples
+///
+/// ```
+/// use ncdac_opi_parser::utilities::is_sqlite_keyword;
+///
+/// assert!(is_sqlite_keyword("order"));
+/// assert!(is_sqlite_keyword("INDEX"));
+/// assert!(!is_sqlite_keyword("CMDORNUM"));
+/// ```
+pub fn is_sqlite_keyword(name: &str) -> bool {
+    SQLITE_KEYWORDS
+        .binary_search(&name.to_ascii_uppercase().as_str())
+        .is_ok()
+}
+
+/// Renames an identifier that collides with an SQLite keyword.
+///
+/// Appends `RESERVED_WORD_SUFFIX` to keywords and returns other names
+/// unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use ncdac_opi_parser::utilities::avoid_sqlite_keyword;
+///
+/// assert_eq!(avoid_sqlite_keyword("order"), "order_col");
+/// assert_eq!(avoid_sqlite_keyword("CMDORNUM"), "CMDORNUM");
+/// ```
+pub fn avoid_sqlite_keyword(name: &str) -> Cow<'_, str> {
+    if is_sqlite_keyword(name) {
+        Cow::Owned(format!("{}{}", name, RESERVED_WORD_SUFFIX))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
 /// Gets the primary key field from a schema.
 ///
 /// Searches for specific primary key candidates in the schema in order:
@@ -95,13 +352,10 @@ pub fn to_snake_case(s: &str) -> String {
 pub fn get_primary_key_field<V>(schema: &HashMap<String, V>) -> Option<&'static str> {
     const KEY_CANDIDATES: &[&str] = &["CMDORNUM", "CIDORNUM", "CDDORNUM"];
 
-    for &key in KEY_CANDIDATES {
-        if schema.contains_key(key) {
-            return Some(key);
-        }
-    }
-
-    None
+    KEY_CANDIDATES
+        .iter()
+        .find(|&&key| schema.contains_key(key))
+        .copied()
 }
 
 /// Formats a number with thousand separators.
@@ -127,14 +381,11 @@ pub fn format_count(n: usize) -> String {
     }
 
     let mut result = String::with_capacity(len + (len - 1) / 3);
-    let mut digit_count = 0;
-
-    for c in s.chars().rev() {
+    for (digit_count, c) in s.chars().rev().enumerate() {
         if digit_count > 0 && digit_count % 3 == 0 {
             result.push(',');
         }
         result.push(c);
-        digit_count += 1;
     }
 
     result.chars().rev().collect()
@@ -222,7 +473,24 @@ pub fn format_duration(start: SystemTime, end: Option<SystemTime>) -> Result<Str
 /// # }
 /// ```
 pub async fn delete_data_subdirectory(subdirectory: &str) -> Result<()> {
-    let target_path = data_directory().join(subdirectory);
+    delete_data_subdirectory_in(&data_directory(), subdirectory).await
+}
+
+/// Deletes a subdirectory within an explicit data directory.
+///
+/// Behaves like `delete_data_subdirectory`, but resolves `subdirectory`
+/// against `data_dir` instead of the default data directory.
+///
+/// # Arguments
+///
+/// * `data_dir` - The data directory containing the subdirectory
+/// * `subdirectory` - The name of the subdirectory to delete
+///
+/// # Errors
+///
+/// Returns an error if the directory exists but cannot be deleted.
+pub async fn delete_data_subdirectory_in(data_dir: &Path, subdirectory: &str) -> Result<()> {
+    let target_path = data_dir.join(subdirectory);
 
     match tokio::fs::remove_dir_all(&target_path).await {
         Ok(()) => Ok(()),
@@ -238,10 +506,104 @@ pub async fn delete_data_subdirectory(subdirectory: &str) -> Result<()> {
     }
 }
 
+/// Removes all extracted subdirectories from the default data directory.
+///
+/// Downloaded `.zip` archives and the database structure PDF are preserved,
+/// so the data can be re-extracted without downloading it again.
+///
+/// # Errors
+///
+/// Returns an error if the data directory cannot be read or an entry cannot
+/// be deleted.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ncdac_opi_parser::utilities::clean_data_directory;
+///
+/// clean_data_directory().unwrap();
+/// ```
+pub fn clean_data_directory() -> Result<()> {
+    clean_data_directory_in(&data_directory(), true)
+}
+
+/// Removes extracted data from an explicit data directory.
+///
+/// Every subdirectory of `data_dir` is deleted. Files directly inside
+/// `data_dir` are deleted only when `keep_archives` is `false`. Symbolic
+/// links are removed without following them, and any entry that resolves
+/// outside `data_dir` is refused, so nothing outside the data directory can
+/// be deleted. A missing data directory is treated as already clean.
+///
+/// # Arguments
+///
+/// * `data_dir` - The data directory to clean
+/// * `keep_archives` - Preserve `.zip` archives, the PDF and other top-level files
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - `data_dir` is the filesystem root
+/// - An entry resolves to a path outside `data_dir`
+/// - The directory cannot be read or an entry cannot be deleted
+pub fn clean_data_directory_in(data_dir: &Path, keep_archives: bool) -> Result<()> {
+    let data_dir = match data_dir.canonicalize() {
+        Ok(path) => path,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!("Failed to resolve data directory: {}", data_dir.display())
+            })
+        }
+    };
+
+    if data_dir.parent().is_none() {
+        anyhow::bail!("Refusing to clean filesystem root: {}", data_dir.display());
+    }
+
+    let entries = std::fs::read_dir(&data_dir)
+        .with_context(|| format!("Failed to read data directory: {}", data_dir.display()))?;
+
+    for entry in entries {
+        let entry = entry.context("Failed to read data directory entry")?;
+        let path = entry.path();
+        let file_type = entry
+            .file_type()
+            .with_context(|| format!("Failed to inspect {}", path.display()))?;
+
+        if file_type.is_symlink() {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to delete link: {}", path.display()))?;
+        } else if file_type.is_dir() {
+            let resolved = path
+                .canonicalize()
+                .with_context(|| format!("Failed to resolve {}", path.display()))?;
+
+            if !resolved.starts_with(&data_dir) || resolved == data_dir {
+                anyhow::bail!(
+                    "Refusing to delete {} outside data directory {}",
+                    resolved.display(),
+                    data_dir.display()
+                );
+            }
+
+            std::fs::remove_dir_all(&resolved)
+                .with_context(|| format!("Failed to delete directory: {}", resolved.display()))?;
+        } else if !keep_archives {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to delete file: {}", path.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
 /// Counts the number of lines in a file.
 ///
 /// This function efficiently counts lines in a file by reading it in buffered chunks.
 /// It's optimized for large files and skips empty lines.
+/// When the file is missing but a gzipped copy (`{file_path}.gz`) exists,
+/// the copy is counted instead.
 ///
 /// # Arguments
 ///
@@ -265,8 +627,17 @@ pub async fn delete_data_subdirectory(subdirectory: &str) -> Result<()> {
 /// println!("File has {} lines", count);
 /// ```
 pub fn count_lines(file_path: &Path) -> Result<u64> {
-    let file = File::open(file_path)
-        .with_context(|| format!("Failed to open file: {}", file_path.display()))?;
+    let mut gz_path = file_path.as_os_str().to_owned();
+    gz_path.push(".gz");
+    let gz_path = PathBuf::from(gz_path);
+
+    let file: Box<dyn Read> = if !file_path.exists() && gz_path.exists() {
+        Box::new(GzDecoder::new(File::open(&gz_path).with_context(|| {
+            format!("Failed to open file: {}", gz_path.display())
+        })?))
+    } else {
+        Box::new(File::open(file_path).with_context(|| format!("Failed to open file: {}", file_path.display()))?)
+    };
 
     let reader = BufReader::new(file);
     let mut count = 0u64;
@@ -279,11 +650,87 @@ pub fn count_lines(file_path: &Path) -> Result<u64> {
     Ok(count)
 }
 
+/// Creates a spinner with the ora-compatible "bouncingBar" style.
+///
+/// The spinner ticks every 80ms until it is finished by the caller.
+///
+/// # Arguments
+///
+/// * `message` - The message shown next to the spinner
+pub fn create_spinner(message: &str) -> ProgressBar {
+    let spinner = ProgressBar::new_spinner();
+    spinner.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.cyan} {msg}")
+            .expect("Invalid template")
+            .tick_strings(&["[    ]", "[=   ]", "[==  ]", "[=== ]", "[ ===]", "[  ==]", "[   =]", "[    ]", "[   =]", "[  ==]", "[ ===]", "[====]"]),
+    );
+    spinner.set_message(message.to_string());
+    spinner.enable_steady_tick(std::time::Duration::from_millis(80));
+    spinner
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::write_fixture;
     use std::time::Duration;
 
+    #[test]
+    fn test_sqlite_keywords_are_sorted() {
+        assert!(SQLITE_KEYWORDS.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(avoid_sqlite_keyword("Order"), "Order_col");
+        assert_eq!(avoid_sqlite_keyword("ORDERS"), "ORDERS");
+    }
+
+    #[test]
+    fn test_clean_data_directory_keeps_archives() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path();
+
+        for file_id in ["OFNT1BA1", "OFNT3AA1", "APPT7AA1"] {
+            write_fixture(data_dir, file_id, "des", "data");
+        }
+        std::fs::write(data_dir.join("OFNT1BA1.zip"), "zip").unwrap();
+        std::fs::write(data_dir.join("PublicTables.pdf"), "pdf").unwrap();
+
+        clean_data_directory_in(data_dir, true).unwrap();
+
+        assert!(!data_dir.join("OFNT1BA1").exists());
+        assert!(!data_dir.join("OFNT3AA1").exists());
+        assert!(!data_dir.join("APPT7AA1").exists());
+        assert!(data_dir.join("OFNT1BA1.zip").exists());
+        assert!(data_dir.join("PublicTables.pdf").exists());
+
+        clean_data_directory_in(data_dir, false).unwrap();
+
+        assert!(!data_dir.join("OFNT1BA1.zip").exists());
+        assert!(data_dir.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_clean_data_directory_does_not_follow_links() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let outside = temp_dir.path().join("outside");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(outside.join("keep.txt"), "keep").unwrap();
+        std::os::unix::fs::symlink(&outside, data_dir.join("link")).unwrap();
+
+        clean_data_directory_in(&data_dir, true).unwrap();
+
+        assert!(!data_dir.join("link").exists());
+        assert!(outside.join("keep.txt").exists());
+    }
+
+    #[test]
+    fn test_clean_missing_data_directory() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        assert!(clean_data_directory_in(&temp_dir.path().join("missing"), true).is_ok());
+    }
+
     #[test]
     fn test_to_snake_case() {
         assert_eq!(to_snake_case("Hello World"), "hello_world");
@@ -301,6 +748,13 @@ mod tests {
         assert_eq!(to_snake_case("___"), "");
     }
 
+    #[test]
+    fn test_quote_identifier() {
+        assert_eq!(quote_identifier("CMDORNUM"), "\"CMDORNUM\"");
+        assert_eq!(quote_identifier("a\"b"), "\"a\"\"b\"");
+        assert_eq!(quote_identifier(""), "\"\"");
+    }
+
     #[test]
     fn test_get_primary_key_field() {
         let mut schema1 = HashMap::new();
@@ -382,8 +836,9 @@ mod tests {
 
     #[test]
     fn test_data_directory() {
+        let _guard = DataDirectoryGuard::new(None);
         let data_dir = data_directory();
+        assert!(data_dir.ends_with("data"));
         assert!(data_dir.to_string_lossy().contains("data"));
-        assert!(data_dir.is_absolute());
     }
 }